@@ -0,0 +1,8 @@
+// AeroFlow Debug Adapter - Library
+// Launches programs on the runtime VM under a DAP client's control
+
+pub mod protocol;
+pub mod session;
+
+pub use protocol::{read_message, write_message, Client};
+pub use session::Session;
@@ -0,0 +1,9 @@
+// AeroFlow Debug Adapter (aeroflow-dap)
+// Debug Adapter Protocol over stdio for editors
+
+use aeroflow_dap::Session;
+
+fn main() -> anyhow::Result<()> {
+    let stdin = std::io::stdin();
+    Session::new(std::io::stdout()).run(stdin.lock())
+}
@@ -0,0 +1,81 @@
+// AeroFlow Debug Adapter - Wire Protocol
+// Content-Length framed JSON messages
+
+use anyhow::{anyhow, Context as _};
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+use std::io::{BufRead, Write};
+
+/// Read one framed message; `Ok(None)` at end of input.
+pub fn read_message(input: &mut impl BufRead) -> anyhow::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = Some(value.trim().parse::<usize>().context("invalid Content-Length")?);
+            }
+        }
+    }
+    let length = length.ok_or_else(|| anyhow!("message without Content-Length"))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+pub fn write_message(output: &mut impl Write, message: &Value) -> std::io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+/// The adapter's side of the connection: numbers and writes responses and
+/// events, from any thread.
+pub struct Client {
+    out: Mutex<(Box<dyn Write + Send>, u64)>,
+}
+
+impl Client {
+    pub fn new(out: impl Write + Send + 'static) -> Self {
+        Self { out: Mutex::new((Box::new(out), 0)) }
+    }
+
+    fn send(&self, mut message: Value) {
+        let mut out = self.out.lock();
+        out.1 += 1;
+        message["seq"] = json!(out.1);
+        // A client that hung up cannot be told anything more
+        let _ = write_message(&mut out.0, &message);
+    }
+
+    pub fn respond(&self, request: &Value, body: Value) {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": true,
+            "body": body,
+        }));
+    }
+
+    pub fn fail(&self, request: &Value, message: &str) {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": false,
+            "message": message,
+        }));
+    }
+
+    pub fn event(&self, event: &str, body: Value) {
+        self.send(json!({ "type": "event", "event": event, "body": body }));
+    }
+}
@@ -0,0 +1,390 @@
+// AeroFlow Debug Adapter - Session
+// One launched program: breakpoints, stepping and inspection
+
+use crate::protocol::{read_message, Client};
+use aeroflow_compiler::afm::TrustStore;
+use aeroflow_compiler::ir::{Code, Value};
+use aeroflow_runtime::snapshot::VmState;
+use aeroflow_runtime::vm::{DebugHook, VMContext};
+use aeroflow_runtime::{Program, RenderEvent, RenderSink, Scheduler, VM};
+use parking_lot::{Condvar, Mutex};
+use serde_json::{json, Value as Json};
+use std::collections::BTreeSet;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// `variablesReference` of the paused actor's state; frame `i`'s locals are
+/// `LOCALS + i`.
+const STATE: i64 = 1;
+const LOCALS: i64 = 100;
+
+/// A message delivery: the receiving actor and its logical time.
+type Activation = (Option<String>, u64);
+
+#[derive(Debug, Clone, PartialEq)]
+enum Mode {
+    Run,
+    Entry,
+    Pause,
+    StepIn,
+    StepOver { activation: Activation, depth: usize },
+    StepOut { activation: Activation, depth: usize },
+}
+
+struct Paused {
+    thread: i64,
+    ip: usize,
+    activation: Activation,
+    vm: VmState,
+}
+
+struct ControlState {
+    breakpoints: BTreeSet<u32>,
+    mode: Mode,
+    paused: Option<Paused>,
+    terminated: bool,
+}
+
+/// Shared between the adapter and the scheduler thread, which it stops at
+/// statement boundaries.
+struct Control {
+    program: Program,
+    /// One DAP thread per actor; thread ids are indices + 1.
+    threads: Vec<String>,
+    client: Arc<Client>,
+    state: Mutex<ControlState>,
+    resumed: Condvar,
+}
+
+impl Control {
+    fn thread_id(&self, actor: Option<&str>) -> i64 {
+        let actor = actor.unwrap_or("main");
+        self.threads.iter().position(|t| t == actor).map_or(0, |i| i as i64 + 1)
+    }
+
+    /// Resume the scheduler thread in `mode`.
+    fn resume(&self, mode: Mode) {
+        let mut state = self.state.lock();
+        state.mode = mode;
+        state.paused = None;
+        self.resumed.notify_all();
+    }
+
+    fn terminate(&self) {
+        let mut state = self.state.lock();
+        state.terminated = true;
+        state.paused = None;
+        self.resumed.notify_all();
+    }
+}
+
+impl DebugHook for Control {
+    fn before(&self, ip: usize, vm: &VM, ctx: &VMContext) {
+        let lines = self.program.lines();
+        let Ok(i) = lines.binary_search_by_key(&ip, |l| l.ip) else { return };
+        let mut state = self.state.lock();
+        if state.terminated {
+            return;
+        }
+        let activation = (ctx.actor_id.clone(), ctx.logical_time);
+        let depth = vm.depth();
+        let reason = if state.breakpoints.contains(&lines[i].line) {
+            Some("breakpoint")
+        } else {
+            match &state.mode {
+                Mode::Run => None,
+                Mode::Entry => Some("entry"),
+                Mode::Pause => Some("pause"),
+                Mode::StepIn => Some("step"),
+                // A different activation means the stepped-from handler finished
+                Mode::StepOver { activation: from, depth: d } => (*from != activation || depth <= *d).then_some("step"),
+                Mode::StepOut { activation: from, depth: d } => (*from != activation || depth < *d).then_some("step"),
+            }
+        };
+        let Some(reason) = reason else { return };
+
+        let thread = self.thread_id(ctx.actor_id.as_deref());
+        state.mode = Mode::Run;
+        state.paused = Some(Paused { thread, ip, activation, vm: vm.save_state() });
+        self.client.event("stopped", json!({ "reason": reason, "threadId": thread, "allThreadsStopped": true }));
+        while state.paused.is_some() && !state.terminated {
+            self.resumed.wait(&mut state);
+        }
+    }
+}
+
+/// Program output as DAP `output` events.
+struct OutputSink(Arc<Client>);
+
+impl RenderSink for OutputSink {
+    fn emit(&self, event: RenderEvent) {
+        let output = match event {
+            RenderEvent::Value(v) => v.to_string(),
+            other => serde_json::to_string(&other).unwrap_or_default(),
+        };
+        self.0.event("output", json!({ "category": "stdout", "output": output + "\n" }));
+    }
+}
+
+struct Launched {
+    path: PathBuf,
+    scheduler: Arc<Scheduler>,
+    control: Arc<Control>,
+}
+
+/// A debug session over one connection.
+pub struct Session {
+    client: Arc<Client>,
+    launched: Option<Launched>,
+    configured: bool,
+    running: bool,
+}
+
+impl Session {
+    pub fn new(out: impl Write + Send + 'static) -> Self {
+        Self { client: Arc::new(Client::new(out)), launched: None, configured: false, running: false }
+    }
+
+    /// Serve requests until `disconnect` or end of input.
+    pub fn run(mut self, mut input: impl BufRead) -> anyhow::Result<()> {
+        while let Some(request) = read_message(&mut input)? {
+            if request["type"] != "request" {
+                continue;
+            }
+            let command = request["command"].as_str().unwrap_or_default().to_string();
+            let args = request.get("arguments").cloned().unwrap_or_else(|| json!({}));
+            match self.handle(&command, &args, &request) {
+                Ok(Some(body)) => self.client.respond(&request, body),
+                Ok(None) => {}
+                Err(e) => self.client.fail(&request, &format!("{:#}", e)),
+            }
+            if command == "disconnect" {
+                break;
+            }
+        }
+        if let Some(launched) = &self.launched {
+            launched.control.terminate();
+        }
+        Ok(())
+    }
+
+    /// `Ok(None)` when the handler already responded.
+    fn handle(&mut self, command: &str, args: &Json, request: &Json) -> anyhow::Result<Option<Json>> {
+        Ok(Some(match command {
+            "initialize" => json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsTerminateRequest": true,
+            }),
+            "launch" => {
+                self.launch(args)?;
+                self.client.respond(request, json!({}));
+                self.client.event("initialized", json!({}));
+                return Ok(None);
+            }
+            "setBreakpoints" => self.set_breakpoints(args)?,
+            "setExceptionBreakpoints" => json!({}),
+            "configurationDone" => {
+                self.configured = true;
+                self.client.respond(request, json!({}));
+                self.start();
+                return Ok(None);
+            }
+            "threads" => {
+                let control = &self.launched()?.control;
+                json!({ "threads": control.threads.iter().enumerate()
+                    .map(|(i, name)| json!({ "id": i + 1, "name": name }))
+                    .collect::<Vec<_>>() })
+            }
+            "stackTrace" => self.stack_trace(args)?,
+            "scopes" => {
+                let frame = args["frameId"].as_i64().unwrap_or(0);
+                json!({ "scopes": [
+                    { "name": "Locals", "variablesReference": LOCALS + frame, "expensive": false },
+                    { "name": "State", "variablesReference": STATE, "expensive": false },
+                ] })
+            }
+            "variables" => self.variables(args)?,
+            "continue" | "next" | "stepIn" | "stepOut" => {
+                let control = self.launched()?.control.clone();
+                let mode = {
+                    let state = control.state.lock();
+                    let paused = state.paused.as_ref().ok_or_else(|| anyhow::anyhow!("not paused"))?;
+                    let (activation, depth) = (paused.activation.clone(), paused.vm.frames.len());
+                    match command {
+                        "continue" => Mode::Run,
+                        "next" => Mode::StepOver { activation, depth },
+                        "stepIn" => Mode::StepIn,
+                        _ => Mode::StepOut { activation, depth },
+                    }
+                };
+                // Respond before resuming so the next `stopped` follows the response
+                self.client.respond(request, json!({ "allThreadsContinued": true }));
+                control.resume(mode);
+                return Ok(None);
+            }
+            "pause" => {
+                self.launched()?.control.state.lock().mode = Mode::Pause;
+                json!({})
+            }
+            "terminate" | "disconnect" => {
+                if let Some(launched) = &self.launched {
+                    launched.control.terminate();
+                }
+                json!({})
+            }
+            _ => anyhow::bail!("unsupported request '{}'", command),
+        }))
+    }
+
+    fn launched(&self) -> anyhow::Result<&Launched> {
+        self.launched.as_ref().ok_or_else(|| anyhow::anyhow!("no program launched"))
+    }
+
+    fn launch(&mut self, args: &Json) -> anyhow::Result<()> {
+        let path = PathBuf::from(args["program"].as_str().ok_or_else(|| anyhow::anyhow!("launch needs 'program'"))?);
+        let program = if path.extension().and_then(|e| e.to_str()) == Some("afm") {
+            // SAFETY: the debuggee's module is not rebuilt while it runs
+            unsafe { Program::open(&path, &TrustStore::default())? }.0
+        } else {
+            Program::from(aeroflow_compiler::compile(&std::fs::read_to_string(&path)?)?)
+        };
+
+        let scheduler = Scheduler::shared();
+        scheduler.set_render_sink(Arc::new(OutputSink(self.client.clone())));
+        let mut threads = scheduler.spawn_program(program.clone());
+        threads.sort();
+        let stop_on_entry = args["stopOnEntry"].as_bool().unwrap_or(false);
+        let control = Arc::new(Control {
+            program,
+            threads,
+            client: self.client.clone(),
+            state: Mutex::new(ControlState {
+                breakpoints: BTreeSet::new(),
+                mode: if stop_on_entry { Mode::Entry } else { Mode::Run },
+                paused: None,
+                terminated: false,
+            }),
+            resumed: Condvar::new(),
+        });
+        scheduler.set_debug_hook(control.clone());
+        self.launched = Some(Launched { path, scheduler, control });
+        Ok(())
+    }
+
+    /// Run the program on its own thread once launched and configured.
+    fn start(&mut self) {
+        let Some(launched) = &self.launched else { return };
+        if !self.configured || self.running {
+            return;
+        }
+        self.running = true;
+        let (scheduler, client) = (launched.scheduler.clone(), self.client.clone());
+        std::thread::spawn(move || {
+            scheduler.run(None);
+            let failures = scheduler.failures();
+            for failure in &failures {
+                let output = format!("[T={}] Actor '{}' failed: {}\n", failure.logical_time, failure.actor, failure.reason);
+                client.event("output", json!({ "category": "stderr", "output": output }));
+            }
+            client.event("exited", json!({ "exitCode": if failures.is_empty() { 0 } else { 2 } }));
+            client.event("terminated", json!({}));
+        });
+    }
+
+    /// Breakpoints land on the first statement at or after the requested line.
+    fn set_breakpoints(&mut self, args: &Json) -> anyhow::Result<Json> {
+        let control = &self.launched()?.control;
+        let starts: BTreeSet<u32> = control.program.lines().iter().map(|l| l.line).collect();
+        let mut lines = BTreeSet::new();
+        let breakpoints: Vec<Json> = args["breakpoints"].as_array().into_iter().flatten().map(|bp| {
+            let requested = bp["line"].as_u64().unwrap_or(0) as u32;
+            match starts.range(requested..).next() {
+                Some(&line) => {
+                    lines.insert(line);
+                    json!({ "verified": true, "line": line })
+                }
+                None => json!({ "verified": false, "line": requested, "message": "no code at or after this line" }),
+            }
+        }).collect();
+        control.state.lock().breakpoints = lines;
+        Ok(json!({ "breakpoints": breakpoints }))
+    }
+
+    /// Innermost first: the paused instruction, then each caller's call site.
+    fn stack_trace(&self, args: &Json) -> anyhow::Result<Json> {
+        let launched = self.launched()?;
+        let control = &launched.control;
+        let state = control.state.lock();
+        let frames = match &state.paused {
+            Some(paused) if args["threadId"].as_i64() == Some(paused.thread) => call_stack(paused),
+            _ => Vec::new(),
+        };
+        let source = json!({
+            "name": launched.path.file_name().map(|n| n.to_string_lossy().to_string()),
+            "path": launched.path.display().to_string(),
+        });
+        let frames: Vec<Json> = frames.iter().enumerate().map(|(i, (ip, _))| json!({
+            "id": i,
+            "name": control.program.function_at(*ip).map_or("main", |f| f.name.as_str()),
+            "line": control.program.line_at(*ip).unwrap_or(0),
+            "column": 1,
+            "source": source,
+        })).collect();
+        Ok(json!({ "totalFrames": frames.len(), "stackFrames": frames }))
+    }
+
+    fn variables(&self, args: &Json) -> anyhow::Result<Json> {
+        let state = self.launched()?.control.state.lock();
+        let paused = state.paused.as_ref().ok_or_else(|| anyhow::anyhow!("not paused"))?;
+        let reference = args["variablesReference"].as_i64().unwrap_or(0);
+        let vars = if reference == STATE {
+            Some(&paused.vm.globals)
+        } else {
+            let stack = call_stack(paused);
+            usize::try_from(reference - LOCALS).ok()
+                .and_then(|i| stack.get(i).copied())
+                .and_then(|(_, frame)| frame)
+                .map(|frame| &paused.vm.frames[frame].locals)
+        };
+        let variables: Vec<Json> = vars.into_iter().flatten().map(|(name, value)| json!({
+            "name": name,
+            "value": display(value),
+            "type": type_name(value),
+            "variablesReference": 0,
+        })).collect();
+        Ok(json!({ "variables": variables }))
+    }
+}
+
+/// `(ip, frame index)` per stack frame, innermost first. Code outside any
+/// `fn` call (the main program) has no frame of its own.
+fn call_stack(paused: &Paused) -> Vec<(usize, Option<usize>)> {
+    let frames = &paused.vm.frames;
+    let mut stack = vec![(paused.ip, frames.len().checked_sub(1))];
+    let mut frame = frames.len().checked_sub(1);
+    while let Some(i) = frame {
+        let Some(return_ip) = frames[i].return_ip else { break };
+        frame = i.checked_sub(1);
+        stack.push((return_ip - 1, frame));
+    }
+    stack
+}
+
+fn display(value: &Value) -> String {
+    match value {
+        Value::String(s) => format!("{:?}", s),
+        other => other.to_string(),
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Bool(_) => "bool",
+        Value::Nil => "nil",
+        Value::List(_) => "list",
+        Value::Map(_) => "map",
+    }
+}
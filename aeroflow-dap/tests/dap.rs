@@ -0,0 +1,212 @@
+use aeroflow_dap::{read_message, write_message};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+const PROGRAM: &str = r#"fn double(x: int) {
+    let y = x * 2
+    return y
+}
+
+actor Counter {
+    state total = 0
+    on Add(n) {
+        let total = total + double(n)
+        print("total " + total)
+    }
+}
+
+send("Counter", "Add", 1)
+send("Counter", "Add", 2)
+"#;
+
+type Variables = Vec<(String, String)>;
+
+/// Drives `aeroflow-dap` over its stdio like an editor would.
+struct Client {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    seq: u64,
+    events: VecDeque<Value>,
+}
+
+impl Client {
+    fn start() -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_aeroflow-dap"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        Self { child, stdin, stdout, seq: 0, events: VecDeque::new() }
+    }
+
+    fn request(&mut self, command: &str, arguments: Value) -> Value {
+        self.seq += 1;
+        write_message(&mut self.stdin, &json!({ "seq": self.seq, "type": "request", "command": command, "arguments": arguments })).unwrap();
+        loop {
+            let message = read_message(&mut self.stdout).unwrap().expect("adapter closed the connection");
+            if message["type"] == "response" && message["request_seq"] == json!(self.seq) {
+                assert_eq!(message["success"], json!(true), "{} failed: {}", command, message);
+                return message["body"].clone();
+            }
+            self.events.push_back(message);
+        }
+    }
+
+    fn event(&mut self, name: &str) -> Value {
+        loop {
+            let message = match self.events.pop_front() {
+                Some(message) => message,
+                None => read_message(&mut self.stdout).unwrap().expect("adapter closed the connection"),
+            };
+            if message["type"] == "event" && message["event"] == name {
+                return message["body"].clone();
+            }
+        }
+    }
+
+    /// `(function, line)` of each frame of the stopped thread.
+    fn stack(&mut self, thread: &Value) -> Vec<(String, u64)> {
+        let trace = self.request("stackTrace", json!({ "threadId": thread }));
+        trace["stackFrames"].as_array().unwrap().iter()
+            .map(|f| (f["name"].as_str().unwrap().to_string(), f["line"].as_u64().unwrap()))
+            .collect()
+    }
+
+    fn variables(&mut self, reference: Value) -> Variables {
+        let vars = self.request("variables", json!({ "variablesReference": reference }));
+        vars["variables"].as_array().unwrap().iter()
+            .map(|v| (v["name"].as_str().unwrap().to_string(), v["value"].as_str().unwrap().to_string()))
+            .collect()
+    }
+
+    /// Locals and actor state of `frame`.
+    fn scopes(&mut self, frame: u64) -> (Variables, Variables) {
+        let scopes = self.request("scopes", json!({ "frameId": frame }));
+        let locals = self.variables(scopes["scopes"][0]["variablesReference"].clone());
+        let state = self.variables(scopes["scopes"][1]["variablesReference"].clone());
+        (locals, state)
+    }
+
+    fn finish(mut self) {
+        self.request("disconnect", json!({}));
+        assert!(self.child.wait().unwrap().success());
+    }
+}
+
+/// `PROGRAM` written to a temp file, removed again when dropped.
+struct ProgramFile(PathBuf);
+
+impl Drop for ProgramFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+fn program_file(name: &str) -> ProgramFile {
+    let path = std::env::temp_dir().join(format!("aeroflow-dap-{}-{}.aefl", name, std::process::id()));
+    std::fs::write(&path, PROGRAM).unwrap();
+    ProgramFile(path)
+}
+
+fn pair(name: &str, value: &str) -> (String, String) {
+    (name.to_string(), value.to_string())
+}
+
+#[test]
+fn breakpoints_and_stepping_across_frames() {
+    let program = program_file("stepping");
+    let path = &program.0;
+    let mut client = Client::start();
+    let caps = client.request("initialize", json!({ "adapterID": "aeroflow" }));
+    assert_eq!(caps["supportsConfigurationDoneRequest"], json!(true));
+    client.request("launch", json!({ "program": path }));
+    client.event("initialized");
+
+    // Blank and declaration-only lines move to the next statement
+    let set = client.request("setBreakpoints", json!({ "source": { "path": path }, "breakpoints": [{ "line": 12 }, { "line": 40 }] }));
+    assert_eq!(set["breakpoints"][0], json!({ "verified": true, "line": 14 }));
+    assert_eq!(set["breakpoints"][1]["verified"], json!(false));
+    let set = client.request("setBreakpoints", json!({ "source": { "path": path }, "breakpoints": [{ "line": 9 }] }));
+    assert_eq!(set["breakpoints"][0], json!({ "verified": true, "line": 9 }));
+    client.request("configurationDone", json!({}));
+
+    let stopped = client.event("stopped");
+    assert_eq!(stopped["reason"], json!("breakpoint"));
+    let threads = client.request("threads", json!({}));
+    assert_eq!(threads["threads"], json!([{ "id": 1, "name": "Counter" }, { "id": 2, "name": "main" }]));
+    let thread = stopped["threadId"].clone();
+    assert_eq!(thread, json!(1));
+    assert_eq!(client.stack(&thread), vec![("Counter.Add".to_string(), 9)]);
+    assert!(client.stack(&json!(2)).is_empty());
+    let (locals, state) = client.scopes(0);
+    assert_eq!(locals, vec![pair("n", "1")]);
+    assert_eq!(state, vec![pair("total", "0")]);
+
+    // Into `double`: the caller's frame stays on the stack
+    client.request("stepIn", json!({ "threadId": thread }));
+    assert_eq!(client.event("stopped")["reason"], json!("step"));
+    assert_eq!(client.stack(&thread), vec![("double".to_string(), 2), ("Counter.Add".to_string(), 9)]);
+    assert_eq!(client.scopes(0).0, vec![pair("x", "1")]);
+    assert_eq!(client.scopes(1).0, vec![pair("n", "1")]);
+
+    client.request("next", json!({ "threadId": thread }));
+    client.event("stopped");
+    assert_eq!(client.stack(&thread)[0], ("double".to_string(), 3));
+    assert_eq!(client.scopes(0).0, vec![pair("x", "1"), pair("y", "2")]);
+
+    client.request("stepOut", json!({ "threadId": thread }));
+    client.event("stopped");
+    assert_eq!(client.stack(&thread), vec![("Counter.Add".to_string(), 10)]);
+    assert_eq!(client.scopes(0).1, vec![pair("total", "2")]);
+
+    // Past the end of the handler; the next delivery hits the breakpoint
+    client.request("next", json!({ "threadId": thread }));
+    assert_eq!(client.event("output")["output"], json!("total 2\n"));
+    assert_eq!(client.event("stopped")["reason"], json!("breakpoint"));
+    assert_eq!(client.scopes(0).0, vec![pair("n", "2")]);
+
+    // Over the call to `double`
+    client.request("setBreakpoints", json!({ "source": { "path": path }, "breakpoints": [] }));
+    client.request("next", json!({ "threadId": thread }));
+    client.event("stopped");
+    assert_eq!(client.stack(&thread), vec![("Counter.Add".to_string(), 10)]);
+    assert_eq!(client.scopes(0).1, vec![pair("total", "6")]);
+
+    client.request("continue", json!({ "threadId": thread }));
+    assert_eq!(client.event("output")["output"], json!("total 6\n"));
+    assert_eq!(client.event("exited")["exitCode"], json!(0));
+    client.event("terminated");
+    client.finish();
+}
+
+#[test]
+fn stop_on_entry_then_step_between_actors() {
+    let program = program_file("entry");
+    let path = &program.0;
+    let mut client = Client::start();
+    client.request("initialize", json!({}));
+    client.request("launch", json!({ "program": path, "stopOnEntry": true }));
+    client.request("configurationDone", json!({}));
+
+    let stopped = client.event("stopped");
+    assert_eq!(stopped["reason"], json!("entry"));
+    // Actors start before `main`; the first statement run is Counter's state
+    assert_eq!(client.stack(&stopped["threadId"]), vec![("Counter.init".to_string(), 7)]);
+
+    // Stepping off the end of a handler stops in whichever actor runs next
+    client.request("next", json!({ "threadId": stopped["threadId"] }));
+    let stopped = client.event("stopped");
+    assert_eq!(stopped["reason"], json!("step"));
+    assert_eq!(stopped["threadId"], json!(2));
+    assert_eq!(client.stack(&stopped["threadId"]), vec![("main".to_string(), 14)]);
+
+    client.request("continue", json!({ "threadId": stopped["threadId"] }));
+    client.event("terminated");
+    client.finish();
+}
@@ -1,8 +1,30 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
+use aeroflow_compiler::afm::{self, AfmModule, TrustStore};
 use aeroflow_compiler::compile;
-use aeroflow_runtime::VM;
+use ed25519_dalek::SigningKey;
+use manifest::{parse_limit, Manifest};
+use aeroflow_runtime::web::WebBundle;
+use aeroflow_runtime::debugger::Debugger;
+use aeroflow_runtime::mailbox::{MailboxConfig, Overflow};
+use aeroflow_runtime::replay::{Recording, Replayer};
+use aeroflow_runtime::scheduler::{Clock, RunStop};
+use aeroflow_runtime::chrome_trace::ChromeTraceSink;
+use aeroflow_runtime::trace::{self, TraceFilter, TraceReader, TraceSink, TraceSummary};
+use aeroflow_runtime::{get_tracer, MemorySink, Program, Scheduler, Snapshot, VM};
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+mod manifest;
+mod tui;
+
+/// Process exit code when the source does not compile (or flags are invalid).
+const EXIT_COMPILE_ERROR: i32 = 1;
+/// Process exit code when an actor failed while running.
+const EXIT_RUNTIME_ERROR: i32 = 2;
 
 #[derive(Parser)]
 #[command(name = "aeroflow")]
@@ -16,10 +38,10 @@ struct Cli {
 enum Commands {
     /// Run an AeroFlow program with integrated toolchain
     Run {
-        /// The .aefl file to run
+        /// The .aefl source or compiled .afm module to run
         #[arg(long, short)]
         source: PathBuf,
-        /// Build target (mobile, web, server)
+        /// Build target (mobile, web, server, tui)
         #[arg(long, default_value = "server")]
         target: String,
         /// Platforms (android, ios, wasm)
@@ -28,16 +50,50 @@ enum Commands {
         /// Runtime engine (das)
         #[arg(long, default_value = "das")]
         runtime: String,
-        /// Save/load snapshot (.afs)
+        /// Save a snapshot (.afs) of the scheduler when the run stops
         #[arg(long)]
         snapshot: Option<PathBuf>,
+        /// Resume from a snapshot (.afs) of the same program instead of starting fresh
+        #[arg(long)]
+        resume: Option<PathBuf>,
         /// Path to launch IDE
         #[arg(long)]
         ide: Option<PathBuf>,
-        /// Save execution logs
+        /// Save execution logs (with --replay: the recording to replay)
         #[arg(long)]
         log: Option<PathBuf>,
-        /// Replay recorded events
+        #[command(flatten)]
+        tracing: Box<TraceOpts>,
+        /// Record inputs, env reads and per-step state hashes for `aeroflow replay`
+        #[arg(long)]
+        record: Option<PathBuf>,
+        /// Stop before delivering messages scheduled after this logical time
+        #[arg(long)]
+        max_time: Option<u64>,
+        /// Run independent actors on this many threads (same output as 1)
+        #[arg(long, default_value = "1")]
+        threads: usize,
+        /// Pace logical time against the wall clock, one tick per millisecond, instead of skipping idle time
+        #[arg(long)]
+        realtime: bool,
+        /// Once no messages remain, stop every actor gracefully, running its `on stop` handler
+        #[arg(long)]
+        shutdown: bool,
+        #[command(flatten)]
+        limits: Box<LimitOpts>,
+        /// Most messages each actor may have queued (default unbounded)
+        #[arg(long)]
+        mailbox: Option<usize>,
+        /// What a full mailbox does with new messages: drop-new, drop-old, block or fail
+        #[arg(long, default_value = "drop-new")]
+        overflow: String,
+        /// Trusted public keys for `.afm` sources (default ~/.aeroflow/trusted_keys)
+        #[arg(long)]
+        trust: Option<PathBuf>,
+        /// Refuse unsigned `.afm` sources
+        #[arg(long)]
+        require_signature: bool,
+        /// Replay the recording given by --log against this source, verifying every step
         #[arg(long)]
         replay: bool,
         /// Enable AI agents
@@ -70,6 +126,22 @@ enum Commands {
         /// Enable AI-native pipelines
         #[arg(long)]
         ai: bool,
+        /// Output path: a directory for `web`, otherwise the `.afm` file
+        /// (defaults: dist/web, dist/<name>.afm)
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Sign the module with this key (see `aeroflow keygen`)
+        #[arg(long)]
+        key: Option<PathBuf>,
+    },
+    /// Generate an ed25519 key pair for signing modules
+    Keygen {
+        /// Directory for aeroflow.key / aeroflow.pub (default ~/.aeroflow/keys)
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Overwrite an existing key pair
+        #[arg(long)]
+        force: bool,
     },
     /// Install dependencies from aeroflow.toml
     Install,
@@ -84,8 +156,29 @@ enum Commands {
         #[arg(default_value = "aeroflow-conformance/tests")]
         dir: String,
     },
-    /// Export the execution trace to JSON
-    Trace,
+    /// Query and summarize a saved trace (`run --trace` or `run --log`)
+    Trace {
+        /// The trace file (.aft, .jsonl or a --log JSON array)
+        file: PathBuf,
+        /// Only events delivered to these actors
+        #[arg(long)]
+        actor: Vec<String>,
+        /// Only these message kinds
+        #[arg(long)]
+        message: Vec<String>,
+        /// Only events at or after this logical time
+        #[arg(long)]
+        from: Option<u64>,
+        /// Only events at or before this logical time
+        #[arg(long)]
+        to: Option<u64>,
+        /// Print the matching events as JSON Lines before the summary
+        #[arg(long)]
+        events: bool,
+        /// Also convert the matching events to a Chrome trace at this path
+        #[arg(long)]
+        chrome: Option<PathBuf>,
+    },
     /// Start a distributed DAS node
     Cluster {
         /// Unique ID for this node
@@ -135,83 +228,298 @@ enum Commands {
     },
     /// Replay recorded execution logs deterministically
     Replay {
-        /// Path to execution log
+        /// Recording written by `run --record`
         #[arg(long, short)]
         log: PathBuf,
         /// Launch IDE for visualization
         #[arg(long)]
         ide: Option<PathBuf>,
-        /// Step duration for replay
+        /// Pause between replayed steps (e.g. 250us, 10ms, 1s)
         #[arg(long, default_value = "10ms")]
         step: String,
         /// Replay as fast as possible
         #[arg(long)]
         fast_forward: bool,
     },
+    /// Serve the time-travel debugger for a recording over local JSON-RPC
+    Debug {
+        /// Recording written by `run --record`
+        #[arg(long, short)]
+        log: PathBuf,
+        /// Debug this source against the recording instead of the recorded program
+        #[arg(long, short)]
+        source: Option<PathBuf>,
+        /// Port on 127.0.0.1 (0 picks a free one)
+        #[arg(long, default_value = "9229")]
+        port: u16,
+    },
+}
+
+/// `run` options for tracing deliveries.
+#[derive(Args)]
+struct TraceOpts {
+    /// Stream trace events to this file (`.aft` binary, otherwise JSON Lines)
+    #[arg(long)]
+    trace: Option<PathBuf>,
+    /// Only trace deliveries to these actors
+    #[arg(long)]
+    trace_actor: Vec<String>,
+    /// Only trace these message kinds
+    #[arg(long)]
+    trace_message: Vec<String>,
+    /// Trace one in every N matching deliveries
+    #[arg(long, default_value = "1")]
+    trace_sample: u64,
+    /// Write traced deliveries as a Chrome trace (open in Perfetto or chrome://tracing)
+    #[arg(long)]
+    chrome_trace: Option<PathBuf>,
+}
+
+/// `run` options limiting what actors may use and do, on top of aeroflow.toml.
+#[derive(Args)]
+struct LimitOpts {
+    /// Most memory each actor may hold or use handling one message, e.g. 64MB (default from aeroflow.toml [runtime] memory)
+    #[arg(long)]
+    memory: Option<String>,
+    /// Grant every actor a capability, e.g. ENV_READ, NET or FS_READ; repeatable
+    #[arg(long, value_name = "CAPABILITY")]
+    allow: Vec<String>,
+    /// Let env() read this variable; repeatable, grants ENV_READ for the variables named
+    #[arg(long, value_name = "NAME")]
+    allow_env: Vec<String>,
 }
 
 fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
 
     match cli.command {
-        Commands::Run { source, target, platform, runtime, snapshot, ide, log, replay, ai, distributed, dark_theme, light_theme } => {
+        Commands::Run { source, target, platform, runtime, snapshot, resume, ide, log, tracing, record, max_time, threads, realtime, shutdown, limits, mailbox, overflow, trust, require_signature, replay, ai, distributed, dark_theme, light_theme: _ } => {
             println!("🌀 AeroFlow Elite: Executing source: {}", source.display());
             println!("🎯 Build Target: {} | Platforms: {:?}", target, platform);
             
-            if let Some(s) = snapshot {
-                println!("📦 Loading snapshot: {}...", s.display());
-            }
-
             if ai { println!("🧬 AI Agents: ENABLED"); }
             if distributed { println!("🌐 Distributed D-DAS Simulation: ENABLED"); }
-            if replay { println!("⏪ Replay Mode: ACTIVE (consuming events from earlier traces)"); }
 
-            let source_content = fs::read_to_string(source)?;
-            match compile(&source_content) {
-                Ok(chunk) => {
-                    println!("🚀 Launching {} runtime...", runtime);
-                    let scheduler = aeroflow_runtime::Scheduler::new();
-                    
-                    if let Some(l) = &log {
-                        println!("📝 Saving execution logs to: {}", l.display());
-                    }
+            if runtime != "das" {
+                eprintln!("❌ Unknown runtime '{}': only 'das' is supported", runtime);
+                std::process::exit(EXIT_COMPILE_ERROR);
+            }
 
-                    if let Some(i_path) = ide {
-                        let theme = if dark_theme { "Dark" } else { "Light" };
-                        println!("🎨 Opening AeroFlow Studio ({}) at: {}", theme, i_path.display());
-                    }
+            let loaded = if source.extension().and_then(|e| e.to_str()) == Some("afm") {
+                load_module(&source, trust, require_signature)
+            } else {
+                fs::read_to_string(&source).map_err(anyhow::Error::from).and_then(|text| compile(&text)).map(Program::from)
+            };
+            let program = match loaded {
+                Ok(program) => program,
+                Err(e) => {
+                    eprintln!("❌ Compile Error: {:#}", e);
+                    std::process::exit(EXIT_COMPILE_ERROR);
+                }
+            };
+            if target == "tui" {
+                return tui::run(&program.to_chunk());
+            }
 
-                    // Simulated execution
-                    println!("🔒 Running deterministic DAS loop...");
-                    let trace = aeroflow_runtime::get_tracer().export_json();
-                    if log.is_some() {
-                        fs::write("trace.json", trace)?;
-                    }
-                    println!("✅ Execution complete.");
+            println!("🚀 Launching {} runtime...", runtime);
+            if let Some(i_path) = ide {
+                let theme = if dark_theme { "Dark" } else { "Light" };
+                println!("🎨 Opening AeroFlow Studio ({}) at: {}", theme, i_path.display());
+            }
+
+            if replay {
+                let Some(path) = &log else {
+                    eprintln!("❌ --replay needs the recording to replay in --log");
+                    std::process::exit(EXIT_COMPILE_ERROR);
+                };
+                println!("⏪ Replay Mode: ACTIVE (consuming events from {})", path.display());
+                let recording = Recording::from_json(&fs::read_to_string(path)?)?;
+                run_replay(Replayer::with_program(recording, program), None);
+                return Ok(());
+            }
+
+            if record.is_some() && resume.is_some() {
+                eprintln!("❌ --record replays from a fresh start and cannot be combined with --resume");
+                std::process::exit(EXIT_COMPILE_ERROR);
+            }
+            let TraceOpts { trace, trace_actor, trace_message, trace_sample, chrome_trace } = *tracing;
+            let tracer = get_tracer();
+            tracer.set_filter(TraceFilter { actors: trace_actor.into_iter().collect(), messages: trace_message.into_iter().collect(), ..Default::default() });
+            tracer.set_sampling(trace_sample);
+            if let Some(path) = &trace {
+                tracer.add_sink(trace::open_sink(path)?);
+            }
+            let chrome_sink = match &chrome_trace {
+                Some(path) => {
+                    let sink = Arc::new(ChromeTraceSink::create(path)?);
+                    tracer.add_sink(sink.clone());
+                    Some(sink)
+                }
+                None => None,
+            };
+            let scheduler = Scheduler::shared();
+            scheduler.set_parallelism(threads)?;
+            if realtime {
+                scheduler.set_clock(Clock::Paced(Duration::from_millis(1)));
+            }
+            let overflow = match Overflow::parse(&overflow) {
+                Some(overflow) => overflow,
+                None => {
+                    eprintln!("❌ Invalid --overflow '{}': expected drop-new, drop-old, block or fail", overflow);
+                    std::process::exit(EXIT_COMPILE_ERROR);
+                }
+            };
+            scheduler.set_default_mailbox(MailboxConfig { capacity: mailbox, overflow });
+            let project = source.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+            let settings = Manifest::load(project).and_then(|manifest| {
+                let mut quota = manifest.runtime.quota()?;
+                if let Some(text) = &limits.memory {
+                    quota.memory = parse_limit(text, true).ok_or_else(|| anyhow::anyhow!("invalid --memory '{}': expected a size such as 64KB, 512MB or unlimited", text))?;
+                }
+                Ok((quota, manifest.runtime.global_quota()?, manifest.grants(&limits.allow, &limits.allow_env)?))
+            });
+            match settings {
+                Ok((quota, global, grants)) => {
+                    scheduler.set_quota(quota);
+                    scheduler.set_global_quota(global);
+                    scheduler.set_grants(grants);
                 }
                 Err(e) => {
-                    println!("❌ Compile Error: {}", e);
+                    eprintln!("❌ {:#}", e);
+                    std::process::exit(EXIT_COMPILE_ERROR);
                 }
             }
+            let recorder = record.as_ref().map(|_| scheduler.record(&program));
+            match &resume {
+                Some(path) => {
+                    let saved = Snapshot::from_bytes(&fs::read(path)?)?;
+                    scheduler.restore_program(&saved, program)?;
+                    println!("📦 Resumed from snapshot: {} (T={}, {} pending message(s))", path.display(), saved.logical_clock, saved.queue.len());
+                    println!("🔒 Running deterministic DAS loop ({} actor(s))...", saved.actors.len());
+                }
+                None => {
+                    let actors = scheduler.spawn_program(program);
+                    println!("🔒 Running deterministic DAS loop ({} actor(s))...", actors.len());
+                }
+            }
+            let mut report = scheduler.run(max_time);
+            if shutdown && report.stop == RunStop::Quiescent {
+                let stopped = scheduler.shutdown();
+                report.steps += stopped.steps;
+                report.logical_time = stopped.logical_time;
+            }
+
+            if let Some(path) = &snapshot {
+                fs::write(path, scheduler.snapshot()?.to_bytes()?)?;
+                println!("📦 Snapshot written to: {} (T={})", path.display(), report.logical_time);
+            }
+
+            if let Some(l) = &log {
+                fs::write(l, tracer.export_json())?;
+                println!("📝 Execution trace written to: {}", l.display());
+                if tracer.dropped() > 0 {
+                    println!("   (oldest {} event(s) dropped; use --trace to keep them all)", tracer.dropped());
+                }
+            }
+            if let Some(path) = &trace {
+                tracer.flush()?;
+                println!("📝 Trace streamed to: {}", path.display());
+            }
+            if let (Some(path), Some(sink)) = (&chrome_trace, &chrome_sink) {
+                sink.close()?;
+                println!("📈 Chrome trace written to: {} (open in https://ui.perfetto.dev)", path.display());
+            }
+
+            if let (Some(path), Some(recorder)) = (&record, &recorder) {
+                let recording = recorder.recording();
+                fs::write(path, recording.to_json()?)?;
+                println!("⏺️  Recording written to: {} ({} step(s), {} input(s))", path.display(), recording.steps.len(), recording.inputs.len());
+            }
+
+            let dead = scheduler.mailbox_metrics().values().map(|m| m.dropped).sum::<u64>();
+            if dead > 0 {
+                let mut reasons: BTreeMap<&str, usize> = BTreeMap::new();
+                for letter in scheduler.dead_letters() {
+                    *reasons.entry(letter.reason.code()).or_default() += 1;
+                }
+                let reasons: Vec<String> = reasons.into_iter().map(|(code, n)| format!("{} {}", n, code)).collect();
+                eprintln!("📭 {} message(s) undeliverable (latest: {})", dead, reasons.join(", "));
+            }
+
+            let failures = scheduler.failures();
+            for failure in &failures {
+                eprintln!("💥 [T={}] Actor '{}' failed: {}", failure.logical_time, failure.actor, failure.reason);
+            }
+            match report.stop {
+                RunStop::Quiescent => println!("✅ Execution complete: {} message(s), logical time T={}.", report.steps, report.logical_time),
+                RunStop::TimeLimit => println!("⏱️  Stopped at logical-time limit after {} message(s); later messages remain queued.", report.steps),
+            }
+            if !failures.is_empty() {
+                std::process::exit(EXIT_RUNTIME_ERROR);
+            }
         }
-        Commands::Build { source, target, platform, snapshot, ai } => {
+        Commands::Build { source, target, platform, snapshot, ai, out, key } => {
             println!("🔨 AeroFlow Build: Compiling {}...", source.display());
             println!("🎯 Targets: {} | Platforms: {:?}", target, platform);
             if ai { println!("🧬 AI Pipelines: OPTIMIZED"); }
             
             let start_time = std::time::Instant::now();
-            let source_str = fs::read_to_string(source)?;
+            let source_str = fs::read_to_string(&source)?;
             match compile(&source_str) {
-                Ok(_chunk) => {
+                Ok(chunk) => {
                     let compile_time = start_time.elapsed();
                     println!("✓ Build successful in {:.4}ms.", compile_time.as_secs_f64() * 1000.0);
                     if let Some(s) = snapshot {
-                        println!("📦 Writing runtime snapshot to: {}...", s.display());
+                        // The program as spawned, before any message is delivered
+                        let scheduler = Scheduler::new();
+                        scheduler.spawn_program(&chunk);
+                        fs::write(&s, scheduler.snapshot()?.to_bytes()?)?;
+                        println!("📦 Runtime snapshot written to: {}", s.display());
+                    }
+                    let stem = source.file_stem().and_then(|s| s.to_str()).unwrap_or("aeroflow");
+                    if target == "web" {
+                        let out = out.unwrap_or_else(|| PathBuf::from("dist/web"));
+                        WebBundle::build(stem, &chunk)?.write_to(&out)?;
+                        println!("🌐 Web bundle written to: {} ({} screen(s))", out.display(), chunk.screens.len());
+                        println!("   Place the wasm-pack output of the runtime in {}/pkg to serve it.", out.display());
+                    } else {
+                        let out = out.unwrap_or_else(|| PathBuf::from("dist").join(format!("{}.afm", stem)));
+                        let metadata = Manifest::load(Path::new("."))?.metadata(stem);
+                        let module = AfmModule { metadata, chunk };
+                        let key = key.map(|path| fs::read_to_string(path).map_err(anyhow::Error::from).and_then(|k| afm::parse_signing_key(&k))).transpose()?;
+                        let bytes = module.encode(afm::ARCH_IMAGE, key.as_ref())?;
+                        if let Some(dir) = out.parent() {
+                            fs::create_dir_all(dir)?;
+                        }
+                        fs::write(&out, &bytes)?;
+                        let file = AfmModule::inspect(&bytes)?;
+                        println!("📦 Module {}@{} written to: {} ({} bytes)", module.metadata.name, module.metadata.version, out.display(), bytes.len());
+                        println!("   sha256 {}", afm::to_hex(&file.hash));
+                        match file.signer {
+                            Some(signer) => println!("   🔏 signed by {}", afm::to_hex(signer.as_bytes())),
+                            None => println!("   unsigned (pass --key to sign)"),
+                        }
                     }
                 }
                 Err(e) => println!("❌ Build Error: {}", e),
             }
         }
+        Commands::Keygen { out, force } => {
+            let dir = out.unwrap_or_else(|| aeroflow_home().join("keys"));
+            let (secret_path, public_path) = (dir.join("aeroflow.key"), dir.join("aeroflow.pub"));
+            if secret_path.exists() && !force {
+                anyhow::bail!("{} already exists (use --force to replace it)", secret_path.display());
+            }
+            let mut seed = [0u8; 32];
+            getrandom::getrandom(&mut seed).map_err(|e| anyhow::anyhow!("no system randomness: {}", e))?;
+            let key = SigningKey::from_bytes(&seed);
+            fs::create_dir_all(&dir)?;
+            write_secret_key(&secret_path, &(afm::to_hex(&seed) + "\n"))?;
+            fs::write(&public_path, afm::to_hex(key.verifying_key().as_bytes()) + "\n")?;
+            println!("🔑 Signing key: {}", secret_path.display());
+            println!("   Public key:  {}", public_path.display());
+            println!("   Add {} to a trust store to accept modules signed with it.", afm::to_hex(key.verifying_key().as_bytes()));
+        }
         Commands::Install => {
             println!("🌀 AeroFlow Installer v0.1");
             let config_path = std::path::Path::new("aeroflow.toml");
@@ -289,13 +597,31 @@ ai.vision = "0.3"
                     match compile(&_source) {
                         Ok(chunk) => {
                             let mut vm = VM::new();
-                            let ctx = aeroflow_runtime::vm::VMContext {
-                                logical_time: 0,
-                                rand_seed: 0xACE1,
-                            };
-                            vm.execute(&chunk, &ctx);
-                            println!("✅ PASS");
-                            passed += 1;
+                            let sink = Arc::new(MemorySink::new());
+                            let mut ctx = aeroflow_runtime::vm::VMContext::new(0, sink.clone());
+                            ctx.rand_seed = 0xACE1;
+                            // Optional `<test>.out` holds the expected rendered lines
+                            let expected = fs::read_to_string(entry.path().with_extension("out")).ok();
+                            match vm.execute(&chunk, &ctx) {
+                                Ok(()) => {
+                                    let actual = sink.lines().join("\n");
+                                    match expected {
+                                        Some(exp) if exp.trim_end() != actual => {
+                                            println!("❌ FAIL");
+                                            println!("     Expected: {:?}", exp.trim_end());
+                                            println!("     Actual:   {:?}", actual);
+                                        }
+                                        _ => {
+                                            println!("✅ PASS");
+                                            passed += 1;
+                                        }
+                                    }
+                                }
+                                Err(e) => {
+                                    println!("❌ FAIL");
+                                    println!("     Error: {}", e);
+                                }
+                            }
                         }
                         Err(e) => {
                             println!("❌ FAIL");
@@ -307,8 +633,28 @@ ai.vision = "0.3"
 
             println!("\nTest Summary: {}/{} passed", passed, total);
         }
-        Commands::Trace => {
-            println!("{}", aeroflow_runtime::get_tracer().export_json());
+        Commands::Trace { file, actor, message, from, to, events, chrome } => {
+            let filter = TraceFilter { actors: actor.into_iter().collect(), messages: message.into_iter().collect(), from, to };
+            let chrome_sink = chrome.as_ref().map(ChromeTraceSink::create).transpose()?;
+            let mut summary = TraceSummary::default();
+            for event in TraceReader::open(&file)? {
+                let event = event?;
+                if !filter.matches_event(&event) {
+                    continue;
+                }
+                if events {
+                    println!("{}", serde_json::to_string(&event)?);
+                }
+                if let Some(sink) = &chrome_sink {
+                    sink.write(&event);
+                }
+                summary.add(&event);
+            }
+            print!("{}", summary);
+            if let (Some(path), Some(sink)) = (&chrome, &chrome_sink) {
+                sink.close()?;
+                println!("📈 Chrome trace written to: {}", path.display());
+            }
         }
         Commands::Cluster { node_id, peers } => {
             println!("🌐 [D-DAS] Initializing Node: {}", node_id);
@@ -334,7 +680,7 @@ ai.vision = "0.3"
             scheduler.step();
             println!("✅ Node {} synchronized successfully.", node_id);
         }
-        Commands::Ide { file, dark_theme, light_theme, show_timeline, show_distributed_state, ai_debug } => {
+        Commands::Ide { file, dark_theme, light_theme: _, show_timeline, show_distributed_state, ai_debug } => {
             let theme = if dark_theme { "Dark" } else { "Light" };
             println!("🎨 Starting AeroFlow Studio ({}) for {}...", theme, file.display());
             if show_timeline { println!("📈 Timeline: VISIBLE"); }
@@ -351,11 +697,109 @@ ai.vision = "0.3"
         }
         Commands::Replay { log, ide, step, fast_forward } => {
             println!("⏪ AeroFlow Replay: Processing log {}...", log.display());
+            let pace = match parse_duration(&step) {
+                Some(pace) => pace,
+                None => {
+                    eprintln!("❌ Invalid --step '{}': expected e.g. 250us, 10ms or 1s", step);
+                    std::process::exit(EXIT_COMPILE_ERROR);
+                }
+            };
             println!("⏱️ Replay Step: {} | Fast Forward: {}", step, fast_forward);
             if let Some(i) = ide { println!("🎨 Synchronizing Replay with IDE at: {}", i.display()); }
-            println!("🔒 Replaying causal event stream...");
+            let recording = Recording::from_json(&fs::read_to_string(&log)?)?;
+            println!("🔒 Replaying causal event stream ({} step(s))...", recording.steps.len());
+            run_replay(Replayer::new(recording), (!fast_forward).then_some(pace));
+        }
+        Commands::Debug { log, source, port } => {
+            let recording = Recording::from_json(&fs::read_to_string(&log)?)?;
+            let debugger = match source {
+                Some(source) => Debugger::with_program(recording, compile(&fs::read_to_string(source)?)?)?,
+                None => Debugger::new(recording)?,
+            };
+            let listener = TcpListener::bind(("127.0.0.1", port))?;
+            println!("🐞 AeroFlow Debugger: {} step(s) from {}", debugger.len(), log.display());
+            println!("🔌 JSON-RPC listening on {} (one request per line)", listener.local_addr()?);
+            debugger.serve(listener)?;
         }
     }
 
     Ok(())
 }
+
+/// Replay every recorded step, pausing `pace` between steps; exits with
+/// `EXIT_RUNTIME_ERROR` at the first divergence.
+fn run_replay(mut replayer: Replayer, pace: Option<Duration>) {
+    loop {
+        match replayer.step() {
+            Ok(Some(step)) => {
+                if pace.is_some() {
+                    println!("  #{} [T={}] {} <- {}", step.step, step.logical_time, step.actor, step.sender);
+                }
+                if let Some(pace) = pace {
+                    std::thread::sleep(pace);
+                }
+            }
+            Ok(None) => break,
+            Err(divergence) => {
+                eprintln!("💥 Replay {}", divergence);
+                std::process::exit(EXIT_RUNTIME_ERROR);
+            }
+        }
+    }
+    let report = replayer.report();
+    println!("✅ Replay matched the recording: {} step(s), logical time T={}.", report.steps, report.logical_time);
+}
+
+/// `250us`, `10ms`, `1s` or a bare number of milliseconds.
+fn parse_duration(text: &str) -> Option<Duration> {
+    let text = text.trim();
+    let (digits, unit) = text.split_at(text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len()));
+    let n: u64 = digits.parse().ok()?;
+    match unit {
+        "us" => Some(Duration::from_micros(n)),
+        "ms" | "" => Some(Duration::from_millis(n)),
+        "s" => Some(Duration::from_secs(n)),
+        _ => None,
+    }
+}
+
+/// `~/.aeroflow`, where keys and the default trust store live.
+fn aeroflow_home() -> PathBuf {
+    let home = std::env::var("HOME").or_else(|_| std::env::var("USERPROFILE")).unwrap_or_else(|_| ".".to_string());
+    Path::new(&home).join(".aeroflow")
+}
+
+/// Write a signing key only its owner can read. The mode applies only to new
+/// files, so an existing key that others can read is left alone.
+fn write_secret_key(path: &Path, contents: &str) -> anyhow::Result<()> {
+    use std::io::Write;
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        if let Ok(metadata) = fs::metadata(path) {
+            if metadata.permissions().mode() & 0o077 != 0 {
+                anyhow::bail!("{} is readable by other users; delete it before generating a new key", path.display());
+            }
+        }
+    }
+    options.open(path)?.write_all(contents.as_bytes())?;
+    Ok(())
+}
+
+/// Open an `.afm` and accept it only if the trust store does.
+fn load_module(path: &Path, trust: Option<PathBuf>, require_signature: bool) -> anyhow::Result<Program> {
+    let trust_path = trust.unwrap_or_else(|| aeroflow_home().join("trusted_keys"));
+    let mut store = if trust_path.exists() {
+        TrustStore::parse(&fs::read_to_string(&trust_path)?)?
+    } else {
+        TrustStore::new()
+    };
+    store.require_signatures(require_signature);
+    // SAFETY: modules are build outputs that nothing rewrites during a run
+    let (program, metadata) = unsafe { Program::open(path, &store)? };
+    println!("📦 Loaded module {}@{}", metadata.name, metadata.version);
+    Ok(program)
+}
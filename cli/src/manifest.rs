@@ -0,0 +1,140 @@
+// AeroFlow CLI - Project Manifest
+// Typed view of aeroflow.toml
+
+use aeroflow_compiler::afm::AfmMetadata;
+use aeroflow_compiler::ir::parse_size;
+use aeroflow_runtime::quota::{GlobalQuota, Quota};
+use aeroflow_runtime::{Capabilities, CapabilityGuard};
+use serde::Deserialize;
+use std::path::Path;
+
+pub const MANIFEST_FILE: &str = "aeroflow.toml";
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Manifest {
+    #[serde(default)]
+    pub package: Package,
+    #[serde(default)]
+    pub runtime: RuntimeConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Package {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub version: String,
+    #[serde(default)]
+    pub author: String,
+    /// Capabilities the module asks for, e.g. `["NET", "FS_READ"]`.
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+    /// Environment variables `env()` may read; naming any grants `ENV_READ` for just those.
+    #[serde(default)]
+    pub env: Vec<String>,
+}
+
+/// The `[runtime]` section. Each limit is a number or `"unlimited"`, and
+/// memory may also be a size such as `"512MB"`.
+#[derive(Debug, Default, Deserialize)]
+pub struct RuntimeConfig {
+    /// Memory each actor may hold, and use handling one message.
+    #[serde(default)]
+    pub memory: Option<Limit>,
+    /// Instructions each actor may run handling one message.
+    #[serde(default)]
+    pub instructions: Option<Limit>,
+    /// Memory all actors together may hold.
+    #[serde(default)]
+    pub total_memory: Option<Limit>,
+    /// Actors that may run at once, `main` included.
+    #[serde(default)]
+    pub actors: Option<Limit>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Limit {
+    Number(u64),
+    Text(String),
+}
+
+impl RuntimeConfig {
+    /// Limits for each actor.
+    pub fn quota(&self) -> anyhow::Result<Quota> {
+        Ok(Quota {
+            memory: resolve(&self.memory, "memory", true)?,
+            instructions: resolve(&self.instructions, "instructions", false)?,
+        })
+    }
+
+    /// Limits over all actors together.
+    pub fn global_quota(&self) -> anyhow::Result<GlobalQuota> {
+        Ok(GlobalQuota {
+            memory: resolve(&self.total_memory, "total_memory", true)?,
+            actors: resolve(&self.actors, "actors", false)?,
+        })
+    }
+}
+
+/// `limit` as a number, `None` for unset or unlimited.
+fn resolve(limit: &Option<Limit>, key: &str, sizes: bool) -> anyhow::Result<Option<u64>> {
+    match limit {
+        None => Ok(None),
+        Some(Limit::Number(n)) => Ok(Some(*n)),
+        Some(Limit::Text(text)) => parse_limit(text, sizes).ok_or_else(|| {
+            let expected = if sizes { "a size such as 64KB, 512MB or unlimited" } else { "a number or unlimited" };
+            anyhow::anyhow!("invalid [runtime] {} '{}': expected {}", key, text, expected)
+        }),
+    }
+}
+
+/// `unlimited` as `Some(None)`, otherwise a count or, if `sizes`, a size
+/// such as `64KB`.
+pub fn parse_limit(text: &str, sizes: bool) -> Option<Option<u64>> {
+    if text.trim().eq_ignore_ascii_case("unlimited") {
+        return Some(None);
+    }
+    if sizes { parse_size(text).map(Some) } else { text.trim().parse().ok().map(Some) }
+}
+
+impl Manifest {
+    /// Read `aeroflow.toml` from `dir`; a missing file yields an empty manifest.
+    pub fn load(dir: &Path) -> anyhow::Result<Self> {
+        let path = dir.join(MANIFEST_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = std::fs::read_to_string(&path)?;
+        toml::from_str(&text).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))
+    }
+
+    /// What `run` grants every actor: the `[package]` capabilities and
+    /// `env` variables, plus `allow` and `allow_env` from the command line.
+    /// `ENV_READ` with no variables named allows any.
+    pub fn grants(&self, allow: &[String], allow_env: &[String]) -> anyhow::Result<CapabilityGuard> {
+        // Actors may always do what they do inside the runtime
+        let mut allowed = Capabilities::ACTOR;
+        for name in self.package.capabilities.iter().chain(allow) {
+            allowed |= Capabilities::parse(name).ok_or_else(|| {
+                anyhow::anyhow!("unknown capability '{}': expected NET, ALL or one of {}", name, Capabilities::all().names().join(", "))
+            })?;
+        }
+        let env: Vec<&String> = self.package.env.iter().chain(allow_env).collect();
+        if env.is_empty() {
+            return Ok(CapabilityGuard::new(allowed));
+        }
+        Ok(CapabilityGuard::new(allowed | Capabilities::ENV_READ).with_env(env.into_iter().cloned()))
+    }
+
+    /// Module metadata, falling back to `default_name` when the manifest has none.
+    pub fn metadata(&self, default_name: &str) -> AfmMetadata {
+        let package = &self.package;
+        AfmMetadata {
+            name: if package.name.is_empty() { default_name.to_string() } else { package.name.clone() },
+            version: if package.version.is_empty() { "0.0.0".to_string() } else { package.version.clone() },
+            author: package.author.clone(),
+            capabilities: package.capabilities.clone(),
+        }
+    }
+}
@@ -0,0 +1,58 @@
+// AeroFlow CLI - Terminal UI driver
+// Maps crossterm key events onto the runtime's TUI session
+
+use aeroflow_compiler::ir::Chunk;
+use aeroflow_runtime::tui::{Key, TuiSession};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::{cursor, execute, queue, terminal};
+use std::io::Write;
+
+/// Run the first `screen` of `chunk` interactively until Esc.
+pub fn run(chunk: &Chunk) -> anyhow::Result<()> {
+    let screen = chunk.screens.first()
+        .ok_or_else(|| anyhow::anyhow!("no `screen` declared; nothing to show in the terminal"))?;
+    let (width, height) = terminal::size()?;
+    let mut session = TuiSession::mount(chunk, &screen.name, width as usize, height as usize)?;
+
+    let mut stdout = std::io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?;
+    let result = event_loop(&mut session, &mut stdout);
+    execute!(stdout, cursor::Show, terminal::LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+    result
+}
+
+fn event_loop(session: &mut TuiSession, stdout: &mut std::io::Stdout) -> anyhow::Result<()> {
+    loop {
+        let frame = session.render();
+        queue!(stdout, cursor::MoveTo(0, 0))?;
+        for (row, line) in frame.lines.iter().enumerate() {
+            queue!(stdout, cursor::MoveTo(0, row as u16))?;
+            write!(stdout, "{}", line)?;
+        }
+        stdout.flush()?;
+
+        let key = match event::read()? {
+            Event::Key(k) if k.kind != KeyEventKind::Release => match k.code {
+                KeyCode::Char(c) => Key::Char(c),
+                KeyCode::Backspace => Key::Backspace,
+                KeyCode::Enter => Key::Enter,
+                KeyCode::Tab => Key::Tab,
+                KeyCode::BackTab => Key::BackTab,
+                KeyCode::Up => Key::Up,
+                KeyCode::Down => Key::Down,
+                KeyCode::Esc => Key::Esc,
+                _ => continue,
+            },
+            Event::Resize(w, h) => {
+                session.resize(w as usize, h as usize);
+                continue;
+            }
+            _ => continue,
+        };
+        if !session.press(key) {
+            return Ok(());
+        }
+    }
+}
@@ -0,0 +1,13 @@
+use std::process::Command;
+
+#[test]
+fn syntax_errors_are_reported_without_a_panic() {
+    let path = std::env::temp_dir().join(format!("aeroflow-cli-syntax-{}.aefl", std::process::id()));
+    std::fs::write(&path, "actor Broken {\n    on Go() {\n        let x =\n    }\n}\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_aeroflow-cli")).arg("run").arg("--source").arg(&path).output().unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stderr.trim_end(), "❌ Compile Error: Syntax error: Unexpected token in primary: RBrace (slice: })");
+}
@@ -1,13 +1,35 @@
 // AeroFlow Module (.afm) Binary Format
 // Designed for memory mapping and zero-copy loading
+//
+// Layout (all integers little-endian):
+//   [0..32)            AfmHeader
+//   metadata_offset    bincode AfmMetadata
+//   ir_offset          bincode Chunk (ARCH_IR) or code image (ARCH_IMAGE),
+//                      8-byte aligned
+//   ir_offset+ir_len   SHA-256 of everything before it
+//   signature_offset   ed25519 public key (32) + signature over the hash (64), if FLAG_SIGNED
 
 use serde::{Serialize, Deserialize};
-use crate::ir::Chunk;
+use crate::image::{self, AlignedBytes, CodeImage, StableBytes};
+use crate::ir::{ActorDef, Chunk, Code, Function, LineEntry, Op, ScreenDef, SupervisorDef};
+use anyhow::{anyhow, bail, Context as _};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use sha2::{Digest, Sha256};
 
 pub const AFM_MAGIC: [u8; 4] = *b"AFM1";
+pub const AFM_VERSION: u16 = 3;
+/// Portable IR; native sections would use other values.
+pub const ARCH_IR: u16 = 0;
+/// Aligned code image (see `image.rs`) that can be executed in place.
+pub const ARCH_IMAGE: u16 = 1;
+pub const FLAG_SIGNED: u32 = 1;
+
+pub const HEADER_LEN: usize = 32;
+pub const HASH_LEN: usize = 32;
+pub const SIGNATURE_BLOCK_LEN: usize = 32 + 64;
 
 #[repr(C)]
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct AfmHeader {
     pub magic: [u8; 4],
     pub version: u16,
@@ -20,7 +42,56 @@ pub struct AfmHeader {
     pub signature_offset: u32,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl AfmHeader {
+    pub fn to_bytes(&self) -> [u8; HEADER_LEN] {
+        let mut out = [0u8; HEADER_LEN];
+        out[0..4].copy_from_slice(&self.magic);
+        out[4..6].copy_from_slice(&self.version.to_le_bytes());
+        out[6..8].copy_from_slice(&self.arch.to_le_bytes());
+        let words = [self.flags, self.metadata_offset, self.metadata_len, self.ir_offset, self.ir_len, self.signature_offset];
+        for (i, word) in words.iter().enumerate() {
+            out[8 + i * 4..12 + i * 4].copy_from_slice(&word.to_le_bytes());
+        }
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        if bytes.len() < HEADER_LEN {
+            bail!("truncated header ({} bytes)", bytes.len());
+        }
+        let word = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        let header = Self {
+            magic: bytes[0..4].try_into().unwrap(),
+            version: u16::from_le_bytes([bytes[4], bytes[5]]),
+            arch: u16::from_le_bytes([bytes[6], bytes[7]]),
+            flags: word(8),
+            metadata_offset: word(12),
+            metadata_len: word(16),
+            ir_offset: word(20),
+            ir_len: word(24),
+            signature_offset: word(28),
+        };
+        if header.magic != AFM_MAGIC {
+            bail!("not an .afm module (bad magic)");
+        }
+        if header.version != AFM_VERSION {
+            bail!("unsupported .afm version {}", header.version);
+        }
+        Ok(header)
+    }
+
+    pub fn is_signed(&self) -> bool {
+        self.flags & FLAG_SIGNED != 0
+    }
+
+    /// Where the content hash starts, right after the IR section.
+    fn hash_offset(&self) -> anyhow::Result<usize> {
+        (self.ir_offset as usize).checked_add(self.ir_len as usize)
+            .ok_or_else(|| anyhow!("IR section {}+{} overflows", self.ir_offset, self.ir_len))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct AfmMetadata {
     pub name: String,
     pub version: String,
@@ -34,14 +105,281 @@ pub struct AfmModule {
     pub chunk: Chunk,
 }
 
+/// What a parsed module file says about itself, before any trust decision.
+#[derive(Debug)]
+pub struct AfmFile {
+    pub header: AfmHeader,
+    pub module: AfmModule,
+    pub hash: [u8; HASH_LEN],
+    pub signer: Option<VerifyingKey>,
+}
+
 impl AfmModule {
+    /// Encode the IR with bincode, without a signature.
     pub fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
-        let bytes = bincode::serialize(self)?;
+        self.encode(ARCH_IR, None)
+    }
+
+    /// Encode the IR with bincode and sign the content hash with `key`.
+    pub fn to_signed_bytes(&self, key: &SigningKey) -> anyhow::Result<Vec<u8>> {
+        self.encode(ARCH_IR, Some(key))
+    }
+
+    /// Encode the IR section as `arch`, signing if `key` is given.
+    pub fn encode(&self, arch: u16, key: Option<&SigningKey>) -> anyhow::Result<Vec<u8>> {
+        let metadata = bincode::serialize(&self.metadata)?;
+        let ir = match arch {
+            ARCH_IR => bincode::serialize(&self.chunk)?,
+            ARCH_IMAGE => image::encode(&self.chunk)?,
+            other => bail!("unknown .afm arch {}", other),
+        };
+        let metadata_offset = HEADER_LEN;
+        let ir_offset = (metadata_offset + metadata.len()).next_multiple_of(image::IMAGE_ALIGN);
+        let hash_offset = ir_offset + ir.len();
+        let header = AfmHeader {
+            magic: AFM_MAGIC,
+            version: AFM_VERSION,
+            arch,
+            flags: if key.is_some() { FLAG_SIGNED } else { 0 },
+            metadata_offset: u32::try_from(metadata_offset)?,
+            metadata_len: u32::try_from(metadata.len())?,
+            ir_offset: u32::try_from(ir_offset)?,
+            ir_len: u32::try_from(ir.len())?,
+            signature_offset: if key.is_some() { u32::try_from(hash_offset + HASH_LEN)? } else { 0 },
+        };
+
+        let mut bytes = Vec::with_capacity(hash_offset + HASH_LEN + SIGNATURE_BLOCK_LEN);
+        bytes.extend_from_slice(&header.to_bytes());
+        bytes.extend_from_slice(&metadata);
+        bytes.resize(ir_offset, 0);
+        bytes.extend_from_slice(&ir);
+        let hash: [u8; HASH_LEN] = Sha256::digest(&bytes).into();
+        bytes.extend_from_slice(&hash);
+        if let Some(key) = key {
+            bytes.extend_from_slice(key.verifying_key().as_bytes());
+            bytes.extend_from_slice(&key.sign(&hash).to_bytes());
+        }
         Ok(bytes)
     }
 
+    /// Decode a module, checking its content hash and (if present) that the
+    /// signature matches the embedded key. Does not decide whether to trust it.
     pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
-        let module = bincode::deserialize(bytes)?;
-        Ok(module)
+        Ok(Self::inspect(bytes)?.module)
+    }
+
+    /// Decode and accept the module only if `trust` does.
+    pub fn load(bytes: &[u8], trust: &TrustStore) -> anyhow::Result<Self> {
+        let file = Self::inspect(bytes)?;
+        trust.check(&file)?;
+        Ok(file.module)
+    }
+
+    pub fn inspect(bytes: &[u8]) -> anyhow::Result<AfmFile> {
+        let checked = verify(bytes)?;
+        let header = checked.header;
+        let ir = bytes.get(header.ir_offset as usize..header.hash_offset()?)
+            .ok_or_else(|| anyhow!("IR section out of bounds"))?;
+        let chunk = match header.arch {
+            ARCH_IR => bincode::deserialize(ir).context("invalid IR section")?,
+            _ => CodeImage::parse(AlignedBytes::copy_from(ir), 0, ir.len())?.to_chunk(),
+        };
+        Ok(AfmFile {
+            header,
+            module: AfmModule { metadata: checked.metadata, chunk },
+            hash: checked.hash,
+            signer: checked.signer,
+        })
+    }
+}
+
+/// Header, metadata and signer of a module whose hash and signature check out.
+struct Checked {
+    header: AfmHeader,
+    metadata: AfmMetadata,
+    hash: [u8; HASH_LEN],
+    signer: Option<VerifyingKey>,
+}
+
+fn verify(bytes: &[u8]) -> anyhow::Result<Checked> {
+    let header = AfmHeader::from_bytes(bytes)?;
+    if header.arch != ARCH_IR && header.arch != ARCH_IMAGE {
+        bail!("unknown .afm arch {}", header.arch);
+    }
+    let hash_offset = header.hash_offset()?;
+    let section = |start: usize, len: usize| {
+        start.checked_add(len)
+            .and_then(|end| bytes.get(start..end))
+            .ok_or_else(|| anyhow!("section {}+{} out of bounds", start, len))
+    };
+    let metadata = section(header.metadata_offset as usize, header.metadata_len as usize)?;
+    section(header.ir_offset as usize, header.ir_len as usize)?;
+    let stored: [u8; HASH_LEN] = section(hash_offset, HASH_LEN)
+        .map_err(|_| anyhow!("missing content hash"))?
+        .try_into()?;
+    let actual: [u8; HASH_LEN] = Sha256::digest(section(0, hash_offset)?).into();
+    if stored != actual {
+        bail!("content hash mismatch: module is corrupt or was modified");
+    }
+
+    let signer = if header.is_signed() {
+        let block = section(header.signature_offset as usize, SIGNATURE_BLOCK_LEN)
+            .map_err(|_| anyhow!("truncated signature"))?;
+        let key = VerifyingKey::from_bytes(block[..32].try_into()?).context("invalid signer key")?;
+        let signature = Signature::from_bytes(block[32..].try_into()?);
+        key.verify(&stored, &signature).map_err(|_| anyhow!("signature does not match content"))?;
+        Some(key)
+    } else {
+        None
+    };
+
+    Ok(Checked {
+        header,
+        metadata: bincode::deserialize(metadata).context("invalid metadata section")?,
+        hash: stored,
+        signer,
+    })
+}
+
+/// A trusted `ARCH_IMAGE` module executed in place from `B`, typically a
+/// memory map shared by every actor running it.
+pub struct AfmImage<B> {
+    pub header: AfmHeader,
+    pub metadata: AfmMetadata,
+    pub hash: [u8; HASH_LEN],
+    pub signer: Option<VerifyingKey>,
+    code: CodeImage<B>,
+}
+
+impl<B: StableBytes> AfmImage<B> {
+    /// Verify hash, signature and trust, then validate the code image once.
+    pub fn load(bytes: B, trust: &TrustStore) -> anyhow::Result<Self> {
+        let checked = verify(bytes.as_ref())?;
+        trust.check_signer(&checked.metadata.name, checked.signer.as_ref())?;
+        let header = checked.header;
+        if header.arch != ARCH_IMAGE {
+            bail!("module '{}' is not an executable image (arch {})", checked.metadata.name, header.arch);
+        }
+        let code = CodeImage::parse(bytes, header.ir_offset as usize, header.ir_len as usize)?;
+        Ok(Self { header, metadata: checked.metadata, hash: checked.hash, signer: checked.signer, code })
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        self.code.to_chunk()
+    }
+}
+
+impl<B: StableBytes> Code for AfmImage<B> {
+    fn len(&self) -> usize {
+        self.code.len()
+    }
+
+    fn op(&self, ip: usize) -> Op<'_> {
+        self.code.op(ip)
+    }
+
+    fn functions(&self) -> &[Function] {
+        self.code.functions()
+    }
+
+    fn screens(&self) -> &[ScreenDef] {
+        self.code.screens()
+    }
+
+    fn actors(&self) -> &[ActorDef] {
+        self.code.actors()
+    }
+
+    fn lines(&self) -> &[LineEntry] {
+        self.code.lines()
+    }
+
+    fn supervisors(&self) -> &[SupervisorDef] {
+        self.code.supervisors()
+    }
+}
+
+/// Public keys whose signatures are accepted when loading modules.
+///
+/// An empty store accepts any intact module. Once keys are added, modules
+/// must be signed by one of them; `require_signatures` also rejects unsigned
+/// modules when there are none.
+#[derive(Debug, Clone, Default)]
+pub struct TrustStore {
+    keys: Vec<VerifyingKey>,
+    require_signatures: bool,
+}
+
+impl TrustStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse one hex-encoded public key per line; `#` starts a comment.
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let mut store = Self::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if !line.is_empty() {
+                store.trust(parse_public_key(line).with_context(|| format!("trust store line {}", n + 1))?);
+            }
+        }
+        Ok(store)
+    }
+
+    pub fn trust(&mut self, key: VerifyingKey) {
+        if !self.keys.contains(&key) {
+            self.keys.push(key);
+        }
+    }
+
+    pub fn require_signatures(&mut self, required: bool) {
+        self.require_signatures = required;
+    }
+
+    pub fn keys(&self) -> &[VerifyingKey] {
+        &self.keys
+    }
+
+    pub fn check(&self, file: &AfmFile) -> anyhow::Result<()> {
+        self.check_signer(&file.module.metadata.name, file.signer.as_ref())
+    }
+
+    pub fn check_signer(&self, module: &str, signer: Option<&VerifyingKey>) -> anyhow::Result<()> {
+        match signer {
+            // Stripping the signature must not get a module past a keyed store
+            None if self.require_signatures || !self.keys.is_empty() => bail!("module '{}' is not signed", module),
+            None => Ok(()),
+            Some(key) if !self.keys.is_empty() && !self.keys.contains(key) => {
+                bail!("module '{}' is signed by untrusted key {}", module, to_hex(key.as_bytes()))
+            }
+            Some(_) => Ok(()),
+        }
+    }
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn from_hex(text: &str) -> anyhow::Result<Vec<u8>> {
+    let text = text.trim();
+    if !text.len().is_multiple_of(2) {
+        bail!("odd-length hex string");
     }
+    let digit = |c: u8| (c as char).to_digit(16).ok_or_else(|| anyhow!("invalid hex"));
+    text.as_bytes().chunks(2)
+        .map(|pair| Ok((digit(pair[0])? * 16 + digit(pair[1])?) as u8))
+        .collect()
+}
+
+/// Key files hold the 32-byte secret seed as hex.
+pub fn parse_signing_key(text: &str) -> anyhow::Result<SigningKey> {
+    let seed: [u8; 32] = from_hex(text)?.try_into().map_err(|_| anyhow!("signing key must be 32 bytes"))?;
+    Ok(SigningKey::from_bytes(&seed))
+}
+
+pub fn parse_public_key(text: &str) -> anyhow::Result<VerifyingKey> {
+    let bytes: [u8; 32] = from_hex(text)?.try_into().map_err(|_| anyhow!("public key must be 32 bytes"))?;
+    VerifyingKey::from_bytes(&bytes).context("invalid public key")
 }
@@ -58,6 +58,16 @@ pub enum UIWidget {
         label: String,
         on_click: Expr,
     },
+    Column(Vec<UIWidget>),
+    Row(Vec<UIWidget>),
+    List(Expr),
+    Image(Expr),
+    Checkbox {
+        label: String,
+        bind: String,
+    },
+    /// `Name {}`: another screen, rendered by its own actor.
+    Screen(String),
 }
 
 #[derive(Debug, Clone)]
@@ -91,6 +101,22 @@ pub struct EventHandler {
     pub body: Vec<Stmt>,
 }
 
+/// A `supervise` block. Unnamed blocks are named by codegen.
+#[derive(Debug, Clone)]
+pub struct SuperviseBlock {
+    pub name: Option<String>,
+    pub strategy: String,
+    /// `max` and `within` limits.
+    pub options: Vec<(String, f64)>,
+    pub children: Vec<SupervisedChild>,
+}
+
+#[derive(Debug, Clone)]
+pub enum SupervisedChild {
+    Actor { name: String, args: Vec<Expr> },
+    Supervisor(SuperviseBlock),
+}
+
 #[derive(Debug, Clone)]
 pub enum Stmt {
     Fn {
@@ -106,7 +132,11 @@ pub enum Stmt {
     },
     Actor {
         name: String,
+        params: Vec<String>,
+        handlers: Vec<EventHandler>,
         body: Vec<Stmt>,
+        /// `@quota` limits, in bytes for `memory`.
+        quota: Vec<(String, f64)>,
     },
     Agent {
         name: String,
@@ -119,6 +149,7 @@ pub enum Stmt {
         source: String,
         body: Vec<Stmt>,
     },
+    Supervise(SuperviseBlock),
     FromImport {
         package: String,
         layer: String,
@@ -134,4 +165,6 @@ pub enum Stmt {
     While { condition: Expr, body: Vec<Stmt> },
     Return(Expr),
     Expr(Expr),
+    /// The next statement starts on this source line.
+    Line(u32),
 }
@@ -1,33 +1,128 @@
 // AeroFlow Compiler - Codegen
 // AST -> IR
 
-use crate::ast::{Expr, Stmt};
-use crate::ir::{Instr, Chunk, Value};
+use crate::ast::{Expr, Stmt, SupervisedChild as ChildSyntax, SuperviseBlock, UIWidget};
+use crate::ir::{Instr, Chunk, ActorDef, Function, Quota, ScreenDef, StateRef, SupervisedChild, SupervisorDef, TimelineEntry, UiTemplate, Value, DEFAULT_MAX_RESTARTS, DEFAULT_RESTART_WINDOW};
 use crate::lexer::TokenKind;
 
+struct PendingFn {
+    name: String,
+    params: Vec<String>,
+    body: Vec<Stmt>,
+    screen: Option<String>,
+}
+
+#[derive(Default)]
 pub struct Codegen {
     chunk: Chunk,
+    pending_fns: Vec<PendingFn>,
+    screen: Option<String>,
+    /// `supervise` blocks seen so far, for naming.
+    supervisors: usize,
 }
 
 impl Codegen {
     pub fn new() -> Self {
-        Self { chunk: Chunk::new() }
+        Self::default()
     }
 
     pub fn compile(mut self, stmts: Vec<Stmt>) -> Chunk {
         for stmt in stmts {
             self.compile_stmt(stmt);
         }
+
+        // Function bodies are laid out after the main program, which halts first
+        if !self.pending_fns.is_empty() {
+            self.chunk.emit(Instr::Return);
+        }
+        while !self.pending_fns.is_empty() {
+            let PendingFn { name, params, body, screen } = self.pending_fns.remove(0);
+            let entry = self.chunk.instrs.len();
+            self.chunk.functions.push(Function { name, params, entry });
+            self.screen = screen;
+            for s in body { self.compile_stmt(s); }
+            self.chunk.emit(Instr::LoadConst(Value::Nil));
+            self.chunk.emit(Instr::Return);
+        }
+        self.screen = None;
         self.chunk
     }
 
     fn compile_stmt(&mut self, stmt: Stmt) {
         match stmt {
-            Stmt::Fn { .. } => {}
-            Stmt::Screen { body, .. } => {
-                for s in body { self.compile_stmt(s); }
+            Stmt::Fn { name, params, body, .. } => {
+                let params = params.into_iter().map(|(p, _)| p).collect();
+                self.pending_fns.push(PendingFn { name, params, body, screen: self.screen.clone() });
+            }
+            Stmt::Screen { name, body } => {
+                // State declarations become `<Screen>.init`, everything else `<Screen>.render`;
+                // the runtime mounts screens as actors rather than running them inline
+                let outer = self.screen.replace(name.clone());
+                let mut init = Vec::new();
+                let mut state = Vec::new();
+                let mut render = Vec::new();
+                // A line marker goes wherever the statement after it does
+                let mut line = None;
+                for s in body {
+                    match s {
+                        Stmt::Line(_) => line = Some(s),
+                        Stmt::Fn { .. } => self.compile_stmt(s),
+                        Stmt::VarDecl { ref name, .. } => {
+                            state.push(name.clone());
+                            init.extend(line.take());
+                            init.push(s);
+                        }
+                        _ => {
+                            render.extend(line.take());
+                            render.push(s);
+                        }
+                    }
+                }
+                self.screen = outer;
+                let def = ScreenDef {
+                    init: format!("{}.init", name),
+                    render: format!("{}.render", name),
+                    name: name.clone(),
+                    state,
+                };
+                self.pending_fns.push(PendingFn { name: def.init.clone(), params: Vec::new(), body: init, screen: Some(name.clone()) });
+                self.pending_fns.push(PendingFn { name: def.render.clone(), params: Vec::new(), body: render, screen: Some(name) });
+                self.chunk.screens.push(def);
+            }
+            Stmt::Actor { name, params, handlers, body, quota } => {
+                // Top-level statements run once as `<Actor>.init`; handlers become `<Actor>.<Event>`
+                let mut init = Vec::new();
+                let mut state = Vec::new();
+                for s in body {
+                    match s {
+                        Stmt::Fn { .. } => self.compile_stmt(s),
+                        Stmt::VarDecl { ref name, .. } => {
+                            state.push(name.clone());
+                            init.push(s);
+                        }
+                        _ => init.push(s),
+                    }
+                }
+                let limit = |key: &str| quota.iter().rev().find(|(k, _)| k == key).map(|(_, v)| *v as u64);
+                let def = ActorDef {
+                    init: format!("{}.init", name),
+                    handlers: handlers.iter().map(|h| h.name.clone()).collect(),
+                    name: name.clone(),
+                    state,
+                    params,
+                    quota: Quota { memory: limit("memory"), instructions: limit("instructions") },
+                };
+                self.pending_fns.push(PendingFn { name: def.init.clone(), params: Vec::new(), body: init, screen: None });
+                for handler in handlers {
+                    let params = handler.params.into_iter().map(|(p, _)| p).collect();
+                    self.pending_fns.push(PendingFn { name: format!("{}.{}", name, handler.name), params, body: handler.body, screen: None });
+                }
+                self.chunk.actors.push(def);
+            }
+            Stmt::Supervise(block) => {
+                let def = self.supervisor(block);
+                self.chunk.supervisors.push(def);
             }
-            Stmt::Actor { .. } => {}
             Stmt::Agent { .. } => {}
             Stmt::Model { .. } => {}
             Stmt::FromImport { .. } => {}
@@ -41,26 +136,24 @@ impl Codegen {
                         self.compile_expr(&expr);
                         self.chunk.emit(Instr::Render);
                     }
-                    crate::ast::RenderExpression::Timeline(_) => {
-                        // For now, Timeline is a hint for the IDE/Scheduler
-                        self.chunk.emit(Instr::RenderTimeline);
+                    crate::ast::RenderExpression::Timeline(timeline) => {
+                        // Payloads are evaluated in declaration order; the runtime pops them
+                        let mut entries = Vec::with_capacity(timeline.events.len());
+                        for event in timeline.events {
+                            self.compile_expr(&event.payload);
+                            entries.push(TimelineEntry { from: event.from, to: event.to, at: event.at_ms });
+                        }
+                        self.chunk.emit(Instr::RenderTimeline(entries));
                     }
-                    crate::ast::RenderExpression::DistributedState(_) => {
-                        self.chunk.emit(Instr::RenderState);
+                    crate::ast::RenderExpression::DistributedState(state) => {
+                        let refs = state.state_refs.into_iter()
+                            .map(|r| StateRef { node: r.node, field: r.field })
+                            .collect();
+                        self.chunk.emit(Instr::RenderState(refs));
                     }
                     crate::ast::RenderExpression::UIWidgets(widgets) => {
-                        for widget in widgets {
-                            match widget {
-                                crate::ast::UIWidget::Text(expr) => {
-                                    self.compile_expr(&expr);
-                                }
-                                crate::ast::UIWidget::Input { .. } => {} // Bindings handled by runtime
-                                crate::ast::UIWidget::Button { label: _, on_click } => {
-                                    self.compile_expr(&on_click);
-                                }
-                            }
-                        }
-                        self.chunk.emit(Instr::RenderUI);
+                        let widgets = widgets.into_iter().map(|w| self.compile_widget(w)).collect();
+                        self.chunk.emit(Instr::RenderUI { screen: self.screen.clone(), widgets });
                     }
                 }
             }
@@ -103,7 +196,77 @@ impl Codegen {
             }
             Stmt::Expr(expr) => {
                 self.compile_expr(&expr);
+                self.chunk.emit(Instr::Pop);
+            }
+            Stmt::Line(line) => self.chunk.mark_line(line),
+        }
+    }
+
+    /// Unnamed supervisors are `supervisor1`, `supervisor2`, ... in source order.
+    fn supervisor(&mut self, block: SuperviseBlock) -> SupervisorDef {
+        self.supervisors += 1;
+        let name = block.name.unwrap_or_else(|| format!("supervisor{}", self.supervisors));
+        let option = |key: &str| block.options.iter().rev().find(|(k, _)| k == key).map(|(_, v)| *v);
+        let max_restarts = option("max").map_or(DEFAULT_MAX_RESTARTS, |n| n as u32);
+        let within = option("within").map_or(DEFAULT_RESTART_WINDOW, |n| n as u64);
+        let children = block.children.into_iter().map(|child| match child {
+            ChildSyntax::Actor { name, args } => {
+                // The parser only accepts literal arguments
+                let args = args.into_iter().map(|arg| match arg {
+                    Expr::Number(n) => Value::Number(n),
+                    Expr::String(s) => Value::String(s),
+                    Expr::Bool(b) => Value::Bool(b),
+                    _ => Value::Nil,
+                }).collect();
+                SupervisedChild::Actor { name, args }
+            }
+            ChildSyntax::Supervisor(block) => SupervisedChild::Supervisor(self.supervisor(block)),
+        }).collect();
+        SupervisorDef { name, strategy: block.strategy, max_restarts, within, children }
+    }
+
+    /// Emit the widget's dynamic values in pre-order and return its template.
+    fn compile_widget(&mut self, widget: UIWidget) -> UiTemplate {
+        match widget {
+            UIWidget::Text(expr) => {
+                self.compile_expr(&expr);
+                UiTemplate::Text
+            }
+            UIWidget::Input { bind } => {
+                self.chunk.emit(Instr::LoadVar(bind.clone()));
+                UiTemplate::Input { bind }
+            }
+            UIWidget::Button { label, on_click } => {
+                // The handler runs on click, not at render; only its arguments are captured now
+                let (action, args) = match on_click {
+                    Expr::Call { name, args } => (name, args.into_iter().map(|a| *a).collect()),
+                    Expr::Ident(name) => (name, Vec::new()),
+                    other => unreachable!("the parser only accepts calls as onClick, got {:?}", other),
+                };
+                for arg in &args {
+                    self.compile_expr(arg);
+                }
+                UiTemplate::Button { label, action, argc: args.len() }
+            }
+            UIWidget::Column(children) => {
+                UiTemplate::Column(children.into_iter().map(|c| self.compile_widget(c)).collect())
+            }
+            UIWidget::Row(children) => {
+                UiTemplate::Row(children.into_iter().map(|c| self.compile_widget(c)).collect())
+            }
+            UIWidget::List(items) => {
+                self.compile_expr(&items);
+                UiTemplate::List
+            }
+            UIWidget::Image(src) => {
+                self.compile_expr(&src);
+                UiTemplate::Image
+            }
+            UIWidget::Checkbox { label, bind } => {
+                self.chunk.emit(Instr::LoadVar(bind.clone()));
+                UiTemplate::Checkbox { label, bind }
             }
+            UIWidget::Screen(name) => UiTemplate::Screen { name },
         }
     }
 
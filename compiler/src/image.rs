@@ -0,0 +1,435 @@
+// AeroFlow Compiler - Code Image
+// Aligned instruction stream and constant pool, executed in place
+//
+// Section layout (offsets relative to the section, all little-endian):
+//   [0..32)        8 x u32: magic, instr_count, const_count, string_count,
+//                  string_bytes, tables_len, 0, 0
+//   instrs         instr_count x [u32; 4]: opcode, a, b, reserved
+//   consts         const_count x [u64; 2]: tag, bits
+//   strings        string_count x [u32; 2]: offset, len (into string data)
+//   string data    UTF-8, padded to 8 bytes
+//   tables         bincode `Tables`: declarations and rich operands
+//
+// The section must start 8-byte aligned. Everything is bounds- and
+// UTF-8-checked once in `CodeImage::parse`; afterwards `op()` only indexes.
+
+use crate::ir::{ActorDef, Chunk, Code, Const, Function, Instr, LineEntry, Op, ScreenDef, StateRef, SupervisorDef, TimelineEntry, UiTemplate, Value};
+use anyhow::{bail, Context as _};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ops::Range;
+
+pub const IMAGE_MAGIC: u32 = u32::from_le_bytes(*b"AFIM");
+pub const IMAGE_HEADER_LEN: usize = 32;
+pub const INSTR_LEN: usize = 16;
+pub const CONST_LEN: usize = 16;
+pub const STRING_LEN: usize = 8;
+/// Alignment the section (and so the backing buffer) must have.
+pub const IMAGE_ALIGN: usize = 8;
+
+mod opcode {
+    pub const LOAD_CONST: u32 = 0;
+    pub const LOAD_VAR: u32 = 1;
+    pub const STORE_VAR: u32 = 2;
+    pub const LOAD_ENV: u32 = 3;
+    pub const LOAD_TIME: u32 = 4;
+    pub const LOAD_RAND: u32 = 5;
+    pub const ADD: u32 = 6;
+    pub const SUB: u32 = 7;
+    pub const MUL: u32 = 8;
+    pub const DIV: u32 = 9;
+    pub const EQ: u32 = 10;
+    pub const GT: u32 = 11;
+    pub const LT: u32 = 12;
+    pub const CALL: u32 = 13;
+    pub const SPAWN: u32 = 14;
+    pub const JUMP: u32 = 15;
+    pub const JUMP_IF_FALSE: u32 = 16;
+    pub const POP: u32 = 17;
+    pub const RETURN: u32 = 18;
+    pub const RENDER: u32 = 19;
+    pub const RENDER_TIMELINE: u32 = 20;
+    pub const RENDER_STATE: u32 = 21;
+    pub const RENDER_UI: u32 = 22;
+}
+
+mod tag {
+    pub const NIL: u64 = 0;
+    pub const NUMBER: u64 = 1;
+    pub const BOOL: u64 = 2;
+    pub const STR: u64 = 3;
+    /// Lists and maps, decoded into `Tables::values` at load.
+    pub const VALUE: u64 = 4;
+}
+
+/// Declarations and the operands too irregular for fixed-size records.
+/// Small next to the code, so they are decoded at load.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Tables {
+    functions: Vec<Function>,
+    screens: Vec<ScreenDef>,
+    actors: Vec<ActorDef>,
+    timelines: Vec<Vec<TimelineEntry>>,
+    states: Vec<Vec<StateRef>>,
+    uis: Vec<UiBlock>,
+    values: Vec<Value>,
+    lines: Vec<LineEntry>,
+    supervisors: Vec<SupervisorDef>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct UiBlock {
+    screen: Option<String>,
+    widgets: Vec<UiTemplate>,
+}
+
+/// Byte buffers whose contents never change while borrowed, so offsets
+/// validated once stay valid.
+///
+/// # Safety
+/// `as_ref` must return the same bytes, unmodified, for the value's lifetime.
+pub unsafe trait StableBytes: AsRef<[u8]> {}
+
+// SAFETY: owned and shared-borrowed bytes cannot change while borrowed.
+unsafe impl StableBytes for Vec<u8> {}
+unsafe impl StableBytes for &[u8] {}
+
+/// Heap copy of a module with the alignment `CodeImage` needs.
+#[derive(Debug, Clone)]
+pub struct AlignedBytes {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl AlignedBytes {
+    pub fn copy_from(bytes: &[u8]) -> Self {
+        let mut words = vec![0u64; bytes.len().div_ceil(8)];
+        bytemuck::cast_slice_mut::<u64, u8>(&mut words)[..bytes.len()].copy_from_slice(bytes);
+        Self { words, len: bytes.len() }
+    }
+}
+
+impl AsRef<[u8]> for AlignedBytes {
+    fn as_ref(&self) -> &[u8] {
+        &bytemuck::cast_slice(&self.words)[..self.len]
+    }
+}
+
+// SAFETY: the words are owned and never handed out mutably.
+unsafe impl StableBytes for AlignedBytes {}
+
+/// Encode `chunk` as an image section.
+pub fn encode(chunk: &Chunk) -> anyhow::Result<Vec<u8>> {
+    let mut builder = Builder::default();
+    let mut instrs = Vec::with_capacity(chunk.instrs.len());
+    for instr in &chunk.instrs {
+        instrs.push(builder.instr(instr)?);
+    }
+    builder.tables.functions = chunk.functions.clone();
+    builder.tables.screens = chunk.screens.clone();
+    builder.tables.actors = chunk.actors.clone();
+    builder.tables.lines = chunk.lines.clone();
+    builder.tables.supervisors = chunk.supervisors.clone();
+    let tables = bincode::serialize(&builder.tables)?;
+
+    let mut out = Vec::new();
+    let header = [
+        IMAGE_MAGIC,
+        u32::try_from(instrs.len())?,
+        u32::try_from(builder.consts.len())?,
+        u32::try_from(builder.strings.len())?,
+        u32::try_from(builder.data.len())?,
+        u32::try_from(tables.len())?,
+        0,
+        0,
+    ];
+    header.iter().for_each(|w| out.extend_from_slice(&w.to_le_bytes()));
+    instrs.iter().flatten().for_each(|w| out.extend_from_slice(&w.to_le_bytes()));
+    builder.consts.iter().flatten().for_each(|w| out.extend_from_slice(&w.to_le_bytes()));
+    builder.strings.iter().flatten().for_each(|w| out.extend_from_slice(&w.to_le_bytes()));
+    out.extend_from_slice(&builder.data);
+    out.resize(out.len().next_multiple_of(IMAGE_ALIGN), 0);
+    out.extend_from_slice(&tables);
+    Ok(out)
+}
+
+#[derive(Default)]
+struct Builder {
+    consts: Vec<[u64; 2]>,
+    strings: Vec<[u32; 2]>,
+    data: Vec<u8>,
+    interned: HashMap<String, u32>,
+    tables: Tables,
+}
+
+impl Builder {
+    fn string(&mut self, s: &str) -> anyhow::Result<u32> {
+        if let Some(&index) = self.interned.get(s) {
+            return Ok(index);
+        }
+        let index = u32::try_from(self.strings.len())?;
+        self.strings.push([u32::try_from(self.data.len())?, u32::try_from(s.len())?]);
+        self.data.extend_from_slice(s.as_bytes());
+        self.interned.insert(s.to_string(), index);
+        Ok(index)
+    }
+
+    fn constant(&mut self, value: &Value) -> anyhow::Result<u32> {
+        let record = match value {
+            Value::Nil => [tag::NIL, 0],
+            Value::Number(n) => [tag::NUMBER, n.to_bits()],
+            Value::Bool(b) => [tag::BOOL, *b as u64],
+            Value::String(s) => [tag::STR, self.string(s)? as u64],
+            other => {
+                self.tables.values.push(other.clone());
+                [tag::VALUE, (self.tables.values.len() - 1) as u64]
+            }
+        };
+        self.consts.push(record);
+        Ok(u32::try_from(self.consts.len() - 1)?)
+    }
+
+    fn instr(&mut self, instr: &Instr) -> anyhow::Result<[u32; 4]> {
+        use opcode::*;
+        let index = |len: usize| u32::try_from(len - 1);
+        let (op, a, b) = match instr {
+            Instr::LoadConst(v) => (LOAD_CONST, self.constant(v)?, 0),
+            Instr::LoadVar(name) => (LOAD_VAR, self.string(name)?, 0),
+            Instr::StoreVar(name) => (STORE_VAR, self.string(name)?, 0),
+            Instr::LoadEnv(key) => (LOAD_ENV, self.string(key)?, 0),
+            Instr::LoadTime => (LOAD_TIME, 0, 0),
+            Instr::LoadRand => (LOAD_RAND, 0, 0),
+            Instr::Add => (ADD, 0, 0),
+            Instr::Sub => (SUB, 0, 0),
+            Instr::Mul => (MUL, 0, 0),
+            Instr::Div => (DIV, 0, 0),
+            Instr::Eq => (EQ, 0, 0),
+            Instr::Gt => (GT, 0, 0),
+            Instr::Lt => (LT, 0, 0),
+            Instr::Call(name, argc) => (CALL, self.string(name)?, u32::try_from(*argc)?),
+            Instr::Spawn(argc) => (SPAWN, u32::try_from(*argc)?, 0),
+            Instr::Jump(target) => (JUMP, u32::try_from(*target)?, 0),
+            Instr::JumpIfFalse(target) => (JUMP_IF_FALSE, u32::try_from(*target)?, 0),
+            Instr::Pop => (POP, 0, 0),
+            Instr::Return => (RETURN, 0, 0),
+            Instr::Render => (RENDER, 0, 0),
+            Instr::RenderTimeline(entries) => {
+                self.tables.timelines.push(entries.clone());
+                (RENDER_TIMELINE, index(self.tables.timelines.len())?, 0)
+            }
+            Instr::RenderState(refs) => {
+                self.tables.states.push(refs.clone());
+                (RENDER_STATE, index(self.tables.states.len())?, 0)
+            }
+            Instr::RenderUI { screen, widgets } => {
+                self.tables.uis.push(UiBlock { screen: screen.clone(), widgets: widgets.clone() });
+                (RENDER_UI, index(self.tables.uis.len())?, 0)
+            }
+        };
+        Ok([op, a, b, 0])
+    }
+}
+
+/// A validated image section inside `bytes`, executed without copying code
+/// or constants out of the buffer.
+pub struct CodeImage<B> {
+    bytes: B,
+    instrs: Range<usize>,
+    consts: Range<usize>,
+    strings: Range<usize>,
+    data: Range<usize>,
+    tables: Tables,
+}
+
+impl<B: StableBytes> CodeImage<B> {
+    /// Validate the section at `bytes[offset..offset + len]`.
+    pub fn parse(bytes: B, offset: usize, len: usize) -> anyhow::Result<Self> {
+        let buf = bytes.as_ref();
+        let section = offset.checked_add(len).and_then(|end| buf.get(offset..end)).context("image section out of bounds")?;
+        if !(section.as_ptr() as usize).is_multiple_of(IMAGE_ALIGN) {
+            bail!("image section is not {}-byte aligned", IMAGE_ALIGN);
+        }
+        let header: &[u32] = bytemuck::try_cast_slice(section.get(..IMAGE_HEADER_LEN).context("truncated image header")?)
+            .map_err(|e| anyhow::anyhow!("image header: {:?}", e))?;
+        let word = |i: usize| u32::from_le(header[i]) as usize;
+        if word(0) != IMAGE_MAGIC as usize {
+            bail!("not a code image (bad magic)");
+        }
+        let (instr_count, const_count, string_count, string_bytes, tables_len) = (word(1), word(2), word(3), word(4), word(5));
+
+        let mut at = offset + IMAGE_HEADER_LEN;
+        let mut take = |size: usize| {
+            let range = at..at + size;
+            at = range.end;
+            range
+        };
+        let instrs = take(instr_count * INSTR_LEN);
+        let consts = take(const_count * CONST_LEN);
+        let strings = take(string_count * STRING_LEN);
+        let data = take(string_bytes);
+        let padded = (data.end - offset).next_multiple_of(IMAGE_ALIGN) + offset;
+        let tables_range = padded..padded + tables_len;
+        if tables_range.end > offset + len {
+            bail!("image sections exceed the declared length");
+        }
+        let tables: Tables = bincode::deserialize(&buf[tables_range]).context("invalid image tables")?;
+
+        let image = Self { bytes, instrs, consts, strings, data, tables };
+        image.validate(instr_count, const_count, string_count)?;
+        Ok(image)
+    }
+
+    fn validate(&self, instr_count: usize, const_count: usize, string_count: usize) -> anyhow::Result<()> {
+        let data = &self.bytes.as_ref()[self.data.clone()];
+        for i in 0..string_count {
+            let [start, len] = self.words32::<2>(&self.strings, i).map(|w| w as usize);
+            let text = data.get(start..start + len).with_context(|| format!("string {} out of bounds", i))?;
+            std::str::from_utf8(text).with_context(|| format!("string {} is not UTF-8", i))?;
+        }
+        for i in 0..const_count {
+            let [tag, bits] = self.words64::<2>(&self.consts, i);
+            let ok = match tag {
+                tag::NIL | tag::NUMBER => true,
+                tag::BOOL => bits <= 1,
+                tag::STR => (bits as usize) < string_count,
+                tag::VALUE => (bits as usize) < self.tables.values.len(),
+                _ => false,
+            };
+            if !ok {
+                bail!("constant {} is malformed", i);
+            }
+        }
+        for ip in 0..instr_count {
+            use opcode::*;
+            let [op, a, _, _] = self.words32::<4>(&self.instrs, ip).map(|w| w as usize);
+            let limit = match op as u32 {
+                LOAD_CONST => const_count,
+                LOAD_VAR | STORE_VAR | LOAD_ENV | CALL => string_count,
+                JUMP | JUMP_IF_FALSE => instr_count + 1,
+                RENDER_TIMELINE => self.tables.timelines.len(),
+                RENDER_STATE => self.tables.states.len(),
+                RENDER_UI => self.tables.uis.len(),
+                o if o <= RENDER => usize::MAX,
+                _ => bail!("instruction {} has unknown opcode {}", ip, op),
+            };
+            if a >= limit {
+                bail!("instruction {} operand {} out of range", ip, a);
+            }
+        }
+        if let Some(f) = self.tables.functions.iter().find(|f| f.entry >= instr_count) {
+            bail!("function '{}' starts outside the code", f.name);
+        }
+        if self.tables.lines.windows(2).any(|w| w[0].ip >= w[1].ip) {
+            bail!("line table is not sorted by instruction");
+        }
+        Ok(())
+    }
+
+    /// Record `i` of a table of `N` little-endian words.
+    fn words32<const N: usize>(&self, range: &Range<usize>, i: usize) -> [u32; N] {
+        let start = range.start + i * N * 4;
+        let words: &[u32] = bytemuck::cast_slice(&self.bytes.as_ref()[start..start + N * 4]);
+        std::array::from_fn(|k| u32::from_le(words[k]))
+    }
+
+    fn words64<const N: usize>(&self, range: &Range<usize>, i: usize) -> [u64; N] {
+        let start = range.start + i * N * 8;
+        let words: &[u64] = bytemuck::cast_slice(&self.bytes.as_ref()[start..start + N * 8]);
+        std::array::from_fn(|k| u64::from_le(words[k]))
+    }
+
+    fn str(&self, index: u32) -> &str {
+        let [start, len] = self.words32::<2>(&self.strings, index as usize).map(|w| w as usize);
+        let bytes = &self.bytes.as_ref()[self.data.start + start..self.data.start + start + len];
+        // SAFETY: every string was checked to be UTF-8 in `validate`, and
+        // `StableBytes` guarantees the buffer has not changed since.
+        unsafe { std::str::from_utf8_unchecked(bytes) }
+    }
+
+    fn constant(&self, index: u32) -> Const<'_> {
+        let [tag, bits] = self.words64::<2>(&self.consts, index as usize);
+        match tag {
+            tag::NUMBER => Const::Number(f64::from_bits(bits)),
+            tag::BOOL => Const::Bool(bits != 0),
+            tag::STR => Const::Str(self.str(bits as u32)),
+            tag::VALUE => Const::Value(&self.tables.values[bits as usize]),
+            _ => Const::Nil,
+        }
+    }
+
+    /// Decode into an owned chunk, e.g. for tools that edit or re-encode it.
+    pub fn to_chunk(&self) -> Chunk {
+        Chunk {
+            instrs: (0..self.len()).map(|ip| self.op(ip).to_instr()).collect(),
+            functions: self.tables.functions.clone(),
+            screens: self.tables.screens.clone(),
+            actors: self.tables.actors.clone(),
+            lines: self.tables.lines.clone(),
+            supervisors: self.tables.supervisors.clone(),
+        }
+    }
+
+    pub fn bytes(&self) -> &B {
+        &self.bytes
+    }
+}
+
+impl<B: StableBytes> Code for CodeImage<B> {
+    fn len(&self) -> usize {
+        self.instrs.len() / INSTR_LEN
+    }
+
+    fn op(&self, ip: usize) -> Op<'_> {
+        use opcode::*;
+        let [op, a, b, _] = self.words32::<4>(&self.instrs, ip);
+        match op {
+            LOAD_CONST => Op::LoadConst(self.constant(a)),
+            LOAD_VAR => Op::LoadVar(self.str(a)),
+            STORE_VAR => Op::StoreVar(self.str(a)),
+            LOAD_ENV => Op::LoadEnv(self.str(a)),
+            LOAD_TIME => Op::LoadTime,
+            LOAD_RAND => Op::LoadRand,
+            ADD => Op::Add,
+            SUB => Op::Sub,
+            MUL => Op::Mul,
+            DIV => Op::Div,
+            EQ => Op::Eq,
+            GT => Op::Gt,
+            LT => Op::Lt,
+            CALL => Op::Call(self.str(a), b as usize),
+            SPAWN => Op::Spawn(a as usize),
+            JUMP => Op::Jump(a as usize),
+            JUMP_IF_FALSE => Op::JumpIfFalse(a as usize),
+            POP => Op::Pop,
+            RENDER => Op::Render,
+            RENDER_TIMELINE => Op::RenderTimeline(&self.tables.timelines[a as usize]),
+            RENDER_STATE => Op::RenderState(&self.tables.states[a as usize]),
+            RENDER_UI => {
+                let block = &self.tables.uis[a as usize];
+                Op::RenderUI { screen: block.screen.as_deref(), widgets: &block.widgets }
+            }
+            // Opcodes were checked at load; RETURN is the only one left
+            _ => Op::Return,
+        }
+    }
+
+    fn functions(&self) -> &[Function] {
+        &self.tables.functions
+    }
+
+    fn screens(&self) -> &[ScreenDef] {
+        &self.tables.screens
+    }
+
+    fn actors(&self) -> &[ActorDef] {
+        &self.tables.actors
+    }
+
+    fn lines(&self) -> &[LineEntry] {
+        &self.tables.lines
+    }
+
+    fn supervisors(&self) -> &[SupervisorDef] {
+        &self.tables.supervisors
+    }
+}
@@ -2,16 +2,47 @@
 // Flat & Brutal
 
 use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+use std::fmt;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Value {
     Number(f64),
     String(String),
     Bool(bool),
     Nil,
+    List(Vec<Value>),
+    Map(BTreeMap<String, Value>),
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Number(n) => write!(f, "{}", n),
+            Value::String(s) => write!(f, "{}", s),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Nil => write!(f, "nil"),
+            Value::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 { write!(f, ", ")?; }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Value::Map(entries) => {
+                write!(f, "{{")?;
+                for (i, (k, v)) in entries.iter().enumerate() {
+                    if i > 0 { write!(f, ", ")?; }
+                    write!(f, "{}: {}", k, v)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Instr {
     LoadConst(Value),
     LoadVar(String),
@@ -30,24 +61,419 @@ pub enum Instr {
     Spawn(usize),        // arg_count
     Jump(usize),         // exact instruction index
     JumpIfFalse(usize),
+    Pop,                 // Discard result of an expression statement
     Return,
     Render,              // Context-aware output
-    RenderTimeline,      // Emit distributed event graph
-    RenderState,         // Emit engine state snapshot
-    RenderUI,            // Dispatch declarative UI update
+    RenderTimeline(Vec<TimelineEntry>), // Emit distributed event graph; payloads on stack
+    RenderState(Vec<StateRef>), // Emit engine state snapshot
+    RenderUI { screen: Option<String>, widgets: Vec<UiTemplate> }, // Dispatch declarative UI update
+}
+
+/// Borrowed view of one instruction. The VM executes these, so code can come
+/// from an owned [`Chunk`] or straight out of a mapped module image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op<'a> {
+    LoadConst(Const<'a>),
+    LoadVar(&'a str),
+    StoreVar(&'a str),
+    LoadEnv(&'a str),
+    LoadTime,
+    LoadRand,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Eq,
+    Gt,
+    Lt,
+    Call(&'a str, usize),
+    Spawn(usize),
+    Jump(usize),
+    JumpIfFalse(usize),
+    Pop,
+    Return,
+    Render,
+    RenderTimeline(&'a [TimelineEntry]),
+    RenderState(&'a [StateRef]),
+    RenderUI { screen: Option<&'a str>, widgets: &'a [UiTemplate] },
+}
+
+/// A constant operand; scalars and strings need no owned `Value` to exist.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Const<'a> {
+    Number(f64),
+    Str(&'a str),
+    Bool(bool),
+    Nil,
+    Value(&'a Value),
+}
+
+impl Const<'_> {
+    pub fn to_value(self) -> Value {
+        match self {
+            Const::Number(n) => Value::Number(n),
+            Const::Str(s) => Value::String(s.to_string()),
+            Const::Bool(b) => Value::Bool(b),
+            Const::Nil => Value::Nil,
+            Const::Value(v) => v.clone(),
+        }
+    }
+}
+
+impl Instr {
+    pub fn as_op(&self) -> Op<'_> {
+        match self {
+            Instr::LoadConst(Value::Number(n)) => Op::LoadConst(Const::Number(*n)),
+            Instr::LoadConst(Value::String(s)) => Op::LoadConst(Const::Str(s)),
+            Instr::LoadConst(Value::Bool(b)) => Op::LoadConst(Const::Bool(*b)),
+            Instr::LoadConst(Value::Nil) => Op::LoadConst(Const::Nil),
+            Instr::LoadConst(v) => Op::LoadConst(Const::Value(v)),
+            Instr::LoadVar(name) => Op::LoadVar(name),
+            Instr::StoreVar(name) => Op::StoreVar(name),
+            Instr::LoadEnv(key) => Op::LoadEnv(key),
+            Instr::LoadTime => Op::LoadTime,
+            Instr::LoadRand => Op::LoadRand,
+            Instr::Add => Op::Add,
+            Instr::Sub => Op::Sub,
+            Instr::Mul => Op::Mul,
+            Instr::Div => Op::Div,
+            Instr::Eq => Op::Eq,
+            Instr::Gt => Op::Gt,
+            Instr::Lt => Op::Lt,
+            Instr::Call(name, argc) => Op::Call(name, *argc),
+            Instr::Spawn(argc) => Op::Spawn(*argc),
+            Instr::Jump(target) => Op::Jump(*target),
+            Instr::JumpIfFalse(target) => Op::JumpIfFalse(*target),
+            Instr::Pop => Op::Pop,
+            Instr::Return => Op::Return,
+            Instr::Render => Op::Render,
+            Instr::RenderTimeline(entries) => Op::RenderTimeline(entries),
+            Instr::RenderState(refs) => Op::RenderState(refs),
+            Instr::RenderUI { screen, widgets } => Op::RenderUI { screen: screen.as_deref(), widgets },
+        }
+    }
+}
+
+impl Op<'_> {
+    pub fn to_instr(self) -> Instr {
+        match self {
+            Op::LoadConst(c) => Instr::LoadConst(c.to_value()),
+            Op::LoadVar(name) => Instr::LoadVar(name.to_string()),
+            Op::StoreVar(name) => Instr::StoreVar(name.to_string()),
+            Op::LoadEnv(key) => Instr::LoadEnv(key.to_string()),
+            Op::LoadTime => Instr::LoadTime,
+            Op::LoadRand => Instr::LoadRand,
+            Op::Add => Instr::Add,
+            Op::Sub => Instr::Sub,
+            Op::Mul => Instr::Mul,
+            Op::Div => Instr::Div,
+            Op::Eq => Instr::Eq,
+            Op::Gt => Instr::Gt,
+            Op::Lt => Instr::Lt,
+            Op::Call(name, argc) => Instr::Call(name.to_string(), argc),
+            Op::Spawn(argc) => Instr::Spawn(argc),
+            Op::Jump(target) => Instr::Jump(target),
+            Op::JumpIfFalse(target) => Instr::JumpIfFalse(target),
+            Op::Pop => Instr::Pop,
+            Op::Return => Instr::Return,
+            Op::Render => Instr::Render,
+            Op::RenderTimeline(entries) => Instr::RenderTimeline(entries.to_vec()),
+            Op::RenderState(refs) => Instr::RenderState(refs.to_vec()),
+            Op::RenderUI { screen, widgets } => Instr::RenderUI { screen: screen.map(str::to_string), widgets: widgets.to_vec() },
+        }
+    }
+}
+
+/// Executable code: an instruction stream plus the declarations that index into it.
+pub trait Code {
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Instruction at `ip`; callers keep `ip < len()`.
+    fn op(&self, ip: usize) -> Op<'_>;
+    fn functions(&self) -> &[Function];
+    fn screens(&self) -> &[ScreenDef];
+    fn actors(&self) -> &[ActorDef];
+
+    /// Top-level `supervise` trees, in source order.
+    fn supervisors(&self) -> &[SupervisorDef] {
+        &[]
+    }
+
+    fn function(&self, name: &str) -> Option<&Function> {
+        self.functions().iter().find(|f| f.name == name)
+    }
+
+    fn screen(&self, name: &str) -> Option<&ScreenDef> {
+        self.screens().iter().find(|s| s.name == name)
+    }
+
+    fn actor(&self, name: &str) -> Option<&ActorDef> {
+        self.actors().iter().find(|a| a.name == name)
+    }
+
+    /// Where each statement's code starts, sorted by `ip`. Empty for code
+    /// compiled without line information.
+    fn lines(&self) -> &[LineEntry] {
+        &[]
+    }
+
+    /// Source line of the instruction at `ip`.
+    fn line_at(&self, ip: usize) -> Option<u32> {
+        let lines = self.lines();
+        let i = lines.partition_point(|l| l.ip <= ip);
+        i.checked_sub(1).map(|i| lines[i].line)
+    }
+
+    /// The function whose body contains `ip`; `None` for the main program.
+    fn function_at(&self, ip: usize) -> Option<&Function> {
+        self.functions().iter().filter(|f| f.entry <= ip).max_by_key(|f| f.entry)
+    }
+}
+
+/// The statement on source `line` starts at instruction `ip`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LineEntry {
+    pub ip: usize,
+    pub line: u32,
+}
+
+/// One `from -> to at N` line of a `render timeline { ... }` block.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimelineEntry {
+    pub from: String,
+    pub to: String,
+    pub at: u64,
+}
+
+/// One `node.field` line of a `render distributed state { ... }` block.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StateRef {
+    pub node: String,
+    pub field: String,
+}
+
+/// Shape of a widget in a `render { ... }` UI block. Dynamic parts (text, bound
+/// values, button arguments, list items, image sources) are evaluated in
+/// pre-order onto the stack before `RenderUI` consumes them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum UiTemplate {
+    Text,
+    Input { bind: String },
+    Button { label: String, action: String, argc: usize },
+    Column(Vec<UiTemplate>),
+    Row(Vec<UiTemplate>),
+    List,
+    Image,
+    Checkbox { label: String, bind: String },
+    Screen { name: String },
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl UiTemplate {
+    /// Number of stack values this widget (and its children) consumes.
+    pub fn slot_count(&self) -> usize {
+        match self {
+            UiTemplate::Button { argc, .. } => *argc,
+            UiTemplate::Column(children) | UiTemplate::Row(children) => {
+                children.iter().map(UiTemplate::slot_count).sum()
+            }
+            UiTemplate::Screen { .. } => 0,
+            _ => 1,
+        }
+    }
+}
+
+/// A `screen` block: its `state` variables are set up by the `init` function
+/// and its widget tree produced by the `render` function.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScreenDef {
+    pub name: String,
+    pub state: Vec<String>,
+    pub init: String,
+    pub render: String,
+}
+
+/// An `actor` declaration: `init` sets up its `state` variables and each
+/// `on Event` handler is compiled to a function named `<Actor>.<Event>`.
+/// `params` are state variables set from the arguments it is started with.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActorDef {
+    pub name: String,
+    pub state: Vec<String>,
+    pub init: String,
+    pub handlers: Vec<String>,
+    #[serde(default)]
+    pub params: Vec<String>,
+    /// Limits from an `@quota` annotation.
+    #[serde(default)]
+    pub quota: Quota,
+}
+
+/// Per-actor resource limits; unset ones fall back to the runtime's defaults.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Quota {
+    /// Bytes of state, and of temporaries while handling one message.
+    pub memory: Option<u64>,
+    /// Instructions one message may run.
+    pub instructions: Option<u64>,
+}
+
+impl Quota {
+    /// This quota, with the limits it leaves unset taken from `defaults`.
+    pub fn or(self, defaults: Quota) -> Quota {
+        Quota {
+            memory: self.memory.or(defaults.memory),
+            instructions: self.instructions.or(defaults.instructions),
+        }
+    }
+}
+
+/// A byte count such as `4096`, `64KB`, `512MB` or `1GB` (binary units).
+pub fn parse_size(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let digits = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let (number, unit) = text.split_at(digits);
+    let shift = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 0,
+        "K" | "KB" => 10,
+        "M" | "MB" => 20,
+        "G" | "GB" => 30,
+        _ => return None,
+    };
+    number.parse::<u64>().ok()?.checked_mul(1 << shift)
+}
+
+impl ActorDef {
+    pub fn handler(&self, event: &str) -> Option<String> {
+        self.handlers.iter().any(|h| h == event).then(|| format!("{}.{}", self.name, event))
+    }
+}
+
+/// Restarts a supervisor allows within [`DEFAULT_RESTART_WINDOW`] before it gives up.
+pub const DEFAULT_MAX_RESTARTS: u32 = 3;
+/// Logical ticks over which restarts are counted.
+pub const DEFAULT_RESTART_WINDOW: u64 = 5000;
+
+/// A `supervise` block: when a child fails it restarts children according to
+/// `strategy` (`OneForOne`, `AllForOne` or `RestForOne`), at most
+/// `max_restarts` times per `within` ticks before failing itself.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SupervisorDef {
+    pub name: String,
+    pub strategy: String,
+    pub max_restarts: u32,
+    pub within: u64,
+    pub children: Vec<SupervisedChild>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SupervisedChild {
+    /// A declared actor, started with `args`.
+    Actor { name: String, args: Vec<Value> },
+    Supervisor(SupervisorDef),
+}
+
+impl SupervisorDef {
+    /// This supervisor and every one nested in it, depth first.
+    pub fn supervisors(&self) -> Vec<&SupervisorDef> {
+        let mut all = vec![self];
+        for child in &self.children {
+            if let SupervisedChild::Supervisor(def) = child {
+                all.extend(def.supervisors());
+            }
+        }
+        all
+    }
+
+    /// Supervised actor names, depth first.
+    pub fn actors(&self) -> Vec<&str> {
+        self.children.iter().flat_map(|child| match child {
+            SupervisedChild::Actor { name, .. } => vec![name.as_str()],
+            SupervisedChild::Supervisor(def) => def.actors(),
+        }).collect()
+    }
+}
+
+/// A compiled `fn`. Its body lives in the owning chunk's instruction stream
+/// starting at `entry`; parameters are bound as frame locals on call.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Function {
+    pub name: String,
+    pub params: Vec<String>,
+    pub entry: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Chunk {
     pub instrs: Vec<Instr>,
+    pub functions: Vec<Function>,
+    pub screens: Vec<ScreenDef>,
+    pub actors: Vec<ActorDef>,
+    #[serde(default)]
+    pub lines: Vec<LineEntry>,
+    #[serde(default)]
+    pub supervisors: Vec<SupervisorDef>,
 }
 
 impl Chunk {
     pub fn new() -> Self {
-        Self { instrs: Vec::new() }
+        Self { instrs: Vec::new(), functions: Vec::new(), screens: Vec::new(), actors: Vec::new(), lines: Vec::new(), supervisors: Vec::new() }
     }
 
     pub fn emit(&mut self, instr: Instr) {
         self.instrs.push(instr);
     }
+
+    /// Start a statement on `line` at the next instruction. A statement that
+    /// emitted no code is superseded by the next one.
+    pub fn mark_line(&mut self, line: u32) {
+        let ip = self.instrs.len();
+        match self.lines.last_mut() {
+            Some(last) if last.ip == ip => last.line = line,
+            _ => self.lines.push(LineEntry { ip, line }),
+        }
+    }
+
+    pub fn function(&self, name: &str) -> Option<&Function> {
+        Code::function(self, name)
+    }
+
+    pub fn screen(&self, name: &str) -> Option<&ScreenDef> {
+        Code::screen(self, name)
+    }
+
+    pub fn actor(&self, name: &str) -> Option<&ActorDef> {
+        Code::actor(self, name)
+    }
+}
+
+impl Code for Chunk {
+    fn len(&self) -> usize {
+        self.instrs.len()
+    }
+
+    fn op(&self, ip: usize) -> Op<'_> {
+        self.instrs[ip].as_op()
+    }
+
+    fn functions(&self) -> &[Function] {
+        &self.functions
+    }
+
+    fn screens(&self) -> &[ScreenDef] {
+        &self.screens
+    }
+
+    fn actors(&self) -> &[ActorDef] {
+        &self.actors
+    }
+
+    fn lines(&self) -> &[LineEntry] {
+        &self.lines
+    }
+
+    fn supervisors(&self) -> &[SupervisorDef] {
+        &self.supervisors
+    }
 }
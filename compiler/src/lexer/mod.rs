@@ -29,8 +29,6 @@ pub enum TokenKind {
     Spawn,
     #[token("await")]
     Await,
-    #[token("sleep")]
-    Sleep,
 
     // Keywords - Structure
     #[token("actor")]
@@ -45,6 +43,8 @@ pub enum TokenKind {
     State,
     #[token("on")]
     On,
+    #[token("supervise")]
+    Supervise,
 
     #[token("timeline")]
     Timeline,
@@ -70,6 +70,16 @@ pub enum TokenKind {
     InputWidget,
     #[token("Button")]
     ButtonWidget,
+    #[token("Column")]
+    ColumnWidget,
+    #[token("Row")]
+    RowWidget,
+    #[token("List")]
+    ListWidget,
+    #[token("Image")]
+    ImageWidget,
+    #[token("Checkbox")]
+    CheckboxWidget,
     #[token("bind")]
     Bind,
     #[token("onClick")]
@@ -154,23 +164,34 @@ pub enum TokenKind {
     GreaterEqual,
     #[token("?")]
     Question,
+    #[token("@")]
+    AtSign,
 
     EOF,
 }
 
 pub struct Lexer<'a> {
     lexer: logos::Lexer<'a, TokenKind>,
+    /// 1-based line of the last token, counted up to `scanned`.
+    line: u32,
+    scanned: usize,
 }
 
 impl<'a> Lexer<'a> {
     pub fn new(source: &'a str) -> Self {
         Self {
             lexer: TokenKind::lexer(source),
+            line: 1,
+            scanned: 0,
         }
     }
 
     pub fn next_token(&mut self) -> TokenKind {
-        match self.lexer.next() {
+        let token = self.lexer.next();
+        let start = self.lexer.span().start;
+        self.line += self.lexer.source()[self.scanned..start].matches('\n').count() as u32;
+        self.scanned = start;
+        match token {
             Some(Ok(token)) => token,
             Some(Err(_)) => {
                 let slice = self.lexer.slice();
@@ -183,4 +204,9 @@ impl<'a> Lexer<'a> {
     pub fn slice(&self) -> &'a str {
         self.lexer.slice()
     }
+
+    /// Source line the last token starts on.
+    pub fn line(&self) -> u32 {
+        self.line
+    }
 }
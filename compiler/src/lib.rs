@@ -4,17 +4,54 @@ pub mod parser;
 pub mod ir;
 pub mod codegen;
 pub mod afm;
+pub mod image;
 
 pub use lexer::Lexer;
-pub use parser::Parser;
+pub use parser::{ParseError, Parser};
 pub use codegen::Codegen;
 pub use ir::Chunk;
 
 pub fn compile(source: &str) -> anyhow::Result<Chunk> {
-    let lexer = Lexer::new(source);
-    let mut parser = Parser::new(lexer);
-    let stmts = parser.parse();
+    let stmts = Parser::new(Lexer::new(source)).parse().map_err(|e| anyhow::anyhow!("Syntax error: {}", e))?;
     let codegen = Codegen::new();
     let chunk = codegen.compile(stmts);
+    check_supervision(&chunk)?;
+    check_lifecycle(&chunk)?;
     Ok(chunk)
 }
+
+/// `on start` takes no parameters and `on stop` at most the reason.
+fn check_lifecycle(chunk: &Chunk) -> anyhow::Result<()> {
+    for (event, max) in [("start", 0), ("stop", 1)] {
+        for actor in &chunk.actors {
+            let Some(function) = actor.handler(event).and_then(|name| chunk.function(&name)) else { continue };
+            if function.params.len() > max {
+                anyhow::bail!("'on {}' of actor '{}' takes at most {} parameter(s), got {}", event, actor.name, max, function.params.len());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Supervision trees name declared actors, and each actor and supervisor once.
+fn check_supervision(chunk: &Chunk) -> anyhow::Result<()> {
+    let mut names = std::collections::HashSet::new();
+    for def in chunk.supervisors.iter().flat_map(|root| root.supervisors()) {
+        if chunk.actor(&def.name).is_some() || def.name == "main" {
+            anyhow::bail!("supervisor '{}' has the name of an actor", def.name);
+        }
+        if !names.insert(def.name.as_str()) {
+            anyhow::bail!("supervisor '{}' is declared twice", def.name);
+        }
+    }
+    let mut supervised = std::collections::HashSet::new();
+    for name in chunk.supervisors.iter().flat_map(|root| root.actors()) {
+        if chunk.actor(name).is_none() {
+            anyhow::bail!("supervised actor '{}' is not declared", name);
+        }
+        if !supervised.insert(name) {
+            anyhow::bail!("actor '{}' is supervised twice", name);
+        }
+    }
+    Ok(())
+}
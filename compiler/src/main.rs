@@ -13,8 +13,11 @@ fn main() {
     match compile(&source) {
         Ok(chunk) => {
             println!("Compiled successfully. IR Generated.");
+            let mut lines = chunk.lines.iter().peekable();
             for (i, instr) in chunk.instrs.iter().enumerate() {
-                println!("{:04}: {:?}", i, instr);
+                // Source line beside the first instruction of each statement
+                let line = lines.next_if(|l| l.ip == i).map(|l| format!("L{}", l.line)).unwrap_or_default();
+                println!("{:04} {:>5}: {:?}", i, line, instr);
             }
         }
         Err(e) => eprintln!("Compilation Error: {}", e),
@@ -4,11 +4,31 @@
 use crate::lexer::{Lexer, TokenKind};
 use crate::ast::{Expr, Stmt};
 use smallvec::SmallVec;
+use std::fmt;
+
+/// A syntax error and the line it was found on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub message: String,
+    pub line: u32,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+type ParseResult<T> = Result<T, ParseError>;
 
 pub struct Parser<'a> {
     lexer: Lexer<'a>,
     current: TokenKind,
     previous: TokenKind,
+    /// Line `current` starts on.
+    line: u32,
 }
 
 impl<'a> Parser<'a> {
@@ -17,6 +37,7 @@ impl<'a> Parser<'a> {
             lexer,
             current: TokenKind::EOF,
             previous: TokenKind::EOF,
+            line: 1,
         };
         p.advance();
         p
@@ -25,6 +46,7 @@ impl<'a> Parser<'a> {
     fn advance(&mut self) {
         self.previous = self.current.clone();
         self.current = self.lexer.next_token();
+        self.line = self.lexer.line();
     }
 
     fn match_token(&mut self, kind: TokenKind) -> bool {
@@ -35,290 +57,527 @@ impl<'a> Parser<'a> {
         false
     }
 
-    fn consume(&mut self, kind: TokenKind, message: &str) {
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        ParseError { message: message.into(), line: self.line }
+    }
+
+    fn consume(&mut self, kind: TokenKind, message: &str) -> ParseResult<()> {
         if !self.match_token(kind) {
-            panic!("{}", message);
+            return Err(self.error(message));
+        }
+        Ok(())
+    }
+
+    fn consume_ident(&mut self, message: &str) -> ParseResult<String> {
+        self.consume(TokenKind::Ident(String::new()), message)?;
+        Ok(self.previous_ident())
+    }
+
+    fn consume_string(&mut self, message: &str) -> ParseResult<String> {
+        self.consume(TokenKind::String(String::new()), message)?;
+        match &self.previous {
+            TokenKind::String(s) => Ok(s.clone()),
+            _ => unreachable!("consumed a string"),
+        }
+    }
+
+    fn consume_number(&mut self, message: &str) -> ParseResult<f64> {
+        self.consume(TokenKind::Number(0.0), message)?;
+        match self.previous {
+            TokenKind::Number(n) => Ok(n),
+            _ => unreachable!("consumed a number"),
+        }
+    }
+
+    /// The name of the identifier just matched.
+    fn previous_ident(&self) -> String {
+        match &self.previous {
+            TokenKind::Ident(name) => name.clone(),
+            _ => unreachable!("matched an identifier"),
         }
     }
 
-    pub fn parse(&mut self) -> Vec<Stmt> {
+    pub fn parse(&mut self) -> Result<Vec<Stmt>, ParseError> {
         let mut statements = Vec::new();
         while self.current != TokenKind::EOF {
-            statements.push(self.parse_statement());
+            self.parse_statement_into(&mut statements)?;
         }
-        statements
+        Ok(statements)
+    }
+
+    /// Parse one statement into `out`, preceded by the line it starts on.
+    fn parse_statement_into(&mut self, out: &mut Vec<Stmt>) -> ParseResult<()> {
+        out.push(Stmt::Line(self.line));
+        let stmt = self.parse_statement()?;
+        out.push(stmt);
+        Ok(())
     }
 
-    fn parse_statement(&mut self) -> Stmt {
-        if self.match_token(TokenKind::Let) { self.parse_let() }
-        else if self.match_token(TokenKind::Fn) { self.parse_fn(false) }
+    fn parse_statement(&mut self) -> ParseResult<Stmt> {
+        Ok(if self.match_token(TokenKind::Let) { self.parse_let()? }
+        else if self.match_token(TokenKind::Fn) { self.parse_fn(false)? }
         else if self.match_token(TokenKind::Pure) { 
-            self.consume(TokenKind::Fn, "Expect 'fn' after 'pure'");
-            self.parse_fn(true) 
-        }
-        else if self.match_token(TokenKind::Actor) { self.parse_actor() }
-        else if self.match_token(TokenKind::Screen) { self.parse_screen() }
-        else if self.match_token(TokenKind::Agent) { self.parse_agent() }
-        else if self.match_token(TokenKind::Model) { self.parse_model() }
-        else if self.match_token(TokenKind::From) { self.parse_from() }
-        else if self.match_token(TokenKind::Render) { self.parse_render() }
-        else if self.match_token(TokenKind::Spawn) { self.parse_spawn() }
-        else if self.match_token(TokenKind::If) { self.parse_if() }
-        else if self.match_token(TokenKind::While) { self.parse_while() }
+            self.consume(TokenKind::Fn, "Expect 'fn' after 'pure'")?;
+            self.parse_fn(true)? 
+        }
+        else if self.match_token(TokenKind::Actor) { self.parse_actor(Vec::new())? }
+        else if self.match_token(TokenKind::AtSign) { self.parse_annotated()? }
+        else if self.match_token(TokenKind::Supervise) { Stmt::Supervise(self.parse_supervise()?) }
+        else if self.match_token(TokenKind::Screen) { self.parse_screen()? }
+        else if self.match_token(TokenKind::Agent) { self.parse_agent()? }
+        else if self.match_token(TokenKind::Model) { self.parse_model()? }
+        else if self.match_token(TokenKind::From) { self.parse_from()? }
+        else if self.match_token(TokenKind::Render) { self.parse_render()? }
+        else if self.match_token(TokenKind::Spawn) { self.parse_spawn()? }
+        else if self.match_token(TokenKind::If) { self.parse_if()? }
+        else if self.match_token(TokenKind::While) { self.parse_while()? }
         else if self.match_token(TokenKind::Return) { 
-            let expr = self.parse_expression();
+            let expr = self.parse_expression()?;
             Stmt::Return(expr)
         }
-        else { Stmt::Expr(self.parse_expression()) }
+        else { Stmt::Expr(self.parse_expression()?) })
     }
 
-    fn parse_if(&mut self) -> Stmt {
-        let condition = self.parse_expression();
-        self.consume(TokenKind::LBrace, "Expect '{' after if condition");
+    fn parse_if(&mut self) -> ParseResult<Stmt> {
+        let condition = self.parse_expression()?;
+        self.consume(TokenKind::LBrace, "Expect '{' after if condition")?;
         let mut then_branch = Vec::new();
         while self.current != TokenKind::RBrace && self.current != TokenKind::EOF {
-            then_branch.push(self.parse_statement());
+            self.parse_statement_into(&mut then_branch)?;
         }
-        self.consume(TokenKind::RBrace, "Expect '}' after then branch");
+        self.consume(TokenKind::RBrace, "Expect '}' after then branch")?;
         
         let else_branch = if self.match_token(TokenKind::Else) {
             if self.match_token(TokenKind::If) {
-                Some(vec![self.parse_if()])
+                Some(vec![self.parse_if()?])
             } else {
-                self.consume(TokenKind::LBrace, "Expect '{' after else");
+                self.consume(TokenKind::LBrace, "Expect '{' after else")?;
                 let mut branch = Vec::new();
                 while self.current != TokenKind::RBrace && self.current != TokenKind::EOF {
-                    branch.push(self.parse_statement());
+                    self.parse_statement_into(&mut branch)?;
                 }
-                self.consume(TokenKind::RBrace, "Expect '}' after else branch");
+                self.consume(TokenKind::RBrace, "Expect '}' after else branch")?;
                 Some(branch)
             }
         } else {
             None
         };
         
-        Stmt::If { condition, then_branch, else_branch }
+        Ok(Stmt::If { condition, then_branch, else_branch })
     }
 
-    fn parse_while(&mut self) -> Stmt {
-        let condition = self.parse_expression();
-        self.consume(TokenKind::LBrace, "Expect '{' after while condition");
+    fn parse_while(&mut self) -> ParseResult<Stmt> {
+        let condition = self.parse_expression()?;
+        self.consume(TokenKind::LBrace, "Expect '{' after while condition")?;
         let mut body = Vec::new();
         while self.current != TokenKind::RBrace && self.current != TokenKind::EOF {
-            body.push(self.parse_statement());
+            self.parse_statement_into(&mut body)?;
         }
-        self.consume(TokenKind::RBrace, "Expect '}' after while body");
-        Stmt::While { condition, body }
+        self.consume(TokenKind::RBrace, "Expect '}' after while body")?;
+        Ok(Stmt::While { condition, body })
     }
 
-    fn parse_type(&mut self) -> crate::ast::Type {
-        if self.match_token(TokenKind::IntType) { crate::ast::Type::Int }
+    fn parse_type(&mut self) -> ParseResult<crate::ast::Type> {
+        Ok(if self.match_token(TokenKind::IntType) { crate::ast::Type::Int }
         else if self.match_token(TokenKind::FloatType) { crate::ast::Type::Float }
         else if self.match_token(TokenKind::StringType) { crate::ast::Type::String }
         else if self.match_token(TokenKind::BoolType) { crate::ast::Type::Bool }
         else if self.match_token(TokenKind::List) {
-            self.consume(TokenKind::LAngle, "Expect '<' after 'list'");
-            let inner = self.parse_type();
-            self.consume(TokenKind::RAngle, "Expect '>' after list type");
+            self.consume(TokenKind::LAngle, "Expect '<' after 'list'")?;
+            let inner = self.parse_type()?;
+            self.consume(TokenKind::RAngle, "Expect '>' after list type")?;
             crate::ast::Type::List(Box::new(inner))
         }
         else if self.match_token(TokenKind::Dict) {
-            self.consume(TokenKind::LAngle, "Expect '<' after 'dict'");
-            let key = self.parse_type();
-            self.consume(TokenKind::Comma, "Expect ',' between dict types");
-            let value = self.parse_type();
-            self.consume(TokenKind::RAngle, "Expect '>' after dict types");
+            self.consume(TokenKind::LAngle, "Expect '<' after 'dict'")?;
+            let key = self.parse_type()?;
+            self.consume(TokenKind::Comma, "Expect ',' between dict types")?;
+            let value = self.parse_type()?;
+            self.consume(TokenKind::RAngle, "Expect '>' after dict types")?;
             crate::ast::Type::Dict(Box::new(key), Box::new(value))
         }
-        else { crate::ast::Type::Void }
+        else { crate::ast::Type::Void })
     }
 
-    fn parse_let(&mut self) -> Stmt {
-        self.consume(TokenKind::Ident(String::new()), "Expect variable name after let");
-        let name = if let TokenKind::Ident(n) = &self.previous { n.clone() } else { panic!() };
+    fn parse_let(&mut self) -> ParseResult<Stmt> {
+        let name = self.consume_ident("Expect variable name after let")?;
         
-        self.consume(TokenKind::Colon, "Expect ':' after variable name");
-        let r#type = self.parse_type();
+        // Type annotation is optional: `let count = count + 1`
+        let r#type = if self.match_token(TokenKind::Colon) {
+            self.parse_type()?
+        } else {
+            crate::ast::Type::Void
+        };
 
-        self.consume(TokenKind::Equal, "Expect '=' after type");
-        let value = self.parse_expression();
-        Stmt::VarDecl { name, r#type, value }
+        self.consume(TokenKind::Equal, "Expect '=' after variable")?;
+        let value = self.parse_expression()?;
+        Ok(Stmt::VarDecl { name, r#type, value })
     }
 
-    fn parse_fn(&mut self, is_pure: bool) -> Stmt {
-        self.consume(TokenKind::Ident(String::new()), "Expect function name");
-        let name = if let TokenKind::Ident(n) = &self.previous { n.clone() } else { panic!() };
+    fn parse_fn(&mut self, is_pure: bool) -> ParseResult<Stmt> {
+        let name = self.consume_ident("Expect function name")?;
         
-        self.consume(TokenKind::LParen, "Expect '(' after function name");
+        self.consume(TokenKind::LParen, "Expect '(' after function name")?;
         let mut params = Vec::new();
         if self.current != TokenKind::RParen {
             loop {
-                self.consume(TokenKind::Ident(String::new()), "Expect param name");
-                let p_name = if let TokenKind::Ident(p) = &self.previous { p.clone() } else { panic!() };
-                self.consume(TokenKind::Colon, "Expect ':' after param name");
-                let p_type = self.parse_type();
+                let p_name = self.consume_ident("Expect param name")?;
+                self.consume(TokenKind::Colon, "Expect ':' after param name")?;
+                let p_type = self.parse_type()?;
                 params.push((p_name, p_type));
                 if !self.match_token(TokenKind::Comma) { break; }
             }
         }
-        self.consume(TokenKind::RParen, "Expect ')' after params");
+        self.consume(TokenKind::RParen, "Expect ')' after params")?;
         
         let return_type = if self.match_token(TokenKind::Arrow) {
-            self.parse_type()
+            self.parse_type()?
         } else {
             crate::ast::Type::Void
         };
 
-        self.consume(TokenKind::LBrace, "Expect '{' before function body");
+        self.consume(TokenKind::LBrace, "Expect '{' before function body")?;
         let mut body = Vec::new();
         while self.current != TokenKind::RBrace && self.current != TokenKind::EOF {
-            body.push(self.parse_statement());
+            self.parse_statement_into(&mut body)?;
         }
-        self.consume(TokenKind::RBrace, "Expect '}' after function body");
+        self.consume(TokenKind::RBrace, "Expect '}' after function body")?;
         
-        Stmt::Fn { name, params, body, return_type, is_pure }
+        Ok(Stmt::Fn { name, params, body, return_type, is_pure })
     }
 
-    fn parse_from(&mut self) -> Stmt {
-        self.consume(TokenKind::Ident(String::new()), "Expect package name after 'from'");
-        let package = if let TokenKind::Ident(p) = &self.previous { p.clone() } else { panic!() };
-        
-        self.consume(TokenKind::Ident(String::new()), "Expect layer name after package");
-        let layer = if let TokenKind::Ident(l) = &self.previous { l.clone() } else { panic!() };
-        
-        Stmt::FromImport { package, layer }
+    fn parse_from(&mut self) -> ParseResult<Stmt> {
+        let mut package = self.package_name("Expect package name after 'from'")?;
+        while self.match_token(TokenKind::Dot) {
+            package = format!("{}.{}", package, self.package_name("Expect package segment after '.'")?);
+        }
+        let layer = self.package_name("Expect layer name after package")?;
+        Ok(Stmt::FromImport { package, layer })
     }
 
-    fn parse_render(&mut self) -> Stmt {
-        self.consume(TokenKind::LBrace, "Expect '{' after 'render'");
-        
+    /// Package paths may reuse block keywords (`from ai.tensor agent`).
+    fn package_name(&mut self, message: &str) -> ParseResult<String> {
+        let name = match &self.current {
+            TokenKind::Ident(n) => n.clone(),
+            TokenKind::Actor => "actor".to_string(),
+            TokenKind::Agent => "agent".to_string(),
+            TokenKind::Model => "model".to_string(),
+            TokenKind::Tensor => "tensor".to_string(),
+            TokenKind::State => "state".to_string(),
+            TokenKind::Screen => "screen".to_string(),
+            _ => return Err(self.error(message)),
+        };
+        self.advance();
+        Ok(name)
+    }
+
+    fn parse_render(&mut self) -> ParseResult<Stmt> {
+        // Blocks may drop the braces: `render timeline { ... }` == `render { timeline { ... } }`
+        let braced = self.match_token(TokenKind::LBrace);
+        if !braced && !matches!(self.current, TokenKind::Timeline | TokenKind::Distributed) && !self.at_widget() {
+            self.consume(TokenKind::LBrace, "Expect '{' after 'render'")?;
+        }
+
         let render_expr = if self.match_token(TokenKind::Timeline) {
-            crate::ast::RenderExpression::Timeline(self.parse_timeline())
+            crate::ast::RenderExpression::Timeline(self.parse_timeline()?)
         } else if self.match_token(TokenKind::Distributed) {
-            self.consume(TokenKind::State, "Expect 'state' after 'distributed'");
-            crate::ast::RenderExpression::DistributedState(self.parse_distributed_state())
-        } else if self.current == TokenKind::TextWidget || self.current == TokenKind::InputWidget || self.current == TokenKind::ButtonWidget {
+            self.consume(TokenKind::State, "Expect 'state' after 'distributed'")?;
+            crate::ast::RenderExpression::DistributedState(self.parse_distributed_state()?)
+        } else if self.at_widget() {
             let mut widgets = Vec::new();
-            while self.current == TokenKind::TextWidget || self.current == TokenKind::InputWidget || self.current == TokenKind::ButtonWidget {
-                widgets.push(self.parse_ui_widget());
+            while self.at_widget() {
+                widgets.push(self.parse_ui_widget()?);
             }
             crate::ast::RenderExpression::UIWidgets(widgets)
         } else {
-            crate::ast::RenderExpression::Expr(self.parse_expression())
+            match self.parse_expression()? {
+                // `render { CounterScreen {} }` embeds a screen
+                Expr::Ident(name) if braced && self.match_token(TokenKind::LBrace) => {
+                    self.consume(TokenKind::RBrace, "Expect '}' after screen name")?;
+                    crate::ast::RenderExpression::UIWidgets(vec![crate::ast::UIWidget::Screen(name)])
+                }
+                expr => crate::ast::RenderExpression::Expr(expr),
+            }
         };
 
-        self.consume(TokenKind::RBrace, "Expect '}' after render block");
-        Stmt::Render(render_expr)
+        if braced {
+            self.consume(TokenKind::RBrace, "Expect '}' after render block")?;
+        }
+        Ok(Stmt::Render(render_expr))
+    }
+
+    fn at_widget(&self) -> bool {
+        matches!(self.current,
+            TokenKind::TextWidget | TokenKind::InputWidget | TokenKind::ButtonWidget |
+            TokenKind::ColumnWidget | TokenKind::RowWidget | TokenKind::ListWidget |
+            TokenKind::ImageWidget | TokenKind::CheckboxWidget)
+    }
+
+    fn parse_ui_children(&mut self, name: &str) -> ParseResult<Vec<crate::ast::UIWidget>> {
+        self.consume(TokenKind::LBrace, &format!("Expect '{{' after {}", name))?;
+        let mut children = Vec::new();
+        while self.at_widget() {
+            children.push(self.parse_ui_widget()?);
+        }
+        self.consume(TokenKind::RBrace, &format!("Expect '}}' after {} children", name))?;
+        Ok(children)
     }
 
-    fn parse_ui_widget(&mut self) -> crate::ast::UIWidget {
+    fn parse_ui_widget(&mut self) -> ParseResult<crate::ast::UIWidget> {
         if self.match_token(TokenKind::TextWidget) {
-            self.consume(TokenKind::LBrace, "Expect '{' after Text");
-            let expr = self.parse_expression();
-            self.consume(TokenKind::RBrace, "Expect '}' after expression");
-            crate::ast::UIWidget::Text(expr)
+            self.consume(TokenKind::LBrace, "Expect '{' after Text")?;
+            let expr = self.parse_expression()?;
+            self.consume(TokenKind::RBrace, "Expect '}' after expression")?;
+            Ok(crate::ast::UIWidget::Text(expr))
         } else if self.match_token(TokenKind::InputWidget) {
-            self.consume(TokenKind::LBrace, "Expect '{' after Input");
-            self.consume(TokenKind::Bind, "Expect 'bind' in Input");
-            self.consume(TokenKind::Colon, "Expect ':' after bind");
-            self.consume(TokenKind::Ident(String::new()), "Expect variable name");
-            let bind = if let TokenKind::Ident(id) = &self.previous { id.clone() } else { panic!() };
-            self.consume(TokenKind::RBrace, "Expect '}' after Input");
-            crate::ast::UIWidget::Input { bind }
+            self.consume(TokenKind::LBrace, "Expect '{' after Input")?;
+            self.consume(TokenKind::Bind, "Expect 'bind' in Input")?;
+            self.consume(TokenKind::Colon, "Expect ':' after bind")?;
+            let bind = self.consume_ident("Expect variable name")?;
+            self.consume(TokenKind::RBrace, "Expect '}' after Input")?;
+            Ok(crate::ast::UIWidget::Input { bind })
         } else if self.match_token(TokenKind::ButtonWidget) {
-            self.consume(TokenKind::LBrace, "Expect '{' after Button");
-            self.consume(TokenKind::String(String::new()), "Expect button label");
-            let label = if let TokenKind::String(s) = &self.previous { s.clone() } else { panic!() };
-            self.consume(TokenKind::Comma, "Expect ',' after label");
-            self.consume(TokenKind::OnClick, "Expect 'onClick'");
-            self.consume(TokenKind::Colon, "Expect ':' after onClick");
-            let on_click = self.parse_expression();
-            self.consume(TokenKind::RBrace, "Expect '}' after Button");
-            crate::ast::UIWidget::Button { label, on_click }
+            self.consume(TokenKind::LBrace, "Expect '{' after Button")?;
+            let label = self.consume_string("Expect button label")?;
+            self.consume(TokenKind::Comma, "Expect ',' after label")?;
+            self.consume(TokenKind::OnClick, "Expect 'onClick'")?;
+            self.consume(TokenKind::Colon, "Expect ':' after onClick")?;
+            let on_click = self.parse_expression()?;
+            if !matches!(on_click, Expr::Call { .. } | Expr::Ident(_)) {
+                return Err(self.error(format!("Button onClick must be a function call, got {:?}", on_click)));
+            }
+            self.consume(TokenKind::RBrace, "Expect '}' after Button")?;
+            Ok(crate::ast::UIWidget::Button { label, on_click })
+        } else if self.match_token(TokenKind::ColumnWidget) {
+            Ok(crate::ast::UIWidget::Column(self.parse_ui_children("Column")?))
+        } else if self.match_token(TokenKind::RowWidget) {
+            Ok(crate::ast::UIWidget::Row(self.parse_ui_children("Row")?))
+        } else if self.match_token(TokenKind::ListWidget) {
+            self.consume(TokenKind::LBrace, "Expect '{' after List")?;
+            let items = self.parse_expression()?;
+            self.consume(TokenKind::RBrace, "Expect '}' after List items")?;
+            Ok(crate::ast::UIWidget::List(items))
+        } else if self.match_token(TokenKind::ImageWidget) {
+            self.consume(TokenKind::LBrace, "Expect '{' after Image")?;
+            let src = self.parse_expression()?;
+            self.consume(TokenKind::RBrace, "Expect '}' after Image source")?;
+            Ok(crate::ast::UIWidget::Image(src))
+        } else if self.match_token(TokenKind::CheckboxWidget) {
+            self.consume(TokenKind::LBrace, "Expect '{' after Checkbox")?;
+            let label = self.consume_string("Expect checkbox label")?;
+            self.consume(TokenKind::Comma, "Expect ',' after label")?;
+            self.consume(TokenKind::Bind, "Expect 'bind' in Checkbox")?;
+            self.consume(TokenKind::Colon, "Expect ':' after bind")?;
+            let bind = self.consume_ident("Expect variable name")?;
+            self.consume(TokenKind::RBrace, "Expect '}' after Checkbox")?;
+            Ok(crate::ast::UIWidget::Checkbox { label, bind })
         } else {
-            panic!("Unexpected UI widget token: {:?}", self.current);
+            Err(self.error(format!("Unexpected UI widget token: {:?}", self.current)))
         }
     }
 
-    fn parse_screen(&mut self) -> Stmt {
-        self.consume(TokenKind::Ident(String::new()), "Expect screen name");
-        let name = if let TokenKind::Ident(n) = &self.previous { n.clone() } else { panic!() };
-        self.consume(TokenKind::LBrace, "Expect '{'");
+    fn parse_screen(&mut self) -> ParseResult<Stmt> {
+        let name = self.consume_ident("Expect screen name")?;
+        self.consume(TokenKind::LBrace, "Expect '{'")?;
         let mut body = Vec::new();
         while self.current != TokenKind::RBrace {
-            body.push(self.parse_statement());
+            self.parse_statement_into(&mut body)?;
         }
-        self.consume(TokenKind::RBrace, "Expect '}'");
-        Stmt::Screen { name, body }
+        self.consume(TokenKind::RBrace, "Expect '}'")?;
+        Ok(Stmt::Screen { name, body })
     }
 
-    fn parse_timeline(&mut self) -> crate::ast::TimelineBlock {
-        self.consume(TokenKind::LBrace, "Expect '{' after 'timeline'");
+    fn parse_timeline(&mut self) -> ParseResult<crate::ast::TimelineBlock> {
+        self.consume(TokenKind::LBrace, "Expect '{' after 'timeline'")?;
         let mut events = Vec::new();
         while self.current != TokenKind::RBrace {
-            self.consume(TokenKind::Ident(String::new()), "Expect node name");
-            let from = if let TokenKind::Ident(n) = &self.previous { n.clone() } else { panic!() };
-            self.consume(TokenKind::Arrow, "Expect '->'");
-            self.consume(TokenKind::Ident(String::new()), "Expect target node name");
-            let to = if let TokenKind::Ident(n) = &self.previous { n.clone() } else { panic!() };
-            self.consume(TokenKind::At, "Expect 'at'");
+            let from = self.consume_ident("Expect node name")?;
+            self.consume(TokenKind::Arrow, "Expect '->'")?;
+            let to = self.consume_ident("Expect target node name")?;
+            self.consume(TokenKind::At, "Expect 'at'")?;
             
             let at_ms = if self.match_token(TokenKind::Number(0.0)) {
                 if let TokenKind::Number(n) = self.previous { n as u64 } else { 0 }
             } else {
-                self.consume(TokenKind::Tick, "Expect 'tick'");
-                self.consume(TokenKind::Equal, "Expect '=' after 'tick'");
-                self.consume(TokenKind::Number(0.0), "Expect tick number");
+                self.consume(TokenKind::Tick, "Expect 'tick'")?;
+                self.consume(TokenKind::Equal, "Expect '=' after 'tick'")?;
+                self.consume(TokenKind::Number(0.0), "Expect tick number")?;
                 if let TokenKind::Number(n) = self.previous { n as u64 } else { 0 }
             };
 
             // Skip 'ms' if present (it's part of the number token usually or extra token)
             self.match_token(TokenKind::Ident("ms".to_string()));
 
-            self.consume(TokenKind::Payload, "Expect 'payload'");
-            let payload = self.parse_expression();
+            self.consume(TokenKind::Payload, "Expect 'payload'")?;
+            let payload = self.parse_expression()?;
             events.push(crate::ast::TimelineEvent { from, to, at_ms, payload });
         }
-        self.consume(TokenKind::RBrace, "Expect '}' after timeline");
-        crate::ast::TimelineBlock { events }
+        self.consume(TokenKind::RBrace, "Expect '}' after timeline")?;
+        Ok(crate::ast::TimelineBlock { events })
     }
 
-    fn parse_distributed_state(&mut self) -> crate::ast::DistributedStateBlock {
-        self.consume(TokenKind::LBrace, "Expect '{' after 'distributed state'");
+    fn parse_distributed_state(&mut self) -> ParseResult<crate::ast::DistributedStateBlock> {
+        self.consume(TokenKind::LBrace, "Expect '{' after 'distributed state'")?;
         let mut state_refs = Vec::new();
         while self.current != TokenKind::RBrace {
-            self.consume(TokenKind::Ident(String::new()), "Expect node name");
-            let node = if let TokenKind::Ident(n) = &self.previous { n.clone() } else { panic!() };
-            self.consume(TokenKind::Dot, "Expect '.'");
-            self.consume(TokenKind::Ident(String::new()), "Expect field name");
-            let field = if let TokenKind::Ident(f) = &self.previous { f.clone() } else { panic!() };
+            let node = self.consume_ident("Expect node name")?;
+            self.consume(TokenKind::Dot, "Expect '.'")?;
+            let field = self.consume_ident("Expect field name")?;
             state_refs.push(crate::ast::NodeStateRef { node, field });
         }
-        self.consume(TokenKind::RBrace, "Expect '}' after distributed state");
-        crate::ast::DistributedStateBlock { state_refs }
+        self.consume(TokenKind::RBrace, "Expect '}' after distributed state")?;
+        Ok(crate::ast::DistributedStateBlock { state_refs })
+    }
+
+    fn parse_spawn(&mut self) -> ParseResult<Stmt> {
+        let expr = self.parse_expression()?;
+        Ok(Stmt::Spawn(expr))
+    }
+
+    /// `@quota(memory: N | "64KB", instructions: N)` followed by an actor.
+    fn parse_annotated(&mut self) -> ParseResult<Stmt> {
+        let annotation = self.consume_ident("Expect annotation name after '@'")?;
+        if annotation != "quota" {
+            return Err(self.error(format!("Unknown annotation '@{}' (expected @quota)", annotation)));
+        }
+        let mut quota = Vec::new();
+        self.consume(TokenKind::LParen, "Expect '(' after '@quota'")?;
+        loop {
+            let key = self.consume_ident("Expect 'memory' or 'instructions'")?;
+            if key != "memory" && key != "instructions" {
+                return Err(self.error(format!("Unknown quota '{}' (expected memory or instructions)", key)));
+            }
+            self.consume(TokenKind::Colon, "Expect ':' after quota name")?;
+            let value = match self.current.clone() {
+                TokenKind::Number(n) => n,
+                TokenKind::String(size) if key == "memory" => match crate::ir::parse_size(&size) {
+                    Some(bytes) => bytes as f64,
+                    None => return Err(self.error(format!("Invalid memory quota '{}' (expected a size such as 64KB or 16MB)", size))),
+                },
+                _ => return Err(self.error(format!("Expect a number for quota '{}'", key))),
+            };
+            self.advance();
+            quota.push((key, value));
+            if !self.match_token(TokenKind::Comma) { break; }
+        }
+        self.consume(TokenKind::RParen, "Expect ')' after quotas")?;
+        self.consume(TokenKind::Actor, "Expect 'actor' after '@quota'")?;
+        self.parse_actor(quota)
+    }
+
+    fn parse_actor(&mut self, quota: Vec<(String, f64)>) -> ParseResult<Stmt> {
+        let name = self.consume_ident("Expect actor name")?;
+        let mut params = Vec::new();
+        if self.match_token(TokenKind::LParen) {
+            if self.current != TokenKind::RParen {
+                loop {
+                    self.consume(TokenKind::Ident(String::new()), "Expect actor parameter name")?;
+                    if let TokenKind::Ident(p) = &self.previous { params.push(p.clone()); }
+                    if !self.match_token(TokenKind::Comma) { break; }
+                }
+            }
+            self.consume(TokenKind::RParen, "Expect ')' after actor parameters")?;
+        }
+        self.consume(TokenKind::LBrace, "Expect '{'")?;
+        let mut handlers = Vec::new();
+        let mut body = Vec::new();
+        while self.current != TokenKind::RBrace {
+            if self.current == TokenKind::State {
+                // `state count = 0` declares the same kind of variable as `let`
+                body.push(Stmt::Line(self.line));
+                self.advance();
+                body.push(self.parse_let()?);
+            } else if self.match_token(TokenKind::On) {
+                handlers.push(self.parse_handler()?);
+            } else {
+                self.parse_statement_into(&mut body)?;
+            }
+        }
+        self.consume(TokenKind::RBrace, "Expect '}'")?;
+        Ok(Stmt::Actor { name, params, handlers, body, quota })
     }
 
-    fn parse_spawn(&mut self) -> Stmt {
-        let expr = self.parse_expression();
-        Stmt::Spawn(expr)
+    /// `supervise [Name:] Strategy [(max: N, within: T)] { children }`, where a
+    /// child is `Actor`, `Actor(args)` or a nested `supervise` block.
+    fn parse_supervise(&mut self) -> ParseResult<crate::ast::SuperviseBlock> {
+        let mut strategy = self.consume_ident("Expect restart strategy after 'supervise'")?;
+        let mut name = None;
+        if self.match_token(TokenKind::Colon) {
+            let named = self.consume_ident("Expect restart strategy after supervisor name")?;
+            name = Some(std::mem::replace(&mut strategy, named));
+        }
+        if !matches!(strategy.as_str(), "OneForOne" | "AllForOne" | "RestForOne") {
+            return Err(self.error(format!("Unknown restart strategy '{}' (expected OneForOne, AllForOne or RestForOne)", strategy)));
+        }
+        let mut options = Vec::new();
+        if self.match_token(TokenKind::LParen) {
+            loop {
+                let key = self.consume_ident("Expect 'max' or 'within'")?;
+                if key != "max" && key != "within" {
+                    return Err(self.error(format!("Unknown supervise option '{}' (expected max or within)", key)));
+                }
+                self.consume(TokenKind::Colon, "Expect ':' after supervise option")?;
+                let value = self.consume_number("Expect a number")?;
+                options.push((key, value));
+                if !self.match_token(TokenKind::Comma) { break; }
+            }
+            self.consume(TokenKind::RParen, "Expect ')' after supervise options")?;
+        }
+        self.consume(TokenKind::LBrace, "Expect '{' after supervise")?;
+        let mut children = Vec::new();
+        while self.current != TokenKind::RBrace {
+            if self.match_token(TokenKind::Supervise) {
+                children.push(crate::ast::SupervisedChild::Supervisor(self.parse_supervise()?));
+                continue;
+            }
+            let name = self.consume_ident("Expect actor name in supervise block")?;
+            let mut args = Vec::new();
+            if self.match_token(TokenKind::LParen) {
+                if self.current != TokenKind::RParen {
+                    loop {
+                        let arg = self.parse_expression()?;
+                        if !matches!(arg, Expr::Number(_) | Expr::String(_) | Expr::Bool(_)) {
+                            return Err(self.error(format!("Arguments of supervised actor '{}' must be literals", name)));
+                        }
+                        args.push(arg);
+                        if !self.match_token(TokenKind::Comma) { break; }
+                    }
+                }
+                self.consume(TokenKind::RParen, "Expect ')' after actor arguments")?;
+            }
+            children.push(crate::ast::SupervisedChild::Actor { name, args });
+        }
+        self.consume(TokenKind::RBrace, "Expect '}' after supervise block")?;
+        Ok(crate::ast::SuperviseBlock { name, strategy, options, children })
     }
 
-    fn parse_actor(&mut self) -> Stmt {
-        self.consume(TokenKind::Ident(String::new()), "Expect actor name");
-        let name = if let TokenKind::Ident(n) = &self.previous { n.clone() } else { panic!() };
-        self.consume(TokenKind::LBrace, "Expect '{'");
+    /// `on Name { ... }` or `on Name(param: type, ...) { ... }`
+    fn parse_handler(&mut self) -> ParseResult<crate::ast::EventHandler> {
+        let name = self.consume_ident("Expect event name")?;
+        let mut params = Vec::new();
+        if self.match_token(TokenKind::LParen) {
+            if self.current != TokenKind::RParen {
+                loop {
+                    let p_name = self.consume_ident("Expect param name")?;
+                    let p_type = if self.match_token(TokenKind::Colon) { self.parse_type()? } else { crate::ast::Type::Void };
+                    params.push((p_name, p_type));
+                    if !self.match_token(TokenKind::Comma) { break; }
+                }
+            }
+            self.consume(TokenKind::RParen, "Expect ')'")?;
+        }
+        self.consume(TokenKind::LBrace, "Expect '{'")?;
         let mut body = Vec::new();
         while self.current != TokenKind::RBrace {
-            body.push(self.parse_statement());
+            self.parse_statement_into(&mut body)?;
         }
-        self.consume(TokenKind::RBrace, "Expect '}'");
-        Stmt::Actor { name, body }
+        self.consume(TokenKind::RBrace, "Expect '}'")?;
+        Ok(crate::ast::EventHandler { name, params, body })
     }
 
-    fn parse_agent(&mut self) -> Stmt {
-        self.consume(TokenKind::Ident(String::new()), "Expect agent name");
-        let name = if let TokenKind::Ident(n) = &self.previous { n.clone() } else { panic!() };
-        self.consume(TokenKind::LBrace, "Expect '{'");
+    fn parse_agent(&mut self) -> ParseResult<Stmt> {
+        let name = self.consume_ident("Expect agent name")?;
+        self.consume(TokenKind::LBrace, "Expect '{'")?;
         
         let mut model = None;
         let mut handlers = Vec::new();
@@ -326,53 +585,31 @@ impl<'a> Parser<'a> {
 
         while self.current != TokenKind::RBrace {
             if self.match_token(TokenKind::Model) {
-                self.consume(TokenKind::String(String::new()), "Expect model identifier string");
+                self.consume(TokenKind::String(String::new()), "Expect model identifier string")?;
                 if let TokenKind::String(s) = &self.previous { model = Some(s.clone()); }
             } else if self.match_token(TokenKind::On) {
-                self.consume(TokenKind::Ident(String::new()), "Expect event name");
-                let h_name = if let TokenKind::Ident(n) = &self.previous { n.clone() } else { panic!() };
-                self.consume(TokenKind::LParen, "Expect '('");
-                let mut h_params = Vec::new();
-                if self.current != TokenKind::RParen {
-                    loop {
-                        self.consume(TokenKind::Ident(String::new()), "Expect param name");
-                        let p_name = if let TokenKind::Ident(p) = &self.previous { p.clone() } else { panic!() };
-                        self.consume(TokenKind::Colon, "Expect ':' after param name");
-                        let p_type = self.parse_type();
-                        h_params.push((p_name, p_type));
-                        if !self.match_token(TokenKind::Comma) { break; }
-                    }
-                }
-                self.consume(TokenKind::RParen, "Expect ')'");
-                self.consume(TokenKind::LBrace, "Expect '{'");
-                let mut h_body = Vec::new();
-                while self.current != TokenKind::RBrace {
-                    h_body.push(self.parse_statement());
-                }
-                self.consume(TokenKind::RBrace, "Expect '}'");
-                handlers.push(crate::ast::EventHandler { name: h_name, params: h_params, body: h_body });
+                handlers.push(self.parse_handler()?);
             } else {
-                body.push(self.parse_statement());
+                self.parse_statement_into(&mut body)?;
             }
         }
-        self.consume(TokenKind::RBrace, "Expect '}'");
-        Stmt::Agent { name, model, handlers, body }
+        self.consume(TokenKind::RBrace, "Expect '}'")?;
+        Ok(Stmt::Agent { name, model, handlers, body })
     }
 
-    fn parse_model(&mut self) -> Stmt {
-        self.consume(TokenKind::Ident(String::new()), "Expect model name");
-        let name = if let TokenKind::Ident(n) = &self.previous { n.clone() } else { panic!() };
-        self.consume(TokenKind::LBrace, "Expect '{'");
-        self.consume(TokenKind::RBrace, "Expect '}'");
-        Stmt::Model { name, source: String::new(), body: Vec::new() }
+    fn parse_model(&mut self) -> ParseResult<Stmt> {
+        let name = self.consume_ident("Expect model name")?;
+        self.consume(TokenKind::LBrace, "Expect '{'")?;
+        self.consume(TokenKind::RBrace, "Expect '}'")?;
+        Ok(Stmt::Model { name, source: String::new(), body: Vec::new() })
     }
 
-    fn parse_expression(&mut self) -> Expr {
+    fn parse_expression(&mut self) -> ParseResult<Expr> {
         self.parse_binary(0)
     }
 
-    fn parse_binary(&mut self, min_precedence: u8) -> Expr {
-        let mut left = self.parse_primary();
+    fn parse_binary(&mut self, min_precedence: u8) -> ParseResult<Expr> {
+        let mut left = self.parse_primary()?;
 
         while let Some(precedence) = self.get_precedence(&self.current) {
             if precedence < min_precedence {
@@ -381,7 +618,7 @@ impl<'a> Parser<'a> {
 
             let op = self.current.clone();
             self.advance();
-            let right = self.parse_binary(precedence + 1);
+            let right = self.parse_binary(precedence + 1)?;
             left = Expr::Binary {
                 left: Box::new(left),
                 op,
@@ -389,7 +626,7 @@ impl<'a> Parser<'a> {
             };
         }
 
-        left
+        Ok(left)
     }
 
     fn get_precedence(&self, token: &TokenKind) -> Option<u8> {
@@ -402,48 +639,47 @@ impl<'a> Parser<'a> {
         }
     }
 
-    fn parse_primary(&mut self) -> Expr {
+    fn parse_primary(&mut self) -> ParseResult<Expr> {
         if self.match_token(TokenKind::Number(0.0)) {
-            if let TokenKind::Number(n) = self.previous { return Expr::Number(n); }
+            if let TokenKind::Number(n) = self.previous { return Ok(Expr::Number(n)); }
         }
         if self.match_token(TokenKind::String(String::new())) {
-            if let TokenKind::String(s) = &self.previous { return Expr::String(s.clone()); }
+            if let TokenKind::String(s) = &self.previous { return Ok(Expr::String(s.clone())); }
         }
         if self.match_token(TokenKind::Ident(String::new())) {
-            let name = if let TokenKind::Ident(n) = &self.previous { n.clone() } else { panic!() };
+            let name = self.previous_ident();
             if self.match_token(TokenKind::LParen) {
                 let mut args = SmallVec::new();
                 if self.current != TokenKind::RParen {
                     loop {
-                        args.push(Box::new(self.parse_expression()));
+                        args.push(Box::new(self.parse_expression()?));
                         if !self.match_token(TokenKind::Comma) { break; }
                     }
                 }
-                self.consume(TokenKind::RParen, "Expect ')' after args");
-                return Expr::Call { name, args };
+                self.consume(TokenKind::RParen, "Expect ')' after args")?;
+                return Ok(Expr::Call { name, args });
             }
-            return Expr::Ident(name);
+            return Ok(Expr::Ident(name));
         }
         if self.match_token(TokenKind::LParen) {
-            let expr = self.parse_expression();
-            self.consume(TokenKind::RParen, "Expect ')' after expression");
-            return expr;
+            let expr = self.parse_expression()?;
+            self.consume(TokenKind::RParen, "Expect ')' after expression")?;
+            return Ok(expr);
         }
         if self.match_token(TokenKind::Env) {
-            self.consume(TokenKind::LParen, "Expect '(' after 'env'");
-            self.consume(TokenKind::String(String::new()), "Expect string after 'env('");
-            let key = if let TokenKind::String(s) = &self.previous { s.clone() } else { panic!() };
-            self.consume(TokenKind::RParen, "Expect ')' after env key");
-            return Expr::Env(key);
+            self.consume(TokenKind::LParen, "Expect '(' after 'env'")?;
+            let key = self.consume_string("Expect string after 'env('")?;
+            self.consume(TokenKind::RParen, "Expect ')' after env key")?;
+            return Ok(Expr::Env(key));
         }
         if self.match_token(TokenKind::Time) {
-            return Expr::Time;
+            return Ok(Expr::Time);
         }
         if self.match_token(TokenKind::Rand) {
-            self.consume(TokenKind::LParen, "Expect '(' after 'rand'");
-            self.consume(TokenKind::RParen, "Expect ')' after 'rand('");
-            return Expr::Rand;
+            self.consume(TokenKind::LParen, "Expect '(' after 'rand'")?;
+            self.consume(TokenKind::RParen, "Expect ')' after 'rand('")?;
+            return Ok(Expr::Rand);
         }
-        panic!("Unexpected token in primary: {:?} (slice: {})", self.current, self.lexer.slice());
+        Err(self.error(format!("Unexpected token in primary: {:?} (slice: {})", self.current, self.lexer.slice())))
     }
 }
@@ -0,0 +1,63 @@
+// Startup cost of a large module: bincode decode vs mapping the code image.
+//
+//   cargo bench -p aeroflow-runtime --bench afm_load
+
+use aeroflow_compiler::afm::{AfmMetadata, AfmModule, TrustStore, ARCH_IMAGE, ARCH_IR};
+use aeroflow_compiler::compile;
+use aeroflow_runtime::{module, Program, Scheduler};
+use std::fmt::Write as _;
+use std::time::{Duration, Instant};
+
+const FUNCTIONS: usize = 2_000;
+const ACTORS: usize = 64;
+const ROUNDS: u32 = 20;
+
+fn source() -> String {
+    let mut src = String::new();
+    for i in 0..FUNCTIONS {
+        let _ = writeln!(src, "fn f{i}(x: int) -> int {{ let label = \"function number {i}\"\n if x > {i} {{ return x - {i} }}\n return x + len(label) }}");
+    }
+    for i in 0..ACTORS {
+        let _ = writeln!(src, "actor A{i} {{ state n = {i}\n on Ping(x) {{ let n = f{i}(x) }} }}");
+    }
+    src
+}
+
+fn time(label: &str, mut f: impl FnMut()) -> Duration {
+    f();
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        f();
+    }
+    let per_round = start.elapsed() / ROUNDS;
+    println!("{:<28} {:>10.3} ms", label, per_round.as_secs_f64() * 1000.0);
+    per_round
+}
+
+fn main() -> anyhow::Result<()> {
+    let chunk = compile(&source())?;
+    let module = AfmModule { metadata: AfmMetadata { name: "bench".to_string(), ..Default::default() }, chunk };
+    let dir = std::env::temp_dir();
+    let ir_path = dir.join("aeroflow-bench-ir.afm");
+    let image_path = dir.join("aeroflow-bench-image.afm");
+    std::fs::write(&ir_path, module.encode(ARCH_IR, None)?)?;
+    std::fs::write(&image_path, module.encode(ARCH_IMAGE, None)?)?;
+    let trust = TrustStore::new();
+
+    println!("{} instructions, {} actors", module.chunk.instrs.len(), ACTORS + 1);
+    let bincode = time("bincode: read + decode", || {
+        let bytes = std::fs::read(&ir_path).unwrap();
+        let module = AfmModule::load(&bytes, &trust).unwrap();
+        Scheduler::new().spawn_program(module.chunk);
+    });
+    let mapped = time("image: mmap + validate", || {
+        // SAFETY: nothing writes the image while it is mapped
+        let module = unsafe { module::map(&image_path, &trust) }.unwrap();
+        Scheduler::new().spawn_program(Program::from(module));
+    });
+    println!("speedup                      {:>10.1}x", bincode.as_secs_f64() / mapped.as_secs_f64());
+
+    std::fs::remove_file(ir_path)?;
+    std::fs::remove_file(image_path)?;
+    Ok(())
+}
@@ -1,26 +1,94 @@
 // AeroFlow Runtime - Actor System
 // Lightweight isolated execution units
 
-use crate::mailbox::{Message, MessageData};
+use crate::mailbox::{Message, MessageBus};
 use crate::arena::Arena;
+use crate::capability::{CapabilityGuard, Denied};
+use crate::quota::{Quota, QuotaExceeded};
+use crate::render::{RenderSink, StdoutSink};
+use crate::replay::EnvSource;
+use crate::state::StateSource;
+use crate::vm::DebugHook;
+use aeroflow_compiler::ir::Value;
 use std::sync::Arc;
 
 pub type ActorId = String;
 
 pub struct Context {
     pub actor_id: ActorId,
+    /// Scratch memory for the message being handled; reset after every `receive`.
     pub arena: Arena,
+    pub sink: Arc<dyn RenderSink>,
+    pub state: Option<Arc<dyn StateSource>>,
+    pub bus: Option<Arc<dyn MessageBus>>,
+    pub env: Option<Arc<dyn EnvSource>>,
+    pub debug: Option<Arc<dyn DebugHook>>,
+    /// Set by `receive` when handling the message failed; collected by the scheduler.
+    pub failure: Option<String>,
+    /// The quota behind `failure`, if it was one.
+    pub exceeded: Option<QuotaExceeded>,
+    /// The operation refused for lack of a capability behind `failure`, if
+    /// it was one; the scheduler traces it.
+    pub denied: Option<Denied>,
+    /// Limits the scheduler applies to this actor.
+    pub quota: Quota,
+    /// Instructions the last `receive` ran, for actors that count them.
+    pub instructions: u64,
+    /// Logical time of the delivery or lifecycle hook being run.
+    pub logical_time: u64,
 }
 
 pub trait Actor: Send + Sync {
     fn receive(&mut self, msg: Message, ctx: &mut Context);
+
+    /// Called just before the first message is delivered.
+    fn on_start(&mut self, _ctx: &mut Context) {}
+
+    /// Called when the actor is stopped from outside: by a graceful stop
+    /// (`reason` is `shutdown`), by a supervisor restarting its siblings, or
+    /// because an actor linked to it failed. An actor whose own handler
+    /// failed is not stopped this way.
+    fn on_stop(&mut self, _reason: &str, _ctx: &mut Context) {}
+
+    /// Called on a fresh actor that a supervisor started in place of one
+    /// that failed, before `on_start`.
+    fn on_restart(&mut self, _reason: &str, _ctx: &mut Context) {}
+
     fn get_state(&self) -> String { "{}".to_string() }
+
+    /// Structured access to one state field; by default looked up in `get_state`'s JSON.
+    fn get_field(&self, field: &str) -> Option<Value> {
+        let state: serde_json::Value = serde_json::from_str(&self.get_state()).ok()?;
+        state.get(field).cloned().map(crate::value::from_json)
+    }
+
+    /// Whether `receive` touches nothing but this actor and its `Context`, so
+    /// a parallel scheduler may run it alongside other actors. Actors that
+    /// read siblings' state or have other side effects keep the default.
+    fn isolated(&self) -> bool { false }
+
+    /// Approximate bytes of state the actor holds, checked against memory quotas.
+    fn memory(&self) -> u64 { 0 }
+
+    /// Replace what the actor may do with `guard`. Actors with no guarded
+    /// operations ignore it.
+    fn grant(&mut self, _guard: &CapabilityGuard) {}
+
+    /// Encoded state for `.afs` snapshots, or `None` if this actor has none to offer.
+    fn snapshot(&self) -> Option<Vec<u8>> { None }
+
+    /// Reload state produced by [`Actor::snapshot`].
+    fn restore(&mut self, _state: &[u8]) -> anyhow::Result<()> {
+        anyhow::bail!("actor does not support snapshots")
+    }
 }
 
 pub struct ActorCell {
     pub id: ActorId,
     pub actor: Box<dyn Actor>,
     pub context: Context,
+    /// `on_start` has run.
+    pub started: bool,
 }
 
 impl ActorCell {
@@ -30,8 +98,20 @@ impl ActorCell {
             actor,
             context: Context {
                 actor_id: id,
-                arena: Arena::new(1024 * 1024), // 1MB arena per actor
+                arena: Arena::new(),
+                sink: Arc::new(StdoutSink),
+                state: None,
+                bus: None,
+                env: None,
+                debug: None,
+                failure: None,
+                exceeded: None,
+                denied: None,
+                quota: Quota::default(),
+                instructions: 0,
+                logical_time: 0,
             },
+            started: false,
         }
     }
 
@@ -39,14 +119,21 @@ impl ActorCell {
         // DAS handles message delivery directly now
     }
 
-    pub fn snapshot(&self) -> Vec<u8> {
-        // In a real system, we'd use bincode to serialize actor state
-        println!("[Snapshot] Freezing actor {}...", self.id);
-        Vec::new() // Mock snapshot
+    pub fn snapshot(&self) -> anyhow::Result<Vec<u8>> {
+        self.actor.snapshot().ok_or_else(|| anyhow::anyhow!("actor '{}' does not support snapshots", self.id))
     }
 
-    pub fn resume(id: ActorId, snapshot: Vec<u8>, actor: Box<dyn Actor>) -> Self {
-        println!("[Resume] Restoring actor {} from snapshot...", id);
-        Self::new(id, actor) // Mock resume
+    /// Rebuild a cell around `actor` with the state from [`ActorCell::snapshot`].
+    pub fn resume(id: ActorId, snapshot: &[u8], mut actor: Box<dyn Actor>) -> anyhow::Result<Self> {
+        actor.restore(snapshot).map_err(|e| anyhow::anyhow!("restoring actor '{}': {}", id, e))?;
+        Ok(Self::new(id, actor))
+    }
+
+    /// Run `on_start` if it has not run yet.
+    pub fn start(&mut self) {
+        if !self.started {
+            self.started = true;
+            self.actor.on_start(&mut self.context);
+        }
     }
 }
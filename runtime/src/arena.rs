@@ -1,37 +1,88 @@
 // AeroFlow Runtime - Arena Memory Manager
 // Zero-GC, predictable latency
 
+use bumpalo::Bump;
+use std::fmt;
+
+/// Per-actor scratch memory for the message being handled.
+///
+/// A bump allocator the scheduler resets after every `receive`, so nothing
+/// allocated here outlives the message. Values are never dropped. Once the
+/// limit is reached allocation fails instead of panicking.
+#[derive(Default)]
 pub struct Arena {
-    buffer: Vec<u8>,
-    position: usize,
+    bump: Bump,
 }
 
-impl Arena {
-    pub fn new(size: usize) -> Self {
-        Self {
-            buffer: Vec::with_capacity(size),
-            position: 0,
+/// An allocation would have taken an arena past its limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArenaFull {
+    pub limit: Option<usize>,
+    /// Bytes the failed allocation asked for.
+    pub requested: usize,
+}
+
+impl fmt::Display for ArenaFull {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.limit {
+            Some(limit) => write!(f, "arena limit of {} bytes exceeded", limit),
+            None => write!(f, "arena allocation failed"),
         }
     }
+}
 
-    /// Allocate memory for a value of type T
-    /// Note: This is an MVP simplification. Real one uses raw pointers and alignment.
-    pub fn alloc<T: Copy>(&mut self, value: T) -> *mut T {
-        let size = std::mem::size_of::<T>();
-        if self.position + size > self.buffer.capacity() {
-            panic!("Arena overflow");
-        }
-        
-        // This is a hack for the prototype
-        let ptr = self.buffer.as_mut_ptr() as usize + self.position;
-        unsafe {
-            std::ptr::write(ptr as *mut T, value);
-        }
-        self.position += size;
-        ptr as *mut T
+impl std::error::Error for ArenaFull {}
+
+impl Arena {
+    /// An arena with no limit; it grows as needed.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An arena holding at most `limit` bytes, if given.
+    pub fn with_limit(limit: Option<usize>) -> Self {
+        let arena = Self::new();
+        arena.set_limit(limit);
+        arena
+    }
+
+    pub fn limit(&self) -> Option<usize> {
+        self.bump.allocation_limit()
     }
 
+    pub fn set_limit(&self, limit: Option<usize>) {
+        self.bump.set_allocation_limit(limit);
+    }
+
+    /// Bytes the arena currently holds, including unused room in its chunks.
+    pub fn allocated(&self) -> usize {
+        self.bump.allocated_bytes()
+    }
+
+    /// Moves `value` into the arena, suitably aligned.
+    pub fn alloc<T>(&self, value: T) -> Result<&mut T, ArenaFull> {
+        self.bump.try_alloc(value).map_err(|_| self.full(std::mem::size_of::<T>()))
+    }
+
+    pub fn alloc_str(&self, s: &str) -> Result<&mut str, ArenaFull> {
+        self.bump.try_alloc_str(s).map_err(|_| self.full(s.len()))
+    }
+
+    /// `a` followed by `b`.
+    pub fn concat(&self, a: &str, b: &str) -> Result<&str, ArenaFull> {
+        let len = a.len() + b.len();
+        let bytes = self.bump.try_alloc_slice_fill_copy(len, 0u8).map_err(|_| self.full(len))?;
+        bytes[..a.len()].copy_from_slice(a.as_bytes());
+        bytes[a.len()..].copy_from_slice(b.as_bytes());
+        Ok(std::str::from_utf8(bytes).expect("two strings concatenated are UTF-8"))
+    }
+
+    /// Frees everything at once; the largest chunk is kept for reuse.
     pub fn reset(&mut self) {
-        self.position = 0;
+        self.bump.reset();
+    }
+
+    fn full(&self, requested: usize) -> ArenaFull {
+        ArenaFull { limit: self.limit(), requested }
     }
 }
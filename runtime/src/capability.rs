@@ -2,6 +2,7 @@
 // Sandboxing and privilege enforcement
 
 use bitflags::bitflags;
+use std::collections::BTreeSet;
 
 bitflags! {
     #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -12,23 +13,102 @@ bitflags! {
         const FS_READ = 1 << 2;
         const FS_WRITE = 1 << 3;
         const GPU_ACCEL = 1 << 4;
+        /// Read environment variables with `env()`.
+        const ENV_READ = 1 << 5;
+        /// Message other actors with `send`, or through a rendered timeline.
+        const MESSAGE = 1 << 6;
+        /// Write to the render sink with `print` and `render`.
+        const RENDER = 1 << 7;
+        /// Set and cancel timers with `after`, `every`, `sleep` and `cancel`.
+        const TIMERS = 1 << 8;
+        /// `link`, `monitor` and their undoing.
+        const WATCH = 1 << 9;
         const SYS_ADMIN = 1 << 31;
     }
 }
 
+impl Capabilities {
+    /// What an actor does inside the runtime: message, render, set timers
+    /// and watch other actors. New VMs are granted this and nothing else.
+    pub const ACTOR: Self = Self::MESSAGE.union(Self::RENDER).union(Self::TIMERS).union(Self::WATCH);
+
+    /// What the builtin `name` requires; builtins without effects need nothing.
+    pub fn for_builtin(name: &str) -> Self {
+        match name {
+            "send" => Self::MESSAGE,
+            "print" => Self::RENDER,
+            "after" | "every" | "sleep" | "cancel" => Self::TIMERS,
+            "link" | "unlink" | "monitor" | "demonitor" => Self::WATCH,
+            _ => Self::NONE,
+        }
+    }
+
+    /// A capability as named in manifests and on the command line, in any
+    /// case. `NET` stands for both network capabilities and `ALL` for every one.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_uppercase().as_str() {
+            "NET" => Some(Self::NET_RECV | Self::NET_SEND),
+            "ALL" => Some(Self::all()),
+            name => Self::from_name(name),
+        }
+    }
+
+    /// Names of the capabilities set, e.g. `["NET_SEND", "ENV_READ"]`.
+    pub fn names(self) -> Vec<&'static str> {
+        self.iter_names().map(|(name, _)| name).collect()
+    }
+}
+
+/// An operation refused for lack of a capability.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Denied {
+    /// The native function called, or `env(NAME)` for a variable read.
+    pub operation: String,
+    pub required: Capabilities,
+}
+
+/// What an actor may do: its capabilities and, with `ENV_READ`, which
+/// environment variables it may read.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CapabilityGuard {
     allowed: Capabilities,
+    /// Variables `env()` may read; any when `None`.
+    env: Option<BTreeSet<String>>,
 }
 
 impl CapabilityGuard {
     pub fn new(allowed: Capabilities) -> Self {
-        Self { allowed }
+        Self { allowed, env: None }
+    }
+
+    /// Limit `env()` to the variables in `names`.
+    pub fn with_env<S: Into<String>>(mut self, names: impl IntoIterator<Item = S>) -> Self {
+        self.env = Some(names.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn allowed(&self) -> Capabilities {
+        self.allowed
+    }
+
+    pub fn set_allowed(&mut self, allowed: Capabilities) {
+        self.allowed = allowed;
+    }
+
+    /// The variables `env()` is limited to, if it is.
+    pub fn env(&self) -> Option<&BTreeSet<String>> {
+        self.env.as_ref()
     }
 
     pub fn check(&self, required: Capabilities) -> bool {
         self.allowed.contains(required)
     }
 
+    /// Whether `env(name)` may read the variable.
+    pub fn allows_env(&self, name: &str) -> bool {
+        self.check(Capabilities::ENV_READ) && self.env.as_ref().is_none_or(|names| names.contains(name))
+    }
+
     pub fn enforce(&self, required: Capabilities) -> Result<(), String> {
         if self.check(required) {
             Ok(())
@@ -0,0 +1,147 @@
+// AeroFlow Runtime - Chrome Trace Export
+// Trace events in the Trace Event Format for Perfetto and chrome://tracing
+
+use crate::trace::{FileOut, TraceEvent, TraceSink};
+use parking_lot::Mutex;
+use serde_json::{json, Value as Json};
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+/// Trace microseconds per logical tick. A handler's slice is as many
+/// microseconds wide as it ran instructions, up to one tick.
+pub const TICK_US: u64 = 1000;
+
+/// Logical ticks a sent message may wait for delivery and still get a flow
+/// arrow; older sends are forgotten so long runs use bounded memory.
+pub const FLOW_WINDOW: u64 = 60_000;
+
+const PID: u64 = 1;
+
+/// Converts deliveries into Trace Event Format records: one thread track
+/// per actor, a slice per handler run, and a flow arrow from each sending
+/// handler to the delivery of what it sent.
+#[derive(Default)]
+pub struct ChromeTrace {
+    tracks: HashMap<String, u64>,
+    /// Sending slice `(tid, ts)` of messages not yet delivered, by sequence id.
+    pending: HashMap<u64, (u64, u64)>,
+    /// Time `pending` was last trimmed to [`FLOW_WINDOW`].
+    trimmed_at: u64,
+}
+
+impl ChromeTrace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records for one delivery, preceded by track metadata the first time
+    /// its actor appears.
+    pub fn events(&mut self, event: &TraceEvent) -> Vec<Json> {
+        let mut out = Vec::new();
+        if self.tracks.is_empty() {
+            out.push(json!({ "ph": "M", "name": "process_name", "pid": PID, "args": { "name": "AeroFlow" } }));
+        }
+        let next = self.tracks.len() as u64 + 1;
+        let tid = *self.tracks.entry(event.actor_id.clone()).or_insert_with(|| {
+            out.push(json!({ "ph": "M", "name": "thread_name", "pid": PID, "tid": next, "args": { "name": event.actor_id } }));
+            out.push(json!({ "ph": "M", "name": "thread_sort_index", "pid": PID, "tid": next, "args": { "sort_index": next } }));
+            next
+        });
+
+        let ts = event.logical_time * TICK_US;
+        out.push(json!({
+            "ph": "X",
+            "cat": "handler",
+            "name": event.kind(),
+            "pid": PID,
+            "tid": tid,
+            "ts": ts,
+            "dur": event.instructions.clamp(1, TICK_US),
+            "args": {
+                "instructions": event.instructions,
+                "sender": event.input.sender,
+                "logical_time": event.logical_time,
+                "sequence_id": event.input.sequence_id,
+            },
+        }));
+
+        if ts >= self.trimmed_at + FLOW_WINDOW * TICK_US {
+            let horizon = ts - FLOW_WINDOW * TICK_US;
+            self.pending.retain(|_, (_, sent_at)| *sent_at >= horizon);
+            self.trimmed_at = ts;
+        }
+        let seq = event.input.sequence_id;
+        if let Some((from, sent_at)) = self.pending.remove(&seq) {
+            out.push(json!({ "ph": "s", "cat": "message", "name": "send", "id": seq, "pid": PID, "tid": from, "ts": sent_at }));
+            out.push(json!({ "ph": "f", "bp": "e", "cat": "message", "name": "send", "id": seq, "pid": PID, "tid": tid, "ts": ts }));
+        }
+        for &sent in &event.sent {
+            self.pending.insert(sent, (tid, ts));
+        }
+        out
+    }
+
+    /// Sends still waiting for their delivery's flow arrow.
+    pub fn pending_flows(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+/// A whole trace as one Trace Event Format document.
+pub fn to_chrome_json(events: impl IntoIterator<Item = TraceEvent>) -> Json {
+    let mut trace = ChromeTrace::new();
+    let records: Vec<Json> = events.into_iter().flat_map(|e| trace.events(&e)).collect();
+    json!({ "traceEvents": records, "displayTimeUnit": "ms" })
+}
+
+/// Streams a live run to a file in the JSON Array Format, one record per
+/// line. Viewers accept the array without its closing `]`, so the file is
+/// usable even if the run is cut short; [`ChromeTraceSink::close`] ends it.
+pub struct ChromeTraceSink {
+    inner: Mutex<SinkState>,
+}
+
+struct SinkState {
+    out: FileOut,
+    trace: ChromeTrace,
+    records: u64,
+    closed: bool,
+}
+
+impl ChromeTraceSink {
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut out = FileOut::create(path.as_ref())?;
+        out.write(b"[");
+        Ok(Self { inner: Mutex::new(SinkState { out, trace: ChromeTrace::new(), records: 0, closed: false }) })
+    }
+
+    /// Terminate the array and flush; later events are dropped.
+    pub fn close(&self) -> io::Result<()> {
+        let mut state = self.inner.lock();
+        if !state.closed {
+            state.closed = true;
+            state.out.write(b"\n]\n");
+        }
+        state.out.flush()
+    }
+}
+
+impl TraceSink for ChromeTraceSink {
+    fn write(&self, event: &TraceEvent) {
+        let mut state = self.inner.lock();
+        if state.closed {
+            return;
+        }
+        for record in state.trace.events(event) {
+            let separator: &[u8] = if state.records == 0 { b"\n" } else { b",\n" };
+            state.out.write(separator);
+            state.out.write(&serde_json::to_vec(&record).expect("trace records serialize"));
+            state.records += 1;
+        }
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.inner.lock().out.flush()
+    }
+}
@@ -0,0 +1,443 @@
+// AeroFlow Runtime - Time-Travel Debugger
+// Seek, inspect and break anywhere in a recorded run
+
+use crate::actor::ActorId;
+use crate::mailbox::Message;
+use crate::module::Program;
+use crate::render::MemorySink;
+use crate::replay::{RecordedStep, Recording, Replayer};
+use crate::snapshot::Snapshot;
+use aeroflow_compiler::ir::Value;
+use anyhow::{anyhow, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+
+/// Steps between the snapshots a seek restores from.
+pub const DEFAULT_CHECKPOINT_INTERVAL: usize = 64;
+
+/// Stops before a delivery that matches every field that is set.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Breakpoint {
+    /// Only messages to this actor.
+    #[serde(default)]
+    pub actor: Option<ActorId>,
+    /// Only messages for this handler, as named by [`Message::event`].
+    #[serde(default)]
+    pub message: Option<String>,
+    /// `field op value` over the receiver's state, e.g. `hits >= 3`.
+    #[serde(default)]
+    pub condition: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Cmp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Condition {
+    field: String,
+    cmp: Cmp,
+    value: Value,
+}
+
+impl Condition {
+    fn parse(text: &str) -> anyhow::Result<Self> {
+        // Two-character operators first so `<=` is not read as `<`
+        const OPS: [(&str, Cmp); 6] = [("==", Cmp::Eq), ("!=", Cmp::Ne), ("<=", Cmp::Le), (">=", Cmp::Ge), ("<", Cmp::Lt), (">", Cmp::Gt)];
+        for (op, cmp) in OPS {
+            if let Some((field, value)) = text.split_once(op) {
+                let (field, value) = (field.trim(), value.trim());
+                if field.is_empty() || value.is_empty() {
+                    break;
+                }
+                let value = serde_json::from_str(value)
+                    .map(crate::value::from_json)
+                    .unwrap_or_else(|_| Value::String(value.to_string()));
+                return Ok(Self { field: field.to_string(), cmp, value });
+            }
+        }
+        bail!("invalid condition '{}': expected `field op value` with one of == != < <= > >=", text)
+    }
+
+    fn holds(&self, actual: Option<Value>) -> bool {
+        let actual = actual.unwrap_or(Value::Nil);
+        let order = match (&actual, &self.value) {
+            (Value::Number(a), Value::Number(b)) => a.partial_cmp(b),
+            (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+            _ => None,
+        };
+        match self.cmp {
+            Cmp::Eq => actual == self.value,
+            Cmp::Ne => actual != self.value,
+            Cmp::Lt => order.is_some_and(|o| o.is_lt()),
+            Cmp::Le => order.is_some_and(|o| o.is_le()),
+            Cmp::Gt => order.is_some_and(|o| o.is_gt()),
+            Cmp::Ge => order.is_some_and(|o| o.is_ge()),
+        }
+    }
+}
+
+/// The message the next step delivers.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Delivery {
+    pub actor: ActorId,
+    pub sender: ActorId,
+    pub event: String,
+    pub logical_time: u64,
+    pub sequence_id: u64,
+}
+
+/// Why a `continue` stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "reason", content = "breakpoint")]
+pub enum Stop {
+    Breakpoint(u64),
+    /// Reverse-continue found no breakpoint and rewound to step 0.
+    Start,
+    /// The recording is exhausted.
+    End,
+}
+
+struct Checkpoint {
+    snapshot: Snapshot,
+    inputs_injected: usize,
+}
+
+/// Moves freely through a [`Recording`]: seeks restore the nearest
+/// checkpoint and replay forward, so every position is verified.
+pub struct Debugger {
+    program: Program,
+    replayer: Replayer,
+    checkpoints: BTreeMap<usize, Checkpoint>,
+    interval: usize,
+    breakpoints: BTreeMap<u64, (Breakpoint, Option<Condition>)>,
+    next_breakpoint: u64,
+}
+
+impl Debugger {
+    pub fn new(recording: Recording) -> anyhow::Result<Self> {
+        let program = Program::from(recording.program.clone());
+        Self::with_program(recording, program)
+    }
+
+    pub fn with_program(recording: Recording, program: impl Into<Program>) -> anyhow::Result<Self> {
+        let program = program.into();
+        let mut debugger = Self {
+            replayer: quiet(Replayer::with_program(recording, program.clone())),
+            program,
+            checkpoints: BTreeMap::new(),
+            interval: DEFAULT_CHECKPOINT_INTERVAL,
+            breakpoints: BTreeMap::new(),
+            next_breakpoint: 1,
+        };
+        debugger.prepare()?;
+        Ok(debugger)
+    }
+
+    pub fn set_checkpoint_interval(&mut self, steps: usize) {
+        self.interval = steps.max(1);
+    }
+
+    /// Steps delivered so far.
+    pub fn position(&self) -> usize {
+        self.replayer.position()
+    }
+
+    /// Steps in the recording.
+    pub fn len(&self) -> usize {
+        self.replayer.recording().steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Logical time of the last delivered message.
+    pub fn logical_time(&self) -> u64 {
+        self.current().map_or(0, |step| step.logical_time)
+    }
+
+    /// The step that was just delivered.
+    pub fn current(&self) -> Option<&RecordedStep> {
+        self.position().checked_sub(1).and_then(|i| self.replayer.recording().steps.get(i))
+    }
+
+    /// Checkpoint if this position is due one, then queue the inputs the next
+    /// step will see.
+    fn prepare(&mut self) -> anyhow::Result<()> {
+        let position = self.position();
+        if position.is_multiple_of(self.interval) && !self.checkpoints.contains_key(&position) {
+            let snapshot = self.replayer.scheduler().snapshot()?;
+            self.checkpoints.insert(position, Checkpoint { snapshot, inputs_injected: self.replayer.inputs_injected() });
+        }
+        self.replayer.inject_inputs();
+        Ok(())
+    }
+
+    pub fn next_delivery(&mut self) -> anyhow::Result<Option<Delivery>> {
+        self.prepare()?;
+        Ok(self.replayer.scheduler().peek().map(|(actor, msg)| Delivery {
+            event: msg.event().0,
+            actor,
+            sender: msg.sender,
+            logical_time: msg.logical_time,
+            sequence_id: msg.sequence_id,
+        }))
+    }
+
+    /// Deliver one message. `Ok(None)` at the end of the recording.
+    pub fn step_forward(&mut self) -> anyhow::Result<Option<RecordedStep>> {
+        self.prepare()?;
+        Ok(self.replayer.step()?)
+    }
+
+    /// Undo the last step. `false` at step 0.
+    pub fn step_back(&mut self) -> anyhow::Result<bool> {
+        match self.position().checked_sub(1) {
+            Some(target) => self.seek(target).map(|_| true),
+            None => Ok(false),
+        }
+    }
+
+    /// Move to just after `step` deliveries (clamped to the recording).
+    pub fn seek(&mut self, step: usize) -> anyhow::Result<()> {
+        let step = step.min(self.len());
+        // Step 0 is always checkpointed
+        let (&at, checkpoint) = self.checkpoints.range(..=step).next_back()
+            .ok_or_else(|| anyhow!("no checkpoint at or before step {}", step))?;
+        if step < self.position() || at > self.position() {
+            let recording = self.replayer.recording().clone();
+            self.replayer = quiet(Replayer::resume(recording, self.program.clone(), &checkpoint.snapshot, at, checkpoint.inputs_injected)?);
+        }
+        while self.position() < step {
+            if self.step_forward()?.is_none() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Move to just after every delivery at or before logical time `time`.
+    pub fn seek_time(&mut self, time: u64) -> anyhow::Result<()> {
+        let step = self.replayer.recording().steps.iter().take_while(|s| s.logical_time <= time).count();
+        self.seek(step)
+    }
+
+    /// Run forward until the next delivery matches a breakpoint.
+    pub fn continue_forward(&mut self) -> anyhow::Result<Stop> {
+        if self.step_forward()?.is_none() {
+            return Ok(Stop::End);
+        }
+        loop {
+            if let Some(id) = self.hit()? {
+                return Ok(Stop::Breakpoint(id));
+            }
+            if self.step_forward()?.is_none() {
+                return Ok(Stop::End);
+            }
+        }
+    }
+
+    /// Rewind to the last breakpoint hit before the current position.
+    pub fn continue_backward(&mut self) -> anyhow::Result<Stop> {
+        let end = self.position();
+        self.seek(0)?;
+        let mut last = None;
+        while self.position() < end {
+            if let Some(id) = self.hit()? {
+                last = Some((self.position(), id));
+            }
+            self.step_forward()?;
+        }
+        match last {
+            Some((step, id)) => self.seek(step).map(|_| Stop::Breakpoint(id)),
+            None => self.seek(0).map(|_| Stop::Start),
+        }
+    }
+
+    /// The breakpoint the next delivery matches, if any.
+    fn hit(&mut self) -> anyhow::Result<Option<u64>> {
+        if self.breakpoints.is_empty() {
+            return Ok(None);
+        }
+        self.prepare()?;
+        let scheduler = self.replayer.scheduler();
+        let Some((target, msg)) = scheduler.peek() else { return Ok(None) };
+        let event = msg.event().0;
+        Ok(self.breakpoints.iter().find(|(_, (bp, condition))| {
+            bp.actor.as_ref().is_none_or(|a| *a == target)
+                && bp.message.as_ref().is_none_or(|m| *m == event)
+                && condition.as_ref().is_none_or(|c| c.holds(scheduler.read_field(&target, &c.field)))
+        }).map(|(id, _)| *id))
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> anyhow::Result<u64> {
+        let condition = breakpoint.condition.as_deref().map(Condition::parse).transpose()?;
+        let id = self.next_breakpoint;
+        self.next_breakpoint += 1;
+        self.breakpoints.insert(id, (breakpoint, condition));
+        Ok(id)
+    }
+
+    pub fn remove_breakpoint(&mut self, id: u64) -> bool {
+        self.breakpoints.remove(&id).is_some()
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = (u64, &Breakpoint)> {
+        self.breakpoints.iter().map(|(id, (bp, _))| (*id, bp))
+    }
+
+    pub fn actors(&self) -> Vec<ActorId> {
+        self.replayer.scheduler().actor_ids()
+    }
+
+    /// An actor's state at the current position.
+    pub fn state(&self, actor: &str) -> Option<serde_json::Value> {
+        let state = self.replayer.scheduler().actor_state(actor)?;
+        Some(match serde_json::from_str::<BTreeMap<String, Value>>(&state) {
+            Ok(fields) => fields.into_iter().map(|(k, v)| (k, crate::value::into_json(v))).collect(),
+            Err(_) => serde_json::from_str(&state).unwrap_or(serde_json::Value::String(state)),
+        })
+    }
+
+    /// Messages waiting for `actor` at the current position, in delivery order.
+    pub fn mailbox(&mut self, actor: &str) -> anyhow::Result<Vec<Message>> {
+        self.prepare()?;
+        Ok(self.replayer.scheduler().mailbox(actor))
+    }
+
+    fn where_am_i(&mut self) -> anyhow::Result<serde_json::Value> {
+        Ok(json!({
+            "step": self.position(),
+            "steps": self.len(),
+            "logical_time": self.logical_time(),
+            "current": self.current(),
+            "next": self.next_delivery()?,
+        }))
+    }
+
+    /// Answer one JSON-RPC 2.0 request.
+    pub fn handle(&mut self, request: &serde_json::Value) -> serde_json::Value {
+        let id = request.get("id").cloned().unwrap_or(serde_json::Value::Null);
+        let method = request.get("method").and_then(|m| m.as_str()).unwrap_or("");
+        let params = request.get("params").cloned().unwrap_or_else(|| json!({}));
+        match self.dispatch(method, &params) {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(e) => {
+                let (code, message) = match e {
+                    RpcError::UnknownMethod => (-32601, format!("unknown method '{}'", method)),
+                    RpcError::Params(m) => (-32602, m),
+                    RpcError::Debugger(e) => (-32000, format!("{:#}", e)),
+                };
+                json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+            }
+        }
+    }
+
+    fn dispatch(&mut self, method: &str, params: &serde_json::Value) -> Result<serde_json::Value, RpcError> {
+        let actor = || params.get("actor").and_then(|a| a.as_str()).ok_or_else(|| RpcError::Params("missing 'actor'".to_string()));
+        Ok(match method {
+            "position" => self.where_am_i()?,
+            "seek" => {
+                match (params.get("step").and_then(|s| s.as_u64()), params.get("time").and_then(|t| t.as_u64())) {
+                    (Some(step), _) => self.seek(step as usize)?,
+                    (None, Some(time)) => self.seek_time(time)?,
+                    (None, None) => return Err(RpcError::Params("seek needs 'step' or 'time'".to_string())),
+                }
+                self.where_am_i()?
+            }
+            "stepForward" => {
+                self.step_forward()?;
+                self.where_am_i()?
+            }
+            "stepBack" => {
+                self.step_back()?;
+                self.where_am_i()?
+            }
+            "continue" | "reverseContinue" => {
+                let stop = if method == "continue" { self.continue_forward()? } else { self.continue_backward()? };
+                let mut position = self.where_am_i()?;
+                position["stop"] = serde_json::to_value(stop).map_err(anyhow::Error::from)?;
+                position
+            }
+            "actors" => json!(self.actors()),
+            "state" => self.state(actor()?).ok_or_else(|| RpcError::Params(format!("no actor '{}'", actor().unwrap_or_default())))?,
+            "mailbox" => serde_json::to_value(self.mailbox(actor()?)?).map_err(anyhow::Error::from)?,
+            "setBreakpoint" => {
+                let breakpoint: Breakpoint = serde_json::from_value(params.clone()).map_err(|e| RpcError::Params(e.to_string()))?;
+                let id = self.add_breakpoint(breakpoint).map_err(|e| RpcError::Params(e.to_string()))?;
+                json!({ "id": id })
+            }
+            "removeBreakpoint" => {
+                let id = params.get("id").and_then(|i| i.as_u64()).ok_or_else(|| RpcError::Params("missing 'id'".to_string()))?;
+                json!(self.remove_breakpoint(id))
+            }
+            "breakpoints" => json!(self.breakpoints().map(|(id, bp)| json!({ "id": id, "breakpoint": bp })).collect::<Vec<_>>()),
+            _ => return Err(RpcError::UnknownMethod),
+        })
+    }
+
+    /// Serve newline-delimited JSON-RPC to one client at a time until a
+    /// client sends `shutdown`. A connection that fails is logged and
+    /// dropped; the debugger keeps accepting others.
+    pub fn serve(mut self, listener: TcpListener) -> std::io::Result<()> {
+        for stream in listener.incoming() {
+            match stream.and_then(|stream| self.session(stream)) {
+                Ok(true) => return Ok(()),
+                Ok(false) => {}
+                Err(e) => eprintln!("debugger: dropped connection: {}", e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Answer one client's requests; `true` once it asks to shut down.
+    fn session(&mut self, stream: TcpStream) -> std::io::Result<bool> {
+        let mut writer = stream.try_clone()?;
+        for line in BufReader::new(stream).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = match serde_json::from_str::<serde_json::Value>(&line) {
+                Ok(request) if request.get("method").and_then(|m| m.as_str()) == Some("shutdown") => {
+                    let id = request.get("id").cloned().unwrap_or(serde_json::Value::Null);
+                    writeln!(writer, "{}", json!({ "jsonrpc": "2.0", "id": id, "result": null }))?;
+                    return Ok(true);
+                }
+                Ok(request) => self.handle(&request),
+                Err(e) => json!({ "jsonrpc": "2.0", "id": null, "error": { "code": -32700, "message": e.to_string() } }),
+            };
+            writeln!(writer, "{}", response)?;
+        }
+        Ok(false)
+    }
+}
+
+/// Output from re-executed steps is not the client's to see.
+fn quiet(replayer: Replayer) -> Replayer {
+    replayer.scheduler().set_render_sink(Arc::new(MemorySink::new()));
+    replayer
+}
+
+enum RpcError {
+    UnknownMethod,
+    Params(String),
+    Debugger(anyhow::Error),
+}
+
+impl From<anyhow::Error> for RpcError {
+    fn from(e: anyhow::Error) -> Self {
+        RpcError::Debugger(e)
+    }
+}
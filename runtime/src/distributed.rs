@@ -1,6 +1,8 @@
 use crate::scheduler::Scheduler;
-use crate::mailbox::{Message, MessageData};
+use crate::mailbox::MessageData;
 use crate::actor::ActorId;
+use crate::state::StateSource;
+use aeroflow_compiler::ir::Value;
 use serde::{Serialize, Deserialize};
 use std::sync::Arc;
 use parking_lot::Mutex;
@@ -21,6 +23,7 @@ pub struct DistributedScheduler {
     pub node_id: NodeId,
     inner: Arc<Scheduler>,
     peers: Mutex<Vec<NodeId>>, // In a real system, these would be network connections
+    peer_state: Mutex<Vec<(NodeId, Arc<dyn StateSource>)>>,
 }
 
 impl DistributedScheduler {
@@ -29,6 +32,7 @@ impl DistributedScheduler {
             node_id,
             inner,
             peers: Mutex::new(Vec::new()),
+            peer_state: Mutex::new(Vec::new()),
         }
     }
 
@@ -36,13 +40,16 @@ impl DistributedScheduler {
         self.peers.lock().push(peer_id);
     }
 
+    /// Add a peer whose actors' state can be read for `render distributed state`.
+    /// In simulations this is the peer's in-process scheduler.
+    pub fn connect_peer(&self, peer_id: NodeId, state: Arc<dyn StateSource>) {
+        self.peer_state.lock().push((peer_id.clone(), state));
+        self.add_peer(peer_id);
+    }
+
     /// Broadcast a message to all nodes in the cluster (Deterministic Broadcast)
     pub fn broadcast(&self, target_actor: ActorId, sender_actor: ActorId, data: MessageData) {
-        let logical_time = {
-            let mut clock = self.inner.logical_clock.lock();
-            *clock += 1;
-            *clock
-        };
+        let logical_time = self.inner.arrival(&sender_actor, &target_actor);
 
         let seq = {
             let mut s = self.inner.sequence_counter.lock();
@@ -70,7 +77,7 @@ impl DistributedScheduler {
     /// Receive a message from another node and insert it into the local DAS queue
     pub fn receive_remote(&self, msg: DistributedMessage) {
         // Enforce determinism: The message is placed into the DAS priority queue.
-        // Since the queue is ordered by (logical_time, target, seq), 
+        // Since the queue is ordered by (logical_time, sender, seq),
         // all nodes with the same messages will process them in the exact same order.
         
         // This is the heart of AeroFlow's multi-node determinism.
@@ -83,3 +90,12 @@ impl DistributedScheduler {
         );
     }
 }
+
+/// Local actors shadow remote ones; peers are consulted in the order they were connected.
+impl StateSource for DistributedScheduler {
+    fn read_field(&self, actor: &str, field: &str) -> Option<Value> {
+        self.inner.read_field(actor, field).or_else(|| {
+            self.peer_state.lock().iter().find_map(|(_, peer)| peer.read_field(actor, field))
+        })
+    }
+}
@@ -0,0 +1,217 @@
+// AeroFlow Runtime - Embedding Engine
+// Host AeroFlow programs inside Rust applications
+
+use crate::actor::{Actor, ActorCell, ActorId};
+use crate::capability::{Capabilities, CapabilityGuard};
+use crate::mailbox::MessageData;
+use crate::render::{CallbackSink, RenderSink, StdoutSink};
+use crate::scheduler::Scheduler;
+use crate::ui::{ScreenActor, UiEvent};
+use crate::value::{from_value, to_value};
+use crate::vm::{Native, VMContext, VM};
+use aeroflow_compiler::afm::{AfmModule, TrustStore};
+use aeroflow_compiler::ir::{Chunk, Value};
+use anyhow::{anyhow, Context as _};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::sync::Arc;
+
+/// High-level entry point for running AeroFlow code in-process.
+///
+/// ```no_run
+/// use aeroflow_runtime::Engine;
+///
+/// let mut engine = Engine::new();
+/// engine.on_output(|v| println!("aeroflow: {}", v));
+/// engine.load_source("fn add(a: int, b: int) -> int { return a + b }").unwrap();
+/// let sum: i64 = engine.call("add", (2, 3)).unwrap();
+/// ```
+pub struct Engine {
+    vm: VM,
+    chunk: Chunk,
+    scheduler: Arc<Scheduler>,
+    sink: Arc<dyn RenderSink>,
+    trust: TrustStore,
+    logical_time: u64,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        let scheduler = Scheduler::shared();
+        let mut vm = VM::new();
+
+        // `send(target, text)` lets scripts message host actors
+        let outbox = scheduler.clone();
+        vm.register_native("send", Native {
+            required: Capabilities::NONE,
+            func: Arc::new(move |args| {
+                let target = match args.first() {
+                    Some(Value::String(s)) => s.clone(),
+                    _ => return Err(anyhow!("send expects an actor id as its first argument")),
+                };
+                let text = args.get(1).map(|v| v.to_string()).unwrap_or_default();
+                outbox.send(target, MessageData::Text(text), "engine".to_string());
+                Ok(Value::Nil)
+            }),
+        });
+
+        let mut engine = Self {
+            vm,
+            chunk: Chunk::new(),
+            scheduler,
+            sink: Arc::new(StdoutSink),
+            trust: TrustStore::new(),
+            logical_time: 0,
+        };
+        engine.set_render_sink(Arc::new(StdoutSink));
+        engine
+    }
+
+    /// Compile AeroFlow source and run its top-level statements.
+    pub fn load_source(&mut self, source: &str) -> anyhow::Result<()> {
+        let chunk = aeroflow_compiler::compile(source)?;
+        self.load_chunk(chunk)
+    }
+
+    /// Load a compiled `.afm` module and run its top-level statements. The
+    /// module's hash and signature are checked against the engine's trust store.
+    pub fn load_afm(&mut self, bytes: &[u8]) -> anyhow::Result<()> {
+        let module = AfmModule::load(bytes, &self.trust).context("refusing to load .afm module")?;
+        self.load_chunk(module.chunk)
+    }
+
+    /// Keys accepted by [`Engine::load_afm`].
+    pub fn set_trust_store(&mut self, trust: TrustStore) {
+        self.trust = trust;
+    }
+
+    pub fn load_chunk(&mut self, chunk: Chunk) -> anyhow::Result<()> {
+        self.chunk = chunk;
+        let ctx = self.next_context();
+        self.vm.execute(&self.chunk, &ctx)?;
+        Ok(())
+    }
+
+    /// Expose a Rust closure to scripts. Arguments and result are converted via serde.
+    pub fn register_fn<A, R, F>(&mut self, name: &str, f: F)
+    where
+        A: DeserializeOwned,
+        R: Serialize,
+        F: Fn(A) -> anyhow::Result<R> + Send + Sync + 'static,
+    {
+        self.register_fn_with_capabilities(name, Capabilities::NONE, f);
+    }
+
+    /// Like [`Engine::register_fn`], but calls are refused unless the engine holds `required`.
+    pub fn register_fn_with_capabilities<A, R, F>(&mut self, name: &str, required: Capabilities, f: F)
+    where
+        A: DeserializeOwned,
+        R: Serialize,
+        F: Fn(A) -> anyhow::Result<R> + Send + Sync + 'static,
+    {
+        self.vm.register_native(name, Native {
+            required,
+            func: Arc::new(move |args| {
+                // Zero arguments map to `()`, anything else to a tuple
+                let args = if args.is_empty() { Value::Nil } else { Value::List(args.to_vec()) };
+                let args = from_value(args)?;
+                to_value(&f(args)?)
+            }),
+        });
+    }
+
+    /// Register a Rust actor that scripts can reach with `send(id, text)`.
+    pub fn register_actor(&mut self, id: &str, actor: impl Actor + 'static) {
+        self.scheduler.spawn(ActorCell::new(id.to_string(), Box::new(actor)));
+    }
+
+    /// Spawn the loaded program's `screen` as an actor with the same id and render it.
+    /// Interactions are delivered with [`Engine::ui_event`].
+    pub fn mount_screen(&mut self, name: &str) -> anyhow::Result<ActorId> {
+        let mut screen = ScreenActor::new(self.chunk.clone(), name)
+            .ok_or_else(|| anyhow!("unknown screen '{}'", name))?;
+        for (native, def) in self.vm.natives() {
+            screen.vm_mut().register_native(native, def.clone());
+        }
+        screen.vm_mut().set_guard(self.vm.guard().clone());
+        self.scheduler.spawn(ActorCell::new(name.to_string(), Box::new(screen)));
+        self.ui_event(name, UiEvent::Mount);
+        Ok(name.to_string())
+    }
+
+    /// Queue a click, edit or toggle for a mounted screen.
+    pub fn ui_event(&self, screen: &str, event: UiEvent) {
+        self.scheduler.send(screen.to_string(), event.into(), "ui".to_string());
+    }
+
+    pub fn set_capabilities(&mut self, capabilities: Capabilities) {
+        self.vm.set_capabilities(capabilities);
+    }
+
+    /// Like [`Engine::set_capabilities`], also limiting which variables `env()` may read.
+    pub fn set_guard(&mut self, guard: CapabilityGuard) {
+        self.vm.set_guard(guard);
+    }
+
+    /// Receive rendered values instead of printing them to stdout.
+    pub fn on_output(&mut self, f: impl Fn(&Value) + Send + Sync + 'static) {
+        self.set_render_sink(Arc::new(CallbackSink(f)));
+    }
+
+    /// Receive every render event, including timelines, state snapshots and UI trees.
+    pub fn set_render_sink(&mut self, sink: Arc<dyn RenderSink>) {
+        self.scheduler.set_render_sink(sink.clone());
+        self.sink = sink;
+    }
+
+    /// Call an exported `fn`. `args` is a tuple (or a single value) of serializable arguments.
+    pub fn call<A: Serialize, R: DeserializeOwned>(&mut self, name: &str, args: A) -> anyhow::Result<R> {
+        let args = match to_value(&args)? {
+            Value::List(items) => items,
+            Value::Nil => Vec::new(),
+            single => vec![single],
+        };
+        let ctx = self.next_context();
+        let result = self.vm.call(&self.chunk, name, args, &ctx)?;
+        from_value(result)
+    }
+
+    /// Read a global variable set by the script.
+    pub fn global<R: DeserializeOwned>(&self, name: &str) -> anyhow::Result<R> {
+        let value = self.vm.get_globals().get(name).cloned().unwrap_or(Value::Nil);
+        from_value(value)
+    }
+
+    pub fn scheduler(&self) -> &Arc<Scheduler> {
+        &self.scheduler
+    }
+
+    /// Deliver a message to a registered actor.
+    pub fn send(&self, target: ActorId, data: MessageData) {
+        self.scheduler.send(target, data, "engine".to_string());
+    }
+
+    /// Drive the scheduler until no messages remain. Returns the number of messages delivered.
+    pub fn run_until_idle(&self) -> usize {
+        let mut processed = 0;
+        while let Some(delivered) = self.scheduler.advance() {
+            processed += usize::from(delivered);
+        }
+        processed
+    }
+
+    fn next_context(&mut self) -> VMContext<'static> {
+        self.logical_time += 1;
+        let mut ctx = VMContext::new(self.logical_time, self.sink.clone());
+        ctx.state = Some(self.scheduler.clone());
+        // `render timeline { ... }` blocks schedule their messages into the DAS
+        ctx.bus = Some(Arc::new(Arc::downgrade(&self.scheduler)));
+        ctx
+    }
+}
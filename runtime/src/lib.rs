@@ -1,21 +1,43 @@
 pub mod vm;
 pub mod arena;
+pub mod quota;
 pub mod actor;
 pub mod mailbox;
 pub mod scheduler;
+pub mod ordering;
 pub mod capability;
 pub mod supervisor;
 pub mod system;
 pub mod trace;
+pub mod chrome_trace;
 pub mod vm_actor;
 pub mod distributed;
 pub mod wasm;
+pub mod engine;
+pub mod render;
+pub mod value;
+pub mod timeline;
+pub mod state;
+pub mod ui;
+pub mod web;
+pub mod tui;
+pub mod module;
+pub mod snapshot;
+pub mod replay;
+pub mod debugger;
 
-pub use vm::VM;
+pub use vm::{VM, VmError};
 pub use arena::Arena;
 pub use actor::{Actor, ActorCell, Context};
 pub use mailbox::{Message, MessageData};
 pub use scheduler::Scheduler;
-pub use capability::{Capabilities, CapabilityGuard};
+pub use capability::{Capabilities, CapabilityGuard, Denied};
 pub use trace::{Tracer, TraceEvent, get_tracer};
 pub use vm_actor::VMActor;
+pub use module::Program;
+pub use snapshot::Snapshot;
+pub use state::StateSource;
+pub use engine::Engine;
+pub use ui::{ScreenActor, UiEvent, UiNode};
+pub use render::{RenderEvent, RenderSink, StdoutSink, MemorySink, JsonLinesSink};
+pub use value::{to_value, from_value};
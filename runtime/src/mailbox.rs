@@ -1,6 +1,7 @@
 // AeroFlow Runtime - Messages (Deterministic)
 // Core sequence and timestamping for DAS
 
+use aeroflow_compiler::ir::Value;
 use serde::{Serialize, Deserialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -11,6 +12,55 @@ pub enum MessageData {
     Signal(String),
 }
 
+impl From<Value> for MessageData {
+    /// Strings travel as text; everything else as JSON.
+    fn from(value: Value) -> Self {
+        match value {
+            Value::String(s) => MessageData::Text(s),
+            other => MessageData::Json(crate::value::into_json(other).to_string()),
+        }
+    }
+}
+
+/// Lets running code message other actors; implemented by the scheduler.
+pub trait MessageBus: Send + Sync {
+    fn send(&self, from: &str, to: &str, data: MessageData);
+
+    /// Send like [`MessageBus::send`], returning `false` if a full `block`
+    /// mailbox holds the message back. `from` should then wait: it is sent
+    /// a message from `wake` once the message is let in. Buses without such
+    /// mailboxes never hold messages back.
+    fn send_or_wait(&self, from: &str, to: &str, data: MessageData, wake: &str) -> bool {
+        let _ = wake;
+        self.send(from, to, data);
+        true
+    }
+
+    /// Deliver at logical time `at`, for timers. Buses without a clock
+    /// deliver right away.
+    fn send_at(&self, from: &str, to: &str, data: MessageData, at: u64) {
+        let _ = at;
+        self.send(from, to, data);
+    }
+
+    /// Link or monitor another actor; buses that do not track actors'
+    /// lifecycles ignore it.
+    fn watch(&self, from: &str, to: &str, watch: Watch) {
+        let _ = (from, to, watch);
+    }
+}
+
+/// What `from` asks of the runtime about `to` in [`MessageBus::watch`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Watch {
+    /// A failure of either stops the other.
+    Link,
+    Unlink,
+    /// Send `from` a `Down(actor, reason)` message when `to` fails or stops.
+    Monitor,
+    Demonitor,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
     pub sender: String,
@@ -28,4 +78,131 @@ impl Message {
             sequence_id: seq,
         }
     }
+
+    /// Handler name and arguments carried by the message: text and signals name
+    /// the event, a JSON array is `[event, args...]`, anything else is `message(data)`.
+    pub fn event(&self) -> (String, Vec<Value>) {
+        match &self.data {
+            MessageData::Text(t) | MessageData::Signal(t) => (t.clone(), Vec::new()),
+            MessageData::Json(j) => match serde_json::from_str(j).map(crate::value::from_json) {
+                Ok(Value::List(mut items)) if matches!(items.first(), Some(Value::String(_))) => {
+                    let name = items.remove(0).to_string();
+                    (name, items)
+                }
+                Ok(other) => ("message".to_string(), vec![other]),
+                Err(_) => ("message".to_string(), vec![Value::String(j.clone())]),
+            },
+            MessageData::Binary(b) => ("message".to_string(), vec![Value::Number(b.len() as f64)]),
+        }
+    }
+}
+
+/// Signal that stops its target gracefully once delivered (see
+/// `Scheduler::stop`).
+pub const STOP: &str = "stop";
+
+/// The actor that receives `Undelivered(reason, to, sender, event, args)` for
+/// every message that could not be delivered, when one is spawned.
+pub const DEAD_LETTERS: &str = "DeadLetters";
+
+/// What happens to a message sent to a full mailbox.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Overflow {
+    /// Turn the new message away.
+    #[default]
+    DropNew,
+    /// Evict the oldest message not yet due to make room.
+    DropOld,
+    /// Hold the message back until a slot frees, suspending the sending
+    /// actor until then; nothing is lost.
+    Block,
+    /// Turn the message away and report the sender as failed.
+    Fail,
+}
+
+impl Overflow {
+    /// `drop-new`, `drop-old`, `block` or `fail`.
+    pub fn parse(text: &str) -> Option<Self> {
+        match text {
+            "drop-new" => Some(Self::DropNew),
+            "drop-old" => Some(Self::DropOld),
+            "block" => Some(Self::Block),
+            "fail" => Some(Self::Fail),
+            _ => None,
+        }
+    }
+}
+
+/// Limits on an actor's queued messages. Timer messages are always accepted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct MailboxConfig {
+    /// Most messages queued at once; `None` is unbounded.
+    pub capacity: Option<usize>,
+    pub overflow: Overflow,
+}
+
+impl MailboxConfig {
+    pub fn bounded(capacity: usize, overflow: Overflow) -> Self {
+        Self { capacity: Some(capacity), overflow }
+    }
+}
+
+/// Queue statistics for one actor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct MailboxMetrics {
+    /// Messages queued now.
+    pub depth: usize,
+    /// Most messages ever queued at once.
+    pub high_water: usize,
+    /// Messages held back by a full `Block` mailbox.
+    pub blocked: usize,
+    pub delivered: u64,
+    /// Messages turned away, evicted or undeliverable.
+    pub dropped: u64,
+}
+
+/// Why a message went to the dead letters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeadReason {
+    /// No actor has the target id.
+    UnknownActor,
+    /// The target's mailbox was full.
+    MailboxFull,
+    /// Evicted from a full mailbox by a newer message.
+    Evicted,
+}
+
+impl DeadReason {
+    pub fn code(&self) -> &'static str {
+        match self {
+            DeadReason::UnknownActor => "unknown_actor",
+            DeadReason::MailboxFull => "mailbox_full",
+            DeadReason::Evicted => "evicted",
+        }
+    }
+}
+
+/// A message that could not be delivered.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub target: String,
+    pub message: Message,
+    pub reason: DeadReason,
+    /// When it was given up on.
+    pub logical_time: u64,
+}
+
+impl DeadLetter {
+    /// The `Undelivered(reason, to, sender, event, args)` message for [`DEAD_LETTERS`].
+    pub fn to_data(&self) -> MessageData {
+        let (event, args) = self.message.event();
+        MessageData::from(Value::List(vec![
+            Value::String("Undelivered".to_string()),
+            Value::String(self.reason.code().to_string()),
+            Value::String(self.target.clone()),
+            Value::String(self.message.sender.clone()),
+            Value::String(event),
+            Value::List(args),
+        ]))
+    }
 }
@@ -1,16 +0,0 @@
-// AeroFlow Memory Model
-// Arena-based, Zero GC
-
-pub struct Arena {
-    // Memory chunks
-}
-
-impl Arena {
-    pub fn new() -> Self {
-        Self {}
-    }
-
-    pub fn alloc(&self) {
-        // Linear allocation
-    }
-}
@@ -0,0 +1,161 @@
+// AeroFlow Runtime - Modules
+// Memory-mapped `.afm` images shared by every actor that runs them
+
+use aeroflow_compiler::afm::{AfmHeader, AfmImage, AfmMetadata, AfmModule, TrustStore, ARCH_IMAGE, HEADER_LEN};
+use aeroflow_compiler::image::{AlignedBytes, StableBytes};
+use aeroflow_compiler::ir::{ActorDef, Chunk, Code, Function, LineEntry, Op, ScreenDef, SupervisorDef};
+use memmap2::Mmap;
+use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Backing store of a loaded image.
+pub enum ModuleBytes {
+    Mapped(Mmap),
+    Owned(AlignedBytes),
+}
+
+impl AsRef<[u8]> for ModuleBytes {
+    fn as_ref(&self) -> &[u8] {
+        match self {
+            ModuleBytes::Mapped(map) => map,
+            ModuleBytes::Owned(bytes) => bytes.as_ref(),
+        }
+    }
+}
+
+// SAFETY: owned bytes are never handed out mutably, and maps only come from
+// `map`, whose caller promises the file does not change while it is loaded.
+unsafe impl StableBytes for ModuleBytes {}
+
+pub type LoadedModule = AfmImage<ModuleBytes>;
+
+/// Map an image module from disk; its code is executed straight from the
+/// page cache. Strings are checked as UTF-8 here, once, and read unchecked
+/// afterwards.
+///
+/// # Safety
+/// The file must not be modified or truncated while the module, or
+/// anything borrowed from it, is alive.
+pub unsafe fn map(path: &Path, trust: &TrustStore) -> anyhow::Result<LoadedModule> {
+    let file = std::fs::File::open(path)?;
+    // SAFETY: the caller keeps the file unchanged; the mapping is only read.
+    let map = unsafe { Mmap::map(&file)? };
+    AfmImage::load(ModuleBytes::Mapped(map), trust)
+}
+
+/// Load an image module from memory into an aligned buffer.
+pub fn from_bytes(bytes: &[u8], trust: &TrustStore) -> anyhow::Result<LoadedModule> {
+    AfmImage::load(ModuleBytes::Owned(AlignedBytes::copy_from(bytes)), trust)
+}
+
+/// Code shared by the actors running it: an owned chunk or a loaded image.
+#[derive(Clone)]
+pub enum Program {
+    Chunk(Arc<Chunk>),
+    Module(Arc<LoadedModule>),
+}
+
+impl Program {
+    /// Open a `.afm`: images are mapped in place, bincode modules decoded.
+    ///
+    /// # Safety
+    /// As for [`map`]: the file must not change while the program is alive.
+    pub unsafe fn open(path: &Path, trust: &TrustStore) -> anyhow::Result<(Self, AfmMetadata)> {
+        let mut header = [0u8; HEADER_LEN];
+        std::fs::File::open(path)?.read_exact(&mut header)?;
+        if AfmHeader::from_bytes(&header)?.arch == ARCH_IMAGE {
+            // SAFETY: passed on to our caller
+            let module = unsafe { map(path, trust)? };
+            let metadata = module.metadata.clone();
+            return Ok((Program::Module(Arc::new(module)), metadata));
+        }
+        let module = AfmModule::load(&std::fs::read(path)?, trust)?;
+        Ok((Program::Chunk(Arc::new(module.chunk)), module.metadata))
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        match self {
+            Program::Chunk(chunk) => (**chunk).clone(),
+            Program::Module(module) => module.to_chunk(),
+        }
+    }
+
+    /// Identifies the program's code, whether compiled or loaded from a module.
+    pub fn hash(&self) -> u64 {
+        let chunk = match self {
+            Program::Chunk(chunk) => bincode::serialize(&**chunk),
+            Program::Module(module) => bincode::serialize(&module.to_chunk()),
+        };
+        crate::replay::state_hash(&chunk.unwrap_or_default())
+    }
+}
+
+impl From<Chunk> for Program {
+    fn from(chunk: Chunk) -> Self {
+        Program::Chunk(Arc::new(chunk))
+    }
+}
+
+impl From<&Chunk> for Program {
+    fn from(chunk: &Chunk) -> Self {
+        Program::Chunk(Arc::new(chunk.clone()))
+    }
+}
+
+impl From<LoadedModule> for Program {
+    fn from(module: LoadedModule) -> Self {
+        Program::Module(Arc::new(module))
+    }
+}
+
+impl Code for Program {
+    fn len(&self) -> usize {
+        match self {
+            Program::Chunk(chunk) => chunk.len(),
+            Program::Module(module) => module.len(),
+        }
+    }
+
+    fn op(&self, ip: usize) -> Op<'_> {
+        match self {
+            Program::Chunk(chunk) => chunk.op(ip),
+            Program::Module(module) => module.op(ip),
+        }
+    }
+
+    fn functions(&self) -> &[Function] {
+        match self {
+            Program::Chunk(chunk) => &chunk.functions,
+            Program::Module(module) => module.functions(),
+        }
+    }
+
+    fn screens(&self) -> &[ScreenDef] {
+        match self {
+            Program::Chunk(chunk) => &chunk.screens,
+            Program::Module(module) => module.screens(),
+        }
+    }
+
+    fn actors(&self) -> &[ActorDef] {
+        match self {
+            Program::Chunk(chunk) => &chunk.actors,
+            Program::Module(module) => module.actors(),
+        }
+    }
+
+    fn lines(&self) -> &[LineEntry] {
+        match self {
+            Program::Chunk(chunk) => &chunk.lines,
+            Program::Module(module) => module.lines(),
+        }
+    }
+
+    fn supervisors(&self) -> &[SupervisorDef] {
+        match self {
+            Program::Chunk(chunk) => &chunk.supervisors,
+            Program::Module(module) => module.supervisors(),
+        }
+    }
+}
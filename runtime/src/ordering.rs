@@ -0,0 +1,72 @@
+// AeroFlow Runtime - Message Ordering
+// When a message is due, and which of two due together goes first
+
+use crate::actor::ActorId;
+use crate::mailbox::Message;
+
+/// Orders messages due at the same logical time; the smaller key goes first.
+pub type TieKey = (ActorId, u64);
+
+/// The time model of a [`Scheduler`](crate::Scheduler).
+///
+/// Every actor keeps a Lamport clock: delivering a message moves it forward
+/// to the message's time, and what the actor sends is stamped with
+/// [`OrderingPolicy::arrival`] of that clock. Host sends are stamped from the
+/// scheduler's clock, the time of the latest delivery. Messages are delivered
+/// by time, then [`OrderingPolicy::tie_key`], then sequence id, which is
+/// unique and grows with every send.
+///
+/// Both methods must be pure functions of their arguments so that every
+/// run, replay and thread count orders messages the same way.
+pub trait OrderingPolicy: Send + Sync {
+    /// Logical time at which a message sent at `now` arrives. Anything not
+    /// after `now` is delayed to `now + 1`, so effects follow their causes.
+    fn arrival(&self, from: &str, to: &str, now: u64) -> u64;
+
+    fn tie_key(&self, target: &str, message: &Message) -> TieKey;
+}
+
+/// The default: a fixed latency, and ties broken by sender, then by the
+/// order that sender sent in. Renaming a receiver never reorders anything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lamport {
+    pub latency: u64,
+}
+
+impl Default for Lamport {
+    fn default() -> Self {
+        Self { latency: 1 }
+    }
+}
+
+impl OrderingPolicy for Lamport {
+    fn arrival(&self, _from: &str, _to: &str, now: u64) -> u64 {
+        now + self.latency
+    }
+
+    fn tie_key(&self, _target: &str, message: &Message) -> TieKey {
+        (message.sender.clone(), message.sequence_id)
+    }
+}
+
+/// A fixed latency, and ties broken by global send order whoever sent them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SendOrder {
+    pub latency: u64,
+}
+
+impl Default for SendOrder {
+    fn default() -> Self {
+        Self { latency: 1 }
+    }
+}
+
+impl OrderingPolicy for SendOrder {
+    fn arrival(&self, _from: &str, _to: &str, now: u64) -> u64 {
+        now + self.latency
+    }
+
+    fn tie_key(&self, _target: &str, message: &Message) -> TieKey {
+        (ActorId::new(), message.sequence_id)
+    }
+}
@@ -0,0 +1,78 @@
+// AeroFlow Runtime - Quotas
+// Per-actor and global resource limits
+
+use aeroflow_compiler::ir::Value;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+pub use aeroflow_compiler::ir::Quota;
+
+/// Limits over all actors together.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GlobalQuota {
+    /// Bytes of state held by every actor.
+    pub memory: Option<u64>,
+    /// Actors running at once, `main` included.
+    pub actors: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Resource {
+    Memory,
+    Instructions,
+    Actors,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QuotaScope {
+    /// The actor's own quota.
+    Actor,
+    /// A [`GlobalQuota`].
+    Global,
+}
+
+/// A quota an actor went over, reported with its failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaExceeded {
+    pub resource: Resource,
+    pub scope: QuotaScope,
+    pub limit: u64,
+    pub used: u64,
+}
+
+impl fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scope = match self.scope {
+            QuotaScope::Actor => "Actor",
+            QuotaScope::Global => "Global",
+        };
+        let (resource, unit) = match self.resource {
+            Resource::Memory => ("memory", "bytes"),
+            Resource::Instructions => ("instruction", "instructions"),
+            Resource::Actors => ("actor", "actors"),
+        };
+        write!(f, "{} {} quota of {} {} exceeded: used {}", scope, resource, self.limit, unit, self.used)
+    }
+}
+
+/// What an actor has used so far in its current incarnation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ActorUsage {
+    /// Bytes of state after its latest message.
+    pub memory: u64,
+    pub peak_memory: u64,
+    /// Instructions run over all its messages.
+    pub instructions: u64,
+}
+
+/// Approximate bytes `value` holds, itself included. Strings count their
+/// length rather than capacity, so sizes are the same on every run.
+pub fn value_size(value: &Value) -> u64 {
+    let nested = match value {
+        Value::String(s) => s.len() as u64,
+        Value::List(items) => items.iter().map(value_size).sum(),
+        Value::Map(entries) => entries.iter().map(|(key, value)| key.len() as u64 + value_size(value)).sum(),
+        Value::Number(_) | Value::Bool(_) | Value::Nil => 0,
+    };
+    std::mem::size_of::<Value>() as u64 + nested
+}
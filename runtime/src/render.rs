@@ -0,0 +1,134 @@
+// AeroFlow Runtime - Render Sinks
+// Structured output for terminals, tests, IDEs and web frontends
+
+use aeroflow_compiler::ir::Value;
+use parking_lot::Mutex;
+use serde::{Serialize, Deserialize};
+use std::io::Write;
+
+pub use crate::timeline::{TimelineEdge, TimelineGraph, TimelineNode};
+pub use crate::ui::{UiNode, UiPatch, UiTree};
+
+/// Values captured by `render distributed state { ... }` at one logical time.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StateSnapshot {
+    pub logical_time: u64,
+    pub entries: Vec<StateEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StateEntry {
+    pub node: String,
+    pub field: String,
+    pub value: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "data", rename_all = "snake_case")]
+pub enum RenderEvent {
+    Value(Value),
+    Timeline(TimelineGraph),
+    State(StateSnapshot),
+    Ui(UiTree),
+}
+
+/// Destination for everything the VM renders.
+pub trait RenderSink: Send + Sync {
+    fn emit(&self, event: RenderEvent);
+}
+
+/// Human-readable output on stdout (the CLI default).
+pub struct StdoutSink;
+
+impl RenderSink for StdoutSink {
+    fn emit(&self, event: RenderEvent) {
+        match event {
+            RenderEvent::Value(v) => println!("{}", v),
+            RenderEvent::Timeline(graph) => {
+                println!("🌀 [DAS] TIMELINE DAG: {} events, {} edges", graph.nodes.len(), graph.edges.len());
+                print!("{}", graph.to_dot());
+            }
+            RenderEvent::State(snapshot) => {
+                println!("🧩 [DAS] STATE SNAPSHOT @ T={}", snapshot.logical_time);
+                for entry in &snapshot.entries {
+                    println!("  {}.{} = {}", entry.node, entry.field, entry.value);
+                }
+            }
+            RenderEvent::Ui(tree) => {
+                println!("📱 [DAS] UI UPDATE{}", tree.screen.map(|s| format!(" ({})", s)).unwrap_or_default());
+                for node in &tree.root {
+                    println!("  {}", serde_json::to_string(node).unwrap_or_default());
+                }
+            }
+        }
+    }
+}
+
+/// Collects events in memory, for tests and conformance checks.
+#[derive(Default)]
+pub struct MemorySink {
+    events: Mutex<Vec<RenderEvent>>,
+}
+
+impl MemorySink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn events(&self) -> Vec<RenderEvent> {
+        self.events.lock().clone()
+    }
+
+    /// Rendered plain values, formatted as `render` would print them.
+    pub fn lines(&self) -> Vec<String> {
+        self.events.lock().iter().filter_map(|e| match e {
+            RenderEvent::Value(v) => Some(v.to_string()),
+            _ => None,
+        }).collect()
+    }
+
+    pub fn take(&self) -> Vec<RenderEvent> {
+        std::mem::take(&mut *self.events.lock())
+    }
+}
+
+impl RenderSink for MemorySink {
+    fn emit(&self, event: RenderEvent) {
+        self.events.lock().push(event);
+    }
+}
+
+/// Writes one JSON object per event, for IDEs and web frontends.
+pub struct JsonLinesSink<W: Write + Send> {
+    writer: Mutex<W>,
+}
+
+impl<W: Write + Send> JsonLinesSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer: Mutex::new(writer) }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner()
+    }
+}
+
+impl<W: Write + Send> RenderSink for JsonLinesSink<W> {
+    fn emit(&self, event: RenderEvent) {
+        let mut writer = self.writer.lock();
+        if let Ok(line) = serde_json::to_string(&event) {
+            let _ = writeln!(writer, "{}", line);
+        }
+    }
+}
+
+/// Forwards plain values to a closure and ignores structured events.
+pub struct CallbackSink<F: Fn(&Value) + Send + Sync>(pub F);
+
+impl<F: Fn(&Value) + Send + Sync> RenderSink for CallbackSink<F> {
+    fn emit(&self, event: RenderEvent) {
+        if let RenderEvent::Value(v) = event {
+            (self.0)(&v);
+        }
+    }
+}
@@ -0,0 +1,368 @@
+// AeroFlow Runtime - Record & Replay
+// Re-execute a recorded run and prove it takes the same path
+
+use crate::actor::ActorId;
+use crate::capability::{Capabilities, CapabilityGuard};
+use crate::mailbox::MessageData;
+use crate::module::Program;
+use crate::scheduler::Scheduler;
+use crate::snapshot::Snapshot;
+use aeroflow_compiler::ir::Chunk;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+
+/// Where `env("KEY")` reads come from; the recorder logs them, the replayer
+/// serves them back.
+pub trait EnvSource: Send + Sync {
+    fn var(&self, actor: Option<&str>, key: &str) -> String;
+}
+
+/// How a host-injected message was scheduled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InputTiming {
+    /// `Scheduler::send`: one latency after the latest delivery.
+    Next,
+    /// `Scheduler::send_at`.
+    At(u64),
+    /// `Scheduler::send_with_time`.
+    Exact { time: u64, seq: u64 },
+}
+
+/// A message that entered the system from outside any actor.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExternalInput {
+    /// Number of steps delivered before it was sent.
+    pub before_step: usize,
+    pub target: ActorId,
+    pub sender: ActorId,
+    pub data: MessageData,
+    pub timing: InputTiming,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnvRead {
+    pub step: usize,
+    pub actor: Option<ActorId>,
+    pub key: String,
+    pub value: String,
+}
+
+/// One delivered message and the receiving actor's state hash afterwards.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedStep {
+    pub step: usize,
+    pub actor: ActorId,
+    pub sender: ActorId,
+    pub logical_time: u64,
+    pub sequence_id: u64,
+    /// FNV-1a of the actor's snapshot; `None` if it cannot be snapshotted.
+    pub state_hash: Option<u64>,
+}
+
+/// Everything needed to re-run a program: the code, its external inputs and
+/// env reads, and the path it took.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Recording {
+    pub program: Chunk,
+    pub inputs: Vec<ExternalInput>,
+    pub env: Vec<EnvRead>,
+    pub steps: Vec<RecordedStep>,
+    /// What the host granted the run's actors, if it said; a replay is held
+    /// to the same grants.
+    #[serde(default)]
+    pub grants: Option<RecordedGrants>,
+}
+
+/// A [`CapabilityGuard`] as written into a recording.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedGrants {
+    pub capabilities: Vec<String>,
+    /// Variables `env()` could read; any when `None`.
+    pub env: Option<Vec<String>>,
+}
+
+impl RecordedGrants {
+    pub fn guard(&self) -> CapabilityGuard {
+        let allowed = self.capabilities.iter().filter_map(|name| Capabilities::from_name(name)).fold(Capabilities::NONE, |all, c| all | c);
+        let guard = CapabilityGuard::new(allowed);
+        match &self.env {
+            Some(names) => guard.with_env(names.iter().cloned()),
+            None => guard,
+        }
+    }
+}
+
+impl From<&CapabilityGuard> for RecordedGrants {
+    fn from(guard: &CapabilityGuard) -> Self {
+        Self {
+            capabilities: guard.allowed().names().into_iter().map(str::to_string).collect(),
+            env: guard.env().map(|names| names.iter().cloned().collect()),
+        }
+    }
+}
+
+impl Recording {
+    pub fn to_json(&self) -> anyhow::Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(text: &str) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(text)?)
+    }
+}
+
+pub fn state_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| (hash ^ *b as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// Collects a [`Recording`] while a scheduler runs; see `Scheduler::record`.
+pub struct Recorder {
+    recording: Mutex<Recording>,
+}
+
+impl Recorder {
+    pub fn new(program: Chunk) -> Self {
+        Self { recording: Mutex::new(Recording { program, ..Default::default() }) }
+    }
+
+    pub(crate) fn grants(&self, guard: &CapabilityGuard) {
+        self.recording.lock().grants = Some(guard.into());
+    }
+
+    pub(crate) fn input(&self, target: &str, sender: &str, data: &MessageData, timing: InputTiming) {
+        let mut recording = self.recording.lock();
+        let before_step = recording.steps.len();
+        recording.inputs.push(ExternalInput {
+            before_step,
+            target: target.to_string(),
+            sender: sender.to_string(),
+            data: data.clone(),
+            timing,
+        });
+    }
+
+    pub(crate) fn step(&self, step: RecordedStep) {
+        self.recording.lock().steps.push(step);
+    }
+
+    pub(crate) fn steps(&self) -> usize {
+        self.recording.lock().steps.len()
+    }
+
+    pub fn recording(&self) -> Recording {
+        self.recording.lock().clone()
+    }
+}
+
+impl EnvSource for Recorder {
+    fn var(&self, actor: Option<&str>, key: &str) -> String {
+        let value = std::env::var(key).unwrap_or_default();
+        let mut recording = self.recording.lock();
+        let step = recording.steps.len();
+        recording.env.push(EnvRead { step, actor: actor.map(str::to_string), key: key.to_string(), value: value.clone() });
+        value
+    }
+}
+
+/// The first point where a replay left the recorded path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    pub step: usize,
+    pub what: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "diverged at step {}: {} was {}, recorded {}", self.step, self.what, self.actual, self.expected)
+    }
+}
+
+impl std::error::Error for Divergence {}
+
+/// Serves recorded env reads in order and notes the first mismatch.
+struct ReplayEnv {
+    reads: Vec<EnvRead>,
+    next: Mutex<usize>,
+    step: Mutex<usize>,
+    divergence: Mutex<Option<Divergence>>,
+}
+
+impl EnvSource for ReplayEnv {
+    fn var(&self, actor: Option<&str>, key: &str) -> String {
+        let mut next = self.next.lock();
+        let step = *self.step.lock();
+        let actual = format!("{}:env({:?})", actor.unwrap_or("main"), key);
+        let diverge = |expected: String| {
+            let mut slot = self.divergence.lock();
+            if slot.is_none() {
+                *slot = Some(Divergence { step, what: "env read".to_string(), expected, actual: actual.clone() });
+            }
+            String::new()
+        };
+        let Some(read) = self.reads.get(*next) else { return diverge("no further reads".to_string()) };
+        let expected = format!("{}:env({:?})", read.actor.as_deref().unwrap_or("main"), read.key);
+        if read.step != step || expected != actual {
+            return diverge(format!("{} at step {}", expected, read.step));
+        }
+        *next += 1;
+        read.value.clone()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReplayReport {
+    pub steps: usize,
+    pub logical_time: u64,
+}
+
+/// Feeds a recording's inputs into a fresh scheduler and checks every step
+/// against the recorded one.
+pub struct Replayer {
+    scheduler: Arc<Scheduler>,
+    recording: Recording,
+    env: Arc<ReplayEnv>,
+    next_input: usize,
+    next_step: usize,
+}
+
+impl Replayer {
+    /// Replay against the program stored in the recording.
+    pub fn new(recording: Recording) -> Self {
+        let program = Program::from(recording.program.clone());
+        Self::with_program(recording, program)
+    }
+
+    /// Replay the recorded inputs against `program`, e.g. a changed build.
+    pub fn with_program(recording: Recording, program: impl Into<Program>) -> Self {
+        let scheduler = Scheduler::shared();
+        let env = Arc::new(ReplayEnv {
+            reads: recording.env.clone(),
+            next: Mutex::new(0),
+            step: Mutex::new(0),
+            divergence: Mutex::new(None),
+        });
+        scheduler.set_env_source(env.clone());
+        if let Some(grants) = &recording.grants {
+            scheduler.set_grants(grants.guard());
+        }
+        scheduler.spawn_actors(&program.into());
+        Self { scheduler, recording, env, next_input: 0, next_step: 0 }
+    }
+
+    /// Continue a replay from a snapshot taken at `position` with
+    /// `next_input` inputs already injected.
+    pub(crate) fn resume(recording: Recording, program: Program, snapshot: &Snapshot, position: usize, next_input: usize) -> anyhow::Result<Self> {
+        let scheduler = Scheduler::shared();
+        let env = Arc::new(ReplayEnv {
+            next: Mutex::new(recording.env.iter().take_while(|r| r.step < position).count()),
+            reads: recording.env.clone(),
+            step: Mutex::new(position),
+            divergence: Mutex::new(None),
+        });
+        scheduler.set_env_source(env.clone());
+        if let Some(grants) = &recording.grants {
+            scheduler.set_grants(grants.guard());
+        }
+        scheduler.restore_program(snapshot, program)?;
+        Ok(Self { scheduler, recording, env, next_input, next_step: position })
+    }
+
+    pub fn scheduler(&self) -> &Arc<Scheduler> {
+        &self.scheduler
+    }
+
+    pub fn recording(&self) -> &Recording {
+        &self.recording
+    }
+
+    /// Steps replayed so far.
+    pub fn position(&self) -> usize {
+        self.next_step
+    }
+
+    /// Recorded inputs injected so far.
+    pub fn inputs_injected(&self) -> usize {
+        self.next_input
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.next_step >= self.recording.steps.len()
+    }
+
+    /// Inject the external inputs that arrived before the next step, so the
+    /// scheduler's queue shows what that step will deliver.
+    pub fn inject_inputs(&mut self) {
+        while let Some(input) = self.recording.inputs.get(self.next_input).filter(|i| i.before_step <= self.next_step) {
+            let (target, sender, data) = (input.target.clone(), input.sender.clone(), input.data.clone());
+            match input.timing {
+                InputTiming::Next => self.scheduler.send(target, data, sender),
+                InputTiming::At(time) => self.scheduler.send_at(target, data, sender, time),
+                InputTiming::Exact { time, seq } => self.scheduler.send_with_time(target, data, sender, time, seq),
+            }
+            self.next_input += 1;
+        }
+    }
+
+    /// Replay one recorded step. Returns `Ok(None)` once the recording is exhausted.
+    pub fn step(&mut self) -> Result<Option<RecordedStep>, Divergence> {
+        self.inject_inputs();
+        let Some(expected) = self.recording.steps.get(self.next_step).cloned() else { return Ok(None) };
+
+        *self.env.step.lock() = self.next_step;
+        // Messages put back a tick later, or turned into dead letters, are
+        // not recorded steps
+        let actual = loop {
+            match self.scheduler.step_recorded(true) {
+                Some(actual) => break actual,
+                None if self.scheduler.queue_depth() > 0 => continue,
+                None => {
+                    return Err(Divergence {
+                        step: self.next_step,
+                        what: "delivery".to_string(),
+                        expected: describe(&expected),
+                        actual: "no message".to_string(),
+                    })
+                }
+            }
+        };
+        if let Some(divergence) = self.env.divergence.lock().take() {
+            return Err(divergence);
+        }
+        let actual = RecordedStep { step: self.next_step, ..actual };
+        if let Some(divergence) = compare(&expected, &actual) {
+            return Err(divergence);
+        }
+        self.next_step += 1;
+        Ok(Some(actual))
+    }
+
+    /// Replay every remaining step.
+    pub fn run(&mut self) -> Result<ReplayReport, Divergence> {
+        while self.step()?.is_some() {}
+        Ok(self.report())
+    }
+
+    pub fn report(&self) -> ReplayReport {
+        ReplayReport { steps: self.next_step, logical_time: self.scheduler.logical_time() }
+    }
+}
+
+fn describe(step: &RecordedStep) -> String {
+    format!("{} <- {} @ T={} #{}", step.actor, step.sender, step.logical_time, step.sequence_id)
+}
+
+fn compare(expected: &RecordedStep, actual: &RecordedStep) -> Option<Divergence> {
+    let diverged = |what: String, want: String, got: String| Some(Divergence { step: expected.step, what, expected: want, actual: got });
+    if describe(expected) != describe(actual) {
+        return diverged("delivery".to_string(), describe(expected), describe(actual));
+    }
+    if expected.state_hash != actual.state_hash {
+        let hex = |h: Option<u64>| h.map_or("none".to_string(), |h| format!("{:016x}", h));
+        return diverged(format!("state of '{}'", actual.actor), hex(expected.state_hash), hex(actual.state_hash));
+    }
+    None
+}
@@ -1,35 +1,50 @@
 // AeroFlow Runtime - Deterministic Actor Scheduler (DAS)
 // Concurrency without nondeterminism
 
-use crate::actor::{ActorCell, ActorId};
-use crate::mailbox::Message;
-use crate::vm::{VM, VMContext};
-use std::collections::{HashMap, BinaryHeap};
-use std::sync::Arc;
-use parking_lot::Mutex;
+use crate::actor::{Actor, ActorCell, ActorId, Context};
+use crate::capability::CapabilityGuard;
+use crate::mailbox::{DeadLetter, DeadReason, MailboxConfig, MailboxMetrics, Message, MessageBus, MessageData, Overflow, Watch, DEAD_LETTERS, STOP};
+use crate::module::Program;
+use crate::ordering::{Lamport, OrderingPolicy, TieKey};
+use crate::quota::{ActorUsage, GlobalQuota, Quota, QuotaExceeded, QuotaScope, Resource};
+use aeroflow_compiler::ir::{Code, Value};
+use crate::render::{MemorySink, RenderEvent, RenderSink, StdoutSink};
+use crate::replay::{self, EnvSource, InputTiming, RecordedStep, Recorder};
+use crate::snapshot::{ActorSnapshot, HeldMessage, QueuedMessage, Snapshot};
+use crate::state::StateSource;
+use crate::supervisor::{ChildSpec, Outcome, Supervisor};
+use crate::trace::{TraceEvent, Tracer};
+use crate::vm::{timer_id, DebugHook};
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Weak};
+use parking_lot::{Condvar, Mutex};
+use rayon::prelude::*;
 use std::cmp::Ordering;
+use std::time::{Duration, Instant};
 
 #[derive(Debug)]
 struct ScheduledMessage {
     message: Message,
     target: ActorId,
+    key: TieKey,
 }
 
 impl PartialEq for ScheduledMessage {
     fn eq(&self, other: &Self) -> bool {
-        self.message.logical_time == other.message.logical_time &&
-        self.message.sequence_id == other.message.sequence_id &&
-        self.target == other.target
+        self.cmp(other) == Ordering::Equal
     }
 }
 
 impl Eq for ScheduledMessage {}
 
+/// Greatest first: earliest time, then smallest tie key and sequence id.
+/// The target only separates copies of one message.
 impl Ord for ScheduledMessage {
     fn cmp(&self, other: &Self) -> Ordering {
         other.message.logical_time.cmp(&self.message.logical_time)
-            .then_with(|| other.target.cmp(&self.target))
+            .then_with(|| other.key.cmp(&self.key))
             .then_with(|| other.message.sequence_id.cmp(&self.message.sequence_id))
+            .then_with(|| other.target.cmp(&self.target))
     }
 }
 
@@ -39,11 +54,120 @@ impl PartialOrd for ScheduledMessage {
     }
 }
 
+/// Dead letters kept for [`Scheduler::dead_letters`]; older ones are
+/// forgotten but still counted in the metrics.
+pub const DEAD_LETTER_LOG: usize = 1024;
+
+/// An actor's mailbox: its limits, statistics, and messages held back by
+/// a full `Block` mailbox. The messages themselves wait in the shared queue.
+#[derive(Default)]
+struct Mailbox {
+    config: Option<MailboxConfig>,
+    metrics: MailboxMetrics,
+    /// Each with the timer sender that wakes its suspended sender, if one
+    /// is waiting for it.
+    blocked: VecDeque<(ScheduledMessage, Option<ActorId>)>,
+}
+
+impl Mailbox {
+    fn admit(&mut self) {
+        self.metrics.depth += 1;
+        self.metrics.high_water = self.metrics.high_water.max(self.metrics.depth);
+    }
+}
+
+/// An actor's `receive` reported an error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActorFailure {
+    pub actor: ActorId,
+    pub logical_time: u64,
+    pub reason: String,
+    /// The quota the actor went over, when that is why it failed.
+    pub quota: Option<QuotaExceeded>,
+}
+
+/// Why [`Scheduler::run`] returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunStop {
+    /// No messages remain.
+    Quiescent,
+    /// The next message is scheduled past the logical-time limit.
+    TimeLimit,
+}
+
+/// How logical time, counted in milliseconds, relates to wall time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Clock {
+    /// Deliver as fast as possible, jumping over idle time to the next
+    /// message. Runs are independent of the machine's speed.
+    #[default]
+    Simulated,
+    /// Deliver no earlier than one `Duration` of wall time per logical tick
+    /// after the first delivery, for live servers. Delivery order is the
+    /// same as in simulated time.
+    Paced(Duration),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunReport {
+    pub steps: usize,
+    pub logical_time: u64,
+    pub stop: RunStop,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub struct Scheduler {
     actors: Mutex<HashMap<ActorId, ActorCell>>,
     queue: Mutex<BinaryHeap<ScheduledMessage>>,
+    /// Time of the latest delivery.
     pub(crate) logical_clock: Mutex<u64>,
+    /// Each actor's Lamport clock: the latest time it was delivered a message.
+    clocks: Mutex<HashMap<ActorId, u64>>,
+    ordering: Mutex<Arc<dyn OrderingPolicy>>,
     pub(crate) sequence_counter: Mutex<u64>,
+    sink: Mutex<Arc<dyn RenderSink>>,
+    state_source: Mutex<Option<Arc<dyn StateSource>>>,
+    bus: Mutex<Option<Arc<dyn MessageBus>>>,
+    env: Mutex<Option<Arc<dyn EnvSource>>>,
+    recorder: Mutex<Option<Arc<Recorder>>>,
+    debug: Mutex<Option<Arc<dyn DebugHook>>>,
+    tracer: Mutex<Option<Arc<Tracer>>>,
+    failures: Mutex<Vec<ActorFailure>>,
+    pool: Mutex<Option<Arc<rayon::ThreadPool>>>,
+    /// Sends go back into this scheduler (see [`Scheduler::shared`]).
+    own_bus: Mutex<bool>,
+    /// Signalled whenever a message is queued.
+    arrived: Condvar,
+    mailboxes: Mutex<HashMap<ActorId, Mailbox>>,
+    default_mailbox: Mutex<MailboxConfig>,
+    /// Quota of every actor not given its own.
+    quota: Mutex<Quota>,
+    /// Per-actor quotas, e.g. from `@quota` annotations.
+    quotas: Mutex<HashMap<ActorId, Quota>>,
+    global_quota: Mutex<GlobalQuota>,
+    /// What every actor may do, once the host has said; see [`Scheduler::set_grants`].
+    grants: Mutex<Option<CapabilityGuard>>,
+    usage: Mutex<HashMap<ActorId, ActorUsage>>,
+    dead_letters: Mutex<VecDeque<DeadLetter>>,
+    /// Every actor id, including actors checked out while they run.
+    spawned: Mutex<HashSet<ActorId>>,
+    /// [`Program::hash`] of the program spawned, recorded in snapshots.
+    program: Mutex<Option<u64>>,
+    /// Top-level supervision trees.
+    supervisors: Mutex<Vec<Supervisor>>,
+    supervised: Mutex<HashSet<ActorId>>,
+    /// Linked pairs, smaller id first.
+    links: Mutex<BTreeSet<(ActorId, ActorId)>>,
+    /// Watcher and watched actor.
+    monitors: Mutex<BTreeSet<(ActorId, ActorId)>>,
+    clock: Mutex<Clock>,
+    /// Wall time and logical time of the first paced delivery.
+    epoch: Mutex<Option<(Instant, u64)>>,
 }
 
 impl Scheduler {
@@ -52,74 +176,1316 @@ impl Scheduler {
             actors: Mutex::new(HashMap::new()),
             queue: Mutex::new(BinaryHeap::new()),
             logical_clock: Mutex::new(0),
+            clocks: Mutex::new(HashMap::new()),
+            ordering: Mutex::new(Arc::new(Lamport::default())),
             sequence_counter: Mutex::new(0),
+            sink: Mutex::new(Arc::new(StdoutSink)),
+            state_source: Mutex::new(None),
+            bus: Mutex::new(None),
+            env: Mutex::new(None),
+            recorder: Mutex::new(None),
+            debug: Mutex::new(None),
+            tracer: Mutex::new(None),
+            failures: Mutex::new(Vec::new()),
+            pool: Mutex::new(None),
+            own_bus: Mutex::new(false),
+            arrived: Condvar::new(),
+            mailboxes: Mutex::new(HashMap::new()),
+            default_mailbox: Mutex::new(MailboxConfig::default()),
+            quota: Mutex::new(Quota::default()),
+            quotas: Mutex::new(HashMap::new()),
+            global_quota: Mutex::new(GlobalQuota::default()),
+            grants: Mutex::new(None),
+            usage: Mutex::new(HashMap::new()),
+            dead_letters: Mutex::new(VecDeque::new()),
+            spawned: Mutex::new(HashSet::new()),
+            program: Mutex::new(None),
+            supervisors: Mutex::new(Vec::new()),
+            supervised: Mutex::new(HashSet::new()),
+            links: Mutex::new(BTreeSet::new()),
+            monitors: Mutex::new(BTreeSet::new()),
+            clock: Mutex::new(Clock::Simulated),
+            epoch: Mutex::new(None),
+        }
+    }
+
+    /// A scheduler whose actors can read each other's state and `send` to each other.
+    pub fn shared() -> Arc<Self> {
+        Arc::new_cyclic(|weak: &Weak<Scheduler>| {
+            let scheduler = Self::new();
+            *scheduler.state_source.lock() = Some(Arc::new(weak.clone()));
+            *scheduler.bus.lock() = Some(Arc::new(weak.clone()));
+            *scheduler.own_bus.lock() = true;
+            scheduler
+        })
+    }
+
+    /// Route render output of every actor, current and future, to `sink`.
+    pub fn set_render_sink(&self, sink: Arc<dyn RenderSink>) {
+        for cell in self.actors.lock().values_mut() {
+            cell.context.sink = sink.clone();
+        }
+        *self.sink.lock() = sink;
+    }
+
+    /// Where hosted actors resolve `render distributed state` references.
+    pub fn set_state_source(&self, source: Arc<dyn StateSource>) {
+        for cell in self.actors.lock().values_mut() {
+            cell.context.state = Some(source.clone());
+        }
+        *self.state_source.lock() = Some(source);
+    }
+
+    /// Spawn every declared `actor` plus the top-level code as `main`, and queue
+    /// their start signals (actors first, so their state exists when `main` runs).
+    /// All of them share the program's code. Supervised actors start with the
+    /// arguments their `supervise` block gives them.
+    pub fn spawn_program(&self, program: impl Into<Program>) -> Vec<ActorId> {
+        let ids = self.spawn_actors(&program.into());
+        let starts: HashMap<ActorId, MessageData> = self.supervisors.lock().iter()
+            .flat_map(|root| root.specs().into_iter().map(|(_, spec)| (spec.id.clone(), spec.start_message())))
+            .collect();
+        for id in &ids {
+            let start = starts.get(id).cloned().unwrap_or_else(|| MessageData::Signal(String::new()));
+            self.send(id.clone(), start, "runtime".to_string());
+        }
+        ids
+    }
+
+    /// Spawn the actors of [`Scheduler::spawn_program`] without starting them,
+    /// under the program's supervisors.
+    pub(crate) fn spawn_actors(&self, program: &Program) -> Vec<ActorId> {
+        *self.program.lock() = Some(program.hash());
+        for root in Supervisor::from_program(program) {
+            self.adopt(root);
+        }
+        let mut ids = Vec::new();
+        self.adopt_quotas(program);
+        for def in program.actors() {
+            if let Some(actor) = crate::VMActor::declared(program.clone(), &def.name) {
+                self.spawn(ActorCell::new(def.name.clone(), Box::new(actor)));
+                ids.push(def.name.clone());
+            }
+        }
+        self.spawn(ActorCell::new("main".to_string(), Box::new(crate::VMActor::new(program.clone()))));
+        ids.push("main".to_string());
+        ids
+    }
+
+    /// Take the `@quota` limits the program's actors declare.
+    fn adopt_quotas(&self, program: &Program) {
+        let mut quotas = self.quotas.lock();
+        for def in program.actors().iter().filter(|def| def.quota != Quota::default()) {
+            quotas.insert(def.name.clone(), def.quota);
+        }
+    }
+
+    /// Spawn and start `supervisor`'s actors, depth first, then restart them
+    /// as it directs whenever one fails.
+    pub fn supervise(&self, supervisor: Supervisor) {
+        for (owner, spec) in supervisor.specs() {
+            self.spawn(ActorCell::new(spec.id.clone(), (spec.factory)()));
+            self.send(spec.id.clone(), spec.start_message(), owner.to_string());
         }
+        self.adopt(supervisor);
     }
 
-    pub fn spawn(&self, actor_cell: ActorCell) {
+    fn adopt(&self, supervisor: Supervisor) {
+        self.supervised.lock().extend(supervisor.specs().into_iter().map(|(_, spec)| spec.id.clone()));
+        self.supervisors.lock().push(supervisor);
+    }
+
+    /// `actor`'s handler failed: its monitors hear of it, its supervisor
+    /// acts, and the actors linked to it are stopped and fail in turn.
+    fn failed(&self, actor: &str, reason: &str, time: u64) {
+        self.notify_down(actor, reason);
+        let linked: Vec<ActorId> = {
+            let mut links = self.links.lock();
+            let linked = links.iter().filter_map(|(a, b)| if a == actor { Some(b.clone()) } else if b == actor { Some(a.clone()) } else { None }).collect();
+            links.retain(|(a, b)| a != actor && b != actor);
+            linked
+        };
+        self.child_failed(actor, reason, time);
+        for other in linked {
+            let reason = format!("linked actor '{}' failed", actor);
+            if !self.stop_cell(&other, &reason, actor, time) {
+                continue;
+            }
+            self.failures.lock().push(ActorFailure { actor: other.clone(), logical_time: time, reason: reason.clone(), quota: None });
+            self.failed(&other, &reason, time);
+            // Unless its supervisor just restarted it, it is gone
+            if !self.actors.lock().contains_key(&other) {
+                self.spawned.lock().remove(&other);
+                self.supervised.lock().remove(&other);
+                self.forget(&other);
+            }
+        }
+    }
+
+    /// Apply the failed actor's supervisor's strategy, if it has one.
+    fn child_failed(&self, actor: &str, reason: &str, time: u64) {
+        let mut gave_up = None;
+        let restarts = {
+            let mut roots = self.supervisors.lock();
+            let Some(i) = roots.iter().position(|root| root.supervises(actor)) else { return };
+            match roots[i].child_failed(actor, time) {
+                Some(Outcome::Restart(restarts)) => restarts,
+                Some(Outcome::Escalate) => {
+                    gave_up = Some(roots.remove(i));
+                    Vec::new()
+                }
+                None => Vec::new(),
+            }
+        };
+        for restart in restarts {
+            // Children stopped gracefully stay stopped
+            if self.supervised.lock().contains(&restart.child.id) {
+                self.restart(restart.supervisor, &restart.child, actor, reason, time);
+            }
+        }
+        if let Some(root) = gave_up {
+            // Nothing is left to restart them, so its actors stop
+            let reason = format!("gave up after {} restart(s) within {} ticks", root.max_restarts, root.within);
+            for (_, spec) in root.specs() {
+                self.stop_cell(&spec.id, &reason, &root.name, time);
+                self.stopped(&spec.id, &reason);
+            }
+            self.failures.lock().push(ActorFailure { actor: root.name, logical_time: time, reason, quota: None });
+        }
+    }
+
+    /// Replace a child with a fresh actor and start it at once, ahead of
+    /// the messages already waiting for it. Siblings of the `failed` actor
+    /// are stopped first. The trace shows a `restart` event from the
+    /// supervisor, where `on_restart` runs, followed by the start delivery.
+    fn restart(&self, supervisor: String, child: &ChildSpec, failed: &str, reason: &str, time: u64) {
+        if child.id != failed && self.stop_cell(&child.id, "restart", &supervisor, time) {
+            self.notify_down(&child.id, "restart");
+        }
+        self.forget(&child.id);
+        self.spawn(ActorCell::new(child.id.clone(), (child.factory)()));
+        let seq = self.next_seq();
+        let fresh = self.actors.lock().remove(&child.id);
+        if let Some(mut cell) = fresh {
+            let input = Message::new(supervisor.clone(), MessageData::Signal("restart".to_string()), time, 0);
+            self.hook(&mut cell, input, seq, |actor, ctx| actor.on_restart(reason, ctx));
+            self.actors.lock().insert(child.id.clone(), cell);
+        }
+        let message = Message::new(supervisor, child.start_message(), time, seq);
+        let key = self.ordering.lock().tie_key(&child.id, &message);
+        self.mailboxes.lock().entry(child.id.clone()).or_default().admit();
+        self.deliver(ScheduledMessage { message, target: child.id.clone(), key }, false);
+    }
+
+    /// Run a lifecycle hook of `cell`, traced as a delivery of `input`.
+    /// Its sends from `first_seq` on and any failure count as the actor's.
+    fn hook(&self, cell: &mut ActorCell, input: Message, first_seq: u64, run: impl FnOnce(&mut dyn Actor, &mut Context)) {
+        let time = input.logical_time;
+        let tracer = self.tracer();
+        let traced = tracer.begin(&cell.id, &input, || cell.actor.get_state());
+        cell.context.instructions = 0;
+        cell.context.logical_time = time;
+        self.advance_clock(&cell.id, time);
+        run(cell.actor.as_mut(), &mut cell.context);
+        cell.context.arena.reset();
+        if let Some(mut event) = traced {
+            event.instructions = cell.context.instructions;
+            event.sent = (first_seq..=*self.sequence_counter.lock()).collect();
+            tracer.finish(event);
+        }
+        if let Some(event) = denial(cell) {
+            tracer.record_unsampled(event);
+        }
+        if let Some(reason) = cell.context.failure.take() {
+            self.failures.lock().push(ActorFailure { actor: cell.id.clone(), logical_time: time, reason, quota: cell.context.exceeded.take() });
+        }
+    }
+
+    /// Take `actor` out and run its `on_stop` if it had started, traced as
+    /// a stop signal from `sender`. False if it is not running.
+    fn stop_cell(&self, actor: &str, reason: &str, sender: &str, time: u64) -> bool {
+        let Some(mut cell) = self.actors.lock().remove(actor) else { return false };
+        if !cell.started {
+            return true;
+        }
+        let input = Message::new(sender.to_string(), MessageData::Signal(STOP.to_string()), time, 0);
+        let first_seq = *self.sequence_counter.lock() + 1;
+        self.hook(&mut cell, input, first_seq, |actor, ctx| actor.on_stop(reason, ctx));
+        true
+    }
+
+    /// `actor` stopped for good: tell its monitors and drop everything about it.
+    fn stopped(&self, actor: &str, reason: &str) {
+        self.actors.lock().remove(actor);
+        self.spawned.lock().remove(actor);
+        self.supervised.lock().remove(actor);
+        self.notify_down(actor, reason);
+        self.forget(actor);
+    }
+
+    /// Send `Down(actor, reason)` to everything monitoring `actor`; each
+    /// monitor fires once.
+    fn notify_down(&self, actor: &str, reason: &str) {
+        let watchers: Vec<ActorId> = {
+            let mut monitors = self.monitors.lock();
+            let watchers = monitors.iter().filter(|(_, watched)| watched == actor).map(|(watcher, _)| watcher.clone()).collect();
+            monitors.retain(|(_, watched)| watched != actor);
+            watchers
+        };
+        for watcher in watchers {
+            self.enqueue(watcher, down(actor, reason), actor.to_string());
+        }
+    }
+
+    /// Drop `actor`'s links and monitors, either way round.
+    fn forget(&self, actor: &str) {
+        self.usage.lock().remove(actor);
+        self.links.lock().retain(|(a, b)| a != actor && b != actor);
+        self.monitors.lock().retain(|(watcher, watched)| watcher != actor && watched != actor);
+    }
+
+    /// Link `from` and `to`, or have `from` monitor `to` (see [`Watch`]).
+    /// Links only join running actors; monitoring an actor that is not
+    /// running reports it down at once with reason `unknown_actor`.
+    pub fn watch(&self, from: &str, to: &str, watch: Watch) {
+        let pair = if from < to { (from.to_string(), to.to_string()) } else { (to.to_string(), from.to_string()) };
+        let running = {
+            let spawned = self.spawned.lock();
+            spawned.contains(from) && spawned.contains(to)
+        };
+        match watch {
+            Watch::Link if running && from != to => {
+                self.links.lock().insert(pair);
+            }
+            Watch::Link => {}
+            Watch::Unlink => {
+                self.links.lock().remove(&pair);
+            }
+            Watch::Monitor if running => {
+                self.monitors.lock().insert((from.to_string(), to.to_string()));
+            }
+            Watch::Monitor => self.enqueue(from.to_string(), down(to, DeadReason::UnknownActor.code()), to.to_string()),
+            Watch::Demonitor => {
+                self.monitors.lock().remove(&(from.to_string(), to.to_string()));
+            }
+        }
+    }
+
+    /// Actors linked to `actor`, and those monitoring it, in id order.
+    pub fn watchers(&self, actor: &str) -> (Vec<ActorId>, Vec<ActorId>) {
+        let links = self.links.lock().iter()
+            .filter_map(|(a, b)| if a == actor { Some(b.clone()) } else if b == actor { Some(a.clone()) } else { None })
+            .collect();
+        let monitors = self.monitors.lock().iter().filter(|(_, watched)| watched == actor).map(|(watcher, _)| watcher.clone()).collect();
+        (links, monitors)
+    }
+
+    /// Stop `actor` gracefully: a stop signal queued behind its pending
+    /// messages runs its `on_stop` with reason `shutdown` once its mailbox
+    /// is empty. Messages sent to it afterwards become dead letters.
+    pub fn stop(&self, actor: &str) {
+        let now = self.arrival("runtime", actor);
+        let last = self.queue.lock().iter()
+            .filter(|s| s.target == actor && timer_id(&s.message.sender).is_none())
+            .map(|s| s.message.logical_time + 1)
+            .max();
+        self.send_at(actor.to_string(), MessageData::Signal(STOP.to_string()), "runtime".to_string(), last.unwrap_or(now).max(now));
+    }
+
+    /// Stop every actor gracefully and run until all are stopped and
+    /// their mailboxes drained.
+    pub fn shutdown(&self) -> RunReport {
+        for id in self.actor_ids() {
+            self.stop(&id);
+        }
+        self.run(None)
+    }
+
+    /// How hosted actors deliver messages sent from AeroFlow code.
+    pub fn set_message_bus(&self, bus: Arc<dyn MessageBus>) {
+        for cell in self.actors.lock().values_mut() {
+            cell.context.bus = Some(bus.clone());
+        }
+        *self.bus.lock() = Some(bus);
+        *self.own_bus.lock() = false;
+    }
+
+    /// Where `env("KEY")` reads resolve; the process environment by default.
+    pub fn set_env_source(&self, env: Arc<dyn EnvSource>) {
+        for cell in self.actors.lock().values_mut() {
+            cell.context.env = Some(env.clone());
+        }
+        *self.env.lock() = Some(env);
+    }
+
+    /// Attach a debugger to every VM the scheduler runs.
+    pub fn set_debug_hook(&self, hook: Arc<dyn DebugHook>) {
+        for cell in self.actors.lock().values_mut() {
+            cell.context.debug = Some(hook.clone());
+        }
+        *self.debug.lock() = Some(hook);
+    }
+
+    /// Start recording external inputs, env reads and every delivered step
+    /// (with the receiver's state hash) for `aeroflow replay`.
+    pub fn record(&self, program: &Program) -> Arc<Recorder> {
+        let recorder = Arc::new(Recorder::new(program.to_chunk()));
+        self.set_env_source(recorder.clone());
+        if let Some(guard) = &*self.grants.lock() {
+            recorder.grants(guard);
+        }
+        *self.recorder.lock() = Some(recorder.clone());
+        recorder
+    }
+
+    pub fn spawn(&self, mut actor_cell: ActorCell) {
+        actor_cell.context.sink = self.sink.lock().clone();
+        actor_cell.context.state = self.state_source.lock().clone();
+        actor_cell.context.bus = self.bus.lock().clone();
+        actor_cell.context.env = self.env.lock().clone();
+        actor_cell.context.debug = self.debug.lock().clone();
+        self.apply_quota(&mut actor_cell);
+        let grants = self.grants.lock().clone();
+        if let Some(guard) = &grants {
+            actor_cell.actor.grant(guard);
+        }
         let id = actor_cell.id.clone();
+        {
+            let mut spawned = self.spawned.lock();
+            let limit = self.global_quota.lock().actors;
+            if let Some(limit) = limit.filter(|&limit| !spawned.contains(&id) && spawned.len() as u64 >= limit) {
+                let quota = QuotaExceeded { resource: Resource::Actors, scope: QuotaScope::Global, limit, used: spawned.len() as u64 + 1 };
+                drop(spawned);
+                let logical_time = self.logical_time();
+                self.failures.lock().push(ActorFailure { actor: id, logical_time, reason: quota.to_string(), quota: Some(quota) });
+                return;
+            }
+            spawned.insert(id.clone());
+        }
         self.actors.lock().insert(id, actor_cell);
     }
 
+    /// Inject a message from the host, due one latency after the latest delivery.
     pub fn send(&self, target: ActorId, message_data: crate::mailbox::MessageData, sender: ActorId) {
-        let mut clock = self.logical_clock.lock();
+        self.record_input(&target, &sender, &message_data, InputTiming::Next);
+        self.enqueue(target, message_data, sender);
+    }
+
+    pub fn send_with_time(&self, target: ActorId, message_data: crate::mailbox::MessageData, sender: ActorId, time: u64, seq: u64) {
+        self.record_input(&target, &sender, &message_data, InputTiming::Exact { time, seq });
+        self.enqueue_with_time(target, message_data, sender, time, seq);
+    }
+
+    /// Enqueue a message for delivery at an explicit logical time.
+    pub fn send_at(&self, target: ActorId, message_data: crate::mailbox::MessageData, sender: ActorId, time: u64) {
+        self.record_input(&target, &sender, &message_data, InputTiming::At(time));
+        self.enqueue_at(target, message_data, sender, time);
+    }
+
+    fn record_input(&self, target: &str, sender: &str, data: &MessageData, timing: InputTiming) {
+        if let Some(recorder) = self.recorder.lock().as_ref() {
+            recorder.input(target, sender, data, timing);
+        }
+    }
+
+    /// Replace the time model. Queued messages keep their times and are
+    /// re-ordered by the new tie keys.
+    pub fn set_ordering(&self, policy: Arc<dyn OrderingPolicy>) {
+        *self.ordering.lock() = policy.clone();
+        let rekey = |s: ScheduledMessage| ScheduledMessage { key: policy.tie_key(&s.target, &s.message), ..s };
+        let mut mailboxes = self.mailboxes.lock();
+        for mailbox in mailboxes.values_mut() {
+            mailbox.blocked = std::mem::take(&mut mailbox.blocked).into_iter().map(|(s, wake)| (rekey(s), wake)).collect();
+        }
+        let mut queue = self.queue.lock();
+        *queue = std::mem::take(&mut *queue).into_iter().map(rekey).collect();
+    }
+
+    /// Logical time of a message `from` sends to `to` now: its Lamport
+    /// clock, or the scheduler's for the host, plus the policy's latency.
+    pub(crate) fn arrival(&self, from: &str, to: &str) -> u64 {
+        let own = self.clocks.lock().get(from).copied();
+        let now = own.unwrap_or_else(|| self.logical_time());
+        self.ordering.lock().arrival(from, to, now).max(now + 1)
+    }
+
+    fn next_seq(&self) -> u64 {
         let mut seq = self.sequence_counter.lock();
-        
-        *clock += 1;
         *seq += 1;
+        *seq
+    }
 
-        let msg = Message::new(sender, message_data, *clock, *seq);
-        self.queue.lock().push(ScheduledMessage { message: msg, target });
+    /// Messages between actors; unlike the `send*` entry points these are not
+    /// external inputs, since a replay regenerates them.
+    fn enqueue(&self, target: ActorId, message_data: MessageData, sender: ActorId) {
+        let time = self.arrival(&sender, &target);
+        self.enqueue_at(target, message_data, sender, time);
     }
 
-    pub fn send_with_time(&self, target: ActorId, message_data: crate::mailbox::MessageData, sender: ActorId, time: u64, seq: u64) {
-        let mut clock = self.logical_clock.lock();
-        if time > *clock {
-            *clock = time;
+    /// A message due at `time`, such as a timer, whatever the sender's clock.
+    fn enqueue_at(&self, target: ActorId, message_data: MessageData, sender: ActorId, time: u64) {
+        let seq = self.next_seq();
+        self.enqueue_with_time(target, message_data, sender, time, seq);
+    }
+
+    fn enqueue_with_time(&self, target: ActorId, message_data: MessageData, sender: ActorId, time: u64, seq: u64) {
+        self.schedule(target, Message::new(sender, message_data, time, seq), None);
+    }
+
+    /// [`Scheduler::enqueue`] for a sender that waits while a full `Block`
+    /// mailbox holds its message back; it is then sent a wake-up from
+    /// `wake` once the message is let in. Returns `false` if held back.
+    fn enqueue_or_wait(&self, target: ActorId, message_data: MessageData, sender: ActorId, wake: ActorId) -> bool {
+        let time = self.arrival(&sender, &target);
+        let seq = self.next_seq();
+        self.schedule(target, Message::new(sender, message_data, time, seq), Some(wake))
+    }
+
+    /// Queue a message if its target's mailbox has room, applying the
+    /// mailbox's overflow policy if not. Returns `false` if the message is
+    /// held back.
+    fn schedule(&self, target: ActorId, message: Message, wake: Option<ActorId>) -> bool {
+        let key = self.ordering.lock().tie_key(&target, &message);
+        let s = ScheduledMessage { message, target, key };
+        let default = *self.default_mailbox.lock();
+        let mut mailboxes = self.mailboxes.lock();
+        let mailbox = mailboxes.entry(s.target.clone()).or_default();
+        // Dead letters are only bounded if asked for explicitly
+        let config = mailbox.config.unwrap_or(if s.target == DEAD_LETTERS { MailboxConfig::default() } else { default });
+        let full = config.capacity.is_some_and(|capacity| mailbox.metrics.depth >= capacity);
+        if !full || timer_id(&s.message.sender).is_some() || is_stop(&s.message) {
+            mailbox.admit();
+            drop(mailboxes);
+            self.push(s);
+            return true;
+        }
+        match config.overflow {
+            // A waiting sender has at most one message held back; the host
+            // never waits
+            Overflow::Block => {
+                mailbox.blocked.push_back((s, wake));
+                mailbox.metrics.blocked += 1;
+                return false;
+            }
+            Overflow::DropOld => {
+                mailbox.metrics.dropped += 1;
+                drop(mailboxes);
+                match self.evict(&s.target) {
+                    Some(old) => {
+                        self.push(s);
+                        self.dead_letter(old, DeadReason::Evicted);
+                    }
+                    None => self.dead_letter(s, DeadReason::MailboxFull),
+                }
+            }
+            Overflow::DropNew | Overflow::Fail => {
+                mailbox.metrics.dropped += 1;
+                drop(mailboxes);
+                if config.overflow == Overflow::Fail {
+                    let reason = format!("mailbox of '{}' is full", s.target);
+                    self.failures.lock().push(ActorFailure { actor: s.message.sender.clone(), logical_time: self.logical_time(), reason, quota: None });
+                }
+                self.dead_letter(s, DeadReason::MailboxFull);
+            }
+        }
+        true
+    }
+
+    fn push(&self, s: ScheduledMessage) {
+        self.queue.lock().push(s);
+        self.arrived.notify_all();
+    }
+
+    /// Take `target`'s earliest queued message that is not yet due. Messages
+    /// due now may already be running on another thread, so they stay, and
+    /// timers are never evicted since the actor is counting on them.
+    fn evict(&self, target: &str) -> Option<ScheduledMessage> {
+        let now = self.logical_time();
+        let mut queue = self.queue.lock();
+        let mut pending = std::mem::take(&mut *queue).into_vec();
+        let oldest = pending.iter().enumerate()
+            .filter(|(_, s)| s.target == target && s.message.logical_time > now && timer_id(&s.message.sender).is_none())
+            .max_by(|a, b| a.1.cmp(b.1))
+            .map(|(i, _)| i);
+        let evicted = oldest.map(|i| pending.swap_remove(i));
+        *queue = pending.into();
+        evicted
+    }
+
+    /// A message leaves `target`'s mailbox at `time`, delivered or not. A
+    /// blocked message takes its slot, due a tick later.
+    fn leave_mailbox(&self, target: &str, time: u64, delivered: bool) {
+        let mut mailboxes = self.mailboxes.lock();
+        let Some(mailbox) = mailboxes.get_mut(target) else { return };
+        mailbox.metrics.depth = mailbox.metrics.depth.saturating_sub(1);
+        if delivered {
+            mailbox.metrics.delivered += 1;
+        } else {
+            mailbox.metrics.dropped += 1;
+        }
+        if let Some((mut s, wake)) = mailbox.blocked.pop_front() {
+            mailbox.metrics.blocked -= 1;
+            mailbox.admit();
+            drop(mailboxes);
+            s.message.logical_time = s.message.logical_time.max(time + 1);
+            if let Some(wake) = wake {
+                self.enqueue_at(s.message.sender.clone(), MessageData::Signal("wake".to_string()), wake, time + 1);
+            }
+            self.push(s);
+        }
+    }
+
+    /// Log an undeliverable message and forward it to [`DEAD_LETTERS`].
+    fn dead_letter(&self, s: ScheduledMessage, reason: DeadReason) {
+        let letter = DeadLetter { target: s.target, message: s.message, reason, logical_time: self.logical_time() };
+        if letter.target != DEAD_LETTERS && self.spawned.lock().contains(DEAD_LETTERS) {
+            self.enqueue(DEAD_LETTERS.to_string(), letter.to_data(), "runtime".to_string());
+        }
+        let mut log = self.dead_letters.lock();
+        if log.len() == DEAD_LETTER_LOG {
+            log.pop_front();
+        }
+        log.push_back(letter);
+    }
+
+    /// Limit `actor`'s mailbox; others keep the default.
+    pub fn set_mailbox(&self, actor: &str, config: MailboxConfig) {
+        self.mailboxes.lock().entry(actor.to_string()).or_default().config = Some(config);
+    }
+
+    /// Limits for every mailbox not given its own by [`Scheduler::set_mailbox`],
+    /// except [`DEAD_LETTERS`], which stays unbounded.
+    pub fn set_default_mailbox(&self, config: MailboxConfig) {
+        *self.default_mailbox.lock() = config;
+    }
+
+    /// Limits for every actor, current and future, not given its own by
+    /// [`Scheduler::set_actor_quota`]. An actor over its quota fails, and its
+    /// supervisor handles that like any other failure.
+    pub fn set_quota(&self, quota: Quota) {
+        *self.quota.lock() = quota;
+        for cell in self.actors.lock().values_mut() {
+            self.apply_quota(cell);
+        }
+    }
+
+    /// Limits for `actor`; those it leaves unset come from [`Scheduler::set_quota`].
+    pub fn set_actor_quota(&self, actor: &str, quota: Quota) {
+        self.quotas.lock().insert(actor.to_string(), quota);
+        if let Some(cell) = self.actors.lock().get_mut(actor) {
+            self.apply_quota(cell);
+        }
+    }
+
+    /// Limits over all actors together.
+    pub fn set_global_quota(&self, quota: GlobalQuota) {
+        *self.global_quota.lock() = quota;
+    }
+
+    /// Grant every actor, current and future, exactly what `guard` allows.
+    /// Until this is called actors keep their own grants, which for
+    /// compiled actors are [`Capabilities::ACTOR`]. An actor that attempts an
+    /// operation it was not granted fails with a security error, and a
+    /// `CapabilityDenied` trace event records what it tried.
+    pub fn set_grants(&self, guard: CapabilityGuard) {
+        for cell in self.actors.lock().values_mut() {
+            cell.actor.grant(&guard);
         }
+        if let Some(recorder) = &*self.recorder.lock() {
+            recorder.grants(&guard);
+        }
+        *self.grants.lock() = Some(guard);
+    }
 
-        let msg = Message::new(sender, message_data, time, seq);
-        self.queue.lock().push(ScheduledMessage { message: msg, target });
+    /// Resource use of every actor that has handled a message.
+    pub fn usage(&self) -> BTreeMap<ActorId, ActorUsage> {
+        self.usage.lock().iter().map(|(id, u)| (id.clone(), *u)).collect()
+    }
+
+    fn apply_quota(&self, cell: &mut ActorCell) {
+        let own = self.quotas.lock().get(&cell.id).copied().unwrap_or_default();
+        cell.context.quota = own.or(*self.quota.lock());
+        cell.context.arena.set_limit(cell.context.quota.memory.map(|bytes| bytes as usize));
+    }
+
+    /// Record what `actor` used handling a message, returning the quota it
+    /// went over, if any: its own memory quota, or the global one if its
+    /// memory grew while all actors together are over it.
+    fn account(&self, actor: &str, memory: u64, instructions: u64, quota: &Quota) -> Option<QuotaExceeded> {
+        let (grew, total) = {
+            let mut usage = self.usage.lock();
+            let entry = usage.entry(actor.to_string()).or_default();
+            let grew = memory > entry.memory;
+            entry.memory = memory;
+            entry.peak_memory = entry.peak_memory.max(memory);
+            entry.instructions += instructions;
+            (grew, usage.values().map(|u| u.memory).sum::<u64>())
+        };
+        if let Some(limit) = quota.memory.filter(|&limit| memory > limit) {
+            return Some(QuotaExceeded { resource: Resource::Memory, scope: QuotaScope::Actor, limit, used: memory });
+        }
+        let limit = self.global_quota.lock().memory?;
+        (grew && total > limit).then_some(QuotaExceeded { resource: Resource::Memory, scope: QuotaScope::Global, limit, used: total })
     }
 
+    /// Queue statistics of every actor that has been sent a message.
+    pub fn mailbox_metrics(&self) -> BTreeMap<ActorId, MailboxMetrics> {
+        self.mailboxes.lock().iter().map(|(id, m)| (id.clone(), m.metrics)).collect()
+    }
+
+    /// Messages waiting in the queue, across all actors.
+    pub fn queue_depth(&self) -> usize {
+        self.queue.lock().len()
+    }
+
+    /// The most recent undeliverable messages, oldest first.
+    pub fn dead_letters(&self) -> Vec<DeadLetter> {
+        self.dead_letters.lock().iter().cloned().collect()
+    }
+
+    /// Take the next message off the queue, returning whether there was
+    /// one. It may become a dead letter or be put back a tick later rather
+    /// than be delivered, so `false` is the only sign the queue is empty.
     pub fn step(&self) -> bool {
-        let scheduled = {
-            let mut q = self.queue.lock();
-            q.pop()
+        self.advance().is_some()
+    }
+
+    /// Like [`Scheduler::step`], but `None` once the queue is empty and
+    /// otherwise whether the message was delivered.
+    pub fn advance(&self) -> Option<bool> {
+        let scheduled = self.queue.lock().pop()?;
+        let recorder = self.recorder.lock().clone();
+        let step = self.deliver(scheduled, recorder.is_some());
+        let delivered = step.is_some();
+        if let (Some(recorder), Some(step)) = (recorder, step) {
+            recorder.step(step);
+        }
+        Some(delivered)
+    }
+
+    /// Deliver the next message and describe it, hashing the receiver's
+    /// state afterwards if `hash` is set.
+    pub(crate) fn step_recorded(&self, hash: bool) -> Option<RecordedStep> {
+        let scheduled = self.queue.lock().pop();
+        scheduled.and_then(|s| self.deliver(s, hash))
+    }
+
+    fn deliver(&self, s: ScheduledMessage, hash: bool) -> Option<RecordedStep> {
+        // The cell is checked out while it runs so the actor table stays
+        // readable (e.g. for state snapshots) from inside `receive`
+        let logical_time = s.message.logical_time;
+        let Some(mut actor_cell) = self.actors.lock().remove(&s.target) else {
+            {
+                let mut clock = self.logical_clock.lock();
+                *clock = (*clock).max(logical_time);
+            }
+            self.leave_mailbox(&s.target, logical_time, false);
+            self.dead_letter(s, DeadReason::UnknownActor);
+            return None;
         };
+        if is_stop(&s.message) {
+            return self.deliver_stop(s, actor_cell);
+        }
+        self.leave_mailbox(&s.target, logical_time, true);
+        // Record event (Step 2: Tracing)
+        let tracer = self.tracer();
+        let traced = tracer.begin(&s.target, &s.message, || actor_cell.actor.get_state());
+        let first_seq = *self.sequence_counter.lock() + 1;
+        actor_cell.context.instructions = 0;
+        actor_cell.context.logical_time = logical_time;
 
-        if let Some(s) = scheduled {
-            let mut actors = self.actors.lock();
-            if let Some(actor_cell) = actors.get_mut(&s.target) {
-                // In DAS, time is explicit
-                let ctx = VMContext {
-                    logical_time: s.message.logical_time,
-                    rand_seed: 0xDEADBEEF ^ s.message.logical_time,
-                };
-                
-                // Record event (Step 2: Tracing)
-                let trace_state = actor_cell.actor.get_state();
-                let tracer = crate::get_tracer();
-                tracer.record(crate::trace::TraceEvent {
-                    logical_time: s.message.logical_time,
-                    actor_id: s.target.clone(),
-                    input: s.message.clone(),
-                    state_snapshot: trace_state,
-                });
+        self.advance_clock(&s.target, logical_time);
+        let mut step = RecordedStep {
+            step: self.recorder.lock().as_ref().map_or(0, |r| r.steps()),
+            actor: s.target.clone(),
+            sender: s.message.sender.clone(),
+            logical_time,
+            sequence_id: s.message.sequence_id,
+            state_hash: None,
+        };
+        actor_cell.start();
+        actor_cell.actor.receive(s.message, &mut actor_cell.context);
+        actor_cell.context.arena.reset();
+        if let Some(mut event) = traced {
+            event.instructions = actor_cell.context.instructions;
+            event.sent = (first_seq..=*self.sequence_counter.lock()).collect();
+            tracer.finish(event);
+        }
+        if let Some(event) = denial(&mut actor_cell) {
+            tracer.record_unsampled(event);
+        }
+        let over = self.account(&s.target, actor_cell.actor.memory(), actor_cell.context.instructions, &actor_cell.context.quota);
+        let (failed, quota) = failure_or(actor_cell.context.failure.take(), actor_cell.context.exceeded.take(), over);
+        if let Some(reason) = failed.clone() {
+            self.failures.lock().push(ActorFailure { actor: s.target.clone(), logical_time, reason, quota });
+        }
+        if hash {
+            step.state_hash = actor_cell.actor.snapshot().map(|bytes| replay::state_hash(&bytes));
+        }
+        self.actors.lock().entry(s.target.clone()).or_insert(actor_cell);
+        if let Some(reason) = failed {
+            self.failed(&s.target, &reason, logical_time);
+        }
+        Some(step)
+    }
+
+    /// Put a message back a tick later without delivering it.
+    fn defer(&self, mut s: ScheduledMessage, cell: ActorCell) -> Option<RecordedStep> {
+        let time = s.message.logical_time;
+        let mut clock = self.logical_clock.lock();
+        *clock = (*clock).max(time);
+        drop(clock);
+        self.actors.lock().insert(s.target.clone(), cell);
+        s.message.logical_time = time + 1;
+        self.push(s);
+        None
+    }
 
-                actor_cell.actor.receive(s.message, &mut actor_cell.context);
-                return true;
+    /// Whether any mailbox may hold senders back, in which case messages
+    /// are delivered one at a time: a parallel batch only applies its sends
+    /// after it runs, too late for a sender to wait for a slot.
+    fn blocks_senders(&self) -> bool {
+        let blocks = |config: &MailboxConfig| config.capacity.is_some() && config.overflow == Overflow::Block;
+        blocks(&self.default_mailbox.lock()) || self.mailboxes.lock().values().any(|m| m.config.as_ref().is_some_and(blocks))
+    }
+
+    /// Deliver a stop signal: wait a tick longer while other messages for
+    /// the actor are still queued or held back, then stop it.
+    fn deliver_stop(&self, s: ScheduledMessage, mut cell: ActorCell) -> Option<RecordedStep> {
+        let time = s.message.logical_time;
+        let waiting = self.mailboxes.lock().get(&s.target).is_some_and(|m| !m.blocked.is_empty())
+            || self.queue.lock().iter().any(|q| q.target == s.target && timer_id(&q.message.sender).is_none());
+        if waiting {
+            return self.defer(s, cell);
+        }
+        self.leave_mailbox(&s.target, time, true);
+        let step = RecordedStep {
+            step: self.recorder.lock().as_ref().map_or(0, |r| r.steps()),
+            actor: s.target.clone(),
+            sender: s.message.sender.clone(),
+            logical_time: time,
+            sequence_id: s.message.sequence_id,
+            state_hash: None,
+        };
+        let first_seq = *self.sequence_counter.lock() + 1;
+        if cell.started {
+            self.hook(&mut cell, s.message, first_seq, |actor, ctx| actor.on_stop("shutdown", ctx));
+        }
+        self.stopped(&s.target, "shutdown");
+        Some(step)
+    }
+
+    pub fn logical_time(&self) -> u64 {
+        *self.logical_clock.lock()
+    }
+
+    /// Move the scheduler's and `actor`'s clocks forward to a delivery at
+    /// `time`, so what the handler sends comes after it.
+    fn advance_clock(&self, actor: &str, time: u64) {
+        let mut clock = self.logical_clock.lock();
+        *clock = (*clock).max(time);
+        let mut clocks = self.clocks.lock();
+        let own = clocks.entry(actor.to_string()).or_default();
+        *own = (*own).max(time);
+    }
+
+    pub fn set_clock(&self, clock: Clock) {
+        *self.clock.lock() = clock;
+        *self.epoch.lock() = None;
+    }
+
+    /// Whether a message at `time` may be delivered now. A paced clock
+    /// waits for its wall time, returning false early if another message
+    /// arrives in the meantime since that one may be due sooner.
+    fn due(&self, time: u64) -> bool {
+        let Clock::Paced(tick) = *self.clock.lock() else { return true };
+        let (start, origin) = *self.epoch.lock().get_or_insert_with(|| (Instant::now(), time));
+        let ticks = u32::try_from(time.saturating_sub(origin)).unwrap_or(u32::MAX);
+        let deadline = start + tick.saturating_mul(ticks);
+        let mut queue = self.queue.lock();
+        Instant::now() >= deadline || self.arrived.wait_until(&mut queue, deadline).timed_out()
+    }
+
+    /// Step until no messages remain or the next one is due after `max_time`.
+    pub fn run(&self, max_time: Option<u64>) -> RunReport {
+        if let Some(pool) = self.parallel_pool() {
+            return self.run_parallel(&pool, max_time);
+        }
+        let mut steps = 0;
+        let stop = loop {
+            let next = self.queue.lock().peek().map(|s| s.message.logical_time);
+            match next {
+                None => break RunStop::Quiescent,
+                Some(t) if max_time.is_some_and(|limit| t > limit) => break RunStop::TimeLimit,
+                Some(t) => {
+                    if self.due(t) && self.advance() == Some(true) {
+                        steps += 1;
+                    }
+                }
+            }
+        };
+        RunReport { steps, logical_time: *self.logical_clock.lock(), stop }
+    }
+
+    /// Failures reported by actors so far, in the order they happened.
+    pub fn failures(&self) -> Vec<ActorFailure> {
+        self.failures.lock().clone()
+    }
+
+    /// Freeze actors, pending messages and counters. Call between steps.
+    pub fn snapshot(&self) -> anyhow::Result<Snapshot> {
+        let actors = self.actors.lock();
+        let mut ids: Vec<&ActorId> = actors.keys().collect();
+        ids.sort();
+        let actors = ids.into_iter()
+            .map(|id| Ok(ActorSnapshot { id: id.clone(), state: actors[id].snapshot()?, started: actors[id].started }))
+            .collect::<anyhow::Result<_>>()?;
+        let queue = self.pending().into_iter()
+            .map(|(target, message)| QueuedMessage { target, message })
+            .collect();
+        let mailboxes = self.mailboxes.lock();
+        let mut held: Vec<&ActorId> = mailboxes.iter().filter(|(_, m)| !m.blocked.is_empty()).map(|(id, _)| id).collect();
+        held.sort();
+        let blocked = held.into_iter()
+            .flat_map(|id| mailboxes[id].blocked.iter().map(|(s, wake)| HeldMessage { target: s.target.clone(), message: s.message.clone(), wake: wake.clone() }))
+            .collect();
+        Ok(Snapshot {
+            program: *self.program.lock(),
+            logical_clock: *self.logical_clock.lock(),
+            sequence_counter: *self.sequence_counter.lock(),
+            clocks: self.clocks.lock().iter().map(|(id, t)| (id.clone(), *t)).collect(),
+            actors,
+            queue,
+            blocked,
+            supervisors: self.supervisors.lock().iter().flat_map(|root| root.histories()).collect(),
+            links: self.links.lock().iter().cloned().collect(),
+            monitors: self.monitors.lock().iter().cloned().collect(),
+        })
+    }
+
+    /// Replace all actors, pending messages and counters with `snapshot`.
+    /// `actor_for` supplies a fresh actor for each id, which is then restored.
+    pub fn restore(&self, snapshot: &Snapshot, mut actor_for: impl FnMut(&str) -> Option<Box<dyn Actor>>) -> anyhow::Result<()> {
+        let mut cells = Vec::with_capacity(snapshot.actors.len());
+        for saved in &snapshot.actors {
+            let actor = actor_for(&saved.id).ok_or_else(|| anyhow::anyhow!("no actor to restore '{}' into", saved.id))?;
+            let mut cell = ActorCell::resume(saved.id.clone(), &saved.state, actor)?;
+            cell.started = saved.started;
+            cells.push(cell);
+        }
+        self.actors.lock().clear();
+        self.spawned.lock().clear();
+        // Memory in use counts toward the global quota; instruction counts start over
+        *self.usage.lock() = cells.iter().filter(|cell| cell.started).map(|cell| {
+            let memory = cell.actor.memory();
+            (cell.id.clone(), ActorUsage { memory, peak_memory: memory, instructions: 0 })
+        }).collect();
+        for cell in cells {
+            self.spawn(cell);
+        }
+        // Restored messages were admitted once already, so no limits apply
+        let policy = self.ordering.lock().clone();
+        let scheduled = |q: &QueuedMessage| ScheduledMessage { key: policy.tie_key(&q.target, &q.message), message: q.message.clone(), target: q.target.clone() };
+        let mut mailboxes = self.mailboxes.lock();
+        for mailbox in mailboxes.values_mut() {
+            mailbox.metrics = MailboxMetrics::default();
+            mailbox.blocked.clear();
+        }
+        let mut queue = self.queue.lock();
+        queue.clear();
+        for q in &snapshot.queue {
+            mailboxes.entry(q.target.clone()).or_default().admit();
+            queue.push(scheduled(q));
+        }
+        for held in &snapshot.blocked {
+            let q = QueuedMessage { target: held.target.clone(), message: held.message.clone() };
+            let mailbox = mailboxes.entry(q.target.clone()).or_default();
+            mailbox.blocked.push_back((scheduled(&q), held.wake.clone()));
+            mailbox.metrics.blocked += 1;
+        }
+        drop((queue, mailboxes));
+        self.dead_letters.lock().clear();
+        // Supervisors missing from the snapshot had given up
+        let mut roots = self.supervisors.lock();
+        roots.retain(|root| snapshot.supervisors.contains_key(&root.name));
+        for root in roots.iter_mut() {
+            for (name, restarts) in &snapshot.supervisors {
+                root.set_history(name, restarts);
             }
         }
-        false
+        // Children missing from it had been stopped
+        *self.supervised.lock() = roots.iter().flat_map(|root| root.specs())
+            .map(|(_, spec)| spec.id.clone())
+            .filter(|id| snapshot.actors.iter().any(|saved| saved.id == *id))
+            .collect();
+        drop(roots);
+        *self.links.lock() = snapshot.links.iter().cloned().collect();
+        *self.monitors.lock() = snapshot.monitors.iter().cloned().collect();
+        *self.logical_clock.lock() = snapshot.logical_clock;
+        *self.clocks.lock() = snapshot.clocks.iter().map(|(id, t)| (id.clone(), *t)).collect();
+        *self.sequence_counter.lock() = snapshot.sequence_counter;
+        self.failures.lock().clear();
+        Ok(())
+    }
+
+    /// [`Scheduler::restore`] for a snapshot of [`Scheduler::spawn_program`]:
+    /// `main`, declared actors and screens are rebuilt from `program`.
+    pub fn restore_program(&self, snapshot: &Snapshot, program: impl Into<Program>) -> anyhow::Result<()> {
+        let program = program.into();
+        let hash = program.hash();
+        if snapshot.program.is_some_and(|saved| saved != hash) {
+            anyhow::bail!("snapshot was taken of a different program");
+        }
+        *self.program.lock() = Some(hash);
+        *self.supervisors.lock() = Supervisor::from_program(&program);
+        self.adopt_quotas(&program);
+        self.restore(snapshot, |id| -> Option<Box<dyn Actor>> {
+            if id == "main" {
+                return Some(Box::new(crate::VMActor::new(program.clone())));
+            }
+            if let Some(actor) = crate::VMActor::declared(program.clone(), id) {
+                return Some(Box::new(actor));
+            }
+            program.screen(id)?;
+            Some(Box::new(crate::ScreenActor::new(program.to_chunk(), id)?))
+        })
+    }
+
+    pub fn actor_ids(&self) -> Vec<ActorId> {
+        let mut ids: Vec<ActorId> = self.actors.lock().keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    /// An actor's state as JSON (its `get_state`).
+    pub fn actor_state(&self, actor: &str) -> Option<String> {
+        self.actors.lock().get(actor).map(|cell| cell.actor.get_state())
+    }
+
+    /// Messages waiting for `actor`, in delivery order.
+    pub fn mailbox(&self, actor: &str) -> Vec<Message> {
+        self.pending().into_iter().filter(|(target, _)| target == actor).map(|(_, msg)| msg).collect()
+    }
+
+    /// The message the next step will deliver, and its target.
+    pub fn peek(&self) -> Option<(ActorId, Message)> {
+        self.queue.lock().peek().map(|s| (s.target.clone(), s.message.clone()))
+    }
+
+    fn pending(&self) -> Vec<(ActorId, Message)> {
+        // The heap's greatest element is delivered first
+        let queue = self.queue.lock();
+        let mut pending: Vec<&ScheduledMessage> = queue.iter().collect();
+        pending.sort_by(|a, b| b.cmp(a));
+        pending.into_iter().map(|s| (s.target.clone(), s.message.clone())).collect()
+    }
+
+    /// Read one field of a local actor's state.
+    pub fn read_field(&self, actor: &str, field: &str) -> Option<Value> {
+        self.actors.lock().get(actor).and_then(|cell| cell.actor.get_field(field))
     }
 
     pub fn run_deterministic_loop(&self) {
         println!("🌀 DAS: Starting Deterministic Scheduler Loop...");
         loop {
-            if !self.step() {
-                std::thread::sleep(std::time::Duration::from_micros(10));
+            self.run(None);
+            let mut queue = self.queue.lock();
+            if queue.is_empty() {
+                self.arrived.wait(&mut queue);
+            }
+        }
+    }
+
+    /// Run messages for different actors on up to `threads` threads
+    /// (1 turns it off). Output, traces, failures, sends and final state are
+    /// exactly those of a single-threaded run: handlers run in isolation and
+    /// their effects are applied in delivery order.
+    pub fn set_parallelism(&self, threads: usize) -> anyhow::Result<()> {
+        *self.pool.lock() = match threads {
+            0 | 1 => None,
+            n => Some(Arc::new(rayon::ThreadPoolBuilder::new().num_threads(n).build()?)),
+        };
+        Ok(())
+    }
+
+    /// The thread pool, unless something observes deliveries one at a time:
+    /// a recorder or debugger, an env source whose reads are logged, or a
+    /// message bus other than the scheduler's own.
+    fn parallel_pool(&self) -> Option<Arc<rayon::ThreadPool>> {
+        let observed = self.recorder.lock().is_some()
+            || self.debug.lock().is_some()
+            || self.env.lock().is_some()
+            || !*self.own_bus.lock();
+        if observed { None } else { self.pool.lock().clone() }
+    }
+
+    /// Everything sent while a batch runs arrives after the batch's time, so
+    /// the messages due at the earliest time are a batch whose delivery order
+    /// is fixed. Runs of isolated actors in it execute concurrently; anything
+    /// else runs alone at its place in the order, and if it sets a timer due
+    /// before the rest of the batch, the batch is cut short.
+    fn run_parallel(&self, pool: &rayon::ThreadPool, max_time: Option<u64>) -> RunReport {
+        let mut steps = 0;
+        let stop = 'batches: loop {
+            let Some(head) = self.queue.lock().peek().map(|s| s.message.logical_time) else { break RunStop::Quiescent };
+            if max_time.is_some_and(|limit| head > limit) {
+                break RunStop::TimeLimit;
+            }
+            if !self.due(head) {
+                continue;
+            }
+            if self.blocks_senders() {
+                // Whether a sender waits depends on the whole queue, so
+                // messages are taken one at a time
+                let next = self.queue.lock().pop();
+                steps += next.and_then(|s| self.deliver(s, false)).map_or(0, |_| 1);
+                continue;
+            }
+            let batch: Vec<ScheduledMessage> = {
+                let mut queue = self.queue.lock();
+                std::iter::from_fn(|| {
+                    let due = queue.peek().is_some_and(|s| s.message.logical_time == head);
+                    if due { queue.pop() } else { None }
+                }).collect()
+            };
+
+            let mut batch = batch.into_iter().peekable();
+            while batch.peek().is_some() {
+                let mut run = Vec::new();
+                // Stops and restarts replace actors between their messages
+                while let Some(s) = batch.next_if(|s| !self.has_lifecycle(s) && self.actors.lock().get(&s.target).is_some_and(|cell| cell.actor.isolated())) {
+                    run.push(s);
+                }
+                if run.is_empty() {
+                    if let Some(s) = batch.next() {
+                        steps += usize::from(self.deliver(s, false).is_some());
+                    }
+                    let mut queue = self.queue.lock();
+                    if batch.peek().is_some_and(|next| queue.peek().is_some_and(|head| head > next)) {
+                        queue.extend(batch);
+                        continue 'batches;
+                    }
+                } else {
+                    steps += self.deliver_isolated(pool, run);
+                }
+            }
+        };
+        RunReport { steps, logical_time: *self.logical_clock.lock(), stop }
+    }
+
+    /// Whether delivering `s` may stop or restart actors or notify others,
+    /// which only happens in sequential delivery.
+    fn has_lifecycle(&self, s: &ScheduledMessage) -> bool {
+        is_stop(&s.message)
+            || self.supervised.lock().contains(&s.target)
+            || self.links.lock().iter().any(|(a, b)| *a == s.target || *b == s.target)
+            || self.monitors.lock().iter().any(|(_, watched)| *watched == s.target)
+    }
+
+    /// Deliver messages to isolated actors, one thread per actor at a time,
+    /// then apply their effects in delivery order.
+    fn deliver_isolated(&self, pool: &rayon::ThreadPool, run: Vec<ScheduledMessage>) -> usize {
+        let tracer = self.tracer();
+        // Each checked-out actor with its messages: batch position, message, traced
+        type Group = (ActorCell, Vec<(usize, ScheduledMessage, bool)>);
+        let mut groups: Vec<Group> = Vec::new();
+        {
+            let mut actors = self.actors.lock();
+            let mut index: HashMap<ActorId, usize> = HashMap::new();
+            for (position, s) in run.into_iter().enumerate() {
+                let traced = tracer.admit(&s.target, &s.message);
+                let group = *index.entry(s.target.clone()).or_insert_with(|| {
+                    let cell = actors.remove(&s.target).expect("isolated actors were just seen");
+                    groups.push((cell, Vec::new()));
+                    groups.len() - 1
+                });
+                groups[group].1.push((position, s, traced));
+            }
+        }
+
+        let mut outcomes: Vec<Isolated> = pool.install(|| {
+            groups.par_iter_mut()
+                .flat_map_iter(|(cell, messages)| std::mem::take(messages).into_iter().map(|(position, s, traced)| Isolated::run(cell, position, s, traced)).collect::<Vec<_>>())
+                .collect()
+        });
+        outcomes.sort_by_key(|o| o.position);
+
+        let delivered = outcomes.len();
+        for outcome in outcomes {
+            for event in outcome.output {
+                outcome.sink.emit(event);
+            }
+            self.advance_clock(&outcome.target, outcome.logical_time);
+            self.leave_mailbox(&outcome.target, outcome.logical_time, true);
+            let first_seq = *self.sequence_counter.lock() + 1;
+            for (from, to, data) in outcome.sends {
+                self.enqueue(to, data, from);
             }
+            if let Some(mut event) = outcome.trace {
+                event.sent = (first_seq..=*self.sequence_counter.lock()).collect();
+                tracer.finish(event);
+            }
+            if let Some(event) = outcome.denied {
+                tracer.record_unsampled(event);
+            }
+            let over = self.account(&outcome.target, outcome.memory, outcome.instructions, &outcome.quota);
+            if let (Some(reason), quota) = failure_or(outcome.failure, outcome.exceeded, over) {
+                self.failures.lock().push(ActorFailure { actor: outcome.target, logical_time: outcome.logical_time, reason, quota });
+            }
+        }
+        let mut actors = self.actors.lock();
+        for (cell, _) in groups {
+            actors.entry(cell.id.clone()).or_insert(cell);
+        }
+        delivered
+    }
+
+    /// Trace events to `tracer` instead of the global one.
+    pub fn set_tracer(&self, tracer: Arc<Tracer>) {
+        *self.tracer.lock() = Some(tracer);
+    }
+
+    fn tracer(&self) -> TracerRef {
+        match self.tracer.lock().clone() {
+            Some(tracer) => TracerRef::Own(tracer),
+            None => TracerRef::Global(crate::get_tracer()),
+        }
+    }
+}
+
+enum TracerRef {
+    Own(Arc<Tracer>),
+    Global(&'static Tracer),
+}
+
+impl std::ops::Deref for TracerRef {
+    type Target = Tracer;
+
+    fn deref(&self) -> &Tracer {
+        match self {
+            TracerRef::Own(tracer) => tracer,
+            TracerRef::Global(tracer) => tracer,
+        }
+    }
+}
+
+/// One delivery run apart from the scheduler, with its effects held back.
+struct Isolated {
+    position: usize,
+    target: ActorId,
+    logical_time: u64,
+    sink: Arc<dyn RenderSink>,
+    output: Vec<RenderEvent>,
+    sends: Vec<(ActorId, ActorId, MessageData)>,
+    trace: Option<TraceEvent>,
+    failure: Option<String>,
+    exceeded: Option<QuotaExceeded>,
+    denied: Option<TraceEvent>,
+    memory: u64,
+    instructions: u64,
+    quota: Quota,
+}
+
+impl Isolated {
+    fn run(cell: &mut ActorCell, position: usize, s: ScheduledMessage, traced: bool) -> Self {
+        let output = Arc::new(MemorySink::new());
+        let outbox = Arc::new(Outbox::default());
+        let sink = std::mem::replace(&mut cell.context.sink, output.clone());
+        // Without a bus, sends go nowhere, as they would in a sequential run
+        let bus = cell.context.bus.take();
+        if bus.is_some() {
+            cell.context.bus = Some(outbox.clone());
+        }
+        let trace = traced.then(|| TraceEvent {
+            logical_time: s.message.logical_time,
+            actor_id: s.target.clone(),
+            input: s.message.clone(),
+            state_snapshot: cell.actor.get_state(),
+            instructions: 0,
+            sent: Vec::new(),
+        });
+        let logical_time = s.message.logical_time;
+        cell.context.instructions = 0;
+        cell.context.logical_time = logical_time;
+        cell.start();
+        cell.actor.receive(s.message, &mut cell.context);
+        cell.context.arena.reset();
+        cell.context.sink = sink.clone();
+        cell.context.bus = bus;
+        let sends = std::mem::take(&mut *outbox.0.lock());
+        let denied = denial(cell);
+        Self {
+            position,
+            target: s.target,
+            logical_time,
+            sink,
+            output: output.take(),
+            sends,
+            trace: trace.map(|event| TraceEvent { instructions: cell.context.instructions, ..event }),
+            failure: cell.context.failure.take(),
+            exceeded: cell.context.exceeded.take(),
+            denied,
+            memory: cell.actor.memory(),
+            instructions: cell.context.instructions,
+            quota: cell.context.quota,
+        }
+    }
+}
+
+/// The trace event for an operation `cell` was just refused, if any.
+fn denial(cell: &mut ActorCell) -> Option<TraceEvent> {
+    let denied = cell.context.denied.take()?;
+    Some(TraceEvent::denied(&cell.id, cell.context.logical_time, &denied, cell.actor.get_state()))
+}
+
+/// A handler's own failure comes first; otherwise the quota it went over.
+fn failure_or(failure: Option<String>, exceeded: Option<QuotaExceeded>, over: Option<QuotaExceeded>) -> (Option<String>, Option<QuotaExceeded>) {
+    match (failure, over) {
+        (None, Some(over)) => (Some(over.to_string()), Some(over)),
+        (failure, _) => (failure, exceeded),
+    }
+}
+
+/// Collects the sends of an isolated delivery.
+#[derive(Default)]
+struct Outbox(Mutex<Vec<(ActorId, ActorId, MessageData)>>);
+
+impl MessageBus for Outbox {
+    fn send(&self, from: &str, to: &str, data: MessageData) {
+        self.0.lock().push((from.to_string(), to.to_string(), data));
+    }
+}
+
+impl MessageBus for Weak<Scheduler> {
+    fn send(&self, from: &str, to: &str, data: MessageData) {
+        if let Some(scheduler) = self.upgrade() {
+            scheduler.enqueue(to.to_string(), data, from.to_string());
+        }
+    }
+
+    fn send_or_wait(&self, from: &str, to: &str, data: MessageData, wake: &str) -> bool {
+        self.upgrade().is_none_or(|scheduler| scheduler.enqueue_or_wait(to.to_string(), data, from.to_string(), wake.to_string()))
+    }
+
+    fn send_at(&self, from: &str, to: &str, data: MessageData, at: u64) {
+        if let Some(scheduler) = self.upgrade() {
+            scheduler.enqueue_at(to.to_string(), data, from.to_string(), at);
+        }
+    }
+
+    fn watch(&self, from: &str, to: &str, watch: Watch) {
+        if let Some(scheduler) = self.upgrade() {
+            scheduler.watch(from, to, watch);
         }
     }
 }
+
+/// A graceful stop signal (see [`Scheduler::stop`]).
+fn is_stop(message: &Message) -> bool {
+    matches!(&message.data, MessageData::Signal(signal) if signal == STOP)
+}
+
+/// `Down(actor, reason)`, as monitors receive it.
+fn down(actor: &str, reason: &str) -> MessageData {
+    MessageData::from(Value::List(vec![Value::String("Down".to_string()), Value::String(actor.to_string()), Value::String(reason.to_string())]))
+}
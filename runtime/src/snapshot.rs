@@ -0,0 +1,108 @@
+// AeroFlow Runtime - Snapshots (.afs)
+// Freeze a whole scheduler between steps and resume it bit-for-bit
+
+use crate::mailbox::Message;
+use crate::vm::{Suspension, Timer};
+use aeroflow_compiler::ir::Value;
+use anyhow::{bail, Context as _};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub const AFS_MAGIC: [u8; 4] = *b"AFS1";
+/// Bump whenever the encoding of [`Snapshot`] or anything in it changes.
+pub const AFS_VERSION: u16 = 2;
+
+/// Everything a VM carries between instructions. Maps are ordered so equal
+/// states always encode to equal bytes.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct VmState {
+    pub globals: BTreeMap<String, Value>,
+    pub stack: Vec<Value>,
+    pub frames: Vec<FrameState>,
+    pub rng: u64,
+    /// Capability grants, as `Capabilities` bits.
+    pub capabilities: u32,
+    /// Variables `env()` is limited to, if it is.
+    pub env: Option<Vec<String>>,
+    pub timers: BTreeMap<u64, Timer>,
+    pub next_timer: u64,
+    pub suspended: Option<Suspension>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FrameState {
+    pub return_ip: Option<usize>,
+    pub locals: BTreeMap<String, Value>,
+}
+
+/// A message held back by a full `Block` mailbox.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeldMessage {
+    pub target: String,
+    pub message: Message,
+    /// Timer sender that wakes the suspended sender once it is let in.
+    pub wake: Option<String>,
+}
+
+/// One actor's state as produced by `Actor::snapshot`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActorSnapshot {
+    pub id: String,
+    pub state: Vec<u8>,
+    /// Its `on_start` has run.
+    pub started: bool,
+}
+
+/// A message still waiting for delivery.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedMessage {
+    pub target: String,
+    pub message: Message,
+}
+
+/// A scheduler frozen between steps: actors sorted by id, the queue in
+/// delivery order, and the clocks and sequence counter.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// [`Program::hash`](crate::Program::hash) of the spawned program, if
+    /// one was; resuming it against another program is refused.
+    pub program: Option<u64>,
+    pub logical_clock: u64,
+    pub sequence_counter: u64,
+    /// Each actor's Lamport clock.
+    pub clocks: BTreeMap<String, u64>,
+    pub actors: Vec<ActorSnapshot>,
+    pub queue: Vec<QueuedMessage>,
+    /// Messages held back by full `Block` mailboxes, by actor id then in
+    /// arrival order.
+    pub blocked: Vec<HeldMessage>,
+    /// Recent restart times of every running supervisor, by name.
+    pub supervisors: BTreeMap<String, Vec<u64>>,
+    /// Linked pairs, smaller id first.
+    pub links: Vec<(String, String)>,
+    /// Watcher and watched actor of every monitor.
+    pub monitors: Vec<(String, String)>,
+}
+
+impl Snapshot {
+    /// `.afs` encoding: magic, little-endian format version, then the
+    /// bincode snapshot.
+    pub fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        let mut bytes = AFS_MAGIC.to_vec();
+        bytes.extend(AFS_VERSION.to_le_bytes());
+        bytes.extend(bincode::serialize(self)?);
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        if bytes.get(..4) != Some(&AFS_MAGIC[..]) {
+            bail!("not an .afs snapshot (bad magic)");
+        }
+        let version = bytes.get(4..6).map(|v| u16::from_le_bytes([v[0], v[1]]))
+            .ok_or_else(|| anyhow::anyhow!("truncated .afs header"))?;
+        if version != AFS_VERSION {
+            bail!("unsupported .afs version {} (expected {})", version, AFS_VERSION);
+        }
+        bincode::deserialize(&bytes[6..]).context("corrupt .afs snapshot")
+    }
+}
@@ -0,0 +1,57 @@
+// AeroFlow Runtime - Distributed State
+// Resolving `node.field` references for deterministic state snapshots
+
+use crate::render::{StateEntry, StateSnapshot};
+use crate::scheduler::Scheduler;
+use aeroflow_compiler::ir::{StateRef, Value};
+use std::collections::HashMap;
+use std::sync::Weak;
+
+/// Anything that can look up a field of a named actor's state.
+pub trait StateSource: Send + Sync {
+    fn read_field(&self, actor: &str, field: &str) -> Option<Value>;
+}
+
+impl StateSource for Scheduler {
+    fn read_field(&self, actor: &str, field: &str) -> Option<Value> {
+        Scheduler::read_field(self, actor, field)
+    }
+}
+
+/// Lets actors hosted by a scheduler read their siblings without a reference cycle.
+impl StateSource for Weak<Scheduler> {
+    fn read_field(&self, actor: &str, field: &str) -> Option<Value> {
+        self.upgrade().and_then(|s| s.read_field(actor, field))
+    }
+}
+
+/// The running actor's own fields in front of everyone else's. A running actor
+/// is checked out of its scheduler, so its fields must be read from the VM.
+pub(crate) struct Running<'a> {
+    pub actor: Option<&'a str>,
+    pub fields: &'a HashMap<String, Value>,
+    pub others: Option<&'a dyn StateSource>,
+}
+
+impl StateSource for Running<'_> {
+    fn read_field(&self, actor: &str, field: &str) -> Option<Value> {
+        if self.actor == Some(actor) {
+            self.fields.get(field).cloned()
+        } else {
+            self.others.and_then(|s| s.read_field(actor, field))
+        }
+    }
+}
+
+/// Resolve every reference against `source`. Since the DAS executes one message at
+/// a time, all reads observe the same logical time. Unknown refs resolve to `nil`.
+pub fn snapshot(source: &dyn StateSource, refs: &[StateRef], logical_time: u64) -> StateSnapshot {
+    StateSnapshot {
+        logical_time,
+        entries: refs.iter().map(|r| StateEntry {
+            node: r.node.clone(),
+            field: r.field.clone(),
+            value: source.read_field(&r.node, &r.field).unwrap_or(Value::Nil),
+        }).collect(),
+    }
+}
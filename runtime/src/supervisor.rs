@@ -1,36 +1,241 @@
 // AeroFlow Runtime - Supervisor
 // Erlang-grade reliability and failure isolation
 
-use crate::actor::{ActorId, ActorCell};
-use crate::scheduler::Scheduler;
+use crate::actor::{Actor, ActorId};
+use crate::mailbox::MessageData;
+use crate::module::Program;
+use aeroflow_compiler::ir::{Code, SupervisedChild, SupervisorDef, Value, DEFAULT_MAX_RESTARTS, DEFAULT_RESTART_WINDOW};
+use std::collections::VecDeque;
+use std::sync::Arc;
 
+/// Which children a supervisor restarts when one of them fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Strategy {
+    /// Only the failed child.
     OneForOne,
+    /// Every child.
     AllForOne,
+    /// The failed child and every child after it.
+    RestForOne,
 }
 
+impl Strategy {
+    /// `OneForOne`, `AllForOne` or `RestForOne`.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "OneForOne" => Some(Self::OneForOne),
+            "AllForOne" => Some(Self::AllForOne),
+            "RestForOne" => Some(Self::RestForOne),
+            _ => None,
+        }
+    }
+}
+
+/// Builds a fresh actor, at start and again on every restart.
+pub type ChildFactory = Arc<dyn Fn() -> Box<dyn Actor> + Send + Sync>;
+
+/// How to start one supervised actor.
+#[derive(Clone)]
+pub struct ChildSpec {
+    pub id: ActorId,
+    pub factory: ChildFactory,
+    /// Sent with the start signal; declared actors bind them to their parameters.
+    pub args: Vec<Value>,
+}
+
+impl ChildSpec {
+    pub fn new(id: impl Into<ActorId>, factory: impl Fn() -> Box<dyn Actor> + Send + Sync + 'static) -> Self {
+        Self { id: id.into(), factory: Arc::new(factory), args: Vec::new() }
+    }
+
+    pub fn with_args(self, args: Vec<Value>) -> Self {
+        Self { args, ..self }
+    }
+
+    /// The start signal, as an unnamed event carrying `args` if there are any.
+    pub fn start_message(&self) -> MessageData {
+        if self.args.is_empty() {
+            return MessageData::Signal(String::new());
+        }
+        let mut event = vec![Value::String(String::new())];
+        event.extend(self.args.iter().cloned());
+        MessageData::from(Value::List(event))
+    }
+}
+
+pub enum Child {
+    Actor(ChildSpec),
+    Supervisor(Supervisor),
+}
+
+impl Child {
+    fn supervises(&self, actor: &str) -> bool {
+        match self {
+            Child::Actor(spec) => spec.id == actor,
+            Child::Supervisor(supervisor) => supervisor.supervises(actor),
+        }
+    }
+}
+
+/// A child the scheduler must replace with a fresh actor.
+pub(crate) struct Restart {
+    pub supervisor: String,
+    pub child: ChildSpec,
+}
+
+pub(crate) enum Outcome {
+    Restart(Vec<Restart>),
+    /// Too many restarts within the window: the supervisor itself fails.
+    Escalate,
+}
+
+/// Restarts children that fail, as its [`Strategy`] directs. More than
+/// `max_restarts` restarts within `within` logical ticks make it fail in
+/// turn, so its parent restarts it with all its children; a top-level
+/// supervisor that fails stops its children instead.
 pub struct Supervisor {
-    children: Vec<ActorId>,
-    strategy: Strategy,
+    pub name: String,
+    pub strategy: Strategy,
+    pub max_restarts: u32,
+    pub within: u64,
+    children: Vec<Child>,
+    /// Logical times of recent restarts, oldest first.
+    restarts: VecDeque<u64>,
 }
 
 impl Supervisor {
-    pub fn new(strategy: Strategy) -> Self {
+    pub fn new(name: impl Into<String>, strategy: Strategy) -> Self {
         Self {
-            children: Vec::new(),
+            name: name.into(),
             strategy,
+            max_restarts: DEFAULT_MAX_RESTARTS,
+            within: DEFAULT_RESTART_WINDOW,
+            children: Vec::new(),
+            restarts: VecDeque::new(),
         }
     }
 
-    pub fn handle_failure(&self, actor_id: ActorId, scheduler: &Scheduler) {
-        println!("⚠️ Supervisor: Actor {} failed. Applying recovery strategy...", actor_id);
-        match self.strategy {
-            Strategy::OneForOne => {
-                println!("  Restarting actor {}...", actor_id);
-                // Implementation would fetch the actor definition and re-spawn
+    pub fn with_intensity(self, max_restarts: u32, within: u64) -> Self {
+        Self { max_restarts, within, ..self }
+    }
+
+    /// Children start in the order they are added.
+    pub fn add_child(&mut self, spec: ChildSpec) {
+        self.children.push(Child::Actor(spec));
+    }
+
+    pub fn add_supervisor(&mut self, supervisor: Supervisor) {
+        self.children.push(Child::Supervisor(supervisor));
+    }
+
+    pub fn children(&self) -> &[Child] {
+        &self.children
+    }
+
+    /// A `supervise` block of `program`, whose children are its declared actors.
+    pub fn from_def(def: &SupervisorDef, program: &Program) -> Self {
+        let strategy = Strategy::parse(&def.strategy).unwrap_or(Strategy::OneForOne);
+        let mut supervisor = Self::new(def.name.clone(), strategy).with_intensity(def.max_restarts, def.within);
+        for child in &def.children {
+            match child {
+                SupervisedChild::Actor { name, args } => {
+                    let (program, actor) = (program.clone(), name.clone());
+                    let factory = move || -> Box<dyn Actor> {
+                        Box::new(crate::VMActor::declared(program.clone(), &actor).expect("supervised actors are declared"))
+                    };
+                    supervisor.add_child(ChildSpec::new(name.clone(), factory).with_args(args.clone()));
+                }
+                SupervisedChild::Supervisor(nested) => supervisor.add_supervisor(Self::from_def(nested, program)),
+            }
+        }
+        supervisor
+    }
+
+    /// Every top-level `supervise` block of `program`.
+    pub fn from_program(program: &Program) -> Vec<Self> {
+        program.supervisors().iter().map(|def| Self::from_def(def, program)).collect()
+    }
+
+    pub fn supervises(&self, actor: &str) -> bool {
+        self.children.iter().any(|child| child.supervises(actor))
+    }
+
+    /// Every supervised actor with the supervisor directly above it, in start order.
+    pub fn specs(&self) -> Vec<(&str, &ChildSpec)> {
+        self.children.iter().flat_map(|child| match child {
+            Child::Actor(spec) => vec![(self.name.as_str(), spec)],
+            Child::Supervisor(nested) => nested.specs(),
+        }).collect()
+    }
+
+    /// Recent restart times of this supervisor and those under it.
+    pub(crate) fn histories(&self) -> Vec<(String, Vec<u64>)> {
+        let mut all = vec![(self.name.clone(), self.restarts.iter().copied().collect())];
+        for child in &self.children {
+            if let Child::Supervisor(nested) = child {
+                all.extend(nested.histories());
             }
-            Strategy::AllForOne => {
-                println!("  Restarting all child actors...");
+        }
+        all
+    }
+
+    pub(crate) fn set_history(&mut self, name: &str, restarts: &[u64]) {
+        if self.name == name {
+            self.restarts = restarts.iter().copied().collect();
+        }
+        for child in &mut self.children {
+            if let Child::Supervisor(nested) = child {
+                nested.set_history(name, restarts);
+            }
+        }
+    }
+
+    /// Decide what to do about `actor` failing at logical time `now`, or
+    /// `None` if it is not supervised here.
+    pub(crate) fn child_failed(&mut self, actor: &str, now: u64) -> Option<Outcome> {
+        let index = self.children.iter().position(|child| child.supervises(actor))?;
+        if let Child::Supervisor(nested) = &mut self.children[index] {
+            match nested.child_failed(actor, now)? {
+                // The nested supervisor gave up, so it is the child that failed
+                Outcome::Escalate => {}
+                restart => return Some(restart),
+            }
+        }
+        Some(self.restart_from(index, now))
+    }
+
+    fn restart_from(&mut self, index: usize, now: u64) -> Outcome {
+        while self.restarts.front().is_some_and(|&t| t + self.within <= now) {
+            self.restarts.pop_front();
+        }
+        if self.restarts.len() >= self.max_restarts as usize {
+            return Outcome::Escalate;
+        }
+        self.restarts.push_back(now);
+        let range = match self.strategy {
+            Strategy::OneForOne => index..index + 1,
+            Strategy::AllForOne => 0..self.children.len(),
+            Strategy::RestForOne => index..self.children.len(),
+        };
+        let mut restarts = Vec::new();
+        for child in &mut self.children[range] {
+            match child {
+                Child::Actor(spec) => restarts.push(Restart { supervisor: self.name.clone(), child: spec.clone() }),
+                Child::Supervisor(nested) => {
+                    nested.reset();
+                    restarts.extend(nested.specs().into_iter().map(|(supervisor, spec)| Restart { supervisor: supervisor.to_string(), child: spec.clone() }));
+                }
+            }
+        }
+        Outcome::Restart(restarts)
+    }
+
+    /// Forget restart history, as a freshly started supervisor would.
+    fn reset(&mut self) {
+        self.restarts.clear();
+        for child in &mut self.children {
+            if let Child::Supervisor(nested) = child {
+                nested.reset();
             }
         }
     }
@@ -0,0 +1,95 @@
+// AeroFlow Runtime - Timeline Scenarios
+// `render timeline { ... }` as an executable causal event graph
+
+use crate::mailbox::MessageBus;
+use aeroflow_compiler::ir::{TimelineEntry, Value};
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// Happened-before graph emitted by `render timeline { ... }`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TimelineGraph {
+    pub nodes: Vec<TimelineNode>,
+    pub edges: Vec<TimelineEdge>,
+}
+
+/// A single declared message; `id` is its position in the source block.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimelineNode {
+    pub id: usize,
+    pub from: String,
+    pub to: String,
+    pub at: u64,
+    pub payload: Value,
+}
+
+/// `from` happened before `to` (indices into `TimelineGraph::nodes`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimelineEdge {
+    pub from: usize,
+    pub to: usize,
+}
+
+impl TimelineGraph {
+    /// Build the DAG for a timeline block. Messages are delivered instantly at
+    /// their tick, so each one is an event on both its sender and its receiver;
+    /// edges link every event to the previous event on each of those nodes,
+    /// with ties at the same tick broken by declaration order.
+    pub fn build(entries: &[TimelineEntry], payloads: Vec<Value>) -> Self {
+        let nodes: Vec<TimelineNode> = entries.iter().zip(payloads).enumerate()
+            .map(|(id, (entry, payload))| TimelineNode {
+                id,
+                from: entry.from.clone(),
+                to: entry.to.clone(),
+                at: entry.at,
+                payload,
+            })
+            .collect();
+
+        let mut order: Vec<usize> = (0..nodes.len()).collect();
+        order.sort_by_key(|&i| (nodes[i].at, i));
+
+        let mut last_on_node: HashMap<&str, usize> = HashMap::new();
+        let mut edges = Vec::new();
+        for &i in &order {
+            let node = &nodes[i];
+            let mut preds: Vec<usize> = [node.from.as_str(), node.to.as_str()].iter()
+                .filter_map(|n| last_on_node.get(n).copied())
+                .collect();
+            preds.dedup();
+            edges.extend(preds.into_iter().map(|p| TimelineEdge { from: p, to: i }));
+            last_on_node.insert(&node.from, i);
+            last_on_node.insert(&node.to, i);
+        }
+
+        Self { nodes, edges }
+    }
+
+    /// Send every declared message through `bus` at its logical tick.
+    pub fn schedule(&self, bus: &dyn MessageBus) {
+        let mut order: Vec<&TimelineNode> = self.nodes.iter().collect();
+        order.sort_by_key(|n| (n.at, n.id));
+        for node in order {
+            bus.send_at(&node.from, &node.to, node.payload.clone().into(), node.at);
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_else(|_| "{}".to_string())
+    }
+
+    /// Graphviz rendering; one vertex per message, edges point forward in time.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph timeline {\n  rankdir=LR;\n");
+        for node in &self.nodes {
+            let label = format!("{} -> {} @{}\\n{}", node.from, node.to, node.at, node.payload);
+            let _ = writeln!(dot, "  e{} [label=\"{}\"];", node.id, label.replace('"', "\\\""));
+        }
+        for edge in &self.edges {
+            let _ = writeln!(dot, "  e{} -> e{};", edge.from, edge.to);
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
@@ -1,10 +1,27 @@
 // AeroFlow Runtime - Event Tracing & Time-Travel Debugging
 // Recording the deterministic path of execution
 
-use crate::mailbox::Message;
+use crate::mailbox::{Message, MessageData};
 use crate::actor::ActorId;
+use crate::capability::Denied;
+use anyhow::{bail, Context as _};
+use parking_lot::{Mutex, RwLock};
 use serde::{Serialize, Deserialize};
-use std::sync::Mutex;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Magic prefix of the compact binary trace format (`.aft`).
+pub const AFT_MAGIC: [u8; 4] = *b"AFT1";
+/// Longest `.aft` record `TraceReader` accepts; a larger length prefix is
+/// taken as corruption rather than allocated.
+pub const MAX_RECORD_LEN: usize = 16 << 20;
+/// Events the global tracer keeps in memory.
+pub const DEFAULT_CAPACITY: usize = 10_000;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TraceEvent {
@@ -12,25 +29,317 @@ pub struct TraceEvent {
     pub actor_id: ActorId,
     pub input: Message,
     pub state_snapshot: String, // JSON representation of state
+    /// Instructions the handler ran.
+    #[serde(default)]
+    pub instructions: u64,
+    /// Sequence ids of the messages the handler sent.
+    #[serde(default)]
+    pub sent: Vec<u64>,
+}
+
+impl TraceEvent {
+    /// `actor_id` was refused an operation at `logical_time`: a
+    /// `CapabilityDenied` event whose input carries the operation and the
+    /// names of the capabilities it required.
+    pub fn denied(actor_id: &str, logical_time: u64, denied: &Denied, state_snapshot: String) -> Self {
+        let input = serde_json::json!(["CapabilityDenied", denied.operation, denied.required.names()]);
+        Self {
+            logical_time,
+            actor_id: actor_id.to_string(),
+            input: Message::new(actor_id.to_string(), MessageData::Json(input.to_string()), logical_time, 0),
+            state_snapshot,
+            instructions: 0,
+            sent: Vec::new(),
+        }
+    }
+
+    /// The delivered message's event name (e.g. `Add`, `start`).
+    pub fn kind(&self) -> String {
+        kind(&self.input)
+    }
+}
+
+fn kind(message: &Message) -> String {
+    // The runtime starts every actor with an unnamed event
+    match message.event().0 {
+        event if event.is_empty() => "start".to_string(),
+        event => event,
+    }
+}
+
+/// Where traced events go.
+pub trait TraceSink: Send + Sync {
+    fn write(&self, event: &TraceEvent);
+
+    /// Push buffered events out, reporting the first error seen since the
+    /// last flush.
+    fn flush(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Keeps the most recent `capacity` events, dropping the oldest.
+pub struct RingBuffer {
+    capacity: usize,
+    events: Mutex<VecDeque<TraceEvent>>,
+    dropped: AtomicU64,
+}
+
+impl RingBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, events: Mutex::new(VecDeque::with_capacity(capacity.min(DEFAULT_CAPACITY))), dropped: AtomicU64::new(0) }
+    }
+
+    /// Buffered events, oldest first.
+    pub fn events(&self) -> Vec<TraceEvent> {
+        self.events.lock().iter().cloned().collect()
+    }
+
+    /// Events pushed out to make room.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    pub fn clear(&self) {
+        self.events.lock().clear();
+    }
+}
+
+impl TraceSink for RingBuffer {
+    fn write(&self, event: &TraceEvent) {
+        if self.capacity == 0 {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        let mut events = self.events.lock();
+        if events.len() == self.capacity {
+            events.pop_front();
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        events.push_back(event.clone());
+    }
+}
+
+/// A buffered file writer that remembers its first error for `flush`.
+pub(crate) struct FileOut {
+    out: BufWriter<File>,
+    error: Option<io::Error>,
+}
+
+impl FileOut {
+    pub(crate) fn create(path: &Path) -> io::Result<Self> {
+        Ok(Self { out: BufWriter::new(File::create(path)?), error: None })
+    }
+
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        if self.error.is_none() {
+            if let Err(e) = self.out.write_all(bytes) {
+                self.error = Some(e);
+            }
+        }
+    }
+
+    pub(crate) fn flush(&mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => self.out.flush(),
+        }
+    }
+}
+
+/// One JSON object per line (`.jsonl`).
+pub struct JsonLinesSink(Mutex<FileOut>);
+
+impl JsonLinesSink {
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self(Mutex::new(FileOut::create(path.as_ref())?)))
+    }
+}
+
+impl TraceSink for JsonLinesSink {
+    fn write(&self, event: &TraceEvent) {
+        let mut line = serde_json::to_vec(event).expect("trace events serialize");
+        line.push(b'\n');
+        self.0.lock().write(&line);
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.0.lock().flush()
+    }
+}
+
+/// `AFT_MAGIC`, then each event as a little-endian `u32` length and its
+/// bincode encoding (`.aft`).
+pub struct BinarySink(Mutex<FileOut>);
+
+impl BinarySink {
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut out = FileOut::create(path.as_ref())?;
+        out.write(&AFT_MAGIC);
+        Ok(Self(Mutex::new(out)))
+    }
+}
+
+impl TraceSink for BinarySink {
+    fn write(&self, event: &TraceEvent) {
+        let body = bincode::serialize(event).expect("trace events serialize");
+        let mut out = self.0.lock();
+        out.write(&(body.len() as u32).to_le_bytes());
+        out.write(&body);
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.0.lock().flush()
+    }
+}
+
+/// A file sink picked by extension: `.aft` is binary, anything else JSON Lines.
+pub fn open_sink(path: impl AsRef<Path>) -> io::Result<Arc<dyn TraceSink>> {
+    let path = path.as_ref();
+    Ok(if path.extension().and_then(|e| e.to_str()) == Some("aft") {
+        Arc::new(BinarySink::create(path)?)
+    } else {
+        Arc::new(JsonLinesSink::create(path)?)
+    })
+}
+
+/// Which events to keep; empty sets and open bounds match everything.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TraceFilter {
+    pub actors: BTreeSet<String>,
+    pub messages: BTreeSet<String>,
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+}
+
+impl TraceFilter {
+    pub fn matches(&self, actor: &str, input: &Message) -> bool {
+        (self.actors.is_empty() || self.actors.contains(actor))
+            && self.from.is_none_or(|t| input.logical_time >= t)
+            && self.to.is_none_or(|t| input.logical_time <= t)
+            && (self.messages.is_empty() || self.messages.contains(&kind(input)))
+    }
+
+    pub fn matches_event(&self, event: &TraceEvent) -> bool {
+        self.matches(&event.actor_id, &event.input)
+    }
 }
 
 pub struct Tracer {
-    events: Mutex<Vec<TraceEvent>>,
+    buffer: RingBuffer,
+    sinks: RwLock<Vec<Arc<dyn TraceSink>>>,
+    filter: RwLock<TraceFilter>,
+    /// Keep one in this many matching events.
+    sample: AtomicU64,
+    matched: AtomicU64,
+}
+
+impl Default for Tracer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Tracer {
     pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+
+    /// A tracer whose in-memory buffer keeps the last `capacity` events.
+    pub fn with_capacity(capacity: usize) -> Self {
         Self {
-            events: Mutex::new(Vec::with_capacity(10000)),
+            buffer: RingBuffer::new(capacity),
+            sinks: RwLock::new(Vec::new()),
+            filter: RwLock::new(TraceFilter::default()),
+            sample: AtomicU64::new(1),
+            matched: AtomicU64::new(0),
+        }
+    }
+
+    /// Also send events to `sink`.
+    pub fn add_sink(&self, sink: Arc<dyn TraceSink>) {
+        self.sinks.write().push(sink);
+    }
+
+    pub fn clear_sinks(&self) {
+        self.sinks.write().clear();
+    }
+
+    pub fn set_filter(&self, filter: TraceFilter) {
+        *self.filter.write() = filter;
+    }
+
+    /// Keep every `n`th matching event (1 keeps them all). Counting rather
+    /// than chance keeps traces of deterministic runs identical.
+    pub fn set_sampling(&self, n: u64) {
+        self.sample.store(n.max(1), Ordering::Relaxed);
+    }
+
+    /// Whether the filter and sampling keep this delivery. Sampling counts
+    /// calls, so deliveries must be admitted in delivery order.
+    pub fn admit(&self, actor_id: &str, input: &Message) -> bool {
+        self.filter.read().matches(actor_id, input)
+            && self.matched.fetch_add(1, Ordering::Relaxed).is_multiple_of(self.sample.load(Ordering::Relaxed))
+    }
+
+    /// Start tracing a delivery, snapshotting the receiver's state only if
+    /// it is admitted. Hand the event to [`Tracer::finish`] once the handler
+    /// has run.
+    pub fn begin(&self, actor_id: &str, input: &Message, state: impl FnOnce() -> String) -> Option<TraceEvent> {
+        self.admit(actor_id, input).then(|| TraceEvent {
+            logical_time: input.logical_time,
+            actor_id: actor_id.to_string(),
+            input: input.clone(),
+            state_snapshot: state(),
+            instructions: 0,
+            sent: Vec::new(),
+        })
+    }
+
+    pub fn finish(&self, event: TraceEvent) {
+        self.buffer.write(&event);
+        for sink in self.sinks.read().iter() {
+            sink.write(&event);
+        }
+    }
+
+    /// Trace a delivery whose handler's effects are not tracked.
+    pub fn record_with(&self, actor_id: &str, input: &Message, state: impl FnOnce() -> String) {
+        if let Some(event) = self.begin(actor_id, input, state) {
+            self.finish(event);
+        }
+    }
+
+    /// Trace `event` if the filter keeps it, whatever the sampling. For
+    /// rare events that must not shift which deliveries are sampled.
+    pub fn record_unsampled(&self, event: TraceEvent) {
+        if self.filter.read().matches_event(&event) {
+            self.finish(event);
         }
     }
 
     pub fn record(&self, event: TraceEvent) {
-        self.events.lock().unwrap().push(event);
+        if self.admit(&event.actor_id, &event.input) {
+            self.finish(event);
+        }
+    }
+
+    /// The in-memory buffer's events, oldest first.
+    pub fn events(&self) -> Vec<TraceEvent> {
+        self.buffer.events()
+    }
+
+    /// Events the in-memory buffer has dropped.
+    pub fn dropped(&self) -> u64 {
+        self.buffer.dropped()
+    }
+
+    pub fn flush(&self) -> io::Result<()> {
+        self.sinks.read().iter().try_for_each(|sink| sink.flush())
     }
 
     pub fn export_json(&self) -> String {
-        serde_json::to_string_pretty(&*self.events.lock().unwrap()).unwrap()
+        serde_json::to_string_pretty(&self.buffer.events()).unwrap()
     }
 }
 
@@ -39,3 +348,114 @@ pub static GLOBAL_TRACER: std::sync::OnceLock<Tracer> = std::sync::OnceLock::new
 pub fn get_tracer() -> &'static Tracer {
     GLOBAL_TRACER.get_or_init(Tracer::new)
 }
+
+/// Streams the events of a saved trace: `.aft` binary, JSON Lines, or the
+/// JSON array `run --log` writes.
+pub struct TraceReader {
+    input: BufReader<Box<dyn Read>>,
+    binary: bool,
+    array: Option<std::vec::IntoIter<TraceEvent>>,
+}
+
+impl TraceReader {
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).with_context(|| format!("cannot open trace {}", path.display()))?;
+        Self::new(Box::new(file))
+    }
+
+    pub fn new(input: Box<dyn Read>) -> anyhow::Result<Self> {
+        let mut input = BufReader::new(input);
+        let head = input.fill_buf()?;
+        let binary = head.starts_with(&AFT_MAGIC);
+        let mut array = None;
+        if binary {
+            input.consume(AFT_MAGIC.len());
+        } else if head.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'[') {
+            let events: Vec<TraceEvent> = serde_json::from_reader(&mut input).context("corrupt JSON trace")?;
+            array = Some(events.into_iter());
+        }
+        Ok(Self { input, binary, array })
+    }
+
+    fn next_binary(&mut self) -> anyhow::Result<Option<TraceEvent>> {
+        // End of input is only clean between records
+        if self.input.fill_buf()?.is_empty() {
+            return Ok(None);
+        }
+        let mut len = [0; 4];
+        self.input.read_exact(&mut len).context("truncated .aft trace")?;
+        let len = u32::from_le_bytes(len) as usize;
+        if len > MAX_RECORD_LEN {
+            bail!("corrupt .aft trace: {}-byte record exceeds {} bytes", len, MAX_RECORD_LEN);
+        }
+        let mut body = vec![0; len];
+        self.input.read_exact(&mut body).context("truncated .aft trace")?;
+        Ok(Some(bincode::deserialize(&body).context("corrupt .aft trace")?))
+    }
+
+    fn next_line(&mut self) -> anyhow::Result<Option<TraceEvent>> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.input.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            if !line.trim().is_empty() {
+                return Ok(Some(serde_json::from_str(&line).context("corrupt JSON Lines trace")?));
+            }
+        }
+    }
+}
+
+impl Iterator for TraceReader {
+    type Item = anyhow::Result<TraceEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(array) = &mut self.array {
+            return array.next().map(Ok);
+        }
+        let next = if self.binary { self.next_binary() } else { self.next_line() };
+        next.transpose()
+    }
+}
+
+/// Counts over a set of trace events.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TraceSummary {
+    pub events: u64,
+    pub first_time: Option<u64>,
+    pub last_time: Option<u64>,
+    pub by_actor: BTreeMap<String, u64>,
+    pub by_message: BTreeMap<String, u64>,
+}
+
+impl TraceSummary {
+    pub fn add(&mut self, event: &TraceEvent) {
+        self.events += 1;
+        self.first_time = Some(self.first_time.map_or(event.logical_time, |t| t.min(event.logical_time)));
+        self.last_time = Some(self.last_time.map_or(event.logical_time, |t| t.max(event.logical_time)));
+        *self.by_actor.entry(event.actor_id.clone()).or_default() += 1;
+        *self.by_message.entry(event.kind()).or_default() += 1;
+    }
+}
+
+impl fmt::Display for TraceSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} event(s)", self.events)?;
+        if let (Some(first), Some(last)) = (self.first_time, self.last_time) {
+            write!(f, ", T={}..{}", first, last)?;
+        }
+        writeln!(f)?;
+        for (title, counts) in [("By actor", &self.by_actor), ("By message", &self.by_message)] {
+            if counts.is_empty() {
+                continue;
+            }
+            writeln!(f, "{}:", title)?;
+            for (name, count) in counts {
+                writeln!(f, "  {:<24} {}", name, count)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,273 @@
+// AeroFlow Runtime - Terminal UI
+// Keyboard-driven rendering of `screen` widget trees into a character buffer
+
+use crate::actor::ActorCell;
+use crate::render::{MemorySink, RenderEvent};
+use crate::scheduler::Scheduler;
+use crate::ui::{self, ScreenActor, UiEvent, UiNode};
+use aeroflow_compiler::ir::Chunk;
+use anyhow::anyhow;
+use std::fmt;
+use std::sync::Arc;
+
+/// Terminal-independent key presses; the CLI maps crossterm events onto these.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    Backspace,
+    Enter,
+    Tab,
+    BackTab,
+    Up,
+    Down,
+    Esc,
+}
+
+impl Key {
+    /// Parse a scripted input stream: plain characters are typed as-is and
+    /// `<tab>`, `<backtab>`, `<enter>`, `<bs>`, `<up>`, `<down>`, `<esc>` name keys.
+    pub fn script(input: &str) -> Vec<Key> {
+        let mut keys = Vec::new();
+        let mut rest = input;
+        while let Some(c) = rest.chars().next() {
+            let named = rest.strip_prefix('<').and_then(|r| r.split_once('>')).and_then(|(name, after)| {
+                let key = match name {
+                    "tab" => Key::Tab,
+                    "backtab" => Key::BackTab,
+                    "enter" => Key::Enter,
+                    "bs" => Key::Backspace,
+                    "up" => Key::Up,
+                    "down" => Key::Down,
+                    "esc" => Key::Esc,
+                    _ => return None,
+                };
+                Some((key, after))
+            });
+            match named {
+                Some((key, after)) => {
+                    keys.push(key);
+                    rest = after;
+                }
+                None => {
+                    keys.push(Key::Char(c));
+                    rest = &rest[c.len_utf8()..];
+                }
+            }
+        }
+        keys
+    }
+}
+
+/// Fixed-size grid of characters, one `String` per row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScreenBuffer {
+    pub width: usize,
+    pub height: usize,
+    pub lines: Vec<String>,
+}
+
+impl ScreenBuffer {
+    fn new(width: usize, height: usize, content: Vec<String>) -> Self {
+        let lines = content.into_iter()
+            .chain(std::iter::repeat(String::new()))
+            .take(height)
+            .map(|l| {
+                let mut line: String = l.chars().take(width).collect();
+                let len = line.chars().count();
+                line.extend(std::iter::repeat_n(' ', width - len));
+                line
+            })
+            .collect();
+        Self { width, height, lines }
+    }
+}
+
+/// Rows with trailing blanks trimmed, for snapshots and logs.
+impl fmt::Display for ScreenBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows: Vec<&str> = self.lines.iter().map(|l| l.trim_end()).collect();
+        let used = rows.iter().rposition(|l| !l.is_empty()).map_or(0, |i| i + 1);
+        write!(f, "{}", rows[..used].join("\n"))
+    }
+}
+
+/// A mounted screen plus keyboard focus. Key presses become [`UiEvent`]s
+/// delivered to the screen actor; the resulting patches update the local tree.
+pub struct TuiSession {
+    scheduler: Scheduler,
+    sink: Arc<MemorySink>,
+    screen: String,
+    tree: Vec<UiNode>,
+    output: Vec<String>,
+    focus: usize,
+    width: usize,
+    height: usize,
+}
+
+impl TuiSession {
+    pub fn mount(chunk: &Chunk, screen: &str, width: usize, height: usize) -> anyhow::Result<Self> {
+        let actor = ScreenActor::new(chunk.clone(), screen)
+            .ok_or_else(|| anyhow!("unknown screen '{}'", screen))?;
+        let scheduler = Scheduler::new();
+        let sink = Arc::new(MemorySink::new());
+        scheduler.set_render_sink(sink.clone());
+        scheduler.spawn(ActorCell::new(screen.to_string(), Box::new(actor)));
+
+        let mut session = Self {
+            scheduler,
+            sink,
+            screen: screen.to_string(),
+            tree: Vec::new(),
+            output: Vec::new(),
+            focus: 0,
+            width,
+            height,
+        };
+        session.dispatch(UiEvent::Mount);
+        Ok(session)
+    }
+
+    pub fn tree(&self) -> &[UiNode] {
+        &self.tree
+    }
+
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+    }
+
+    /// Handle one key press. Returns `false` once the user asked to quit.
+    pub fn press(&mut self, key: Key) -> bool {
+        let focusables = focusable_paths(&self.tree);
+        let focused = focusables.get(self.focus).and_then(|p| node_at(&self.tree, p)).cloned();
+        match (key, focused) {
+            (Key::Esc, _) => return false,
+            (Key::Tab | Key::Down, _) if !focusables.is_empty() => {
+                self.focus = (self.focus + 1) % focusables.len();
+            }
+            (Key::BackTab | Key::Up, _) if !focusables.is_empty() => {
+                self.focus = (self.focus + focusables.len() - 1) % focusables.len();
+            }
+            (Key::Char(c), Some(UiNode::Input { bind, mut value })) => {
+                value.push(c);
+                self.dispatch(UiEvent::Input { bind, value });
+            }
+            (Key::Backspace, Some(UiNode::Input { bind, mut value })) => {
+                value.pop();
+                self.dispatch(UiEvent::Input { bind, value });
+            }
+            (Key::Enter, Some(UiNode::Button { action, args, .. })) => {
+                self.dispatch(UiEvent::Click { action, args });
+            }
+            (Key::Enter | Key::Char(' '), Some(UiNode::Checkbox { bind, checked, .. })) => {
+                self.dispatch(UiEvent::Toggle { bind, checked: !checked });
+            }
+            _ => {}
+        }
+        true
+    }
+
+    /// Draw the current tree: a title row, the widgets, then any `render` output.
+    pub fn render(&self) -> ScreenBuffer {
+        let focused = focusable_paths(&self.tree).get(self.focus).cloned();
+        let mut lines = vec![format!("── {} ──", self.screen)];
+        let mut path = Vec::new();
+        for (i, node) in self.tree.iter().enumerate() {
+            path.push(i);
+            draw_block(node, &mut path, focused.as_deref(), &mut lines);
+            path.pop();
+        }
+        if !self.output.is_empty() {
+            lines.push(String::new());
+            lines.extend(self.output.iter().cloned());
+        }
+        ScreenBuffer::new(self.width, self.height, lines)
+    }
+
+    fn dispatch(&mut self, event: UiEvent) {
+        self.scheduler.send(self.screen.clone(), event.into(), "tui".to_string());
+        while self.scheduler.step() {}
+        for event in self.sink.take() {
+            match event {
+                RenderEvent::Ui(tree) if tree.screen.as_deref() == Some(self.screen.as_str()) => {
+                    ui::apply(&mut self.tree, &tree.patches);
+                }
+                RenderEvent::Value(v) => self.output.push(v.to_string()),
+                _ => {}
+            }
+        }
+        let count = focusable_paths(&self.tree).len();
+        self.focus = self.focus.min(count.saturating_sub(1));
+    }
+}
+
+fn focusable_paths(tree: &[UiNode]) -> Vec<Vec<usize>> {
+    fn walk(nodes: &[UiNode], path: &mut Vec<usize>, out: &mut Vec<Vec<usize>>) {
+        for (i, node) in nodes.iter().enumerate() {
+            path.push(i);
+            match node {
+                UiNode::Input { .. } | UiNode::Button { .. } | UiNode::Checkbox { .. } => out.push(path.clone()),
+                UiNode::Column { children } | UiNode::Row { children } => walk(children, path, out),
+                _ => {}
+            }
+            path.pop();
+        }
+    }
+    let mut out = Vec::new();
+    walk(tree, &mut Vec::new(), &mut out);
+    out
+}
+
+fn node_at<'a>(tree: &'a [UiNode], path: &[usize]) -> Option<&'a UiNode> {
+    let (&first, rest) = path.split_first()?;
+    let node = tree.get(first)?;
+    if rest.is_empty() {
+        Some(node)
+    } else {
+        node_at(node.children()?, rest)
+    }
+}
+
+/// Columns stack children vertically; everything else occupies one row.
+fn draw_block(node: &UiNode, path: &mut Vec<usize>, focused: Option<&[usize]>, lines: &mut Vec<String>) {
+    match node {
+        UiNode::Column { children } => {
+            for (i, child) in children.iter().enumerate() {
+                path.push(i);
+                draw_block(child, path, focused, lines);
+                path.pop();
+            }
+        }
+        UiNode::List { children } => {
+            for child in children {
+                lines.push(format!("• {}", draw_inline(child, path, None)));
+            }
+        }
+        _ => lines.push(draw_inline(node, path, focused)),
+    }
+}
+
+/// Focusable widgets carry a one-character gutter: `>` when focused.
+fn draw_inline(node: &UiNode, path: &mut Vec<usize>, focused: Option<&[usize]>) -> String {
+    let mark = if focused == Some(path.as_slice()) { '>' } else { ' ' };
+    match node {
+        UiNode::Text { text } => text.clone(),
+        UiNode::Input { bind, value } => {
+            let cursor = if mark == '>' { "_" } else { "" };
+            format!("{}{}: [{}{}]", mark, bind, value, cursor)
+        }
+        UiNode::Button { label, .. } => format!("{}[ {} ]", mark, label),
+        UiNode::Checkbox { label, checked, .. } => format!("{}[{}] {}", mark, if *checked { 'x' } else { ' ' }, label),
+        UiNode::Image { src } => format!("[image: {}]", src),
+        UiNode::Screen { name } => format!("[screen: {}]", name),
+        UiNode::Column { children } | UiNode::Row { children } | UiNode::List { children } => {
+            let mut parts = Vec::new();
+            for (i, child) in children.iter().enumerate() {
+                path.push(i);
+                parts.push(draw_inline(child, path, focused));
+                path.pop();
+            }
+            parts.join(" ")
+        }
+    }
+}
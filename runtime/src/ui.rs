@@ -0,0 +1,323 @@
+// AeroFlow Runtime - Declarative UI
+// Widget trees, two-way bindings and minimal diffs for `screen` blocks
+
+use crate::actor::{Actor, Context};
+use crate::capability::CapabilityGuard;
+use crate::mailbox::{Message, MessageData};
+use crate::render::{MemorySink, RenderEvent};
+use crate::snapshot::VmState;
+use crate::vm::{VMContext, VM};
+use aeroflow_compiler::ir::{Chunk, ScreenDef, UiTemplate, Value};
+use serde::{Serialize, Deserialize};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum UiNode {
+    Text { text: String },
+    Input { bind: String, value: String },
+    Button { label: String, action: String, args: Vec<Value> },
+    Column { children: Vec<UiNode> },
+    Row { children: Vec<UiNode> },
+    List { children: Vec<UiNode> },
+    Image { src: String },
+    Checkbox { label: String, bind: String, checked: bool },
+    /// Another screen, mounted and rendered by its own actor.
+    Screen { name: String },
+}
+
+impl UiNode {
+    pub fn children(&self) -> Option<&Vec<UiNode>> {
+        match self {
+            UiNode::Column { children } | UiNode::Row { children } | UiNode::List { children } => Some(children),
+            _ => None,
+        }
+    }
+
+    fn children_mut(&mut self) -> Option<&mut Vec<UiNode>> {
+        match self {
+            UiNode::Column { children } | UiNode::Row { children } | UiNode::List { children } => Some(children),
+            _ => None,
+        }
+    }
+
+    /// Containers of the same kind are diffed child by child instead of replaced.
+    fn same_container(&self, other: &UiNode) -> bool {
+        matches!((self, other),
+            (UiNode::Column { .. }, UiNode::Column { .. }) |
+            (UiNode::Row { .. }, UiNode::Row { .. }) |
+            (UiNode::List { .. }, UiNode::List { .. }))
+    }
+}
+
+/// One edit to a rendered tree. `path` indexes root widgets, then children.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum UiPatch {
+    Replace { path: Vec<usize>, node: UiNode },
+    Insert { path: Vec<usize>, node: UiNode },
+    Remove { path: Vec<usize> },
+}
+
+/// Widget tree dispatched by `render { Text/Input/Button/... }`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UiTree {
+    pub screen: Option<String>,
+    pub root: Vec<UiNode>,
+    /// Changes since the screen's previous render; empty outside mounted screens.
+    pub patches: Vec<UiPatch>,
+}
+
+/// User interaction delivered to a screen actor as a message.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum UiEvent {
+    Mount,
+    Click { action: String, args: Vec<Value> },
+    Input { bind: String, value: String },
+    Toggle { bind: String, checked: bool },
+}
+
+impl From<UiEvent> for MessageData {
+    fn from(event: UiEvent) -> Self {
+        MessageData::Json(serde_json::to_string(&event).unwrap_or_default())
+    }
+}
+
+impl UiEvent {
+    pub fn from_message(data: &MessageData) -> Option<Self> {
+        match data {
+            MessageData::Json(s) | MessageData::Text(s) => serde_json::from_str(s).ok(),
+            _ => None,
+        }
+    }
+}
+
+/// Instantiate templates with the values `RenderUI` popped, in pre-order.
+pub fn build(templates: &[UiTemplate], values: &mut impl Iterator<Item = Value>) -> Vec<UiNode> {
+    templates.iter().map(|t| build_node(t, values)).collect()
+}
+
+fn build_node(template: &UiTemplate, values: &mut impl Iterator<Item = Value>) -> UiNode {
+    let mut next = || values.next().unwrap_or(Value::Nil);
+    match template {
+        UiTemplate::Text => UiNode::Text { text: next().to_string() },
+        UiTemplate::Input { bind } => UiNode::Input {
+            bind: bind.clone(),
+            value: match next() {
+                Value::Nil => String::new(),
+                v => v.to_string(),
+            },
+        },
+        UiTemplate::Button { label, action, argc } => UiNode::Button {
+            label: label.clone(),
+            action: action.clone(),
+            args: (0..*argc).map(|_| next()).collect(),
+        },
+        UiTemplate::Column(children) => UiNode::Column { children: build(children, values) },
+        UiTemplate::Row(children) => UiNode::Row { children: build(children, values) },
+        UiTemplate::List => UiNode::List {
+            children: match next() {
+                Value::List(items) => items.into_iter().map(|i| UiNode::Text { text: i.to_string() }).collect(),
+                Value::Nil => Vec::new(),
+                single => vec![UiNode::Text { text: single.to_string() }],
+            },
+        },
+        UiTemplate::Image => UiNode::Image { src: next().to_string() },
+        UiTemplate::Checkbox { label, bind } => UiNode::Checkbox {
+            label: label.clone(),
+            bind: bind.clone(),
+            checked: matches!(next(), Value::Bool(true)),
+        },
+        UiTemplate::Screen { name } => UiNode::Screen { name: name.clone() },
+    }
+}
+
+/// Whether a button in `nodes` triggers `action`.
+fn offers(nodes: &[UiNode], action: &str) -> bool {
+    nodes.iter().any(|node| match node {
+        UiNode::Button { action: a, .. } => a == action,
+        other => other.children().is_some_and(|children| offers(children, action)),
+    })
+}
+
+/// Minimal edit script turning `old` into `new`.
+pub fn diff(old: &[UiNode], new: &[UiNode]) -> Vec<UiPatch> {
+    let mut patches = Vec::new();
+    diff_children(old, new, &mut Vec::new(), &mut patches);
+    patches
+}
+
+fn diff_children(old: &[UiNode], new: &[UiNode], path: &mut Vec<usize>, out: &mut Vec<UiPatch>) {
+    let common = old.len().min(new.len());
+    for i in 0..common {
+        path.push(i);
+        if old[i].same_container(&new[i]) {
+            diff_children(old[i].children().unwrap(), new[i].children().unwrap(), path, out);
+        } else if old[i] != new[i] {
+            out.push(UiPatch::Replace { path: path.clone(), node: new[i].clone() });
+        }
+        path.pop();
+    }
+    for (i, node) in new.iter().enumerate().skip(common) {
+        path.push(i);
+        out.push(UiPatch::Insert { path: path.clone(), node: node.clone() });
+        path.pop();
+    }
+    // Remove from the back so earlier indices stay valid
+    for i in (common..old.len()).rev() {
+        path.push(i);
+        out.push(UiPatch::Remove { path: path.clone() });
+        path.pop();
+    }
+}
+
+/// Apply patches produced by [`diff`], e.g. in a frontend mirroring the tree.
+pub fn apply(tree: &mut Vec<UiNode>, patches: &[UiPatch]) {
+    for patch in patches {
+        let path = match patch {
+            UiPatch::Replace { path, .. } | UiPatch::Insert { path, .. } | UiPatch::Remove { path } => path,
+        };
+        let Some((&last, parents)) = path.split_last() else { continue };
+        let mut siblings = &mut *tree;
+        for &i in parents {
+            match siblings.get_mut(i).and_then(UiNode::children_mut) {
+                Some(children) => siblings = children,
+                None => return,
+            }
+        }
+        match patch {
+            UiPatch::Replace { node, .. } => {
+                if let Some(slot) = siblings.get_mut(last) {
+                    *slot = node.clone();
+                }
+            }
+            UiPatch::Insert { node, .. } => siblings.insert(last.min(siblings.len()), node.clone()),
+            UiPatch::Remove { .. } => {
+                if last < siblings.len() {
+                    siblings.remove(last);
+                }
+            }
+        }
+    }
+}
+
+/// Hosts one `screen`: owns its state, applies [`UiEvent`]s and re-renders,
+/// emitting the new tree together with the patches since the last render.
+pub struct ScreenActor {
+    vm: VM,
+    chunk: Chunk,
+    screen: ScreenDef,
+    tree: Vec<UiNode>,
+    mounted: bool,
+}
+
+impl ScreenActor {
+    pub fn new(chunk: Chunk, screen: &str) -> Option<Self> {
+        let screen = chunk.screen(screen)?.clone();
+        Some(Self {
+            vm: VM::new(),
+            chunk,
+            screen,
+            tree: Vec::new(),
+            mounted: false,
+        })
+    }
+
+    pub fn vm_mut(&mut self) -> &mut VM {
+        &mut self.vm
+    }
+
+    pub fn tree(&self) -> &[UiNode] {
+        &self.tree
+    }
+
+    fn handle(&mut self, event: &UiEvent, msg: &Message, ctx: &Context) -> Result<bool, crate::VmError> {
+        let capture = Arc::new(MemorySink::new());
+        let mut vm_ctx = VMContext::for_actor(msg.logical_time, ctx);
+        vm_ctx.sink = capture.clone();
+
+        let first = !self.mounted;
+        if first {
+            // Declaring the state as globals first keeps `init` from binding frame locals
+            for name in &self.screen.state {
+                self.vm.set_global(name, Value::Nil);
+            }
+            self.vm.call(&self.chunk, &self.screen.init, Vec::new(), &vm_ctx)?;
+            self.mounted = true;
+        }
+        match event {
+            UiEvent::Mount => {}
+            UiEvent::Click { action, args } => {
+                self.vm.call(&self.chunk, action, args.clone(), &vm_ctx)?;
+            }
+            UiEvent::Input { bind, value } => self.vm.set_global(bind, Value::String(value.clone())),
+            UiEvent::Toggle { bind, checked } => self.vm.set_global(bind, Value::Bool(*checked)),
+        }
+
+        // Re-render, forwarding anything that is not part of this screen's tree
+        let before = capture.take();
+        self.vm.call(&self.chunk, &self.screen.render, Vec::new(), &vm_ctx)?;
+        let mut root = Vec::new();
+        for event in before.into_iter().chain(capture.take()) {
+            match event {
+                RenderEvent::Ui(tree) => root.extend(tree.root),
+                other => ctx.sink.emit(other),
+            }
+        }
+
+        let patches = diff(&self.tree, &root);
+        self.tree = root;
+        if first || !patches.is_empty() {
+            ctx.sink.emit(RenderEvent::Ui(UiTree {
+                screen: Some(self.screen.name.clone()),
+                root: self.tree.clone(),
+                patches,
+            }));
+        }
+        Ok(true)
+    }
+}
+
+impl Actor for ScreenActor {
+    fn receive(&mut self, msg: Message, ctx: &mut Context) {
+        let Some(event) = UiEvent::from_message(&msg.data) else {
+            ctx.failure = Some(format!("Screen '{}' cannot handle message {:?}", self.screen.name, msg.data));
+            return;
+        };
+        // Only the buttons on screen may be clicked, not any function in scope
+        if let UiEvent::Click { action, .. } = &event {
+            if !offers(&self.tree, action) {
+                ctx.failure = Some(format!("Screen '{}' has no button for '{}'", self.screen.name, action));
+                return;
+            }
+        }
+        if let Err(e) = self.handle(&event, &msg, ctx) {
+            crate::vm_actor::fail(e, ctx);
+        }
+    }
+
+    fn grant(&mut self, guard: &CapabilityGuard) {
+        self.vm.set_guard(guard.clone());
+    }
+
+    fn get_field(&self, field: &str) -> Option<Value> {
+        self.vm.get_globals().get(field).cloned()
+    }
+
+    fn get_state(&self) -> String {
+        serde_json::to_string(self.vm.get_globals()).unwrap_or_else(|_| "{}".to_string())
+    }
+
+    fn snapshot(&self) -> Option<Vec<u8>> {
+        bincode::serialize(&(self.mounted, &self.tree, self.vm.save_state())).ok()
+    }
+
+    fn restore(&mut self, state: &[u8]) -> anyhow::Result<()> {
+        let (mounted, tree, vm): (bool, Vec<UiNode>, VmState) = bincode::deserialize(state)?;
+        self.mounted = mounted;
+        self.tree = tree;
+        self.vm.restore_state(vm);
+        Ok(())
+    }
+}
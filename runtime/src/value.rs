@@ -0,0 +1,49 @@
+// AeroFlow Runtime - Value Conversion
+// Bridges AeroFlow values, serde types and JSON
+
+use aeroflow_compiler::ir::Value;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Convert any serializable Rust value into an AeroFlow [`Value`].
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> anyhow::Result<Value> {
+    Ok(from_json(serde_json::to_value(value)?))
+}
+
+/// Convert an AeroFlow [`Value`] into a Rust type.
+pub fn from_value<T: DeserializeOwned>(value: Value) -> anyhow::Result<T> {
+    Ok(serde_json::from_value(into_json(value))?)
+}
+
+pub fn from_json(json: serde_json::Value) -> Value {
+    match json {
+        serde_json::Value::Null => Value::Nil,
+        serde_json::Value::Bool(b) => Value::Bool(b),
+        serde_json::Value::Number(n) => Value::Number(n.as_f64().unwrap_or(f64::NAN)),
+        serde_json::Value::String(s) => Value::String(s),
+        serde_json::Value::Array(items) => Value::List(items.into_iter().map(from_json).collect()),
+        serde_json::Value::Object(entries) => {
+            Value::Map(entries.into_iter().map(|(k, v)| (k, from_json(v))).collect::<BTreeMap<_, _>>())
+        }
+    }
+}
+
+pub fn into_json(value: Value) -> serde_json::Value {
+    match value {
+        Value::Nil => serde_json::Value::Null,
+        Value::Bool(b) => serde_json::Value::Bool(b),
+        // Whole numbers round-trip as integers so they deserialize into i64/u32/...
+        Value::Number(n) if n.fract() == 0.0 && n.abs() < (1u64 << 53) as f64 => {
+            serde_json::Value::Number((n as i64).into())
+        }
+        Value::Number(n) => serde_json::Number::from_f64(n)
+            .map(serde_json::Value::Number)
+            .unwrap_or(serde_json::Value::Null),
+        Value::String(s) => serde_json::Value::String(s),
+        Value::List(items) => serde_json::Value::Array(items.into_iter().map(into_json).collect()),
+        Value::Map(entries) => {
+            serde_json::Value::Object(entries.into_iter().map(|(k, v)| (k, into_json(v))).collect())
+        }
+    }
+}
@@ -1,26 +1,210 @@
 // AeroFlow Runtime - VM
 // High performance bytecode execution
 
-use aeroflow_compiler::ir::{Instr, Chunk, Value};
-use std::collections::HashMap;
+use aeroflow_compiler::ir::{Code, Op, UiTemplate, Value};
+use crate::actor::{ActorId, Context};
+use crate::arena::{Arena, ArenaFull};
+use crate::quota::{value_size, QuotaExceeded, QuotaScope, Resource};
+use crate::capability::{Capabilities, CapabilityGuard, Denied};
+use crate::mailbox::{MessageBus, MessageData, Watch};
+use crate::state::{self, Running, StateSource};
+use crate::render::{RenderEvent, RenderSink, UiTree};
+use crate::replay::EnvSource;
+use crate::snapshot::{FrameState, VmState};
+use crate::timeline::TimelineGraph;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::Arc;
 
-pub struct VMContext {
+pub struct VMContext<'a> {
     pub logical_time: u64,
     pub rand_seed: u64,
+    pub sink: Arc<dyn RenderSink>,
+    /// Actor this VM runs as; its own fields resolve from the VM's globals.
+    pub actor_id: Option<ActorId>,
+    pub state: Option<Arc<dyn StateSource>>,
+    /// Delivery for the `send` builtin when running under a scheduler.
+    pub bus: Option<Arc<dyn MessageBus>>,
+    /// Source of `env()` reads; the process environment when unset.
+    pub env: Option<Arc<dyn EnvSource>>,
+    /// Called before every instruction when a debugger is attached.
+    pub debug: Option<Arc<dyn DebugHook>>,
+    /// Scratch memory for the message being handled; temporaries are built
+    /// here so a handler stays within the actor's memory limit.
+    pub arena: Option<&'a Arena>,
+}
+
+/// Observes execution one instruction at a time.
+pub trait DebugHook: Send + Sync {
+    /// Called before the instruction at `ip` runs; blocking here pauses the VM.
+    fn before(&self, ip: usize, vm: &VM, ctx: &VMContext);
+}
+
+impl<'a> VMContext<'a> {
+    pub fn new(logical_time: u64, sink: Arc<dyn RenderSink>) -> Self {
+        Self {
+            logical_time,
+            rand_seed: 0xDEADBEEF ^ logical_time,
+            sink,
+            actor_id: None,
+            state: None,
+            bus: None,
+            env: None,
+            debug: None,
+            arena: None,
+        }
+    }
+
+    /// Context for code running inside a scheduled actor.
+    pub fn for_actor(logical_time: u64, ctx: &'a Context) -> Self {
+        let mut vm_ctx = Self::new(logical_time, ctx.sink.clone());
+        vm_ctx.actor_id = Some(ctx.actor_id.clone());
+        vm_ctx.state = ctx.state.clone();
+        vm_ctx.bus = ctx.bus.clone();
+        vm_ctx.env = ctx.env.clone();
+        vm_ctx.debug = ctx.debug.clone();
+        vm_ctx.arena = Some(&ctx.arena);
+        vm_ctx
+    }
+}
+
+/// Host function callable from AeroFlow code by name.
+pub type NativeFn = Arc<dyn Fn(&[Value]) -> anyhow::Result<Value> + Send + Sync>;
+
+#[derive(Clone)]
+pub struct Native {
+    pub required: Capabilities,
+    pub func: NativeFn,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VmError {
+    UnknownFunction(String),
+    ArityMismatch { name: String, expected: usize, got: usize },
+    CapabilityDenied { name: String, required: Capabilities },
+    Host { name: String, message: String },
+    MemoryLimit(ArenaFull),
+    Quota(QuotaExceeded),
+}
+
+impl fmt::Display for VmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VmError::UnknownFunction(name) => write!(f, "Unknown function '{}'", name),
+            VmError::ArityMismatch { name, expected, got } => {
+                write!(f, "Function '{}' expects {} arguments, got {}", name, expected, got)
+            }
+            VmError::CapabilityDenied { name, required } => {
+                write!(f, "Security Violation: '{}' requires {}", name, required.names().join(" | "))
+            }
+            VmError::Host { name, message } => write!(f, "Host function '{}' failed: {}", name, message),
+            VmError::MemoryLimit(full) => write!(f, "Memory limit exceeded: {}", full),
+            VmError::Quota(exceeded) => write!(f, "{}", exceeded),
+        }
+    }
+}
+
+impl std::error::Error for VmError {}
+
+impl VmError {
+    /// The refused operation, if this is a [`VmError::CapabilityDenied`].
+    pub fn denied(&self) -> Option<Denied> {
+        match self {
+            VmError::CapabilityDenied { name, required } => Some(Denied { operation: name.clone(), required: *required }),
+            _ => None,
+        }
+    }
+}
+
+/// `a` followed by `b`, built in the actor's arena when there is one so the
+/// message's temporaries count against its memory limit. Stack values own
+/// their strings, so the result is copied out; the arena bounds a
+/// message's temporaries rather than holding the values the VM keeps.
+fn concat(mut a: String, b: &str, ctx: &VMContext) -> Result<String, VmError> {
+    match ctx.arena {
+        Some(arena) => arena.concat(&a, b).map(str::to_string).map_err(VmError::MemoryLimit),
+        None => {
+            a.push_str(b);
+            Ok(a)
+        }
+    }
+}
+
+/// Senders of timer messages are `timer#<id>`, the id being the VM's own.
+pub const TIMER_SENDER: &str = "timer#";
+
+/// The timer id of a message from `sender`, if a timer sent it.
+pub fn timer_id(sender: &str) -> Option<u64> {
+    sender.strip_prefix(TIMER_SENDER)?.parse().ok()
+}
+
+/// What a pending timer does when its message arrives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Timer {
+    /// `after`: deliver once.
+    Once,
+    /// `every`: deliver, then re-arm this many ticks later.
+    Every(u64),
+    /// Resume a `sleep`, or a `send` a full mailbox held back.
+    Wake,
+}
+
+/// A `sleep`, or a `send` waiting for a full mailbox, in progress,
+/// resumed by [`VM::resume`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Suspension {
+    /// Instruction after the `sleep` call.
+    pub ip: usize,
+    /// Frames below the suspended call, kept if it fails.
+    pub base: usize,
+    /// Operand stack height when the call began, restored if it fails.
+    pub height: usize,
+    /// Started by [`VM::call`], so the result is popped on return.
+    pub call: bool,
+}
+
+struct Frame {
+    /// Instruction to resume at in the caller; `None` returns control to the host.
+    return_ip: Option<usize>,
+    locals: HashMap<String, Value>,
 }
 
 pub struct VM {
     stack: Vec<Value>,
     globals: HashMap<String, Value>,
+    frames: Vec<Frame>,
+    natives: HashMap<String, Native>,
+    guard: CapabilityGuard,
     rng: u64, // Simple XorShift seed
+    executed: u64,
+    timers: BTreeMap<u64, Timer>,
+    next_timer: u64,
+    suspended: Option<Suspension>,
+    /// Most instructions to run, and the count they are counted from.
+    budget: Option<(u64, u64)>,
+}
+
+impl Default for VM {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl VM {
     pub fn new() -> Self {
-        Self { 
+        Self {
             stack: Vec::with_capacity(256),
             globals: HashMap::new(),
+            frames: Vec::new(),
+            natives: HashMap::new(),
+            guard: CapabilityGuard::new(Capabilities::ACTOR),
             rng: 0xACE1,
+            executed: 0,
+            timers: BTreeMap::new(),
+            next_timer: 0,
+            suspended: None,
+            budget: None,
         }
     }
 
@@ -28,37 +212,352 @@ impl VM {
         self.rng = seed;
     }
 
+    /// Instructions run since this VM was created.
+    pub fn instructions(&self) -> u64 {
+        self.executed
+    }
+
+    /// Fail with [`VmError::Quota`] once more than `limit` further
+    /// instructions have run; `None` lifts the limit.
+    pub fn limit_instructions(&mut self, limit: Option<u64>) {
+        self.budget = limit.map(|limit| (limit, self.executed));
+    }
+
+    /// Approximate bytes of state: globals, frame locals and the stack.
+    pub fn memory(&self) -> u64 {
+        let named = |vars: &HashMap<String, Value>| vars.iter().map(|(name, value)| name.len() as u64 + value_size(value)).sum::<u64>();
+        named(&self.globals)
+            + self.frames.iter().map(|frame| named(&frame.locals)).sum::<u64>()
+            + self.stack.iter().map(value_size).sum::<u64>()
+    }
+
+    /// Active function calls.
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
     pub fn get_globals(&self) -> &HashMap<String, Value> {
         &self.globals
     }
 
-    pub fn execute(&mut self, chunk: &Chunk, ctx: &VMContext) {
-        let mut ip = 0;
-        
-        while ip < chunk.instrs.len() {
-            let instr = &chunk.instrs[ip];
-            match instr {
-                Instr::LoadConst(val) => {
-                    self.stack.push(val.clone());
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.globals.insert(name.to_string(), value);
+    }
+
+    pub fn register_native(&mut self, name: &str, native: Native) {
+        self.natives.insert(name.to_string(), native);
+    }
+
+    pub fn natives(&self) -> &HashMap<String, Native> {
+        &self.natives
+    }
+
+    pub fn capabilities(&self) -> Capabilities {
+        self.guard.allowed()
+    }
+
+    /// Grant `capabilities`, keeping any limit on which variables `env()` reads.
+    pub fn set_capabilities(&mut self, capabilities: Capabilities) {
+        self.guard.set_allowed(capabilities);
+    }
+
+    /// What this VM may do; every instruction is checked against it before
+    /// it runs. A new VM may do anything.
+    pub fn guard(&self) -> &CapabilityGuard {
+        &self.guard
+    }
+
+    pub fn set_guard(&mut self, guard: CapabilityGuard) {
+        self.guard = guard;
+    }
+
+    /// Pending timers by id.
+    pub fn timers(&self) -> &BTreeMap<u64, Timer> {
+        &self.timers
+    }
+
+    /// A `sleep`, or a held-back `send`, is waiting for its wake-up.
+    pub fn suspended(&self) -> bool {
+        self.suspended.is_some()
+    }
+
+    /// Capture globals, stack, frames, PRNG, capability grants, timers and
+    /// any `sleep` in progress.
+    pub fn save_state(&self) -> VmState {
+        VmState {
+            globals: self.globals.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            stack: self.stack.clone(),
+            frames: self.frames.iter().map(|f| FrameState {
+                return_ip: f.return_ip,
+                locals: f.locals.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            }).collect(),
+            rng: self.rng,
+            capabilities: self.guard.allowed().bits(),
+            env: self.guard.env().map(|names| names.iter().cloned().collect()),
+            timers: self.timers.clone(),
+            next_timer: self.next_timer,
+            suspended: self.suspended,
+        }
+    }
+
+    /// Replace all execution state with `state`; natives stay registered.
+    pub fn restore_state(&mut self, state: VmState) {
+        self.globals = state.globals.into_iter().collect();
+        self.stack = state.stack;
+        self.frames = state.frames.into_iter().map(|f| Frame {
+            return_ip: f.return_ip,
+            locals: f.locals.into_iter().collect(),
+        }).collect();
+        self.rng = state.rng;
+        self.guard = CapabilityGuard::new(Capabilities::from_bits_retain(state.capabilities));
+        if let Some(names) = state.env {
+            self.guard = self.guard.clone().with_env(names);
+        }
+        self.timers = state.timers;
+        self.next_timer = state.next_timer;
+        self.suspended = state.suspended;
+    }
+
+    pub fn execute<C: Code + ?Sized>(&mut self, code: &C, ctx: &VMContext) -> Result<(), VmError> {
+        let height = self.stack.len();
+        let result = self.run(code, 0, ctx);
+        self.finish(result, 0, height, false).map(|_| ())
+    }
+
+    /// Invoke a compiled `fn` by name and return its result, or `Nil` if it
+    /// went to sleep.
+    pub fn call<C: Code + ?Sized>(&mut self, code: &C, name: &str, args: Vec<Value>, ctx: &VMContext) -> Result<Value, VmError> {
+        let height = self.stack.len();
+        let entry = self.enter(code, name, args, None)?;
+        let base = self.frames.len() - 1;
+        let result = self.run(code, entry, ctx);
+        self.finish(result, base, height, true)
+    }
+
+    /// Continue after a `sleep`, returning what the interrupted call returns.
+    pub fn resume<C: Code + ?Sized>(&mut self, code: &C, ctx: &VMContext) -> Result<Value, VmError> {
+        match self.suspended.take() {
+            Some(s) => {
+                let result = self.run(code, s.ip, ctx);
+                self.finish(result, s.base, s.height, s.call)
+            }
+            None => Ok(Value::Nil),
+        }
+    }
+
+    /// Wind up a run that began with `base` frames and `height` operands:
+    /// a failed run leaves both as they were, discarding its partial state.
+    fn finish(&mut self, result: Result<Option<usize>, VmError>, base: usize, height: usize, call: bool) -> Result<Value, VmError> {
+        match result {
+            Err(e) => {
+                self.frames.truncate(base);
+                self.stack.truncate(height);
+                Err(e)
+            }
+            Ok(Some(ip)) => {
+                self.suspended = Some(Suspension { ip, base, height, call });
+                Ok(Value::Nil)
+            }
+            Ok(None) if call => Ok(self.stack.pop().unwrap_or(Value::Nil)),
+            Ok(None) => Ok(Value::Nil),
+        }
+    }
+
+    /// Account for the arrival of timer `id`'s message, re-arming it if it
+    /// repeats. `None` means the timer was cancelled and the message should
+    /// be dropped.
+    pub fn fire_timer(&mut self, id: u64, data: &MessageData, ctx: &VMContext) -> Option<Timer> {
+        let timer = *self.timers.get(&id)?;
+        match timer {
+            Timer::Every(period) => {
+                if let (Some(bus), Some(me)) = (&ctx.bus, &ctx.actor_id) {
+                    bus.send_at(&format!("{}{}", TIMER_SENDER, id), me, data.clone(), ctx.logical_time + period);
+                }
+            }
+            Timer::Once | Timer::Wake => {
+                self.timers.remove(&id);
+            }
+        }
+        Some(timer)
+    }
+
+    /// Start timer `timer` delivering `data` to this actor `delay` ticks from now.
+    fn arm(&mut self, timer: Timer, delay: u64, data: MessageData, ctx: &VMContext) -> Option<u64> {
+        let (bus, me) = (ctx.bus.as_ref()?, ctx.actor_id.as_ref()?);
+        self.next_timer += 1;
+        let id = self.next_timer;
+        self.timers.insert(id, timer);
+        bus.send_at(&format!("{}{}", TIMER_SENDER, id), me, data, ctx.logical_time + delay);
+        Some(id)
+    }
+
+    fn enter<C: Code + ?Sized>(&mut self, code: &C, name: &str, args: Vec<Value>, return_ip: Option<usize>) -> Result<usize, VmError> {
+        let function = code.function(name).ok_or_else(|| VmError::UnknownFunction(name.to_string()))?;
+        if function.params.len() != args.len() {
+            return Err(VmError::ArityMismatch {
+                name: name.to_string(),
+                expected: function.params.len(),
+                got: args.len(),
+            });
+        }
+        let locals = function.params.iter().cloned().zip(args).collect();
+        self.frames.push(Frame { return_ip, locals });
+        Ok(function.entry)
+    }
+
+    fn call_native(&mut self, name: &str, args: Vec<Value>, ctx: &VMContext) -> Result<Value, VmError> {
+        if let Some(native) = self.natives.get(name) {
+            return (native.func)(&args).map_err(|e| VmError::Host { name: name.to_string(), message: e.to_string() });
+        }
+        match name {
+            "len" => match args.first() {
+                Some(Value::String(s)) => Ok(Value::Number(s.len() as f64)),
+                Some(Value::List(items)) => Ok(Value::Number(items.len() as f64)),
+                _ => Ok(Value::Nil),
+            },
+            "print" => {
+                for arg in args {
+                    ctx.sink.emit(RenderEvent::Value(arg));
+                }
+                Ok(Value::Nil)
+            }
+            // `send(target, event, args...)`; extra arguments travel as `[event, args...]`
+            "send" if ctx.bus.is_some() => {
+                let mut args = args.into_iter();
+                let target = match args.next() {
+                    Some(Value::String(s)) => s,
+                    _ => return Err(VmError::Host { name: name.to_string(), message: "expected an actor id".to_string() }),
+                };
+                let data = event_data(args.collect());
+                match (&ctx.bus, &ctx.actor_id) {
+                    // An actor whose message a full `block` mailbox holds back
+                    // waits for it like a `sleep`
+                    (Some(bus), Some(me)) => {
+                        let id = self.next_timer + 1;
+                        if !bus.send_or_wait(me, &target, data, &format!("{}{}", TIMER_SENDER, id)) {
+                            self.next_timer = id;
+                            self.timers.insert(id, Timer::Wake);
+                        }
+                    }
+                    (Some(bus), None) => bus.send("main", &target, data),
+                    _ => {}
                 }
-                Instr::LoadVar(name) => {
-                    let val = self.globals.get(name).cloned().unwrap_or(Value::Nil);
+                Ok(Value::Nil)
+            }
+            // `after(ms, event, args...)` and `every(ms, event, args...)` message
+            // this actor later, returning a timer id for `cancel`
+            "after" | "every" if ctx.actor_id.is_some() && ctx.bus.is_some() => {
+                let mut args = args.into_iter();
+                let delay = ticks(name, args.next())?;
+                let timer = if name == "every" { Timer::Every(delay.max(1)) } else { Timer::Once };
+                let id = self.arm(timer, delay, event_data(args.collect()), ctx);
+                Ok(id.map_or(Value::Nil, |id| Value::Number(id as f64)))
+            }
+            "cancel" => {
+                let id = match args.first() {
+                    Some(Value::Number(n)) => *n as u64,
+                    _ => return Ok(Value::Bool(false)),
+                };
+                Ok(Value::Bool(self.timers.remove(&id).is_some()))
+            }
+            // `link(actor)`, `monitor(actor)` and their undoing; true if asked of a scheduler
+            "link" | "unlink" | "monitor" | "demonitor" => {
+                let target = match args.first() {
+                    Some(Value::String(s)) => s,
+                    _ => return Err(VmError::Host { name: name.to_string(), message: "expected an actor id".to_string() }),
+                };
+                let (Some(bus), Some(me)) = (&ctx.bus, &ctx.actor_id) else { return Ok(Value::Bool(false)) };
+                let watch = match name {
+                    "link" => Watch::Link,
+                    "unlink" => Watch::Unlink,
+                    "monitor" => Watch::Monitor,
+                    _ => Watch::Demonitor,
+                };
+                bus.watch(me, target, watch);
+                Ok(Value::Bool(true))
+            }
+            "sleep" if ctx.actor_id.is_some() && ctx.bus.is_some() => {
+                let delay = ticks(name, args.into_iter().next())?;
+                self.arm(Timer::Wake, delay, MessageData::Signal("wake".to_string()), ctx);
+                Ok(Value::Nil)
+            }
+            _ => Err(VmError::UnknownFunction(name.to_string())),
+        }
+    }
+
+    /// Refuse `op` unless the guard grants what it requires: `ENV_READ`
+    /// and an allowed name for `env()`, whatever a native function was
+    /// registered with, [`Capabilities::for_builtin`] for builtins, and
+    /// `RENDER` for rendering (plus `MESSAGE` for timelines, which schedule
+    /// messages). Other instructions require nothing.
+    fn authorize<C: Code + ?Sized>(&self, code: &C, op: Op) -> Result<(), VmError> {
+        let refuse = |name: &str, required: Capabilities| (!self.guard.check(required)).then(|| (name.to_string(), required));
+        let denied = match op {
+            Op::LoadEnv(key) if !self.guard.allows_env(key) => Some((format!("env({})", key), Capabilities::ENV_READ)),
+            Op::Call(name, _) if code.function(name).is_none() => {
+                refuse(name, self.natives.get(name).map_or_else(|| Capabilities::for_builtin(name), |native| native.required))
+            }
+            Op::Render | Op::RenderState(_) | Op::RenderUI { .. } => refuse("render", Capabilities::RENDER),
+            Op::RenderTimeline(_) => refuse("render timeline", Capabilities::RENDER | Capabilities::MESSAGE),
+            _ => None,
+        };
+        match denied {
+            Some((name, required)) => Err(VmError::CapabilityDenied { name, required }),
+            None => Ok(()),
+        }
+    }
+
+    /// Execute from `ip` until the outermost frame returns, or until a
+    /// `sleep`, returning the instruction to resume at.
+    fn run<C: Code + ?Sized>(&mut self, code: &C, mut ip: usize, ctx: &VMContext) -> Result<Option<usize>, VmError> {
+        while ip < code.len() {
+            if let Some(debug) = &ctx.debug {
+                debug.before(ip, self, ctx);
+            }
+            self.executed += 1;
+            if let Some((limit, from)) = self.budget {
+                let used = self.executed - from;
+                if used > limit {
+                    return Err(VmError::Quota(QuotaExceeded { resource: Resource::Instructions, scope: QuotaScope::Actor, limit, used }));
+                }
+            }
+            let op = code.op(ip);
+            self.authorize(code, op)?;
+            match op {
+                Op::LoadConst(val) => {
+                    self.stack.push(val.to_value());
+                }
+                Op::LoadVar(name) => {
+                    let local = self.frames.last().and_then(|f| f.locals.get(name));
+                    let val = local.or_else(|| self.globals.get(name)).cloned().unwrap_or(Value::Nil);
                     self.stack.push(val);
                 }
-                Instr::StoreVar(name) => {
+                Op::StoreVar(name) => {
                     if let Some(val) = self.stack.pop() {
-                        self.globals.insert(name.clone(), val);
+                        // Inside a function, existing globals are updated in place and
+                        // anything else becomes a frame local
+                        match self.frames.last_mut() {
+                            Some(frame) if frame.locals.contains_key(name) || !self.globals.contains_key(name) => {
+                                frame.locals.insert(name.to_string(), val);
+                            }
+                            _ => {
+                                self.globals.insert(name.to_string(), val);
+                            }
+                        }
                     }
                 }
-                Instr::LoadEnv(key) => {
-                    let val = std::env::var(key).unwrap_or_else(|_| "".to_string());
+                Op::LoadEnv(key) => {
+                    let val = match &ctx.env {
+                        Some(env) => env.var(ctx.actor_id.as_deref(), key),
+                        None => std::env::var(key).unwrap_or_else(|_| "".to_string()),
+                    };
                     self.stack.push(Value::String(val));
                 }
-                Instr::LoadTime => {
+                Op::LoadTime => {
                     // SECURE: Use logical time from scheduler context, NOT wall-clock
                     self.stack.push(Value::Number(ctx.logical_time as f64));
                 }
-                Instr::LoadRand => {
+                Op::LoadRand => {
                     // SECURE: Deterministic XorShift PRNG
                     self.rng ^= self.rng << 13;
                     self.rng ^= self.rng >> 17;
@@ -66,25 +565,19 @@ impl VM {
                     let val = (self.rng as f64) / (u64::MAX as f64);
                     self.stack.push(Value::Number(val));
                 }
-                Instr::Add => {
+                Op::Add => {
                     let b = self.stack.pop().unwrap();
                     let a = self.stack.pop().unwrap();
                     match (a, b) {
                         (Value::Number(a), Value::Number(b)) => self.stack.push(Value::Number(a + b)),
-                        (Value::String(a), Value::String(b)) => {
-                            let mut res = a;
-                            res.push_str(&b);
-                            self.stack.push(Value::String(res));
-                        }
+                        (Value::String(a), Value::String(b)) => self.stack.push(Value::String(concat(a, &b, ctx)?)),
                         (Value::String(a), Value::Number(b)) => {
-                            let mut res = a;
-                            res.push_str(&b.to_string());
-                            self.stack.push(Value::String(res));
+                            self.stack.push(Value::String(concat(a, &b.to_string(), ctx)?));
                         }
                         _ => self.stack.push(Value::Nil),
                     }
                 }
-                Instr::Sub => {
+                Op::Sub => {
                     let b = self.stack.pop().unwrap();
                     let a = self.stack.pop().unwrap();
                     match (a, b) {
@@ -92,7 +585,7 @@ impl VM {
                         _ => self.stack.push(Value::Nil),
                     }
                 }
-                Instr::Mul => {
+                Op::Mul => {
                     let b = self.stack.pop().unwrap();
                     let a = self.stack.pop().unwrap();
                     match (a, b) {
@@ -100,7 +593,7 @@ impl VM {
                         _ => self.stack.push(Value::Nil),
                     }
                 }
-                Instr::Div => {
+                Op::Div => {
                     let b = self.stack.pop().unwrap();
                     let a = self.stack.pop().unwrap();
                     match (a, b) {
@@ -108,12 +601,12 @@ impl VM {
                         _ => self.stack.push(Value::Nil),
                     }
                 }
-                Instr::Eq => {
+                Op::Eq => {
                     let b = self.stack.pop().unwrap();
                     let a = self.stack.pop().unwrap();
                     self.stack.push(Value::Bool(format!("{:?}", a) == format!("{:?}", b)));
                 }
-                Instr::Gt => {
+                Op::Gt => {
                     let b = self.stack.pop().unwrap();
                     let a = self.stack.pop().unwrap();
                     match (a, b) {
@@ -121,7 +614,7 @@ impl VM {
                         _ => self.stack.push(Value::Bool(false)),
                     }
                 }
-                Instr::Lt => {
+                Op::Lt => {
                     let b = self.stack.pop().unwrap();
                     let a = self.stack.pop().unwrap();
                     match (a, b) {
@@ -129,11 +622,11 @@ impl VM {
                         _ => self.stack.push(Value::Bool(false)),
                     }
                 }
-                Instr::Jump(target) => {
-                    ip = *target;
+                Op::Jump(target) => {
+                    ip = target;
                     continue;
                 }
-                Instr::JumpIfFalse(target) => {
+                Op::JumpIfFalse(target) => {
                     if let Some(val) = self.stack.pop() {
                         let is_true = match val {
                             Value::Bool(b) => b,
@@ -142,46 +635,88 @@ impl VM {
                             _ => true,
                         };
                         if !is_true {
-                            ip = *target;
+                            ip = target;
                             continue;
                         }
                     }
                 }
-                Instr::Call(name, _) => {
-                    if name == "len" {
-                        if let Some(Value::String(s)) = self.stack.pop() {
-                            self.stack.push(Value::Number(s.len() as f64));
-                        }
+                Op::Call(name, argc) => {
+                    let args = self.stack.split_off(self.stack.len().saturating_sub(argc));
+                    if code.function(name).is_some() {
+                        ip = self.enter(code, name, args, Some(ip + 1))?;
+                        continue;
+                    }
+                    let sleeping = self.timers.len();
+                    let result = self.call_native(name, args, ctx)?;
+                    self.stack.push(result);
+                    // A sleep, or a send held back by a full mailbox, unwinds
+                    // to the host, which resumes here on wake-up
+                    if (name == "sleep" || name == "send") && self.timers.len() > sleeping {
+                        return Ok(Some(ip + 1));
                     }
                 }
-                Instr::Spawn(_) => {
+                // `spawn expr` has already run its expression; only the value is left
+                Op::Spawn(_) | Op::Pop => {
                     self.stack.pop();
-                    println!("[Runtime] Spawned task");
                 }
-                Instr::Return => {
-                    return;
+                Op::Return => {
+                    match self.frames.pop() {
+                        Some(Frame { return_ip: Some(resume), .. }) => {
+                            ip = resume;
+                            continue;
+                        }
+                        // Returning from a host call or halting the main program
+                        _ => return Ok(None),
+                    }
                 }
-                Instr::Render => {
+                Op::Render => {
                     if let Some(val) = self.stack.pop() {
-                        match val {
-                            Value::Number(n) => println!("{}", n),
-                            Value::String(s) => println!("{}", s),
-                            Value::Bool(b) => println!("{}", b),
-                            Value::Nil => println!("nil"),
-                        }
+                        ctx.sink.emit(RenderEvent::Value(val));
                     }
                 }
-                Instr::RenderTimeline => {
-                    println!("🌀 [DAS] EMITTING DISTRIBUTED TIMELINE DAG...");
+                Op::RenderTimeline(entries) => {
+                    let payloads = self.stack.split_off(self.stack.len().saturating_sub(entries.len()));
+                    // The declared messages run as a scenario on the bus, if there is one
+                    let graph = TimelineGraph::build(entries, payloads);
+                    if let Some(bus) = &ctx.bus {
+                        graph.schedule(bus.as_ref());
+                    }
+                    ctx.sink.emit(RenderEvent::Timeline(graph));
                 }
-                Instr::RenderState => {
-                    println!("🧩 [DAS] EMITTING DETERMINISTIC STATE SNAPSHOT...");
+                Op::RenderState(refs) => {
+                    let source = Running {
+                        actor: ctx.actor_id.as_deref(),
+                        fields: &self.globals,
+                        others: ctx.state.as_deref(),
+                    };
+                    ctx.sink.emit(RenderEvent::State(state::snapshot(&source, refs, ctx.logical_time)));
                 }
-                Instr::RenderUI => {
-                    println!("📱 [DAS] DISPATCHING DECLARATIVE UI UPDATE...");
+                Op::RenderUI { screen, widgets } => {
+                    let slots: usize = widgets.iter().map(UiTemplate::slot_count).sum();
+                    let values = self.stack.split_off(self.stack.len().saturating_sub(slots));
+                    let root = crate::ui::build(widgets, &mut values.into_iter());
+                    ctx.sink.emit(RenderEvent::Ui(UiTree { screen: screen.map(str::to_string), root, patches: Vec::new() }));
                 }
             }
             ip += 1;
         }
+        Ok(None)
+    }
+}
+
+/// Message data for `[event, args...]` call arguments.
+fn event_data(rest: Vec<Value>) -> MessageData {
+    match rest.len() {
+        0 => MessageData::Signal(String::new()),
+        1 => MessageData::from(rest.into_iter().next().unwrap()),
+        _ => MessageData::from(Value::List(rest)),
+    }
+}
+
+/// A timer delay in logical ticks (milliseconds of virtual time).
+fn ticks(name: &str, arg: Option<Value>) -> Result<u64, VmError> {
+    match arg {
+        Some(Value::Number(n)) if n >= 0.0 => Ok(n as u64),
+        _ => Err(VmError::Host { name: name.to_string(), message: "expected a non-negative delay".to_string() }),
     }
 }
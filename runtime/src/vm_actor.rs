@@ -1,32 +1,223 @@
 use crate::actor::{Actor, Context};
+use crate::capability::CapabilityGuard;
 use crate::mailbox::Message;
-use crate::vm::{VM, VMContext};
-use aeroflow_compiler::ir::Chunk;
+use crate::module::Program;
+use crate::snapshot::VmState;
+use crate::arena::ArenaFull;
+use crate::quota::{QuotaExceeded, QuotaScope, Resource};
+use crate::vm::{timer_id, Timer, VM, VMContext, VmError};
+use aeroflow_compiler::ir::{ActorDef, Code, Op, Value};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 
+/// Runs compiled AeroFlow code as an actor. A plain VM actor executes the
+/// whole chunk per message; one created with [`VMActor::declared`] hosts an
+/// `actor` declaration and dispatches each message to its `on` handler.
+/// Actors spawned from the same [`Program`] share its code.
+///
+/// While a `sleep` is in progress, incoming messages wait in the actor and
+/// run in arrival order once it wakes.
+///
+/// A declared actor runs its `on start` handler once its state is set up,
+/// before its first message, and `on stop` (optionally taking the reason)
+/// when it is stopped. Messages cannot invoke either.
 pub struct VMActor {
     vm: VM,
-    chunk: Chunk,
+    chunk: Program,
+    declared: Option<ActorDef>,
+    initialized: bool,
+    /// The code renders other actors' state, schedules a timeline or uses timers.
+    ordered: bool,
+    deferred: VecDeque<Message>,
+}
+
+/// Builtins whose effects depend on when other messages are delivered.
+const ORDERED_BUILTINS: [&str; 7] = ["after", "every", "sleep", "link", "unlink", "monitor", "demonitor"];
+
+/// Handlers the runtime calls; see [`Actor::on_start`] and [`Actor::on_stop`].
+const LIFECYCLE: [&str; 2] = ["start", "stop"];
+
+#[derive(Serialize, Deserialize)]
+struct VmActorState {
+    declared: Option<String>,
+    initialized: bool,
+    vm: VmState,
+    deferred: Vec<Message>,
 }
 
 impl VMActor {
-    pub fn new(chunk: Chunk) -> Self {
+    pub fn new(program: impl Into<Program>) -> Self {
+        let chunk: Program = program.into();
+        let ordered = (0..chunk.len()).any(|ip| match chunk.op(ip) {
+            Op::RenderState(_) | Op::RenderTimeline(_) => true,
+            Op::Call(name, _) => ORDERED_BUILTINS.contains(&name),
+            _ => false,
+        });
         Self {
             vm: VM::new(),
             chunk,
+            declared: None,
+            initialized: false,
+            ordered,
+            deferred: VecDeque::new(),
+        }
+    }
+
+    /// Host the `actor` named `name` from `program`, if it declares one.
+    pub fn declared(program: impl Into<Program>, name: &str) -> Option<Self> {
+        let program = program.into();
+        let def = program.actor(name)?.clone();
+        Some(Self {
+            declared: Some(def),
+            ..Self::new(program)
+        })
+    }
+
+    pub fn vm_mut(&mut self) -> &mut VM {
+        &mut self.vm
+    }
+
+    fn dispatch(&mut self, def: &ActorDef, msg: &Message, vm_ctx: &VMContext) -> Result<(), VmError> {
+        if !self.initialized {
+            // Declaring the state as globals first keeps `init` from binding frame locals
+            for name in &def.state {
+                self.vm.set_global(name, Value::Nil);
+            }
+            // Parameters take the arguments of the start signal
+            let (event, args) = msg.event();
+            let mut args = args.into_iter().filter(|_| event.is_empty());
+            for name in &def.params {
+                self.vm.set_global(name, args.next().unwrap_or(Value::Nil));
+            }
+            self.vm.call(&self.chunk, &def.init, Vec::new(), vm_ctx)?;
+            self.initialized = true;
+            if let Some(start) = def.handler("start").filter(|_| !self.vm.suspended()) {
+                self.vm.call(&self.chunk, &start, Vec::new(), vm_ctx)?;
+            }
+            if self.vm.suspended() {
+                self.deferred.push_front(msg.clone());
+                return Ok(());
+            }
+        }
+        let (event, args) = msg.event();
+        match def.handler(&event).filter(|_| !LIFECYCLE.contains(&event.as_str())) {
+            Some(handler) => self.vm.call(&self.chunk, &handler, args, vm_ctx).map(|_| ()),
+            // An empty signal only starts the actor
+            None if event.is_empty() => Ok(()),
+            None => Err(VmError::UnknownFunction(format!("{}.{}", def.name, event))),
+        }
+    }
+
+    fn run_message(&mut self, msg: &Message, vm_ctx: &VMContext) -> Result<(), VmError> {
+        match self.declared.clone() {
+            Some(def) => self.dispatch(&def, msg, vm_ctx),
+            None => self.vm.execute(&self.chunk, vm_ctx),
+        }
+    }
+
+    fn handle(&mut self, msg: Message, vm_ctx: &VMContext) -> Result<(), VmError> {
+        match timer_id(&msg.sender) {
+            Some(id) => match self.vm.fire_timer(id, &msg.data, vm_ctx) {
+                // Cancelled
+                None => return Ok(()),
+                Some(Timer::Wake) => {
+                    self.vm.resume(&self.chunk, vm_ctx)?;
+                }
+                Some(_) => self.deferred.push_back(msg),
+            },
+            None => self.deferred.push_back(msg),
+        }
+        while !self.vm.suspended() {
+            let Some(msg) = self.deferred.pop_front() else { break };
+            self.run_message(&msg, vm_ctx)?;
         }
+        Ok(())
     }
 }
 
+/// Report `error` as the actor's failure, along with the quota it exceeded
+/// or the operation it was refused if it was either.
+pub(crate) fn fail(error: VmError, ctx: &mut Context) {
+    ctx.exceeded = match &error {
+        VmError::Quota(exceeded) => Some(*exceeded),
+        VmError::MemoryLimit(ArenaFull { limit: Some(limit), requested }) => Some(QuotaExceeded {
+            resource: Resource::Memory,
+            scope: QuotaScope::Actor,
+            limit: *limit as u64,
+            used: (ctx.arena.allocated() + requested) as u64,
+        }),
+        _ => None,
+    };
+    ctx.denied = error.denied();
+    ctx.failure = Some(error.to_string());
+}
+
 impl Actor for VMActor {
-    fn receive(&mut self, msg: Message, _ctx: &mut Context) {
-        let vm_ctx = VMContext {
-            logical_time: msg.logical_time,
-            rand_seed: 0xDEADBEEF ^ msg.logical_time,
-        };
-        self.vm.execute(&self.chunk, &vm_ctx);
+    fn receive(&mut self, msg: Message, ctx: &mut Context) {
+        let time = msg.logical_time;
+        let before = self.vm.instructions();
+        self.vm.limit_instructions(ctx.quota.instructions);
+        let result = self.handle(msg, &VMContext::for_actor(time, ctx));
+        ctx.instructions = self.vm.instructions() - before;
+        if let Err(e) = result {
+            fail(e, ctx);
+        }
+    }
+
+    fn on_stop(&mut self, reason: &str, ctx: &mut Context) {
+        let Some(def) = self.declared.as_ref().filter(|_| self.initialized) else { return };
+        let Some(stop) = def.handler("stop") else { return };
+        // The reason is passed if the handler takes it
+        let takes_reason = self.chunk.function(&stop).is_some_and(|f| f.params.len() == 1);
+        let args = if takes_reason { vec![Value::String(reason.to_string())] } else { Vec::new() };
+        let before = self.vm.instructions();
+        self.vm.limit_instructions(ctx.quota.instructions);
+        let result = self.vm.call(&self.chunk, &stop, args, &VMContext::for_actor(ctx.logical_time, ctx));
+        ctx.instructions = self.vm.instructions() - before;
+        if let Err(e) = result {
+            fail(e, ctx);
+        }
+    }
+
+    fn isolated(&self) -> bool {
+        // Host functions may do anything
+        !self.ordered && self.vm.natives().is_empty()
+    }
+
+    fn memory(&self) -> u64 {
+        self.vm.memory()
+    }
+
+    fn grant(&mut self, guard: &CapabilityGuard) {
+        self.vm.set_guard(guard.clone());
+    }
+
+    fn get_field(&self, field: &str) -> Option<Value> {
+        self.vm.get_globals().get(field).cloned()
     }
 
     fn get_state(&self) -> String {
         serde_json::to_string(self.vm.get_globals()).unwrap_or_else(|_| "{}".to_string())
     }
+
+    fn snapshot(&self) -> Option<Vec<u8>> {
+        bincode::serialize(&VmActorState {
+            declared: self.declared.as_ref().map(|d| d.name.clone()),
+            initialized: self.initialized,
+            vm: self.vm.save_state(),
+            deferred: self.deferred.iter().cloned().collect(),
+        }).ok()
+    }
+
+    fn restore(&mut self, state: &[u8]) -> anyhow::Result<()> {
+        let state: VmActorState = bincode::deserialize(state)?;
+        let declared = self.declared.as_ref().map(|d| d.name.clone());
+        if state.declared != declared {
+            anyhow::bail!("snapshot is for actor {:?}, not {:?}", state.declared, declared);
+        }
+        self.initialized = state.initialized;
+        self.vm.restore_state(state.vm);
+        self.deferred = state.deferred.into();
+        Ok(())
+    }
 }
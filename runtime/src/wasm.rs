@@ -1,36 +1,52 @@
 use wasm_bindgen::prelude::*;
 use crate::scheduler::Scheduler;
-use crate::actor::{ActorCell, ActorId};
+use crate::actor::ActorCell;
 use crate::VMActor;
 use crate::mailbox::MessageData;
+use crate::render::MemorySink;
+use crate::ui::{ScreenActor, UiEvent};
 use std::sync::Arc;
 
 #[wasm_bindgen]
 pub struct WasmScheduler {
     scheduler: Arc<Scheduler>,
+    sink: Arc<MemorySink>,
+}
+
+impl Default for WasmScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[wasm_bindgen]
 impl WasmScheduler {
     #[wasm_bindgen(constructor)]
     pub fn new() -> Self {
-        Self {
-            scheduler: Arc::new(Scheduler::new()),
-        }
+        let scheduler = Arc::new(Scheduler::new());
+        let sink = Arc::new(MemorySink::new());
+        scheduler.set_render_sink(sink.clone());
+        Self { scheduler, sink }
     }
 
     pub fn spawn_vm_actor(&self, id: String, bytecode: Vec<u8>) {
         let chunk: aeroflow_compiler::ir::Chunk = bincode::deserialize(&bytecode).unwrap();
         let actor = VMActor::new(chunk);
-        let actor_cell = ActorCell {
-            id: id.clone(),
-            actor: Box::new(actor),
-            context: crate::actor::Context {
-                actor_id: id,
-                arena: crate::Arena::new(1024 * 1024), // 1MB Arena
-            },
-        };
-        self.scheduler.spawn(actor_cell);
+        self.scheduler.spawn(ActorCell::new(id, Box::new(actor)));
+    }
+
+    /// Mount a `screen` from the chunk as an actor named after it and render it.
+    pub fn mount_screen(&self, screen: String, bytecode: Vec<u8>) {
+        let chunk: aeroflow_compiler::ir::Chunk = bincode::deserialize(&bytecode).unwrap();
+        if let Some(actor) = ScreenActor::new(chunk, &screen) {
+            self.scheduler.spawn(ActorCell::new(screen.clone(), Box::new(actor)));
+            self.scheduler.send(screen, UiEvent::Mount.into(), "web".to_string());
+        }
+    }
+
+    /// Deliver a JSON-encoded `UiEvent` (click, input or toggle) to a mounted screen.
+    pub fn ui_event(&self, screen: String, event: String) {
+        self.scheduler.send(screen, MessageData::Json(event), "ui".to_string());
     }
 
     pub fn send_message(&self, target: String, sender: String, data: String) {
@@ -42,6 +58,11 @@ impl WasmScheduler {
         self.scheduler.step()
     }
 
+    /// Drain render events produced since the last call as a JSON array.
+    pub fn take_render_events(&self) -> String {
+        serde_json::to_string(&self.sink.take()).unwrap_or_else(|_| "[]".to_string())
+    }
+
     pub fn get_logical_time(&self) -> u64 {
         *self.scheduler.logical_clock.lock()
     }
@@ -0,0 +1,247 @@
+// AeroFlow Runtime - Web Target
+// Static HTML/JS bundles that drive `screen` UIs through the WASM scheduler
+
+use crate::actor::ActorCell;
+use crate::render::{MemorySink, RenderEvent};
+use crate::scheduler::Scheduler;
+use crate::ui::{ScreenActor, UiEvent, UiNode};
+use aeroflow_compiler::ir::Chunk;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::sync::Arc;
+
+pub const INDEX_HTML: &str = "index.html";
+pub const GLUE_JS: &str = "aeroflow.js";
+pub const CHUNK_FILE: &str = "app.chunk";
+
+/// Output of `aeroflow build --target web`.
+///
+/// `index.html` is prerendered with every screen's initial tree so it is
+/// readable without scripts; `aeroflow.js` then mounts the screens on a
+/// `WasmScheduler` (from `wasm-pack build runtime --target web`, expected in
+/// `pkg/`) and keeps the DOM in sync by applying UI patches.
+pub struct WebBundle {
+    pub title: String,
+    pub index_html: String,
+    pub glue_js: String,
+    pub chunk: Vec<u8>,
+}
+
+impl WebBundle {
+    pub fn build(title: &str, chunk: &Chunk) -> anyhow::Result<Self> {
+        let mut body = String::new();
+        for screen in &chunk.screens {
+            let _ = writeln!(body, "  <section class=\"af-screen\" data-screen=\"{}\">", escape(&screen.name));
+            for node in prerender(chunk, &screen.name) {
+                render_node(&node, 2, &mut body);
+            }
+            body.push_str("  </section>\n");
+        }
+
+        let index_html = format!(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n  <meta charset=\"utf-8\">\n  <title>{}</title>\n  <style>{}</style>\n</head>\n<body>\n{}  <pre id=\"af-output\"></pre>\n  <script type=\"module\" src=\"{}\"></script>\n</body>\n</html>\n",
+            escape(title), STYLE, body, GLUE_JS,
+        );
+
+        Ok(Self {
+            title: title.to_string(),
+            index_html,
+            glue_js: GLUE.to_string(),
+            chunk: bincode::serialize(chunk)?,
+        })
+    }
+
+    /// Write the bundle into `dir`, creating it if needed.
+    pub fn write_to(&self, dir: &Path) -> anyhow::Result<()> {
+        std::fs::create_dir_all(dir)?;
+        std::fs::write(dir.join(INDEX_HTML), &self.index_html)?;
+        std::fs::write(dir.join(GLUE_JS), &self.glue_js)?;
+        std::fs::write(dir.join(CHUNK_FILE), &self.chunk)?;
+        Ok(())
+    }
+}
+
+/// Mount `screen` on a private scheduler and capture its first tree.
+fn prerender(chunk: &Chunk, screen: &str) -> Vec<UiNode> {
+    let Some(actor) = ScreenActor::new(chunk.clone(), screen) else { return Vec::new() };
+    let scheduler = Scheduler::new();
+    let sink = Arc::new(MemorySink::new());
+    scheduler.set_render_sink(sink.clone());
+    scheduler.spawn(ActorCell::new(screen.to_string(), Box::new(actor)));
+    scheduler.send(screen.to_string(), UiEvent::Mount.into(), "web".to_string());
+    while scheduler.step() {}
+
+    sink.take().into_iter().rev()
+        .find_map(|e| match e {
+            RenderEvent::Ui(tree) => Some(tree.root),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+/// HTML for a widget tree; mirrors `renderNode` in the JS glue.
+pub fn render_html(nodes: &[UiNode]) -> String {
+    let mut out = String::new();
+    for node in nodes {
+        render_node(node, 0, &mut out);
+    }
+    out
+}
+
+fn render_node(node: &UiNode, depth: usize, out: &mut String) {
+    let pad = "  ".repeat(depth);
+    match node {
+        UiNode::Text { text } => {
+            let _ = writeln!(out, "{}<p class=\"af-text\">{}</p>", pad, escape(text));
+        }
+        UiNode::Input { bind, value } => {
+            let _ = writeln!(out, "{}<input class=\"af-input\" data-bind=\"{}\" value=\"{}\">", pad, escape(bind), escape(value));
+        }
+        UiNode::Button { label, action, args } => {
+            let args = serde_json::to_string(args).unwrap_or_else(|_| "[]".to_string());
+            let _ = writeln!(out, "{}<button class=\"af-button\" data-action=\"{}\" data-args=\"{}\">{}</button>",
+                pad, escape(action), escape(&args), escape(label));
+        }
+        UiNode::Column { children } | UiNode::Row { children } => {
+            let class = if matches!(node, UiNode::Column { .. }) { "af-column" } else { "af-row" };
+            let _ = writeln!(out, "{}<div class=\"{}\">", pad, class);
+            for child in children {
+                render_node(child, depth + 1, out);
+            }
+            let _ = writeln!(out, "{}</div>", pad);
+        }
+        UiNode::List { children } => {
+            let _ = writeln!(out, "{}<ul class=\"af-list\">", pad);
+            for child in children {
+                let text = match child {
+                    UiNode::Text { text } => text.clone(),
+                    other => serde_json::to_string(other).unwrap_or_default(),
+                };
+                let _ = writeln!(out, "{}  <li>{}</li>", pad, escape(&text));
+            }
+            let _ = writeln!(out, "{}</ul>", pad);
+        }
+        UiNode::Image { src } => {
+            let _ = writeln!(out, "{}<img class=\"af-image\" src=\"{}\">", pad, escape(src));
+        }
+        UiNode::Checkbox { label, bind, checked } => {
+            let _ = writeln!(out, "{}<label class=\"af-checkbox\"><input type=\"checkbox\" data-bind=\"{}\"{}> {}</label>",
+                pad, escape(bind), if *checked { " checked" } else { "" }, escape(label));
+        }
+        UiNode::Screen { name } => {
+            let _ = writeln!(out, "{}<section class=\"af-screen\" data-screen=\"{}\"></section>", pad, escape(name));
+        }
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+const STYLE: &str = "body{font-family:system-ui,sans-serif;margin:2rem}\
+.af-column{display:flex;flex-direction:column;gap:.5rem}\
+.af-row{display:flex;flex-direction:row;gap:.5rem}\
+.af-screen{margin-bottom:1.5rem}";
+
+const GLUE: &str = r#"// AeroFlow web glue: mounts screens on the WASM scheduler and applies UI patches.
+import init, { WasmScheduler } from "./pkg/aeroflow_runtime.js";
+
+const mounted = new Set();
+
+function escape(s) {
+  return String(s).replace(/&/g, "&amp;").replace(/</g, "&lt;").replace(/>/g, "&gt;").replace(/"/g, "&quot;");
+}
+
+function renderNode(node) {
+  switch (node.type) {
+    case "Text": return `<p class="af-text">${escape(node.text)}</p>`;
+    case "Input": return `<input class="af-input" data-bind="${escape(node.bind)}" value="${escape(node.value)}">`;
+    case "Button": return `<button class="af-button" data-action="${escape(node.action)}" data-args="${escape(JSON.stringify(node.args))}">${escape(node.label)}</button>`;
+    case "Column": return `<div class="af-column">${node.children.map(renderNode).join("")}</div>`;
+    case "Row": return `<div class="af-row">${node.children.map(renderNode).join("")}</div>`;
+    case "List": return `<ul class="af-list">${node.children.map(c => `<li>${escape(c.text ?? JSON.stringify(c))}</li>`).join("")}</ul>`;
+    case "Image": return `<img class="af-image" src="${escape(node.src)}">`;
+    case "Checkbox": return `<label class="af-checkbox"><input type="checkbox" data-bind="${escape(node.bind)}"${node.checked ? " checked" : ""}> ${escape(node.label)}</label>`;
+  }
+  return "";
+}
+
+function element(node) {
+  const t = document.createElement("template");
+  t.innerHTML = renderNode(node);
+  return t.content.firstElementChild;
+}
+
+function parentOf(root, path) {
+  let el = root;
+  for (const i of path.slice(0, -1)) el = el.children[i];
+  return el;
+}
+
+function applyPatches(root, patches) {
+  for (const patch of patches) {
+    const parent = parentOf(root, patch.path);
+    const index = patch.path[patch.path.length - 1];
+    const node = patch.node && (parent.tagName === "UL" ? Object.assign(document.createElement("li"), { textContent: patch.node.text }) : element(patch.node));
+    const current = parent.children[index];
+    if (patch.op === "replace" && current === document.activeElement && patch.node.type === "Input") {
+      // Keep focus and caret while the user is typing into a bound input
+      current.value = patch.node.value;
+    } else if (patch.op === "replace") current.replaceWith(node);
+    else if (patch.op === "insert") parent.insertBefore(node, parent.children[index] ?? null);
+    else if (patch.op === "remove") parent.children[index].remove();
+  }
+}
+
+function pump(scheduler) {
+  while (scheduler.step()) {}
+  for (const event of JSON.parse(scheduler.take_render_events())) {
+    if (event.kind === "ui" && event.data.screen) {
+      const root = document.querySelector(`[data-screen="${event.data.screen}"]`);
+      if (!mounted.has(event.data.screen)) {
+        // Replace the prerendered markup with the live tree on first render
+        mounted.add(event.data.screen);
+        root.innerHTML = event.data.root.map(renderNode).join("");
+      } else {
+        applyPatches(root, event.data.patches);
+      }
+    } else if (event.kind === "value") {
+      document.getElementById("af-output").textContent += JSON.stringify(event.data) + "\n";
+    }
+  }
+}
+
+await init();
+const scheduler = new WasmScheduler();
+const bytecode = new Uint8Array(await (await fetch("./app.chunk")).arrayBuffer());
+
+scheduler.spawn_vm_actor("main", bytecode);
+scheduler.send_message("main", "web", "start");
+for (const root of document.querySelectorAll("[data-screen]")) {
+  scheduler.mount_screen(root.dataset.screen, bytecode);
+}
+pump(scheduler);
+
+function deliver(screen, event) {
+  scheduler.ui_event(screen, JSON.stringify(event));
+  pump(scheduler);
+}
+
+document.addEventListener("click", e => {
+  const button = e.target.closest("[data-action]");
+  if (!button) return;
+  const screen = button.closest("[data-screen]").dataset.screen;
+  deliver(screen, { type: "click", action: button.dataset.action, args: JSON.parse(button.dataset.args) });
+});
+
+document.addEventListener("input", e => {
+  const bind = e.target.dataset.bind;
+  if (!bind) return;
+  const screen = e.target.closest("[data-screen]").dataset.screen;
+  if (e.target.type === "checkbox") deliver(screen, { type: "toggle", bind, checked: e.target.checked });
+  else deliver(screen, { type: "input", bind, value: e.target.value });
+});
+"#;
@@ -0,0 +1,222 @@
+use aeroflow_compiler::afm::{self, AfmHeader, AfmMetadata, AfmModule, TrustStore, AFM_MAGIC, ARCH_IMAGE, HEADER_LEN};
+use aeroflow_compiler::compile;
+use aeroflow_compiler::image::{self, AlignedBytes, CodeImage};
+use aeroflow_compiler::ir::Code;
+use aeroflow_runtime::{Engine, MemorySink, Program, Scheduler};
+use std::sync::{Arc, Mutex};
+
+fn module() -> AfmModule {
+    AfmModule {
+        metadata: AfmMetadata {
+            name: "greeter".to_string(),
+            version: "1.2.0".to_string(),
+            author: "AeroFlow".to_string(),
+            capabilities: vec!["NET".to_string()],
+        },
+        chunk: compile(r#"render { "hello from afm" }"#).unwrap(),
+    }
+}
+
+fn seed(byte: u8) -> String {
+    afm::to_hex(&[byte; 32])
+}
+
+#[test]
+fn round_trips_header_metadata_and_ir() {
+    let bytes = module().to_bytes().unwrap();
+    let header = AfmHeader::from_bytes(&bytes).unwrap();
+    assert_eq!(header.magic, AFM_MAGIC);
+    assert_eq!(header.metadata_offset as usize, HEADER_LEN);
+    assert!(!header.is_signed());
+
+    let decoded = AfmModule::from_bytes(&bytes).unwrap();
+    assert_eq!(decoded.metadata, module().metadata);
+    assert_eq!(decoded.chunk.instrs.len(), module().chunk.instrs.len());
+}
+
+#[test]
+fn rejects_modified_content() {
+    let mut bytes = module().to_bytes().unwrap();
+    let header = AfmHeader::from_bytes(&bytes).unwrap();
+    bytes[header.ir_offset as usize] ^= 0xff;
+    let err = AfmModule::from_bytes(&bytes).unwrap_err();
+    assert!(err.to_string().contains("hash mismatch"), "{}", err);
+
+    bytes[0] = b'X';
+    assert!(AfmModule::from_bytes(&bytes).is_err());
+}
+
+#[test]
+fn trust_store_accepts_only_known_signers() {
+    let signer = afm::parse_signing_key(&seed(1)).unwrap();
+    let bytes = module().to_signed_bytes(&signer).unwrap();
+    let file = AfmModule::inspect(&bytes).unwrap();
+    assert_eq!(file.signer, Some(signer.verifying_key()));
+
+    let trusted = TrustStore::parse(&format!("# release key\n{}\n", afm::to_hex(signer.verifying_key().as_bytes()))).unwrap();
+    assert!(AfmModule::load(&bytes, &trusted).is_ok());
+
+    let other = afm::parse_signing_key(&seed(2)).unwrap();
+    let mut untrusted = TrustStore::new();
+    untrusted.trust(other.verifying_key());
+    let err = AfmModule::load(&bytes, &untrusted).unwrap_err();
+    assert!(err.to_string().contains("untrusted key"), "{}", err);
+
+    // Swapping in another key's signature block is caught by verification
+    let forged = module().to_signed_bytes(&other).unwrap();
+    let mut tampered = bytes.clone();
+    let at = file.header.signature_offset as usize;
+    tampered[at + 32..].copy_from_slice(&forged[at + 32..]);
+    assert!(AfmModule::inspect(&tampered).is_err());
+}
+
+#[test]
+fn unsigned_modules_can_be_required_away() {
+    let bytes = module().to_bytes().unwrap();
+    let mut store = TrustStore::new();
+    assert!(AfmModule::load(&bytes, &store).is_ok());
+    store.require_signatures(true);
+    let err = AfmModule::load(&bytes, &store).unwrap_err();
+    assert!(err.to_string().contains("not signed"), "{}", err);
+}
+
+#[test]
+fn keyed_stores_reject_stripped_signatures() {
+    let signer = afm::parse_signing_key(&seed(3)).unwrap();
+    let signed = module().to_signed_bytes(&signer).unwrap();
+    let mut store = TrustStore::new();
+    store.trust(signer.verifying_key());
+    assert!(AfmModule::load(&signed, &store).is_ok());
+
+    // Clearing the flag and re-hashing the prefix yields the unsigned encoding
+    let mut header = AfmHeader::from_bytes(&signed).unwrap();
+    header.flags = 0;
+    header.signature_offset = 0;
+    let hash_offset = (header.ir_offset + header.ir_len) as usize;
+    let mut prefix = signed[..hash_offset].to_vec();
+    prefix[..HEADER_LEN].copy_from_slice(&header.to_bytes());
+    let stripped = module().to_bytes().unwrap();
+    assert_eq!(prefix, stripped[..hash_offset]);
+
+    assert!(AfmModule::inspect(&stripped).is_ok());
+    let err = AfmModule::load(&stripped, &store).unwrap_err();
+    assert!(err.to_string().contains("not signed"), "{}", err);
+}
+
+#[test]
+fn rejects_sections_out_of_range() {
+    let bytes = module().to_bytes().unwrap();
+    let mut header = AfmHeader::from_bytes(&bytes).unwrap();
+    header.ir_offset = u32::MAX;
+    header.ir_len = u32::MAX;
+    let mut bad = bytes.clone();
+    bad[..HEADER_LEN].copy_from_slice(&header.to_bytes());
+    assert!(AfmModule::inspect(&bad).is_err());
+
+    assert!(afm::from_hex("aéb").is_err());
+    assert!(afm::from_hex("0g").is_err());
+    assert_eq!(afm::from_hex("00ff").unwrap(), [0, 255]);
+}
+
+#[test]
+fn engine_loads_trusted_modules() {
+    let signer = afm::parse_signing_key(&seed(7)).unwrap();
+    let signed = module().to_signed_bytes(&signer).unwrap();
+    let rendered = Arc::new(Mutex::new(Vec::new()));
+    let sink = rendered.clone();
+
+    let mut engine = Engine::new();
+    engine.on_output(move |v| sink.lock().unwrap().push(v.to_string()));
+    let mut store = TrustStore::new();
+    store.require_signatures(true);
+    engine.set_trust_store(store.clone());
+    assert!(engine.load_afm(&module().to_bytes().unwrap()).is_err());
+
+    store.trust(signer.verifying_key());
+    engine.set_trust_store(store);
+    engine.load_afm(&signed).unwrap();
+    assert_eq!(*rendered.lock().unwrap(), vec!["hello from afm"]);
+}
+
+const ACTORS: &str = r#"
+actor Counter {
+    state count = 0
+    on Increment(by) {
+        let count = count + by
+        print("count " + count)
+    }
+}
+send("Counter", "Increment", 2)
+send("Counter", "Increment", 3)
+"#;
+
+fn run(program: impl Into<Program>) -> Vec<String> {
+    let scheduler = Scheduler::shared();
+    let sink = Arc::new(MemorySink::new());
+    scheduler.set_render_sink(sink.clone());
+    scheduler.spawn_program(program);
+    scheduler.run(None);
+    sink.take().into_iter().map(|e| format!("{:?}", e)).collect()
+}
+
+#[test]
+fn image_decodes_to_the_same_chunk() {
+    let chunk = compile(ACTORS).unwrap();
+    let bytes = image::encode(&chunk).unwrap();
+    let image = CodeImage::parse(AlignedBytes::copy_from(&bytes), 0, bytes.len()).unwrap();
+    assert_eq!(image.len(), chunk.instrs.len());
+    for ip in 0..chunk.instrs.len() {
+        assert_eq!(image.op(ip), chunk.instrs[ip].as_op());
+    }
+    assert_eq!(image.to_chunk().instrs, chunk.instrs);
+    assert_eq!(image.actors(), chunk.actors.as_slice());
+}
+
+#[test]
+fn image_validation_rejects_bad_operands() {
+    let chunk = compile(ACTORS).unwrap();
+    let mut bytes = image::encode(&chunk).unwrap();
+    // First instruction's opcode, then its operand
+    let first = image::IMAGE_HEADER_LEN;
+    bytes[first] = 0xee;
+    let err = CodeImage::parse(AlignedBytes::copy_from(&bytes), 0, bytes.len()).err().unwrap();
+    assert!(err.to_string().contains("unknown opcode"), "{}", err);
+
+    let mut bytes = image::encode(&chunk).unwrap();
+    bytes[first + 4..first + 8].copy_from_slice(&u32::MAX.to_le_bytes());
+    let err = CodeImage::parse(AlignedBytes::copy_from(&bytes), 0, bytes.len()).err().unwrap();
+    assert!(err.to_string().contains("out of range"), "{}", err);
+}
+
+#[test]
+fn mapped_modules_run_like_compiled_source() {
+    let module = AfmModule { metadata: module().metadata, chunk: compile(ACTORS).unwrap() };
+    let signer = afm::parse_signing_key(&seed(3)).unwrap();
+    let path = std::env::temp_dir().join(format!("aeroflow-map-{}.afm", std::process::id()));
+    std::fs::write(&path, module.encode(ARCH_IMAGE, Some(&signer)).unwrap()).unwrap();
+
+    let mut store = TrustStore::new();
+    store.trust(signer.verifying_key());
+    store.require_signatures(true);
+    // SAFETY: the test does not touch the file again while it is mapped
+    let mapped = Arc::new(unsafe { aeroflow_runtime::module::map(&path, &store) }.unwrap());
+    assert_eq!(mapped.metadata.name, "greeter");
+
+    // `main` and `Counter` both hold the one mapping rather than a copy
+    let scheduler = Scheduler::new();
+    scheduler.spawn_program(Program::Module(mapped.clone()));
+    assert_eq!(Arc::strong_count(&mapped), 3);
+    drop(scheduler);
+
+    assert_eq!(run(Program::Module(mapped)), run(compile(ACTORS).unwrap()));
+
+    // SAFETY: as above
+    let (opened, _) = unsafe { Program::open(&path, &store) }.unwrap();
+    assert!(matches!(opened, Program::Module(_)));
+    std::fs::remove_file(&path).unwrap();
+
+    // Bincode modules still load, just not in place
+    let decoded = AfmModule::from_bytes(&module.encode(ARCH_IMAGE, None).unwrap()).unwrap();
+    assert_eq!(decoded.chunk.instrs, module.chunk.instrs);
+    assert!(aeroflow_runtime::module::from_bytes(&module.to_bytes().unwrap(), &TrustStore::new()).is_err());
+}
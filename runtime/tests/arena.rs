@@ -0,0 +1,77 @@
+use aeroflow_compiler::compile;
+use aeroflow_compiler::ir::Value;
+use aeroflow_runtime::arena::ArenaFull;
+use aeroflow_runtime::quota::Quota;
+use aeroflow_runtime::{Arena, MemorySink, MessageData, RenderEvent, Scheduler};
+use std::sync::Arc;
+
+/// `Build(n)` concatenates `n` ten-character pieces, so its temporaries
+/// grow with the square of `n`.
+const BUILDER: &str = r#"
+actor Builder {
+    state built = 0
+    on Build(n) {
+        let s = ""
+        let i = 0
+        while i < n {
+            let s = s + "0123456789"
+            let i = i + 1
+        }
+        let built = built + 1
+        print("built " + n)
+    }
+}
+supervise OneForOne(max: 1, within: 50) {
+    Builder
+}
+"#;
+
+#[test]
+fn arena_aligns_values_and_fails_at_its_limit() {
+    let mut arena = Arena::with_limit(Some(4096));
+    arena.alloc(1u8).unwrap();
+    let wide = arena.alloc(7u64).unwrap();
+    assert_eq!(wide as *mut u64 as usize % std::mem::align_of::<u64>(), 0);
+    assert_eq!(*wide, 7);
+    assert_eq!(arena.concat("aero", "flow").unwrap(), "aeroflow");
+
+    let big = "x".repeat(8192);
+    assert_eq!(arena.alloc_str(&big).unwrap_err(), ArenaFull { limit: Some(4096), requested: 8192 });
+    assert_eq!(arena.limit(), Some(4096));
+    arena.reset();
+    assert!(arena.allocated() <= 4096);
+    assert_eq!(arena.alloc_str("after reset").unwrap(), "after reset");
+    assert_eq!(&*Arena::new().alloc_str(&big).unwrap(), big);
+}
+
+#[test]
+fn handlers_over_the_memory_limit_fail_to_their_supervisor() {
+    let scheduler = Scheduler::shared();
+    let sink = Arc::new(MemorySink::new());
+    scheduler.set_render_sink(sink.clone());
+    scheduler.set_quota(Quota { memory: Some(256 * 1024), instructions: None });
+    scheduler.spawn_program(compile(BUILDER).unwrap());
+    let build = |n: f64| {
+        scheduler.send("Builder".to_string(), MessageData::Json(serde_json::json!(["Build", n]).to_string()), "host".to_string());
+        scheduler.run(None);
+    };
+
+    // Each message starts from an empty arena, so many small builds fit
+    for _ in 0..10 {
+        build(100.0);
+    }
+    assert!(scheduler.failures().is_empty());
+    assert_eq!(scheduler.read_field("Builder", "built"), Some(Value::Number(10.0)));
+
+    build(1000.0);
+    let failures = scheduler.failures();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].reason, "Memory limit exceeded: arena limit of 262144 bytes exceeded");
+    // The supervisor restarted it, and it carries on within the limit
+    assert_eq!(scheduler.read_field("Builder", "built"), Some(Value::Number(0.0)));
+    build(100.0);
+    assert_eq!(scheduler.read_field("Builder", "built"), Some(Value::Number(1.0)));
+    let printed: Vec<RenderEvent> = sink.take();
+    assert_eq!(printed.len(), 11);
+    assert!(printed.iter().all(|e| matches!(e, RenderEvent::Value(Value::String(s)) if s == "built 100")));
+}
@@ -0,0 +1,177 @@
+use aeroflow_compiler::compile;
+use aeroflow_compiler::ir::Value;
+use aeroflow_runtime::vm::Native;
+use aeroflow_runtime::{ActorCell, Capabilities, CapabilityGuard, MemorySink, MessageData, RenderEvent, Scheduler, Tracer, VMActor};
+use std::sync::Arc;
+
+/// `Reader` reads an allowed variable or a secret one; `Other` reads the
+/// secret whatever it is asked.
+const READERS: &str = r#"
+actor Reader {
+    state value = ""
+    on Allowed() {
+        let value = env("AEROFLOW_CAP_ALLOWED")
+        print("allowed=" + value)
+    }
+    on Secret() {
+        let value = env("AEROFLOW_CAP_SECRET")
+    }
+}
+actor Other {
+    on Allowed() {
+        print(env("AEROFLOW_CAP_SECRET"))
+    }
+    on Secret() {
+        print(env("AEROFLOW_CAP_SECRET"))
+    }
+}
+"#;
+
+fn read(scheduler: &Scheduler, to: &str, event: &str) {
+    scheduler.send(to.to_string(), MessageData::Json(serde_json::json!([event]).to_string()), "host".to_string());
+}
+
+/// Kind, actor and arguments of every traced event.
+fn events(tracer: &Tracer) -> Vec<(String, String, Vec<Value>)> {
+    tracer.events().into_iter().map(|e| (e.kind(), e.actor_id.clone(), e.input.event().1)).collect()
+}
+
+#[test]
+fn capability_names_parse_as_written_in_manifests() {
+    assert_eq!(Capabilities::parse("env_read"), Some(Capabilities::ENV_READ));
+    assert_eq!(Capabilities::parse("NET"), Some(Capabilities::NET_RECV | Capabilities::NET_SEND));
+    assert_eq!(Capabilities::parse("ALL"), Some(Capabilities::all()));
+    assert_eq!(Capabilities::parse("CAMERA"), None);
+    assert_eq!((Capabilities::NET_SEND | Capabilities::ENV_READ).names(), ["NET_SEND", "ENV_READ"]);
+
+    let guard = CapabilityGuard::new(Capabilities::ENV_READ).with_env(["HOME"]);
+    assert!(guard.allows_env("HOME") && !guard.allows_env("PATH"));
+    assert!(CapabilityGuard::new(Capabilities::ENV_READ).allows_env("PATH"));
+    assert!(!CapabilityGuard::new(Capabilities::NET_SEND).with_env(["HOME"]).allows_env("HOME"));
+}
+
+#[test]
+fn env_reads_outside_the_allow_list_fail_and_are_traced() {
+    std::env::set_var("AEROFLOW_CAP_ALLOWED", "yes");
+    std::env::set_var("AEROFLOW_CAP_SECRET", "hunter2");
+    let scheduler = Scheduler::shared();
+    let sink = Arc::new(MemorySink::new());
+    let tracer = Arc::new(Tracer::new());
+    scheduler.set_render_sink(sink.clone());
+    scheduler.set_tracer(tracer.clone());
+    scheduler.spawn_program(compile(READERS).unwrap());
+    scheduler.set_grants(CapabilityGuard::new(Capabilities::ACTOR | Capabilities::ENV_READ).with_env(["AEROFLOW_CAP_ALLOWED"]));
+    scheduler.run(None);
+
+    read(&scheduler, "Reader", "Allowed");
+    scheduler.run(None);
+    read(&scheduler, "Reader", "Secret");
+    scheduler.run(None);
+    let printed: Vec<RenderEvent> = sink.take();
+    assert!(matches!(&printed[..], [RenderEvent::Value(Value::String(s))] if s == "allowed=yes"));
+    let failures = scheduler.failures();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].reason, "Security Violation: 'env(AEROFLOW_CAP_SECRET)' requires ENV_READ");
+    assert_eq!(scheduler.read_field("Reader", "value"), Some(Value::String("yes".to_string())));
+
+    let denied: Vec<_> = events(&tracer).into_iter().filter(|(kind, _, _)| kind == "CapabilityDenied").collect();
+    assert_eq!(denied, [(
+        "CapabilityDenied".to_string(),
+        "Reader".to_string(),
+        vec![Value::String("env(AEROFLOW_CAP_SECRET)".to_string()), Value::List(vec![Value::String("ENV_READ".to_string())])],
+    )]);
+}
+
+#[test]
+fn native_functions_need_the_capabilities_they_declare() {
+    let program = compile("actor Fetcher {\n    state got = \"\"\n    on Fetch() {\n        let got = fetch()\n    }\n}").unwrap();
+    let scheduler = Scheduler::shared();
+    let mut fetcher = VMActor::declared(program, "Fetcher").unwrap();
+    fetcher.vm_mut().register_native("fetch", Native {
+        required: Capabilities::NET_SEND,
+        func: Arc::new(|_| Ok(Value::String("data".to_string()))),
+    });
+    scheduler.spawn(ActorCell::new("Fetcher".to_string(), Box::new(fetcher)));
+    let fetch = || {
+        scheduler.send("Fetcher".to_string(), MessageData::Json(r#"["Fetch"]"#.to_string()), "host".to_string());
+        scheduler.run(None);
+    };
+
+    // Grants given before or after an actor is spawned apply to it
+    scheduler.set_grants(CapabilityGuard::new(Capabilities::FS_READ | Capabilities::ENV_READ));
+    fetch();
+    assert_eq!(scheduler.failures()[0].reason, "Security Violation: 'fetch' requires NET_SEND");
+    assert_eq!(scheduler.read_field("Fetcher", "got"), Some(Value::String(String::new())));
+
+    scheduler.set_grants(CapabilityGuard::new(Capabilities::parse("NET").unwrap()));
+    fetch();
+    assert_eq!(scheduler.failures().len(), 1);
+    assert_eq!(scheduler.read_field("Fetcher", "got"), Some(Value::String("data".to_string())));
+}
+
+#[test]
+fn denials_are_the_same_at_any_thread_count() {
+    std::env::set_var("AEROFLOW_CAP_ALLOWED", "yes");
+    let chunk = compile(READERS).unwrap();
+    let run = |threads: usize| {
+        let scheduler = Scheduler::shared();
+        let tracer = Arc::new(Tracer::new());
+        scheduler.set_parallelism(threads).unwrap();
+        scheduler.set_tracer(tracer.clone());
+        scheduler.set_render_sink(Arc::new(MemorySink::new()));
+        scheduler.set_grants(CapabilityGuard::new(Capabilities::ACTOR | Capabilities::ENV_READ).with_env(["AEROFLOW_CAP_ALLOWED"]));
+        scheduler.spawn_program(&chunk);
+        scheduler.run(None);
+        for event in ["Allowed", "Secret", "Allowed"] {
+            read(&scheduler, "Reader", event);
+            read(&scheduler, "Other", event);
+        }
+        scheduler.run(None);
+        let failures: Vec<_> = scheduler.failures().into_iter().map(|f| (f.actor, f.logical_time, f.reason)).collect();
+        (failures, events(&tracer))
+    };
+    let (failures, traced) = run(1);
+    assert_eq!(failures.len(), 4);
+    assert_eq!(traced.iter().filter(|(kind, _, _)| kind == "CapabilityDenied").count(), 4);
+    assert_eq!(run(4), (failures, traced));
+}
+
+#[test]
+fn effectful_builtins_need_their_capabilities() {
+    let program = compile(
+        "actor Relay {\n    on Go() {\n        print(\"before\")\n        send(\"Relay\", \"Done\")\n    }\n    on Done() {\n        print(\"done\")\n    }\n}",
+    )
+    .unwrap();
+    let run = |grants: Option<Capabilities>| {
+        let scheduler = Scheduler::shared();
+        let sink = Arc::new(MemorySink::new());
+        let tracer = Arc::new(Tracer::new());
+        scheduler.set_render_sink(sink.clone());
+        scheduler.set_tracer(tracer.clone());
+        if let Some(grants) = grants {
+            scheduler.set_grants(CapabilityGuard::new(grants));
+        }
+        scheduler.spawn_program(&program);
+        read(&scheduler, "Relay", "Go");
+        scheduler.run(None);
+        let reasons: Vec<String> = scheduler.failures().into_iter().map(|f| f.reason).collect();
+        (sink.take().len(), reasons, events(&tracer).into_iter().filter(|(kind, _, _)| kind == "CapabilityDenied").count())
+    };
+
+    // Actors may do what they do inside the runtime unless the host says otherwise
+    assert_eq!(run(None), (2, vec![], 0));
+    assert_eq!(run(Some(Capabilities::ACTOR)), (2, vec![], 0));
+    assert_eq!(run(Some(Capabilities::RENDER)), (1, vec!["Security Violation: 'send' requires MESSAGE".to_string()], 1));
+    assert_eq!(run(Some(Capabilities::NONE)), (0, vec!["Security Violation: 'print' requires RENDER".to_string()], 1));
+}
+
+#[test]
+fn env_reads_are_denied_until_granted() {
+    std::env::set_var("AEROFLOW_CAP_ALLOWED", "yes");
+    let scheduler = Scheduler::shared();
+    scheduler.set_render_sink(Arc::new(MemorySink::new()));
+    scheduler.spawn_program(compile(READERS).unwrap());
+    read(&scheduler, "Reader", "Allowed");
+    scheduler.run(None);
+    assert_eq!(scheduler.failures()[0].reason, "Security Violation: 'env(AEROFLOW_CAP_ALLOWED)' requires ENV_READ");
+}
@@ -0,0 +1,205 @@
+use aeroflow_compiler::compile;
+use aeroflow_runtime::debugger::{Breakpoint, Debugger, Stop};
+use aeroflow_runtime::replay::{Recording, Replayer};
+use aeroflow_runtime::value::into_json;
+use aeroflow_runtime::{MemorySink, Program, Scheduler};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+
+const RALLY: &str = r#"
+actor Ping {
+    state hits = 0
+    on Ball(n) {
+        let hits = hits + 1
+        print("ping " + n + " roll " + rand())
+        if n > 0 {
+            send("Pong", "Ball", n - 1)
+        }
+    }
+}
+
+actor Pong {
+    state hits = 0
+    on Ball(n) {
+        let hits = hits + 1
+        send("Ping", "Ball", n - 1)
+    }
+}
+
+send("Ping", "Ball", 9)
+"#;
+
+fn record() -> Recording {
+    let program = Program::from(compile(RALLY).unwrap());
+    let scheduler = Scheduler::shared();
+    scheduler.set_render_sink(Arc::new(MemorySink::new()));
+    let recorder = scheduler.record(&program);
+    scheduler.spawn_program(program);
+    scheduler.run(None);
+    recorder.recording()
+}
+
+/// Every actor's state after each step of a straight replay.
+fn states(recording: &Recording) -> Vec<Value> {
+    let mut replayer = Replayer::new(recording.clone());
+    replayer.scheduler().set_render_sink(Arc::new(MemorySink::new()));
+    let snapshot = |replayer: &Replayer| {
+        let scheduler = replayer.scheduler();
+        json!(scheduler.actor_ids().iter().map(|id| {
+            let fields: BTreeMap<String, aeroflow_compiler::ir::Value> = serde_json::from_str(&scheduler.actor_state(id).unwrap()).unwrap();
+            (id.clone(), json!(fields.into_iter().map(|(k, v)| (k, into_json(v))).collect::<BTreeMap<_, _>>()))
+        }).collect::<Vec<_>>())
+    };
+    let mut states = vec![snapshot(&replayer)];
+    while replayer.step().unwrap().is_some() {
+        states.push(snapshot(&replayer));
+    }
+    states
+}
+
+fn all_states(debugger: &Debugger) -> Value {
+    json!(debugger.actors().iter().map(|id| (id.clone(), debugger.state(id).unwrap())).collect::<Vec<_>>())
+}
+
+#[test]
+fn seeking_anywhere_matches_a_straight_replay() {
+    let recording = record();
+    let expected = states(&recording);
+    let mut debugger = Debugger::new(recording.clone()).unwrap();
+    debugger.set_checkpoint_interval(3);
+    assert_eq!(debugger.len(), recording.steps.len());
+
+    debugger.seek(debugger.len()).unwrap();
+    for step in [7, 2, 11, 0, 5, 5, 12, 1] {
+        debugger.seek(step).unwrap();
+        assert_eq!(debugger.position(), step);
+        assert_eq!(all_states(&debugger), expected[step]);
+    }
+
+    debugger.seek(6).unwrap();
+    assert!(debugger.step_back().unwrap());
+    assert_eq!(debugger.position(), 5);
+    debugger.seek(0).unwrap();
+    assert!(!debugger.step_back().unwrap());
+
+    let time = recording.steps[4].logical_time;
+    debugger.seek_time(time).unwrap();
+    assert_eq!(debugger.position(), recording.steps.iter().filter(|s| s.logical_time <= time).count());
+    assert_eq!(debugger.logical_time(), time);
+}
+
+#[test]
+fn mailbox_shows_pending_messages() {
+    let mut debugger = Debugger::new(record()).unwrap();
+    let next = debugger.next_delivery().unwrap().unwrap();
+    assert_eq!(debugger.mailbox(&next.actor).unwrap().len(), 1);
+    while debugger.next_delivery().unwrap().is_some_and(|d| d.actor != "Pong" || d.sender != "Ping") {
+        debugger.step_forward().unwrap();
+    }
+    let mailbox = debugger.mailbox("Pong").unwrap();
+    assert_eq!(mailbox.len(), 1);
+    assert_eq!(mailbox[0].sender, "Ping");
+    assert_eq!(mailbox[0].event().0, "Ball");
+}
+
+#[test]
+fn breakpoints_stop_before_matching_deliveries() {
+    let mut debugger = Debugger::new(record()).unwrap();
+    debugger.set_checkpoint_interval(4);
+    let id = debugger.add_breakpoint(Breakpoint {
+        actor: Some("Ping".to_string()),
+        message: Some("Ball".to_string()),
+        condition: Some("hits >= 2".to_string()),
+    }).unwrap();
+
+    assert_eq!(debugger.continue_forward().unwrap(), Stop::Breakpoint(id));
+    assert_eq!(debugger.state("Ping").unwrap()["hits"], json!(2));
+    assert_eq!(debugger.next_delivery().unwrap().unwrap().actor, "Ping");
+    let first = debugger.position();
+
+    assert_eq!(debugger.continue_forward().unwrap(), Stop::Breakpoint(id));
+    assert_eq!(debugger.state("Ping").unwrap()["hits"], json!(3));
+    let second = debugger.position();
+
+    assert_eq!(debugger.continue_backward().unwrap(), Stop::Breakpoint(id));
+    assert_eq!(debugger.position(), first);
+    assert_eq!(debugger.continue_backward().unwrap(), Stop::Start);
+    assert_eq!(debugger.position(), 0);
+
+    debugger.seek(second).unwrap();
+    assert!(debugger.remove_breakpoint(id));
+    assert_eq!(debugger.continue_forward().unwrap(), Stop::End);
+    assert_eq!(debugger.position(), debugger.len());
+
+    assert!(debugger.add_breakpoint(Breakpoint { condition: Some("hits ~ 2".to_string()), ..Default::default() }).is_err());
+}
+
+#[test]
+fn json_rpc_drives_the_debugger() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let debugger = Debugger::new(record()).unwrap();
+    let server = std::thread::spawn(move || debugger.serve(listener));
+
+    let stream = TcpStream::connect(addr).unwrap();
+    let mut writer = stream.try_clone().unwrap();
+    let mut reader = BufReader::new(stream);
+    let mut call = |id: u64, method: &str, params: Value| -> Value {
+        writeln!(writer, "{}", json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })).unwrap();
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let response: Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(response["id"], json!(id));
+        response
+    };
+
+    let position = call(1, "position", json!({}))["result"].clone();
+    assert_eq!(position["step"], json!(0));
+    assert_eq!(position["next"]["actor"], json!("Ping"));
+
+    let bp = call(2, "setBreakpoint", json!({ "actor": "Pong", "condition": "hits == 2" }))["result"]["id"].clone();
+    let stopped = call(3, "continue", json!({}))["result"].clone();
+    assert_eq!(stopped["stop"], json!({ "reason": "breakpoint", "breakpoint": bp }));
+    assert_eq!(stopped["next"]["actor"], json!("Pong"));
+    let at = stopped["step"].as_u64().unwrap();
+
+    assert_eq!(call(4, "state", json!({ "actor": "Pong" }))["result"]["hits"], json!(2));
+    assert_eq!(call(5, "mailbox", json!({ "actor": "Pong" }))["result"].as_array().unwrap().len(), 1);
+    assert_eq!(call(6, "stepBack", json!({}))["result"]["step"], json!(at - 1));
+    assert_eq!(call(7, "seek", json!({ "step": 2 }))["result"]["step"], json!(2));
+    assert_eq!(call(8, "actors", json!({}))["result"], json!(["Ping", "Pong", "main"]));
+    assert_eq!(call(9, "breakpoints", json!({}))["result"][0]["breakpoint"]["condition"], json!("hits == 2"));
+
+    assert_eq!(call(10, "rewind", json!({}))["error"]["code"], json!(-32601));
+    assert_eq!(call(11, "state", json!({}))["error"]["code"], json!(-32602));
+    assert_eq!(call(12, "seek", json!({}))["error"]["code"], json!(-32602));
+
+    call(13, "shutdown", json!({}));
+    server.join().unwrap().unwrap();
+}
+
+#[test]
+fn a_broken_connection_does_not_stop_the_server() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let debugger = Debugger::new(record()).unwrap();
+    let server = std::thread::spawn(move || debugger.serve(listener));
+
+    // Invalid UTF-8 fails the read and drops only this connection
+    let mut broken = TcpStream::connect(addr).unwrap();
+    broken.write_all(&[0xff, 0xfe, b'\n']).unwrap();
+    let mut rest = String::new();
+    BufReader::new(broken).read_line(&mut rest).unwrap();
+    assert!(rest.is_empty());
+
+    let stream = TcpStream::connect(addr).unwrap();
+    let mut writer = stream.try_clone().unwrap();
+    writeln!(writer, "{}", json!({ "jsonrpc": "2.0", "id": 1, "method": "shutdown" })).unwrap();
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line).unwrap();
+    assert_eq!(serde_json::from_str::<Value>(&line).unwrap()["id"], json!(1));
+    server.join().unwrap().unwrap();
+}
@@ -0,0 +1,54 @@
+use aeroflow_compiler::compile;
+use aeroflow_compiler::ir::Value;
+use aeroflow_runtime::distributed::DistributedScheduler;
+use aeroflow_runtime::render::StateEntry;
+use aeroflow_runtime::{ActorCell, MemorySink, MessageData, RenderEvent, Scheduler, VMActor};
+use std::sync::Arc;
+
+fn vm_actor(id: &str, source: &str) -> ActorCell {
+    ActorCell::new(id.to_string(), Box::new(VMActor::new(compile(source).unwrap())))
+}
+
+fn poke(node: &Scheduler, actor: &str) {
+    node.send(actor.to_string(), MessageData::Signal("tick".into()), "test".to_string());
+    assert!(node.step());
+}
+
+#[test]
+fn snapshot_resolves_local_remote_and_self_fields() {
+    let node_a = Arc::new(Scheduler::new());
+    let node_b = Arc::new(Scheduler::new());
+    let sink = Arc::new(MemorySink::new());
+    node_a.set_render_sink(sink.clone());
+
+    node_a.spawn(vm_actor("counter", "let count: int = 7"));
+    node_b.spawn(vm_actor("ledger", "let balance: int = 100"));
+    node_a.spawn(vm_actor("observer", r#"
+        let seen: int = 1
+        render distributed state { counter.count ledger.balance observer.seen ghost.field }
+    "#));
+
+    let cluster = Arc::new(DistributedScheduler::new("a".to_string(), node_a.clone()));
+    cluster.connect_peer("b".to_string(), node_b.clone());
+    node_a.set_state_source(cluster);
+
+    poke(&node_a, "counter");
+    poke(&node_b, "ledger");
+    poke(&node_a, "observer");
+
+    let snapshot = match sink.events().pop() {
+        Some(RenderEvent::State(snapshot)) => snapshot,
+        other => panic!("expected state snapshot, got {:?}", other),
+    };
+    let entry = |node: &str, field: &str, value: Value| StateEntry {
+        node: node.to_string(),
+        field: field.to_string(),
+        value,
+    };
+    assert_eq!(snapshot.entries, vec![
+        entry("counter", "count", Value::Number(7.0)),
+        entry("ledger", "balance", Value::Number(100.0)),
+        entry("observer", "seen", Value::Number(1.0)),
+        entry("ghost", "field", Value::Nil),
+    ]);
+}
@@ -0,0 +1,94 @@
+use aeroflow_runtime::vm::{Native, VMContext};
+use aeroflow_runtime::{Actor, Capabilities, Context, Engine, MemorySink, Message, MessageData, VM};
+use aeroflow_compiler::ir::Value;
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn calls_exported_functions_and_captures_output() {
+    let rendered = Arc::new(Mutex::new(Vec::new()));
+    let sink = rendered.clone();
+
+    let mut engine = Engine::new();
+    engine.on_output(move |v| sink.lock().unwrap().push(v.to_string()));
+    engine.register_fn("greeting", |(name,): (String,)| Ok(format!("hello {}", name)));
+    engine
+        .load_source(
+            r#"
+            let base: int = 40
+            render { "loaded" }
+            fn add(a: int, b: int) -> int { return a + b + base }
+            fn greet(who: string) { render { greeting(who) } }
+            "#,
+        )
+        .unwrap();
+
+    let sum: i64 = engine.call("add", (1, 1)).unwrap();
+    assert_eq!(sum, 42);
+
+    let _: () = engine.call("greet", "aeroflow").unwrap();
+    assert_eq!(*rendered.lock().unwrap(), vec!["loaded", "hello aeroflow"]);
+
+    let base: u32 = engine.global("base").unwrap();
+    assert_eq!(base, 40);
+    assert!(engine.call::<_, Value>("missing", ()).is_err());
+}
+
+#[test]
+fn capabilities_gate_host_functions() {
+    let mut engine = Engine::new();
+    engine.register_fn_with_capabilities("fetch", Capabilities::NET_SEND, |(): ()| Ok("data"));
+    engine.load_source("fn get() -> string { return fetch() }").unwrap();
+
+    engine.set_capabilities(Capabilities::FS_READ);
+    let denied = engine.call::<_, String>("get", ());
+    assert!(denied.unwrap_err().to_string().contains("Security Violation"));
+
+    engine.set_capabilities(Capabilities::NET_SEND);
+    assert_eq!(engine.call::<_, String>("get", ()).unwrap(), "data");
+}
+
+#[test]
+fn failed_calls_leave_no_operands_behind() {
+    let chunk = aeroflow_compiler::compile("fn sum() -> int { return 1 + 2 + boom() }").unwrap();
+    let mut vm = VM::new();
+    vm.register_native("boom", Native { required: Capabilities::NONE, func: Arc::new(|_| anyhow::bail!("boom")) });
+    let ctx = VMContext::new(0, Arc::new(MemorySink::new()));
+    assert!(vm.call(&chunk, "sum", Vec::new(), &ctx).is_err());
+    assert!(vm.save_state().stack.is_empty());
+    assert_eq!(vm.depth(), 0);
+}
+
+struct Recorder(Arc<Mutex<Vec<String>>>);
+
+impl Actor for Recorder {
+    fn receive(&mut self, msg: Message, _ctx: &mut Context) {
+        if let MessageData::Text(text) = msg.data {
+            self.0.lock().unwrap().push(text);
+        }
+    }
+}
+
+#[test]
+fn scripts_message_host_actors() {
+    let inbox = Arc::new(Mutex::new(Vec::new()));
+    let mut engine = Engine::new();
+    engine.register_actor("logger", Recorder(inbox.clone()));
+    engine.load_source(r#"send("logger", "booted")"#).unwrap();
+
+    assert_eq!(engine.run_until_idle(), 1);
+    assert_eq!(*inbox.lock().unwrap(), vec!["booted"]);
+}
+
+#[test]
+fn run_until_idle_carries_on_past_dead_letters() {
+    let inbox = Arc::new(Mutex::new(Vec::new()));
+    let mut engine = Engine::new();
+    engine.register_actor("logger", Recorder(inbox.clone()));
+    engine.send("nobody".to_string(), MessageData::Text("lost".to_string()));
+    engine.send("logger".to_string(), MessageData::Text("kept".to_string()));
+
+    assert_eq!(engine.run_until_idle(), 1);
+    assert_eq!(*inbox.lock().unwrap(), vec!["kept"]);
+    assert_eq!(engine.scheduler().queue_depth(), 0);
+    assert_eq!(engine.scheduler().dead_letters().len(), 1);
+}
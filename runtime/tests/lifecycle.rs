@@ -0,0 +1,241 @@
+use aeroflow_compiler::compile;
+use aeroflow_compiler::ir::{Chunk, Value};
+use aeroflow_runtime::mailbox::{DeadReason, Watch};
+use aeroflow_runtime::supervisor::{ChildSpec, Strategy, Supervisor};
+use aeroflow_runtime::{Actor, ActorCell, Context, MemorySink, Message, MessageData, RenderEvent, Scheduler, Snapshot};
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+/// `Partner` is linked to `Fragile` and `Watcher` monitors it; `Fragile`
+/// fails on `Crash`.
+const LINKED: &str = r#"
+actor Fragile {
+    state crashes = 0
+    on Crash() {
+        let crashes = crashes + 1
+        boom()
+    }
+}
+actor Partner {
+    on start {
+        link("Fragile")
+    }
+    on stop(reason) {
+        print("partner stopped: " + reason)
+    }
+}
+actor Watcher {
+    on start {
+        monitor("Fragile")
+        monitor("Ghost")
+    }
+    on Down(who, reason) {
+        print(who + " is down: " + reason)
+    }
+}
+send("Fragile", "Crash")
+"#;
+
+fn start(chunk: &Chunk) -> (Arc<Scheduler>, Arc<MemorySink>) {
+    let scheduler = Scheduler::shared();
+    let sink = Arc::new(MemorySink::new());
+    scheduler.set_render_sink(sink.clone());
+    scheduler.spawn_program(chunk);
+    (scheduler, sink)
+}
+
+fn lines(sink: &MemorySink) -> Vec<String> {
+    sink.take().into_iter().map(|e| match e {
+        RenderEvent::Value(Value::String(s)) => s,
+        other => format!("{:?}", other),
+    }).collect()
+}
+
+#[test]
+fn start_and_stop_handlers_run_around_the_mailbox() {
+    let source = r#"
+actor Worker {
+    state done = 0
+    on start {
+        print("up at " + time)
+    }
+    on Job(n) {
+        let done = done + 1
+        print("job " + n)
+    }
+    on stop(reason) {
+        print("down: " + reason + " after " + done)
+    }
+}
+send("Worker", "Job", 1)
+send("Worker", "Job", 2)
+"#;
+    let (scheduler, sink) = start(&compile(source).unwrap());
+    // Asked before main has even sent the jobs, the stop still waits for them
+    scheduler.stop("Worker");
+    scheduler.run(None);
+    assert_eq!(lines(&sink), ["up at 1", "job 1", "job 2", "down: shutdown after 2"]);
+    assert_eq!(scheduler.actor_ids(), ["main"]);
+
+    scheduler.send("Worker".to_string(), MessageData::Text("Job".to_string()), "host".to_string());
+    scheduler.run(None);
+    assert!(lines(&sink).is_empty());
+    assert_eq!(scheduler.dead_letters()[0].reason, DeadReason::UnknownActor);
+    assert!(scheduler.failures().is_empty());
+}
+
+#[test]
+fn stepping_waits_out_a_deferred_stop() {
+    let source = r#"
+actor Worker {
+    on Job(n) {
+        print("job " + n)
+    }
+    on stop(reason) {
+        print("down: " + reason)
+    }
+}
+send("Worker", "Job", 1)
+send("Worker", "Job", 2)
+"#;
+    let (scheduler, sink) = start(&compile(source).unwrap());
+    scheduler.stop("Worker");
+    // The stop is put back while the jobs are queued; stepping goes on past it
+    while scheduler.step() {}
+    assert_eq!(lines(&sink), ["job 1", "job 2", "down: shutdown"]);
+    assert_eq!(scheduler.queue_depth(), 0);
+    assert_eq!(scheduler.actor_ids(), ["main"]);
+}
+
+#[test]
+fn links_stop_partners_and_monitors_hear_of_it() {
+    let (scheduler, sink) = start(&compile(LINKED).unwrap());
+    scheduler.run(None);
+    assert_eq!(lines(&sink), [
+        "Ghost is down: unknown_actor",
+        "partner stopped: linked actor 'Fragile' failed",
+        "Fragile is down: Unknown function 'boom'",
+    ]);
+    let failed: Vec<(String, String)> = scheduler.failures().into_iter().map(|f| (f.actor, f.reason)).collect();
+    assert_eq!(failed, [
+        ("Fragile".to_string(), "Unknown function 'boom'".to_string()),
+        ("Partner".to_string(), "linked actor 'Fragile' failed".to_string()),
+    ]);
+    // Links break and monitors fire once; the failed actor itself keeps going
+    assert_eq!(scheduler.actor_ids(), ["Fragile", "Watcher", "main"]);
+    assert_eq!(scheduler.watchers("Fragile"), (vec![], vec![]));
+    scheduler.send("Fragile".to_string(), MessageData::Text("Crash".to_string()), "host".to_string());
+    scheduler.run(None);
+    assert!(lines(&sink).is_empty());
+    assert_eq!(scheduler.read_field("Fragile", "crashes"), Some(Value::Number(2.0)));
+}
+
+#[test]
+fn lifecycles_match_across_threads_and_snapshots() {
+    let chunk = compile(LINKED).unwrap();
+    let (whole, sink) = start(&chunk);
+    let report = whole.run(None);
+    let expected = lines(&sink);
+    let bytes = whole.snapshot().unwrap().to_bytes().unwrap();
+
+    for threads in [2, 4] {
+        let (parallel, sink) = start(&chunk);
+        parallel.set_parallelism(threads).unwrap();
+        assert_eq!(parallel.run(None), report);
+        assert_eq!(lines(&sink), expected, "{} threads diverged", threads);
+        assert_eq!(parallel.failures(), whole.failures());
+        assert_eq!(parallel.snapshot().unwrap().to_bytes().unwrap(), bytes);
+    }
+
+    // The link and monitors set up at the start survive a snapshot
+    let (first, sink) = start(&chunk);
+    first.run(Some(1));
+    let mut seen = lines(&sink);
+    let saved = Snapshot::from_bytes(&first.snapshot().unwrap().to_bytes().unwrap()).unwrap();
+    assert_eq!(saved.links, [("Fragile".to_string(), "Partner".to_string())]);
+    assert_eq!(saved.monitors, [("Watcher".to_string(), "Fragile".to_string())]);
+    let resumed = Scheduler::shared();
+    resumed.set_render_sink(sink.clone());
+    resumed.restore_program(&saved, &chunk).unwrap();
+    resumed.run(None);
+    seen.extend(lines(&sink));
+    assert_eq!(seen, expected);
+    assert_eq!(resumed.snapshot().unwrap().to_bytes().unwrap(), bytes);
+}
+
+type Log = Arc<Mutex<Vec<String>>>;
+
+/// Logs its lifecycle; fails on `Fail`.
+struct Probe {
+    log: Log,
+}
+
+impl Actor for Probe {
+    fn receive(&mut self, msg: Message, ctx: &mut Context) {
+        let (event, args) = msg.event();
+        match event.as_str() {
+            "Fail" => ctx.failure = Some("told to".to_string()),
+            "Down" => self.log.lock().push(format!("{} saw {} down: {}", ctx.actor_id, args[0], args[1])),
+            _ => {}
+        }
+    }
+
+    fn on_start(&mut self, ctx: &mut Context) {
+        self.log.lock().push(format!("{} start at {}", ctx.actor_id, ctx.logical_time));
+    }
+
+    fn on_stop(&mut self, reason: &str, ctx: &mut Context) {
+        self.log.lock().push(format!("{} stop: {}", ctx.actor_id, reason));
+    }
+
+    fn on_restart(&mut self, reason: &str, ctx: &mut Context) {
+        self.log.lock().push(format!("{} restart: {}", ctx.actor_id, reason));
+    }
+}
+
+#[test]
+fn rust_actors_get_lifecycle_hooks() {
+    let log = Log::default();
+    let probe = |log: &Log| {
+        let log = log.clone();
+        move || Box::new(Probe { log: log.clone() }) as Box<dyn Actor>
+    };
+    let scheduler = Scheduler::shared();
+    let mut pair = Supervisor::new("pair", Strategy::AllForOne);
+    pair.add_child(ChildSpec::new("a", probe(&log)));
+    pair.add_child(ChildSpec::new("b", probe(&log)));
+    scheduler.supervise(pair);
+    scheduler.spawn(ActorCell::new("w".to_string(), probe(&log)()));
+    scheduler.watch("w", "b", Watch::Monitor);
+    scheduler.run(None);
+    assert_eq!(std::mem::take(&mut *log.lock()), ["a start at 1", "b start at 1"]);
+
+    scheduler.send("a".to_string(), MessageData::Text("Fail".to_string()), "host".to_string());
+    scheduler.run(None);
+    assert_eq!(std::mem::take(&mut *log.lock()), [
+        "a restart: told to",
+        "a start at 2",
+        "b stop: restart",
+        "b restart: told to",
+        "b start at 2",
+        "w start at 3",
+        "w saw b down: restart",
+    ]);
+
+    scheduler.stop("b");
+    scheduler.run(None);
+    assert_eq!(std::mem::take(&mut *log.lock()), ["b stop: shutdown"]);
+    // A stopped child is not restarted with its siblings
+    scheduler.send("a".to_string(), MessageData::Text("Fail".to_string()), "host".to_string());
+    scheduler.run(None);
+    assert_eq!(std::mem::take(&mut *log.lock()), ["a restart: told to", "a start at 5"]);
+    assert_eq!(scheduler.actor_ids(), ["a", "w"]);
+}
+
+#[test]
+fn lifecycle_handlers_take_no_arguments_but_the_reason() {
+    let error = compile("actor A {\n    on start(x) {\n    }\n}").unwrap_err();
+    assert_eq!(error.to_string(), "'on start' of actor 'A' takes at most 0 parameter(s), got 1");
+    let error = compile("actor A {\n    on stop(a, b) {\n    }\n}").unwrap_err();
+    assert_eq!(error.to_string(), "'on stop' of actor 'A' takes at most 1 parameter(s), got 2");
+}
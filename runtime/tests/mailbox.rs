@@ -0,0 +1,196 @@
+use aeroflow_compiler::compile;
+use aeroflow_compiler::ir::{Chunk, Value};
+use aeroflow_runtime::mailbox::{DeadReason, MailboxConfig, MailboxMetrics, Overflow};
+use aeroflow_runtime::{MemorySink, RenderEvent, Scheduler, Snapshot};
+use std::sync::Arc;
+
+const FLOOD: &str = r#"
+actor Sink {
+    state got = 0
+    on Item(n) {
+        let got = got + 1
+        print("item " + n + " " + time)
+    }
+}
+send("Sink", "Item", 1)
+send("Sink", "Item", 2)
+send("Sink", "Item", 3)
+send("Sink", "Item", 4)
+send("Sink", "Item", 5)
+"#;
+
+fn start(chunk: &Chunk, overflow: Overflow) -> (Arc<Scheduler>, Arc<MemorySink>) {
+    let scheduler = Scheduler::shared();
+    let sink = Arc::new(MemorySink::new());
+    scheduler.set_render_sink(sink.clone());
+    scheduler.set_mailbox("Sink", MailboxConfig::bounded(2, overflow));
+    scheduler.spawn_program(chunk);
+    (scheduler, sink)
+}
+
+fn lines(sink: &MemorySink) -> Vec<String> {
+    sink.take().into_iter().map(|e| match e {
+        RenderEvent::Value(Value::String(s)) => s,
+        other => format!("{:?}", other),
+    }).collect()
+}
+
+fn dead(scheduler: &Scheduler) -> Vec<(DeadReason, String)> {
+    scheduler.dead_letters().into_iter().map(|d| (d.reason, d.message.event().1[0].to_string())).collect()
+}
+
+#[test]
+fn full_mailboxes_drop_new_or_old_messages() {
+    let chunk = compile(FLOOD).unwrap();
+    let (scheduler, sink) = start(&chunk, Overflow::DropNew);
+    scheduler.run(None);
+    assert_eq!(lines(&sink), ["item 1 2", "item 2 2"]);
+    assert_eq!(dead(&scheduler), [
+        (DeadReason::MailboxFull, "3".to_string()),
+        (DeadReason::MailboxFull, "4".to_string()),
+        (DeadReason::MailboxFull, "5".to_string()),
+    ]);
+    // The start signal, then two items
+    assert_eq!(scheduler.mailbox_metrics()["Sink"], MailboxMetrics { depth: 0, high_water: 2, blocked: 0, delivered: 3, dropped: 3 });
+
+    let (scheduler, sink) = start(&chunk, Overflow::DropOld);
+    scheduler.run(None);
+    assert_eq!(lines(&sink), ["item 4 2", "item 5 2"]);
+    assert_eq!(dead(&scheduler), [
+        (DeadReason::Evicted, "1".to_string()),
+        (DeadReason::Evicted, "2".to_string()),
+        (DeadReason::Evicted, "3".to_string()),
+    ]);
+    assert!(scheduler.failures().is_empty());
+}
+
+#[test]
+fn fail_reports_the_sender() {
+    let (scheduler, sink) = start(&compile(FLOOD).unwrap(), Overflow::Fail);
+    scheduler.run(None);
+    assert_eq!(lines(&sink), ["item 1 2", "item 2 2"]);
+    let failures = scheduler.failures();
+    assert_eq!(failures.len(), 3);
+    assert!(failures.iter().all(|f| f.actor == "main" && f.reason == "mailbox of 'Sink' is full"));
+}
+
+#[test]
+fn blocked_messages_wait_for_a_slot() {
+    let chunk = compile(FLOOD).unwrap();
+    let (scheduler, sink) = start(&chunk, Overflow::Block);
+    scheduler.run(Some(1));
+    let metrics = scheduler.mailbox_metrics()["Sink"];
+    // `main` waits for its third item to be let in before sending more
+    assert_eq!((metrics.depth, metrics.blocked), (2, 1));
+    assert_eq!(scheduler.queue_depth(), 2);
+
+    scheduler.run(None);
+    // The held item goes in a tick after a slot frees, and `main` wakes up
+    // then to send the rest
+    assert_eq!(lines(&sink), ["item 1 2", "item 2 2", "item 3 3", "item 4 4", "item 5 4"]);
+    assert!(scheduler.dead_letters().is_empty());
+    assert_eq!(scheduler.mailbox_metrics()["Sink"], MailboxMetrics { depth: 0, high_water: 2, blocked: 0, delivered: 6, dropped: 0 });
+    assert_eq!(scheduler.read_field("Sink", "got"), Some(Value::Number(5.0)));
+}
+
+const PRODUCER: &str = r#"
+actor Sink {
+    state got = 0
+    on Item(n) {
+        let got = got + 1
+    }
+}
+actor Producer {
+    state rounds = 0
+    on Go(n) {
+        let rounds = rounds + 1
+        send("Sink", "Item", n)
+        send("Sink", "Item", n)
+        send("Sink", "Item", n)
+        if n < 6 {
+            send("Producer", "Go", n + 1)
+        }
+    }
+}
+send("Producer", "Go", 1)
+"#;
+
+#[test]
+fn senders_wait_while_their_messages_are_held() {
+    let (scheduler, _) = start(&compile(PRODUCER).unwrap(), Overflow::Block);
+    let (rounds, held): (Vec<_>, Vec<_>) = (0..12).map(|tick| {
+        scheduler.run(Some(tick));
+        let rounds = match scheduler.read_field("Producer", "rounds") {
+            Some(Value::Number(n)) => n as u32,
+            _ => 0,
+        };
+        (rounds, scheduler.mailbox_metrics().get("Sink").map_or(0, |m| m.blocked))
+    }).unzip();
+    // The producer stops at its third send of each round until the sink
+    // lets that item in, so a round takes three ticks
+    assert_eq!(rounds, [0, 0, 1, 1, 1, 2, 2, 2, 3, 3, 3, 4]);
+    assert!(held.iter().all(|&held| held <= 1));
+    scheduler.run(None);
+    assert!(scheduler.dead_letters().is_empty());
+    assert_eq!(scheduler.read_field("Sink", "got"), Some(Value::Number(18.0)));
+}
+
+#[test]
+fn blocked_messages_snapshot_and_resume() {
+    let chunk = compile(FLOOD).unwrap();
+    let (whole, sink) = start(&chunk, Overflow::Block);
+    whole.run(None);
+    let expected = lines(&sink);
+
+    let (first, sink) = start(&chunk, Overflow::Block);
+    first.run(Some(1));
+    let bytes = first.snapshot().unwrap().to_bytes().unwrap();
+    assert_eq!(Snapshot::from_bytes(&bytes).unwrap().blocked.len(), 1);
+
+    let resumed = Scheduler::shared();
+    resumed.set_render_sink(sink.clone());
+    resumed.set_mailbox("Sink", MailboxConfig::bounded(2, Overflow::Block));
+    resumed.restore_program(&Snapshot::from_bytes(&bytes).unwrap(), &chunk).unwrap();
+    resumed.run(None);
+    assert_eq!(lines(&sink), expected);
+    assert_eq!(resumed.snapshot().unwrap().to_bytes().unwrap(), whole.snapshot().unwrap().to_bytes().unwrap());
+}
+
+#[test]
+fn undeliverable_messages_reach_dead_letters() {
+    let source = r#"
+actor DeadLetters {
+    state seen = 0
+    on Undelivered(reason, to, sender, event, args) {
+        let seen = seen + 1
+        print(reason + " " + to + " " + sender + " " + event + " " + time)
+    }
+}
+actor Sink {
+    on Item(n) {
+        print("item " + n)
+    }
+}
+send("Ghost", "Boo", 1)
+send("Sink", "Item", 1)
+send("Sink", "Item", 2)
+"#;
+    let scheduler = Scheduler::shared();
+    let sink = Arc::new(MemorySink::new());
+    scheduler.set_render_sink(sink.clone());
+    scheduler.set_mailbox("Sink", MailboxConfig::bounded(1, Overflow::DropNew));
+    scheduler.spawn_program(compile(source).unwrap());
+    scheduler.run(None);
+    // Turned away at 1, so forwarded at 2; Ghost's message is lost on delivery at 2
+    assert_eq!(lines(&sink), [
+        "item 1",
+        "mailbox_full Sink main Item 2",
+        "unknown_actor Ghost main Boo 3",
+    ]);
+    assert_eq!(scheduler.read_field("DeadLetters", "seen"), Some(Value::Number(2.0)));
+    assert_eq!(scheduler.mailbox_metrics()["Ghost"].dropped, 1);
+    assert_eq!(dead(&scheduler), [
+        (DeadReason::MailboxFull, "2".to_string()),
+        (DeadReason::UnknownActor, "1".to_string()),
+    ]);
+}
@@ -0,0 +1,136 @@
+use aeroflow_compiler::compile;
+use aeroflow_compiler::ir::Value;
+use aeroflow_runtime::ordering::{Lamport, OrderingPolicy, SendOrder};
+use aeroflow_runtime::{MemorySink, MessageData, RenderEvent, Scheduler};
+use std::sync::Arc;
+
+fn boot(source: &str, policy: Arc<dyn OrderingPolicy>) -> (Arc<Scheduler>, Arc<MemorySink>) {
+    let scheduler = Scheduler::shared();
+    let sink = Arc::new(MemorySink::new());
+    scheduler.set_render_sink(sink.clone());
+    scheduler.set_ordering(policy);
+    scheduler.spawn_program(compile(source).unwrap());
+    (scheduler, sink)
+}
+
+fn lines(sink: &MemorySink) -> Vec<String> {
+    sink.take().into_iter().map(|e| match e {
+        RenderEvent::Value(Value::String(s)) => s,
+        other => format!("{:?}", other),
+    }).collect()
+}
+
+fn signal(name: &str) -> MessageData {
+    MessageData::Signal(name.to_string())
+}
+
+const FAN_OUT: &str = r#"
+actor Zed {
+    on Ping() {
+        print("zed " + time)
+    }
+}
+actor Abe {
+    on Ping() {
+        print("abe " + time)
+    }
+}
+send("Zed", "Ping")
+send("Abe", "Ping")
+"#;
+
+#[test]
+fn renaming_a_receiver_keeps_the_order() {
+    let (scheduler, sink) = boot(FAN_OUT, Arc::new(Lamport::default()));
+    scheduler.run(None);
+    assert_eq!(lines(&sink), ["zed 2", "abe 2"]);
+
+    let renamed = FAN_OUT.replace("Zed", "Aaa").replace("zed", "aaa");
+    let (scheduler, sink) = boot(&renamed, Arc::new(Lamport::default()));
+    scheduler.run(None);
+    assert_eq!(lines(&sink), ["aaa 2", "abe 2"]);
+}
+
+#[test]
+fn message_time_is_sender_time_plus_latency() {
+    let source = r#"
+actor Ping {
+    on Ball(n) {
+        print("ping " + n + " " + time)
+        if n > 0 {
+            send("Pong", "Ball", n - 1)
+        }
+    }
+}
+actor Pong {
+    on Ball(n) {
+        print("pong " + n + " " + time)
+        send("Ping", "Ball", n - 1)
+    }
+}
+send("Ping", "Ball", 3)
+"#;
+    let (scheduler, sink) = boot(source, Arc::new(Lamport { latency: 10 }));
+    let report = scheduler.run(None);
+    // Starts at 10, main's send at 20, then one latency per hop
+    assert_eq!(lines(&sink), ["ping 3 20", "pong 2 30", "ping 1 40", "pong 0 50", "ping -1 60"]);
+    assert_eq!(report.logical_time, 60);
+
+    // A host send leaves from the latest delivery
+    scheduler.send("Pong".to_string(), MessageData::from(Value::List(vec![Value::String("Ball".to_string()), Value::Number(9.0)])), "host".to_string());
+    assert_eq!(scheduler.peek().unwrap().1.logical_time, 70);
+
+    // A latency of zero still puts effects after their causes
+    let (scheduler, sink) = boot(source, Arc::new(Lamport { latency: 0 }));
+    scheduler.run(None);
+    assert_eq!(lines(&sink)[0], "ping 3 2");
+}
+
+#[test]
+fn ties_break_by_sender_then_send_order() {
+    let source = r#"
+actor Log {
+    on Beta() {
+        print("beta " + time)
+    }
+    on Alpha() {
+        print("alpha " + time)
+    }
+}
+"#;
+    let send_all = |scheduler: &Scheduler| {
+        scheduler.run(None);
+        scheduler.send("Log".to_string(), signal("Beta"), "beta".to_string());
+        scheduler.send("Log".to_string(), signal("Alpha"), "alpha".to_string());
+        scheduler.send("Log".to_string(), signal("Beta"), "alpha".to_string());
+        scheduler.run(None);
+    };
+
+    let (scheduler, sink) = boot(source, Arc::new(Lamport::default()));
+    send_all(&scheduler);
+    assert_eq!(lines(&sink), ["alpha 2", "beta 2", "beta 2"]);
+
+    let (scheduler, sink) = boot(source, Arc::new(SendOrder::default()));
+    send_all(&scheduler);
+    assert_eq!(lines(&sink), ["beta 2", "alpha 2", "beta 2"]);
+}
+
+#[test]
+fn switching_policy_reorders_queued_messages() {
+    let (scheduler, sink) = boot(FAN_OUT, Arc::new(Lamport::default()));
+    scheduler.run(Some(1));
+    scheduler.send("Zed".to_string(), signal("Ping"), "zz".to_string());
+    scheduler.send("Abe".to_string(), signal("Ping"), "aa".to_string());
+    scheduler.run(None);
+    // Senders aa < main < zz
+    assert_eq!(lines(&sink), ["abe 2", "zed 2", "abe 2", "zed 2"]);
+
+    let (scheduler, sink) = boot(FAN_OUT, Arc::new(Lamport::default()));
+    scheduler.run(Some(1));
+    scheduler.send("Zed".to_string(), signal("Ping"), "zz".to_string());
+    scheduler.send("Abe".to_string(), signal("Ping"), "aa".to_string());
+    scheduler.set_ordering(Arc::new(SendOrder::default()));
+    scheduler.run(None);
+    // main's two sends came first
+    assert_eq!(lines(&sink), ["zed 2", "abe 2", "zed 2", "abe 2"]);
+}
@@ -0,0 +1,208 @@
+use aeroflow_compiler::compile;
+use aeroflow_compiler::ir::Value;
+use aeroflow_runtime::mailbox::{MailboxConfig, MailboxMetrics, Overflow};
+use aeroflow_runtime::scheduler::{ActorFailure, RunReport, RunStop};
+use aeroflow_runtime::trace::Tracer;
+use aeroflow_runtime::{ActorCell, MemorySink, MessageData, Program, Scheduler, VMActor};
+use serde_json::Value as Json;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::sync::Arc;
+
+/// Everything a run lets the outside world see.
+#[derive(Debug, PartialEq)]
+struct Observed {
+    report: RunReport,
+    output: Json,
+    trace: Json,
+    failures: Vec<ActorFailure>,
+    mailboxes: BTreeMap<String, MailboxMetrics>,
+    dead_letters: Vec<String>,
+    snapshot: Vec<u8>,
+}
+
+fn observe(setup: &dyn Fn(&Scheduler), threads: usize, max_time: Option<u64>) -> Observed {
+    let scheduler = Scheduler::shared();
+    let sink = Arc::new(MemorySink::new());
+    let tracer = Arc::new(Tracer::with_capacity(100_000));
+    scheduler.set_render_sink(sink.clone());
+    scheduler.set_tracer(tracer.clone());
+    scheduler.set_parallelism(threads).unwrap();
+    setup(&scheduler);
+    let report = scheduler.run(max_time);
+    Observed {
+        report,
+        output: serde_json::to_value(sink.events()).unwrap(),
+        trace: serde_json::to_value(tracer.events()).unwrap(),
+        failures: scheduler.failures(),
+        mailboxes: scheduler.mailbox_metrics(),
+        dead_letters: scheduler.dead_letters().iter().map(|d| format!("{:?}", d)).collect(),
+        snapshot: scheduler.snapshot().unwrap().to_bytes().unwrap(),
+    }
+}
+
+/// Runs single-threaded and on several thread counts, requiring identical
+/// observations.
+fn differential_with(setup: &dyn Fn(&Scheduler), max_time: Option<u64>) -> Observed {
+    let expected = observe(setup, 1, max_time);
+    for threads in [2, 4, 8] {
+        assert_eq!(observe(setup, threads, max_time), expected, "{} threads diverged", threads);
+    }
+    expected
+}
+
+fn differential(source: &str, max_time: Option<u64>) -> Observed {
+    let program = Program::from(compile(source).unwrap());
+    differential_with(&|scheduler| {
+        scheduler.spawn_program(program.clone());
+    }, max_time)
+}
+
+/// A random mesh of actors that forward and fan out hops, doing some work
+/// per message.
+fn mesh(seed: u64, actors: u64, depth: u64) -> String {
+    let mut rng = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+    let mut next = |n: u64| {
+        rng ^= rng << 13;
+        rng ^= rng >> 7;
+        rng ^= rng << 17;
+        rng % n
+    };
+    let mut src = String::new();
+    for i in 0..actors {
+        let (a, b, c) = (next(actors), next(actors), next(actors));
+        writeln!(src, r#"
+actor A{i} {{
+    state acc = {i}
+    on Hop(n, k) {{
+        let acc = acc * {m} + n - k
+        let i = 0
+        while i < {work} {{
+            let i = i + 1
+        }}
+        print("A{i} " + acc)
+        if k > 0 {{
+            if acc > {threshold} {{
+                send("A{a}", "Hop", n + 1, k - 1)
+            }} else {{
+                send("A{b}", "Hop", n + 2, k - 1)
+            }}
+            send("A{c}", "Hop", n, k - 2)
+        }}
+    }}
+}}"#, m = 2 + next(3), work = 10 + next(40), threshold = next(500)).unwrap();
+    }
+    for start in 0..3 {
+        writeln!(src, r#"send("A{}", "Hop", {}, {})"#, next(actors), start, depth).unwrap();
+    }
+    src
+}
+
+#[test]
+fn random_meshes_match_the_sequential_scheduler() {
+    for seed in 0..12 {
+        let observed = differential(&mesh(seed, 2 + seed % 7, 6), None);
+        assert!(observed.report.steps > 20);
+    }
+}
+
+#[test]
+fn bounded_mailboxes_match() {
+    for overflow in [Overflow::DropNew, Overflow::DropOld, Overflow::Block, Overflow::Fail] {
+        for seed in 0..4 {
+            let program = Program::from(compile(&mesh(seed, 4, 6)).unwrap());
+            let observed = differential_with(&|scheduler| {
+                scheduler.set_default_mailbox(MailboxConfig::bounded(2, overflow));
+                scheduler.spawn_program(program.clone());
+            }, None);
+            let dropped: u64 = observed.mailboxes.values().map(|m| m.dropped).sum();
+            assert_eq!(dropped == 0, overflow == Overflow::Block, "{:?} seed {}", overflow, seed);
+        }
+    }
+}
+
+#[test]
+fn time_limits_and_failures_match() {
+    let source = r#"
+actor Worker {
+    state done = 0
+    on Job(n) {
+        let done = done + n
+        send("Boss", "Done", n)
+        send("Nobody", "Job", n)
+        if n > 0 {
+            send("Worker", "Job", n - 1)
+        }
+    }
+}
+actor Boss {
+    state total = 0
+    on Done(n) {
+        let total = total + n
+        print("total " + total)
+    }
+}
+actor Flaky {
+    on Job(n) {
+        print("flaky " + n)
+    }
+}
+send("Worker", "Job", 8)
+send("Flaky", "Job", 3)
+send("Flaky", "Oops", 4)
+"#;
+    let full = differential(source, None);
+    assert_eq!(full.report.stop, RunStop::Quiescent);
+    assert_eq!(full.failures.len(), 1);
+    assert_eq!(full.failures[0].actor, "Flaky");
+    assert_eq!(full.report.logical_time, 11);
+    // Cut the run early, midway and late
+    for limit in [2, 5, 8] {
+        let partial = differential(source, Some(limit));
+        assert_eq!(partial.report.stop, RunStop::TimeLimit, "limit {}", limit);
+        assert_eq!(partial.report.logical_time, limit, "limit {}", limit);
+        assert!(0 < partial.report.steps && partial.report.steps < full.report.steps, "limit {}", limit);
+        // A cut run did what the full run did up to the limit, and nothing else
+        let (output, trace) = (partial.output.as_array().unwrap(), partial.trace.as_array().unwrap());
+        assert!(!output.is_empty() && output.len() < full.output.as_array().unwrap().len(), "limit {}", limit);
+        assert_eq!(output[..], full.output.as_array().unwrap()[..output.len()], "limit {}", limit);
+        assert_eq!(trace[..], full.trace.as_array().unwrap()[..trace.len()], "limit {}", limit);
+        assert_eq!(partial.failures[..], full.failures[..partial.failures.len()], "limit {}", limit);
+    }
+}
+
+#[test]
+fn actors_reading_siblings_run_in_order() {
+    let counter = compile(r#"
+actor Counter {
+    state count = 0
+    on Add(n) {
+        let count = count + n
+        send("Watch", "Look")
+    }
+}
+"#).unwrap();
+    // `render distributed state` reads another actor, so Watch runs alone
+    let watch = compile(r#"
+actor Watch {
+    on Look() {
+        render distributed state { Counter.count }
+    }
+}
+"#).unwrap();
+    let observed = differential_with(&|scheduler| {
+        for (name, program) in [("Counter", &counter), ("Watch", &watch)] {
+            let actor = VMActor::declared(program.clone(), name).unwrap();
+            scheduler.spawn(ActorCell::new(name.to_string(), Box::new(actor)));
+        }
+        for n in 1..=3 {
+            scheduler.send("Counter".to_string(), MessageData::from(Value::List(vec![Value::String("Add".to_string()), Value::Number(n as f64)])), "test".to_string());
+            scheduler.send("Watch".to_string(), MessageData::from(Value::List(vec![Value::String("Look".to_string())])), "test".to_string());
+        }
+    }, None);
+    // Each look sees exactly the adds delivered before it
+    let counts: Vec<&Json> = observed.output.as_array().unwrap().iter()
+        .map(|e| &e["data"]["entries"][0]["value"]["Number"])
+        .collect();
+    assert_eq!(counts, [1.0, 3.0, 6.0, 6.0, 6.0, 6.0].map(Json::from).iter().collect::<Vec<_>>());
+}
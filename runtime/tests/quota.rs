@@ -0,0 +1,137 @@
+use aeroflow_compiler::compile;
+use aeroflow_compiler::ir::{Chunk, Value};
+use aeroflow_runtime::quota::{GlobalQuota, Quota, QuotaExceeded, QuotaScope, Resource};
+use aeroflow_runtime::{MessageData, Scheduler};
+use std::sync::Arc;
+
+/// `Spinner` may run 200 instructions per message and `Keeper` hold 4KB;
+/// `Keeper` is restarted when it fails.
+const LIMITED: &str = r#"
+@quota(instructions: 200)
+actor Spinner {
+    on Spin(n) {
+        let i = 0
+        while i < n {
+            let i = i + 1
+        }
+    }
+}
+@quota(memory: "4KB")
+actor Keeper {
+    state kept = ""
+    on Keep(s) {
+        let kept = s
+    }
+}
+supervise OneForOne(max: 5, within: 100) {
+    Keeper
+}
+"#;
+
+/// Two actors that keep what they are sent.
+const KEEPERS: &str = r#"
+actor Left {
+    state kept = ""
+    on Keep(s) {
+        let kept = s
+    }
+}
+actor Right {
+    state kept = ""
+    on Keep(s) {
+        let kept = s
+    }
+}
+"#;
+
+fn start(chunk: &Chunk) -> Arc<Scheduler> {
+    let scheduler = Scheduler::shared();
+    scheduler.spawn_program(chunk);
+    scheduler.run(None);
+    scheduler
+}
+
+fn send(scheduler: &Scheduler, to: &str, event: &str, arg: serde_json::Value) {
+    scheduler.send(to.to_string(), MessageData::Json(serde_json::json!([event, arg]).to_string()), "host".to_string());
+    scheduler.run(None);
+}
+
+fn quotas(scheduler: &Scheduler) -> Vec<(String, QuotaExceeded)> {
+    scheduler.failures().into_iter().map(|f| (f.actor, f.quota.expect("a quota failure"))).collect()
+}
+
+#[test]
+fn annotated_quotas_fail_the_actor_that_goes_over() {
+    let scheduler = start(&compile(LIMITED).unwrap());
+    send(&scheduler, "Spinner", "Spin", 10.into());
+    send(&scheduler, "Keeper", "Keep", "x".repeat(1000).into());
+    assert!(scheduler.failures().is_empty());
+    let usage = scheduler.usage();
+    assert!(usage["Spinner"].instructions > 0 && usage["Spinner"].instructions <= 200);
+    assert!(usage["Keeper"].memory > 1000 && usage["Keeper"].memory < 4096);
+
+    send(&scheduler, "Spinner", "Spin", 1000.into());
+    send(&scheduler, "Keeper", "Keep", "x".repeat(5000).into());
+    let failures = quotas(&scheduler);
+    assert_eq!(failures[0], ("Spinner".to_string(), QuotaExceeded { resource: Resource::Instructions, scope: QuotaScope::Actor, limit: 200, used: 201 }));
+    let (actor, memory) = &failures[1];
+    assert_eq!((actor.as_str(), memory.resource, memory.scope, memory.limit), ("Keeper", Resource::Memory, QuotaScope::Actor, 4096));
+    assert!(memory.used > 5000);
+    assert_eq!(scheduler.failures()[0].reason, "Actor instruction quota of 200 instructions exceeded: used 201");
+
+    // Its supervisor restarted `Keeper` empty; `Spinner` carries on unsupervised
+    assert_eq!(scheduler.read_field("Keeper", "kept"), Some(Value::String(String::new())));
+    assert!(scheduler.usage()["Keeper"].memory < 1000);
+    send(&scheduler, "Spinner", "Spin", 10.into());
+    assert_eq!(scheduler.failures().len(), 2);
+}
+
+#[test]
+fn host_quotas_fill_in_what_annotations_leave_unset() {
+    let scheduler = Scheduler::shared();
+    scheduler.set_quota(Quota { memory: Some(2048), instructions: Some(1000) });
+    scheduler.spawn_program(compile(LIMITED).unwrap());
+    scheduler.run(None);
+    // Spinner keeps its own instruction limit and takes the default memory limit
+    send(&scheduler, "Spinner", "Spin", 100.into());
+    send(&scheduler, "Keeper", "Keep", "x".repeat(3000).into());
+    assert_eq!(quotas(&scheduler).iter().map(|(a, q)| (a.as_str(), q.resource)).collect::<Vec<_>>(), [("Spinner", Resource::Instructions)]);
+}
+
+#[test]
+fn global_quotas_cover_every_actor_at_any_thread_count() {
+    let chunk = compile(KEEPERS).unwrap();
+    let run = |threads: usize| {
+        let scheduler = Scheduler::shared();
+        scheduler.set_parallelism(threads).unwrap();
+        scheduler.set_global_quota(GlobalQuota { memory: Some(5000), actors: None });
+        scheduler.spawn_program(&chunk);
+        scheduler.run(None);
+        for to in ["Left", "Right"] {
+            scheduler.send(to.to_string(), MessageData::Json(serde_json::json!(["Keep", "x".repeat(3000)]).to_string()), "host".to_string());
+        }
+        scheduler.run(None);
+        quotas(&scheduler)
+    };
+    let failures = run(1);
+    assert_eq!(failures.len(), 1);
+    let (actor, quota) = &failures[0];
+    assert_eq!((actor.as_str(), quota.resource, quota.scope, quota.limit), ("Right", Resource::Memory, QuotaScope::Global, 5000));
+    assert!(quota.used > 6000);
+    assert_eq!(run(2), failures);
+    assert_eq!(run(4), failures);
+}
+
+#[test]
+fn actors_past_the_global_limit_are_not_spawned() {
+    let scheduler = Scheduler::shared();
+    scheduler.set_global_quota(GlobalQuota { memory: None, actors: Some(2) });
+    scheduler.spawn_program(compile(KEEPERS).unwrap());
+    assert_eq!(scheduler.actor_ids(), ["Left", "Right"]);
+    assert_eq!(quotas(&scheduler), [("main".to_string(), QuotaExceeded { resource: Resource::Actors, scope: QuotaScope::Global, limit: 2, used: 3 })]);
+
+    let error = compile("@quota(cpu: 5)\nactor A {\n}").unwrap_err();
+    assert_eq!(error.to_string(), "Syntax error: Unknown quota 'cpu' (expected memory or instructions)");
+    let error = compile("@pinned\nactor A {\n}").unwrap_err();
+    assert_eq!(error.to_string(), "Syntax error: Unknown annotation '@pinned' (expected @quota)");
+}
@@ -0,0 +1,27 @@
+use aeroflow_runtime::{Engine, JsonLinesSink, MemorySink, RenderEvent, RenderSink};
+use aeroflow_compiler::ir::Value;
+use std::sync::Arc;
+
+#[test]
+fn memory_sink_collects_structured_events() {
+    let sink = Arc::new(MemorySink::new());
+    let mut engine = Engine::new();
+    engine.set_render_sink(sink.clone());
+    engine.load_source(r#"render { "a" } render { 1 + 2 } render { distributed state { } }"#).unwrap();
+
+    assert_eq!(sink.lines(), vec!["a", "3"]);
+    assert!(matches!(sink.events()[2], RenderEvent::State(_)));
+}
+
+#[test]
+fn json_lines_sink_writes_one_object_per_event() {
+    let sink = JsonLinesSink::new(Vec::new());
+    sink.emit(RenderEvent::Value(Value::Number(7.0)));
+    sink.emit(RenderEvent::Value(Value::String("x".into())));
+
+    let out = String::from_utf8(sink.into_inner()).unwrap();
+    let lines: Vec<_> = out.lines().collect();
+    assert_eq!(lines.len(), 2);
+    let first: RenderEvent = serde_json::from_str(lines[0]).unwrap();
+    assert_eq!(first, RenderEvent::Value(Value::Number(7.0)));
+}
@@ -0,0 +1,91 @@
+use aeroflow_compiler::compile;
+use aeroflow_runtime::mailbox::MessageData;
+use aeroflow_runtime::replay::{InputTiming, Recording, Replayer};
+use aeroflow_runtime::{Capabilities, CapabilityGuard, MemorySink, Program, Scheduler};
+use std::sync::Arc;
+
+const COUNTER: &str = r#"
+actor Counter {
+    state total = 0
+    on Add(n) {
+        let total = total + n * rand()
+        print("total " + total + " mode " + env("AEROFLOW_REPLAY_MODE"))
+        if n > 0 {
+            send("Counter", "Add", n - 1)
+        }
+    }
+}
+
+send("Counter", "Add", 3)
+"#;
+
+fn record(source: &str) -> Recording {
+    std::env::set_var("AEROFLOW_REPLAY_MODE", "fast");
+    let program = Program::from(compile(source).unwrap());
+    let scheduler = Scheduler::shared();
+    scheduler.set_render_sink(Arc::new(MemorySink::new()));
+    scheduler.set_grants(CapabilityGuard::new(Capabilities::ACTOR | Capabilities::ENV_READ));
+    let recorder = scheduler.record(&program);
+    scheduler.spawn_program(program);
+    scheduler.run(Some(3));
+    scheduler.send("Counter".to_string(), MessageData::Signal("Add".to_string()), "host".to_string());
+    scheduler.run(None);
+    recorder.recording()
+}
+
+#[test]
+fn replay_follows_the_recorded_path() {
+    let recording = record(COUNTER);
+    assert!(recording.steps.len() > 5);
+    assert!(recording.steps.iter().all(|s| s.state_hash.is_some()));
+    assert!(recording.env.iter().all(|r| r.key == "AEROFLOW_REPLAY_MODE" && r.value == "fast"));
+    assert_eq!(recording.grants.as_ref().map(|g| g.guard()), Some(CapabilityGuard::new(Capabilities::ACTOR | Capabilities::ENV_READ)));
+    let host = recording.inputs.iter().find(|i| i.sender == "host").unwrap();
+    assert_eq!(host.timing, InputTiming::Next);
+    assert!(host.before_step > 0);
+
+    let mut replayer = Replayer::new(Recording::from_json(&recording.to_json().unwrap()).unwrap());
+    replayer.scheduler().set_render_sink(Arc::new(MemorySink::new()));
+    let report = replayer.run().unwrap();
+    assert_eq!(report.steps, recording.steps.len());
+    assert!(replayer.is_done());
+    assert_eq!(replayer.step().unwrap(), None);
+}
+
+#[test]
+fn changed_program_reports_the_first_divergent_step() {
+    let recording = record(COUNTER);
+    let changed = COUNTER.replace("if n > 0", "if n > 1");
+    let mut replayer = Replayer::with_program(recording.clone(), compile(&changed).unwrap());
+    replayer.scheduler().set_render_sink(Arc::new(MemorySink::new()));
+    let divergence = replayer.run().unwrap_err();
+    // `n > 1` only differs once n reaches 1: that step's state still matches,
+    // but the recorded `Add(0)` that followed it is never sent.
+    let last = recording.steps.len() - 1;
+    assert_eq!(divergence.step, last);
+    assert_eq!(divergence.what, "delivery");
+    assert_eq!(divergence.to_string(), format!("diverged at step {}: delivery was no message, recorded Counter <- Counter @ T=5 #7", last));
+}
+
+#[test]
+fn changed_state_is_caught_by_the_hash() {
+    let recording = record(COUNTER);
+    let changed = COUNTER.replace("total + n * rand()", "total + n * rand() + 1");
+    let mut replayer = Replayer::with_program(recording.clone(), compile(&changed).unwrap());
+    replayer.scheduler().set_render_sink(Arc::new(MemorySink::new()));
+    let divergence = replayer.run().unwrap_err();
+    let first = recording.steps.iter().position(|s| s.sender == "main").unwrap();
+    assert_eq!(divergence.step, first);
+    assert_eq!(divergence.what, "state of 'Counter'");
+}
+
+#[test]
+fn changed_env_read_diverges() {
+    let mut recording = record(COUNTER);
+    let dropped = recording.env.pop().unwrap();
+    let mut replayer = Replayer::new(recording.clone());
+    replayer.scheduler().set_render_sink(Arc::new(MemorySink::new()));
+    let divergence = replayer.run().unwrap_err();
+    assert_eq!(divergence.what, "env read");
+    assert_eq!(divergence.step, dropped.step);
+}
@@ -0,0 +1,93 @@
+use aeroflow_compiler::compile;
+use aeroflow_compiler::ir::Value;
+use aeroflow_runtime::scheduler::{RunStop, RunReport};
+use aeroflow_runtime::{MemorySink, MessageData, Scheduler};
+use std::sync::Arc;
+
+const PROGRAM: &str = r#"
+actor Counter {
+    state count = 0
+
+    on Increment(by) {
+        let count = count + by
+    }
+
+    on Report {
+        send("Logger", "Log", count)
+    }
+}
+
+actor Logger {
+    state last = nil
+
+    on Log(value) {
+        let last = value
+        print("logged " + value)
+    }
+}
+
+send("Counter", "Increment", 2)
+send("Counter", "Increment", 3)
+send("Counter", "Report")
+"#;
+
+fn boot(source: &str) -> (Arc<Scheduler>, Arc<MemorySink>) {
+    let scheduler = Scheduler::shared();
+    let sink = Arc::new(MemorySink::new());
+    scheduler.set_render_sink(sink.clone());
+    scheduler.spawn_program(compile(source).unwrap());
+    (scheduler, sink)
+}
+
+#[test]
+fn declared_actors_handle_messages_until_quiescent() {
+    let (scheduler, sink) = boot(PROGRAM);
+    let report = scheduler.run(None);
+
+    // Two actor starts and main at T=1, three sends from main at T=2 and
+    // one from Counter at T=3
+    assert_eq!(report, RunReport { steps: 7, logical_time: 3, stop: RunStop::Quiescent });
+    assert_eq!(scheduler.read_field("Counter", "count"), Some(Value::Number(5.0)));
+    assert_eq!(sink.lines(), vec!["logged 5"]);
+    assert!(scheduler.failures().is_empty());
+}
+
+#[test]
+fn run_stops_at_logical_time_limit() {
+    let (scheduler, _sink) = boot(PROGRAM);
+    scheduler.run(None);
+    scheduler.send_at("Counter".into(), MessageData::Signal("Report".into()), "test".into(), 1_000);
+
+    assert_eq!(scheduler.run(Some(500)), RunReport { steps: 0, logical_time: 3, stop: RunStop::TimeLimit });
+
+    // Report runs at T=1000; the Log it sends is stamped after the limit
+    let report = scheduler.run(Some(1_000));
+    assert_eq!((report.steps, report.stop), (1, RunStop::TimeLimit));
+
+    assert_eq!(scheduler.run(None), RunReport { steps: 1, logical_time: 1_001, stop: RunStop::Quiescent });
+}
+
+#[test]
+fn handler_errors_are_reported_as_failures() {
+    let (scheduler, _sink) = boot(r#"
+        actor Worker {
+            on Work { missing() }
+        }
+        send("Worker", "Work")
+        send("Worker", "Rest")
+    "#);
+    scheduler.run(None);
+
+    let failures = scheduler.failures();
+    let reasons: Vec<_> = failures.iter().map(|f| (f.actor.as_str(), f.reason.as_str())).collect();
+    assert_eq!(reasons, vec![
+        ("Worker", "Unknown function 'missing'"),
+        ("Worker", "Unknown function 'Worker.Rest'"),
+    ]);
+}
+
+#[test]
+fn syntax_errors_are_returned_not_raised() {
+    let err = compile("let x = ").unwrap_err();
+    assert!(err.to_string().starts_with("Syntax error"));
+}
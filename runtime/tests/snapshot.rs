@@ -0,0 +1,138 @@
+use aeroflow_compiler::compile;
+use aeroflow_compiler::ir::{Chunk, Value};
+use aeroflow_runtime::capability::Capabilities;
+use aeroflow_runtime::snapshot::AFS_VERSION;
+use aeroflow_runtime::{Actor, ActorCell, Context, MemorySink, Message, Scheduler, Snapshot, VM};
+use std::sync::Arc;
+
+const RALLY: &str = r#"
+actor Ping {
+    state hits = 0
+    on Ball(n) {
+        let hits = hits + 1
+        print("ping " + n + " at " + time + " roll " + rand())
+        if n > 0 {
+            send("Pong", "Ball", n - 1)
+        }
+    }
+}
+
+actor Pong {
+    state hits = 0
+    on Ball(n) {
+        let hits = hits + 1
+        print("pong " + n + " roll " + rand())
+        send("Ping", "Ball", n - 1)
+    }
+}
+
+send("Ping", "Ball", 12)
+send("Pong", "Ball", 7)
+"#;
+
+fn start(chunk: &Chunk) -> (Arc<Scheduler>, Arc<MemorySink>) {
+    let scheduler = Scheduler::shared();
+    let sink = Arc::new(MemorySink::new());
+    scheduler.set_render_sink(sink.clone());
+    scheduler.spawn_program(chunk);
+    (scheduler, sink)
+}
+
+fn output(sink: &MemorySink) -> Vec<String> {
+    sink.take().into_iter().map(|e| format!("{:?}", e)).collect()
+}
+
+#[test]
+fn split_run_matches_uninterrupted_run() {
+    let chunk = compile(RALLY).unwrap();
+    let (whole, sink) = start(&chunk);
+    let report = whole.run(None);
+    let expected = output(&sink);
+    assert!(expected.len() > 10);
+
+    for split in [1, 4, 9] {
+        let (first, sink) = start(&chunk);
+        first.run(Some(split));
+        let mut seen = output(&sink);
+        let bytes = first.snapshot().unwrap().to_bytes().unwrap();
+        drop(first);
+
+        let (resumed, sink) = (Scheduler::shared(), Arc::new(MemorySink::new()));
+        resumed.set_render_sink(sink.clone());
+        resumed.restore_program(&Snapshot::from_bytes(&bytes).unwrap(), &chunk).unwrap();
+        let rest = resumed.run(None);
+        seen.extend(output(&sink));
+
+        assert_eq!(seen, expected, "split at T={}", split);
+        assert_eq!(rest.logical_time, report.logical_time);
+        assert_eq!(resumed.read_field("Ping", "hits"), whole.read_field("Ping", "hits"));
+        assert_eq!(resumed.snapshot().unwrap().to_bytes().unwrap(), whole.snapshot().unwrap().to_bytes().unwrap());
+    }
+}
+
+#[test]
+fn snapshot_captures_queue_clock_and_sequence() {
+    let chunk = compile(RALLY).unwrap();
+    let (scheduler, _sink) = start(&chunk);
+    scheduler.run(Some(3));
+    let snapshot = scheduler.snapshot().unwrap();
+    assert_eq!(snapshot.actors.iter().map(|a| a.id.as_str()).collect::<Vec<_>>(), ["Ping", "Pong", "main"]);
+    assert!(!snapshot.queue.is_empty());
+    let times: Vec<u64> = snapshot.queue.iter().map(|q| q.message.logical_time).collect();
+    assert!(times.windows(2).all(|w| w[0] <= w[1]), "queue is in delivery order: {:?}", times);
+    assert!(snapshot.logical_clock < times[0], "everything queued is due after the latest delivery");
+    assert_eq!(snapshot.clocks.get("main"), Some(&1));
+
+    let bytes = snapshot.to_bytes().unwrap();
+    assert_eq!(&bytes[..4], b"AFS1");
+    assert_eq!(Snapshot::from_bytes(&bytes).unwrap().to_bytes().unwrap(), bytes);
+    assert!(Snapshot::from_bytes(b"nope").is_err());
+}
+
+#[test]
+fn snapshots_carry_their_format_version_and_program() {
+    let chunk = compile(RALLY).unwrap();
+    let (scheduler, _sink) = start(&chunk);
+    scheduler.run(Some(3));
+    let mut bytes = scheduler.snapshot().unwrap().to_bytes().unwrap();
+    assert_eq!(&bytes[4..6], &AFS_VERSION.to_le_bytes());
+
+    let snapshot = Snapshot::from_bytes(&bytes).unwrap();
+    let other = compile(&RALLY.replace("send(\"Pong\", \"Ball\", 7)", "send(\"Pong\", \"Ball\", 8)")).unwrap();
+    let err = Scheduler::shared().restore_program(&snapshot, &other).unwrap_err();
+    assert!(err.to_string().contains("different program"), "{}", err);
+    Scheduler::shared().restore_program(&snapshot, &chunk).unwrap();
+
+    bytes[4] = bytes[4].wrapping_add(1);
+    let err = Snapshot::from_bytes(&bytes).unwrap_err();
+    assert!(err.to_string().contains("unsupported .afs version"), "{}", err);
+}
+
+#[test]
+fn vm_state_round_trips_capabilities_and_rng() {
+    let mut vm = VM::new();
+    vm.set_global("score", Value::Number(3.0));
+    vm.set_capabilities(Capabilities::NET_RECV | Capabilities::FS_READ);
+    vm.set_seed(42);
+    let state = vm.save_state();
+
+    let mut restored = VM::new();
+    restored.restore_state(state.clone());
+    assert_eq!(restored.capabilities(), Capabilities::NET_RECV | Capabilities::FS_READ);
+    assert_eq!(restored.get_globals().get("score"), Some(&Value::Number(3.0)));
+    assert_eq!(restored.save_state(), state);
+}
+
+struct Opaque;
+
+impl Actor for Opaque {
+    fn receive(&mut self, _msg: Message, _ctx: &mut Context) {}
+}
+
+#[test]
+fn actors_without_snapshot_support_are_reported() {
+    let scheduler = Scheduler::new();
+    scheduler.spawn(ActorCell::new("opaque".to_string(), Box::new(Opaque)));
+    let err = scheduler.snapshot().unwrap_err();
+    assert!(err.to_string().contains("'opaque'"), "{}", err);
+}
@@ -0,0 +1,279 @@
+use aeroflow_compiler::compile;
+use aeroflow_compiler::ir::{Chunk, Value};
+use aeroflow_runtime::supervisor::{ChildSpec, Strategy, Supervisor};
+use aeroflow_runtime::trace::Tracer;
+use aeroflow_runtime::{Actor, Context, MemorySink, Message, MessageData, RenderEvent, Scheduler, Snapshot};
+use std::sync::Arc;
+
+/// Three counters that crash on `Crash`; `{strategy}` and `{limits}` are
+/// filled in per test.
+const COUNTERS: &str = r#"
+actor Counter(name, base) {
+    state count = 0
+    let count = base
+    on Add(n) {
+        let count = count + n
+        print(name + " " + count)
+    }
+    on Crash() {
+        boom()
+    }
+}
+actor Second(base) {
+    state count = 0
+    let count = base
+    on Add(n) {
+        let count = count + n
+        print("second " + count)
+    }
+}
+actor Third {
+    state count = 0
+    on Add(n) {
+        let count = count + n
+        print("third " + count)
+    }
+}
+supervise Root: {strategy}{limits} {
+    Counter("first", 10)
+    Second(20)
+    Third
+}
+send("Counter", "Add", 1)
+send("Second", "Add", 1)
+send("Third", "Add", 1)
+send("Counter", "Crash")
+send("Counter", "Add", 1)
+send("Second", "Add", 1)
+send("Third", "Add", 1)
+"#;
+
+fn program(strategy: &str, limits: &str) -> Chunk {
+    compile(&COUNTERS.replace("{strategy}", strategy).replace("{limits}", limits)).unwrap()
+}
+
+fn start(chunk: &Chunk) -> (Arc<Scheduler>, Arc<MemorySink>, Arc<Tracer>) {
+    let scheduler = Scheduler::shared();
+    let sink = Arc::new(MemorySink::new());
+    let tracer = Arc::new(Tracer::new());
+    scheduler.set_render_sink(sink.clone());
+    scheduler.set_tracer(tracer.clone());
+    scheduler.spawn_program(chunk);
+    (scheduler, sink, tracer)
+}
+
+/// Events as JSON, with each state snapshot parsed so field order is moot.
+fn events(tracer: &Tracer) -> serde_json::Value {
+    let mut events = serde_json::to_value(tracer.events()).unwrap();
+    for event in events.as_array_mut().unwrap() {
+        let state = event["state_snapshot"].as_str().unwrap().to_string();
+        event["state_snapshot"] = serde_json::from_str(&state).unwrap();
+    }
+    events
+}
+
+fn lines(sink: &MemorySink) -> Vec<String> {
+    sink.take().into_iter().map(|e| match e {
+        RenderEvent::Value(Value::String(s)) => s,
+        other => format!("{:?}", other),
+    }).collect()
+}
+
+#[test]
+fn strategies_choose_which_children_restart() {
+    let before = ["first 11", "second 21", "third 1"];
+    for (strategy, after) in [
+        ("OneForOne", ["first 11", "second 22", "third 2"]),
+        ("AllForOne", ["first 11", "second 21", "third 1"]),
+        ("RestForOne", ["first 11", "second 21", "third 1"]),
+    ] {
+        let (scheduler, sink, _) = start(&program(strategy, ""));
+        scheduler.run(None);
+        let mut expected = before.to_vec();
+        expected.extend(after);
+        assert_eq!(lines(&sink), expected, "{}", strategy);
+        assert_eq!(scheduler.failures().len(), 1);
+    }
+
+    // RestForOne leaves children declared before the failed one alone
+    let source = COUNTERS.replace("{strategy}", "RestForOne").replace("{limits}", "")
+        .replace("send(\"Counter\", \"Crash\")", "send(\"Second\", \"Crash\")")
+        .replace("on Add(n) {\n        let count = count + n\n        print(\"second \" + count)\n    }", "on Add(n) {\n        let count = count + n\n        print(\"second \" + count)\n    }\n    on Crash() {\n        boom()\n    }");
+    let (scheduler, sink, _) = start(&compile(&source).unwrap());
+    scheduler.run(None);
+    assert_eq!(lines(&sink)[3..], ["first 12", "second 21", "third 1"]);
+}
+
+#[test]
+fn restarts_are_traced() {
+    let (scheduler, _, tracer) = start(&program("AllForOne", ""));
+    scheduler.run(None);
+    let restarts: Vec<(String, String, u64)> = tracer.events().iter()
+        .filter(|e| e.kind() == "restart")
+        .map(|e| (e.actor_id.clone(), e.input.sender.clone(), e.logical_time))
+        .collect();
+    assert_eq!(restarts, [
+        ("Counter".to_string(), "Root".to_string(), 2),
+        ("Second".to_string(), "Root".to_string(), 2),
+        ("Third".to_string(), "Root".to_string(), 2),
+    ]);
+    // Each restart links to the start delivery that follows it
+    let events = tracer.events();
+    for (i, event) in events.iter().enumerate().filter(|(_, e)| e.kind() == "restart") {
+        let next = &events[i + 1];
+        assert_eq!((next.kind().as_str(), next.input.sequence_id), ("start", event.sent[0]));
+    }
+}
+
+#[test]
+fn too_many_restarts_escalate() {
+    let source = r#"
+actor Flaky {
+    state lives = 0
+    on Crash() {
+        let lives = lives + 1
+        boom()
+    }
+    on Ping() {
+        print("flaky " + lives)
+    }
+}
+actor Steady {
+    state pings = 0
+    on Ping() {
+        let pings = pings + 1
+        print("steady " + pings)
+    }
+}
+supervise Top: OneForOne(max: 1, within: 100) {
+    Steady
+    supervise Inner: OneForOne(max: 1, within: 100) {
+        Flaky
+    }
+}
+send("Steady", "Ping")
+send("Flaky", "Crash")
+send("Flaky", "Crash")
+send("Flaky", "Ping")
+send("Steady", "Ping")
+send("Flaky", "Crash")
+send("Flaky", "Crash")
+send("Flaky", "Ping")
+send("Steady", "Ping")
+"#;
+    let (scheduler, sink, _) = start(&compile(source).unwrap());
+    scheduler.run(None);
+    // Inner restarts Flaky once; the second crash makes Inner fail, and Top
+    // restarts it. Two more crashes fail Inner again, past Top's limit, so
+    // Top gives up and its actors stop.
+    assert_eq!(lines(&sink), ["steady 1", "flaky 0", "steady 2"]);
+    let failures = scheduler.failures();
+    assert_eq!(failures.len(), 5);
+    assert_eq!(failures[4].actor, "Top");
+    assert_eq!(failures[4].reason, "gave up after 1 restart(s) within 100 ticks");
+    assert!(scheduler.actor_ids().iter().all(|id| id == "main"));
+    let lost: Vec<String> = scheduler.dead_letters().iter().map(|d| d.target.clone()).collect();
+    assert_eq!(lost, ["Flaky", "Steady"]);
+}
+
+#[test]
+fn restart_windows_expire() {
+    let source = r#"
+actor Flaky {
+    on Crash() {
+        boom()
+    }
+    on Later(n) {
+        after(n, "Crash")
+    }
+}
+supervise OneForOne(max: 1, within: 50) {
+    Flaky
+}
+send("Flaky", "Crash")
+send("Flaky", "Later", 60)
+"#;
+    let (scheduler, _, _) = start(&compile(source).unwrap());
+    scheduler.run(None);
+    // The second crash comes 60 ticks after the first, outside the window
+    let failures = scheduler.failures();
+    assert_eq!(failures.iter().map(|f| f.actor.as_str()).collect::<Vec<_>>(), ["Flaky", "Flaky"]);
+    assert!(scheduler.actor_ids().contains(&"Flaky".to_string()));
+}
+
+#[test]
+fn supervised_runs_match_across_threads_and_snapshots() {
+    let chunk = program("RestForOne", "(max: 5, within: 1000)");
+    let (whole, sink, tracer) = start(&chunk);
+    let report = whole.run(None);
+    let expected = lines(&sink);
+    let trace = events(&tracer);
+    let bytes = whole.snapshot().unwrap().to_bytes().unwrap();
+    assert_eq!(Snapshot::from_bytes(&bytes).unwrap().supervisors["Root"], [2]);
+
+    for threads in [2, 4] {
+        let (parallel, sink, tracer) = start(&chunk);
+        parallel.set_parallelism(threads).unwrap();
+        assert_eq!(parallel.run(None), report);
+        assert_eq!(lines(&sink), expected, "{} threads diverged", threads);
+        assert_eq!(events(&tracer), trace);
+        assert_eq!(parallel.snapshot().unwrap().to_bytes().unwrap(), bytes);
+    }
+
+    // Resumed after the crash, the restart history comes along
+    let (first, sink, _) = start(&chunk);
+    first.run(Some(1));
+    let mut seen = lines(&sink);
+    let saved = first.snapshot().unwrap().to_bytes().unwrap();
+    let resumed = Scheduler::shared();
+    resumed.set_render_sink(sink.clone());
+    resumed.restore_program(&Snapshot::from_bytes(&saved).unwrap(), &chunk).unwrap();
+    resumed.run(None);
+    seen.extend(lines(&sink));
+    assert_eq!(seen, expected);
+    assert_eq!(resumed.snapshot().unwrap().to_bytes().unwrap(), bytes);
+}
+
+/// Fails on every message after the first `budget`.
+struct Fuse {
+    budget: u32,
+    seen: u32,
+}
+
+impl Actor for Fuse {
+    fn receive(&mut self, msg: Message, ctx: &mut Context) {
+        self.seen += 1;
+        if self.seen > self.budget {
+            ctx.failure = Some(format!("blown by {}", msg.sender));
+        }
+    }
+
+    fn get_state(&self) -> String {
+        format!("{{\"seen\":{}}}", self.seen)
+    }
+}
+
+#[test]
+fn host_supervisors_restart_rust_actors() {
+    let scheduler = Scheduler::shared();
+    scheduler.set_tracer(Arc::new(Tracer::new()));
+    let mut supervisor = Supervisor::new("fuses", Strategy::OneForOne).with_intensity(2, 1000);
+    supervisor.add_child(ChildSpec::new("fuse", || Box::new(Fuse { budget: 2, seen: 0 }) as Box<dyn Actor>));
+    scheduler.supervise(supervisor);
+    for _ in 0..2 {
+        scheduler.send("fuse".to_string(), MessageData::Signal("poke".to_string()), "host".to_string());
+    }
+    scheduler.run(None);
+    // The start signal and one poke fit the budget; the restart's start is the first of the next
+    assert_eq!(scheduler.failures().len(), 1);
+    assert_eq!(scheduler.read_field("fuse", "seen"), Some(Value::Number(1.0)));
+}
+
+#[test]
+fn supervision_must_name_declared_actors() {
+    let error = compile("supervise OneForOne {\n    Ghost\n}").unwrap_err();
+    assert_eq!(error.to_string(), "supervised actor 'Ghost' is not declared");
+    let error = compile("actor A {\n}\nsupervise OneForOne {\n    A\n    A\n}").unwrap_err();
+    assert_eq!(error.to_string(), "actor 'A' is supervised twice");
+    assert!(compile("actor A {\n}\nsupervise OneForTwo {\n    A\n}").unwrap_err().to_string().contains("Unknown restart strategy"));
+}
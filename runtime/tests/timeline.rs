@@ -0,0 +1,90 @@
+use aeroflow_runtime::render::TimelineEdge;
+use aeroflow_compiler::compile;
+use aeroflow_runtime::{Actor, ActorCell, Context, Engine, MemorySink, Message, MessageData, RenderEvent, Scheduler};
+use std::sync::{Arc, Mutex};
+
+type Log = Arc<Mutex<Vec<(u64, String, String)>>>;
+
+struct Recorder(&'static str, Log);
+
+impl Actor for Recorder {
+    fn receive(&mut self, msg: Message, _ctx: &mut Context) {
+        let body = match msg.data {
+            MessageData::Text(t) | MessageData::Json(t) => t,
+            other => format!("{:?}", other),
+        };
+        self.1.lock().unwrap().push((msg.logical_time, self.0.to_string(), body));
+    }
+}
+
+const SCENARIO: &str = r#"
+render timeline {
+    A -> B at 10ms payload "ping"
+    B -> C at 20ms payload "fwd"
+    A -> C at 5ms payload 1 + 1
+}
+"#;
+
+#[test]
+fn timeline_builds_happened_before_dag() {
+    let sink = Arc::new(MemorySink::new());
+    let mut engine = Engine::new();
+    engine.set_render_sink(sink.clone());
+    engine.load_source(SCENARIO).unwrap();
+
+    let graph = match &sink.events()[0] {
+        RenderEvent::Timeline(graph) => graph.clone(),
+        other => panic!("expected timeline, got {:?}", other),
+    };
+    assert_eq!(graph.nodes.len(), 3);
+    // A->C@5 precedes A->B@10 (same sender) and B->C@20 (same receiver);
+    // A->B@10 precedes B->C@20 (B received before it sent).
+    let mut edges = graph.edges.clone();
+    edges.sort_by_key(|e| (e.from, e.to));
+    assert_eq!(edges, vec![
+        TimelineEdge { from: 0, to: 1 },
+        TimelineEdge { from: 2, to: 0 },
+        TimelineEdge { from: 2, to: 1 },
+    ]);
+
+    let dot = graph.to_dot();
+    assert!(dot.starts_with("digraph timeline {"));
+    assert!(dot.contains("e2 -> e0;"));
+    assert!(graph.to_json().contains("\"payload\""));
+}
+
+#[test]
+fn timeline_messages_run_at_their_ticks() {
+    let log: Log = Arc::new(Mutex::new(Vec::new()));
+    let mut engine = Engine::new();
+    engine.set_render_sink(Arc::new(MemorySink::new()));
+    engine.register_actor("B", Recorder("B", log.clone()));
+    engine.register_actor("C", Recorder("C", log.clone()));
+    engine.load_source(SCENARIO).unwrap();
+
+    assert_eq!(engine.run_until_idle(), 3);
+    assert_eq!(*log.lock().unwrap(), vec![
+        (5, "C".to_string(), "2".to_string()),
+        (10, "B".to_string(), "ping".to_string()),
+        (20, "C".to_string(), "fwd".to_string()),
+    ]);
+}
+
+#[test]
+fn timelines_rendered_by_actors_run_without_an_engine() {
+    let log: Log = Arc::new(Mutex::new(Vec::new()));
+    let scheduler = Scheduler::shared();
+    let sink = Arc::new(MemorySink::new());
+    scheduler.set_render_sink(sink.clone());
+    scheduler.spawn(ActorCell::new("B".to_string(), Box::new(Recorder("B", log.clone()))));
+    scheduler.spawn(ActorCell::new("C".to_string(), Box::new(Recorder("C", log.clone()))));
+    let director = format!("actor Director {{\n    on Play() {{\n{}\n    }}\n}}", SCENARIO);
+    scheduler.spawn_program(compile(&director).unwrap());
+    scheduler.run(None);
+    scheduler.send("Director".to_string(), MessageData::Json(r#"["Play"]"#.to_string()), "host".to_string());
+    scheduler.run(None);
+
+    assert!(matches!(&sink.events()[..], [RenderEvent::Timeline(graph)] if graph.nodes.len() == 3));
+    let ticks: Vec<(u64, String)> = log.lock().unwrap().iter().map(|(at, to, _)| (*at, to.clone())).collect();
+    assert_eq!(ticks, vec![(5, "C".to_string()), (10, "B".to_string()), (20, "C".to_string())]);
+}
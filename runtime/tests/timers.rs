@@ -0,0 +1,165 @@
+use aeroflow_compiler::compile;
+use aeroflow_compiler::ir::{Chunk, Value};
+use aeroflow_runtime::scheduler::{Clock, RunStop};
+use aeroflow_runtime::{MemorySink, RenderEvent, Scheduler, Snapshot};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const METRONOME: &str = r#"
+actor Metronome {
+    state ticks = 0
+    state id = 0
+    on Start() {
+        let id = every(100, "Tick")
+        after(350, "Stop")
+        print("start " + time)
+    }
+    on Tick() {
+        let ticks = ticks + 1
+        print("tick " + ticks + " " + time)
+    }
+    on Stop() {
+        if cancel(id) {
+            print("stop " + time)
+        }
+    }
+}
+actor Napper {
+    state naps = 0
+    on Nap(n) {
+        print("nap " + time)
+        sleep(n)
+        let naps = naps + 1
+        print("woke " + time)
+    }
+    on Poke() {
+        print("poked " + time + " after " + naps)
+    }
+}
+send("Metronome", "Start")
+send("Napper", "Nap", 200)
+send("Napper", "Poke")
+"#;
+
+fn start(chunk: &Chunk) -> (Arc<Scheduler>, Arc<MemorySink>) {
+    let scheduler = Scheduler::shared();
+    let sink = Arc::new(MemorySink::new());
+    scheduler.set_render_sink(sink.clone());
+    scheduler.spawn_program(chunk);
+    (scheduler, sink)
+}
+
+fn lines(sink: &MemorySink) -> Vec<String> {
+    sink.take().into_iter().map(|e| match e {
+        RenderEvent::Value(Value::String(s)) => s,
+        other => format!("{:?}", other),
+    }).collect()
+}
+
+#[test]
+fn timers_fire_at_logical_times_and_sleep_defers_messages() {
+    let (scheduler, sink) = start(&compile(METRONOME).unwrap());
+    let report = scheduler.run(None);
+    assert_eq!(lines(&sink), [
+        "start 2",
+        "nap 2",
+        "tick 1 102",
+        "woke 202",
+        // Poke arrived during the nap and waited for it
+        "poked 202 after 1",
+        "tick 2 202",
+        "tick 3 302",
+        "stop 352",
+    ]);
+    // The cancelled tick at 402 is dropped on arrival
+    assert_eq!((report.stop, report.logical_time), (RunStop::Quiescent, 402));
+    assert_eq!(scheduler.read_field("Metronome", "ticks"), Some(Value::Number(3.0)));
+}
+
+#[test]
+fn sleeping_actors_snapshot_and_resume() {
+    let chunk = compile(METRONOME).unwrap();
+    let (whole, sink) = start(&chunk);
+    whole.run(None);
+    let expected = lines(&sink);
+
+    // Mid-nap, with Poke deferred inside Napper and timers pending
+    let (first, sink) = start(&chunk);
+    first.run(Some(150));
+    let mut seen = lines(&sink);
+    let bytes = first.snapshot().unwrap().to_bytes().unwrap();
+    drop(first);
+
+    let (resumed, sink) = (Scheduler::shared(), Arc::new(MemorySink::new()));
+    resumed.set_render_sink(sink.clone());
+    resumed.restore_program(&Snapshot::from_bytes(&bytes).unwrap(), &chunk).unwrap();
+    resumed.run(None);
+    seen.extend(lines(&sink));
+    assert_eq!(seen, expected);
+    assert_eq!(resumed.snapshot().unwrap().to_bytes().unwrap(), whole.snapshot().unwrap().to_bytes().unwrap());
+}
+
+#[test]
+fn timers_keep_their_order_on_many_threads() {
+    let source = r#"
+actor Relay {
+    state seen = 0
+    on Hop(n) {
+        let seen = seen + 1
+        print("relay " + n + " " + time)
+        if n > 0 {
+            send("Echo", "Hop", n - 1)
+        }
+    }
+}
+actor Echo {
+    on Hop(n) {
+        print("echo " + n + " " + time)
+        send("Relay", "Hop", n)
+    }
+}
+actor Alarm {
+    on Set(n) {
+        after(0, "Ring", n)
+        after(3, "Ring", n + 1)
+        print("set " + time)
+    }
+    on Ring(n) {
+        print("ring " + n + " " + time)
+        sleep(2)
+        send("Relay", "Hop", 0)
+    }
+}
+send("Relay", "Hop", 6)
+send("Alarm", "Set", 10)
+send("Alarm", "Set", 20)
+"#;
+    let chunk = compile(source).unwrap();
+    let (sequential, sink) = start(&chunk);
+    let expected_report = sequential.run(None);
+    let expected = lines(&sink);
+    assert!(expected.iter().any(|l| l.starts_with("ring 21")));
+    for threads in [2, 4] {
+        let (parallel, sink) = start(&chunk);
+        parallel.set_parallelism(threads).unwrap();
+        assert_eq!(parallel.run(None), expected_report);
+        assert_eq!(lines(&sink), expected, "{} threads diverged", threads);
+        assert_eq!(parallel.snapshot().unwrap().to_bytes().unwrap(), sequential.snapshot().unwrap().to_bytes().unwrap());
+    }
+}
+
+#[test]
+fn paced_clock_waits_for_wall_time() {
+    let chunk = compile(METRONOME).unwrap();
+    let (simulated, sink) = start(&chunk);
+    simulated.run(None);
+    let expected = lines(&sink);
+
+    // One tick per 100µs: the run spans 400 ticks, so at least 40ms
+    let (paced, sink) = start(&chunk);
+    paced.set_clock(Clock::Paced(Duration::from_micros(100)));
+    let started = Instant::now();
+    paced.run(None);
+    assert!(started.elapsed() >= Duration::from_millis(40));
+    assert_eq!(lines(&sink), expected);
+}
@@ -0,0 +1,215 @@
+use aeroflow_compiler::compile;
+use aeroflow_compiler::ir::Value;
+use aeroflow_runtime::chrome_trace::{to_chrome_json, ChromeTrace, ChromeTraceSink, FLOW_WINDOW};
+use aeroflow_runtime::trace::{open_sink, RingBuffer, TraceEvent, TraceFilter, TraceReader, TraceSink, TraceSummary, Tracer};
+use aeroflow_runtime::{get_tracer, MemorySink, Message, MessageData, Program, Scheduler};
+use serde_json::{json, Value as Json};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+fn ball(sender: &str, n: i64, time: u64) -> Message {
+    let data = MessageData::from(Value::List(vec![Value::String("Ball".to_string()), Value::Number(n as f64)]));
+    Message::new(sender.to_string(), data, time, time)
+}
+
+/// A rally of `n` deliveries alternating between Ping and Pong, after both start.
+fn rally(tracer: &Tracer, n: u64) {
+    tracer.record_with("Ping", &Message::new("runtime".to_string(), MessageData::Signal(String::new()), 1, 1), || "{}".to_string());
+    tracer.record_with("Pong", &Message::new("runtime".to_string(), MessageData::Signal(String::new()), 2, 2), || "{}".to_string());
+    for t in 3..3 + n {
+        let (to, from) = if t % 2 == 1 { ("Ping", "Pong") } else { ("Pong", "Ping") };
+        tracer.record_with(to, &ball(from, t as i64, t), || format!("{{\"hits\":{}}}", t));
+    }
+}
+
+/// A path in the temp dir; whatever the test writes there is removed when
+/// it is dropped.
+struct TempPath(PathBuf);
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+impl Deref for TempPath {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempPath {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+fn temp(name: &str) -> TempPath {
+    TempPath(std::env::temp_dir().join(format!("aeroflow-trace-{}-{}", std::process::id(), name)))
+}
+
+#[test]
+fn ring_buffer_keeps_the_newest_events() {
+    let tracer = Tracer::with_capacity(4);
+    rally(&tracer, 8);
+    let times: Vec<u64> = tracer.events().iter().map(|e| e.logical_time).collect();
+    assert_eq!(times, vec![7, 8, 9, 10]);
+    assert_eq!(tracer.dropped(), 6);
+
+    let ring = Arc::new(RingBuffer::new(2));
+    let tracer = Tracer::with_capacity(0);
+    tracer.add_sink(ring.clone());
+    rally(&tracer, 3);
+    assert!(tracer.events().is_empty());
+    assert_eq!(ring.events().iter().map(|e| e.logical_time).collect::<Vec<_>>(), vec![4, 5]);
+}
+
+#[test]
+fn filters_and_sampling_skip_state_snapshots() {
+    let tracer = Tracer::new();
+    tracer.set_filter(TraceFilter { actors: ["Ping".to_string()].into(), messages: ["Ball".to_string()].into(), ..Default::default() });
+    tracer.set_sampling(2);
+    let mut snapshots = 0;
+    for t in 3..13 {
+        tracer.record_with(if t % 2 == 1 { "Ping" } else { "Pong" }, &ball("x", 0, t), || {
+            snapshots += 1;
+            String::new()
+        });
+    }
+    // Ping gets 3, 5, 7, 9, 11; every other one is kept
+    assert_eq!(tracer.events().iter().map(|e| e.logical_time).collect::<Vec<_>>(), vec![3, 7, 11]);
+    assert_eq!(snapshots, 3);
+}
+
+#[test]
+fn saved_traces_read_back_in_every_format() {
+    let tracer = Tracer::new();
+    let (jsonl, aft, json) = (temp("t.jsonl"), temp("t.aft"), temp("t.json"));
+    tracer.add_sink(open_sink(&jsonl).unwrap());
+    tracer.add_sink(open_sink(&aft).unwrap());
+    rally(&tracer, 6);
+    tracer.flush().unwrap();
+    std::fs::write(&json, tracer.export_json()).unwrap();
+
+    assert!(std::fs::read(&aft).unwrap().starts_with(b"AFT1"));
+    assert!(std::fs::metadata(&aft).unwrap().len() < std::fs::metadata(&jsonl).unwrap().len());
+    let expected = serde_json::to_value(tracer.events()).unwrap();
+    for path in [&jsonl, &aft, &json] {
+        let events: Vec<_> = TraceReader::open(path).unwrap().collect::<anyhow::Result<_>>().unwrap();
+        assert_eq!(serde_json::to_value(&events).unwrap(), expected, "{}", path.display());
+    }
+
+    let filter = TraceFilter { actors: ["Pong".to_string()].into(), from: Some(2), to: Some(6), ..Default::default() };
+    let mut summary = TraceSummary::default();
+    for event in TraceReader::open(&aft).unwrap() {
+        let event = event.unwrap();
+        if filter.matches_event(&event) {
+            summary.add(&event);
+        }
+    }
+    assert_eq!(summary.events, 3);
+    assert_eq!((summary.first_time, summary.last_time), (Some(2), Some(6)));
+    assert_eq!(summary.by_message.into_iter().collect::<Vec<_>>(), vec![("Ball".to_string(), 2), ("start".to_string(), 1)]);
+
+    std::fs::write(&aft, b"AFT1\x10\x00\x00\x00abc").unwrap();
+    assert!(TraceReader::open(&aft).unwrap().next().unwrap().is_err());
+}
+
+#[test]
+fn corrupt_binary_records_are_errors_not_eof() {
+    let read = |bytes: &[u8]| TraceReader::new(Box::new(std::io::Cursor::new(bytes.to_vec()))).unwrap().next();
+    assert!(read(b"AFT1").is_none());
+
+    let err = read(b"AFT1\x10\x00").unwrap().unwrap_err();
+    assert!(err.to_string().contains("truncated"), "{}", err);
+
+    // A huge length is rejected before anything is allocated for it
+    let err = read(b"AFT1\xff\xff\xff\xff").unwrap().unwrap_err();
+    assert!(err.to_string().contains("exceeds"), "{}", err);
+}
+
+#[test]
+fn chrome_trace_has_actor_tracks_and_send_flows() {
+    let program = Program::from(compile(r#"
+actor Ping {
+    on Ball(n) {
+        if n > 0 {
+            send("Pong", "Ball", n - 1)
+        }
+    }
+}
+actor Pong {
+    on Ball(n) {
+        send("Ping", "Ball", n - 1)
+    }
+}
+send("Ping", "Ball", 4)
+"#).unwrap());
+    // The only test here that runs a scheduler, so the global tracer is ours
+    let ring = Arc::new(RingBuffer::new(100));
+    let path = temp("chrome.json");
+    let sink = Arc::new(ChromeTraceSink::create(&path).unwrap());
+    get_tracer().add_sink(ring.clone());
+    get_tracer().add_sink(sink.clone());
+    let scheduler = Scheduler::shared();
+    scheduler.set_render_sink(Arc::new(MemorySink::new()));
+    scheduler.spawn_program(program);
+    scheduler.run(None);
+    get_tracer().clear_sinks();
+    sink.close().unwrap();
+    sink.write(&ring.events()[0]);
+
+    let events = ring.events();
+    let trace = to_chrome_json(events.clone());
+    let records = trace["traceEvents"].as_array().unwrap();
+    let streamed: Json = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+    assert_eq!(&streamed, &trace["traceEvents"]);
+
+    let of = |ph: &str| records.iter().filter(|r| r["ph"] == ph).collect::<Vec<_>>();
+    let tracks: Vec<(&Json, &Json)> = of("M").iter().filter(|r| r["name"] == "thread_name").map(|r| (&r["args"]["name"], &r["tid"])).collect();
+    assert_eq!(tracks, vec![(&json!("Ping"), &json!(1)), (&json!("Pong"), &json!(2)), (&json!("main"), &json!(3))]);
+
+    let slices = of("X");
+    assert_eq!(slices.len(), events.len());
+    assert!(slices.iter().all(|s| s["args"]["instructions"].as_u64().unwrap() > 0));
+    assert_eq!(slices.iter().filter(|s| s["name"] == "Ball").count(), 5);
+
+    // main -> Ping -> Pong -> Ping -> Pong -> Ping
+    let (starts, ends) = (of("s"), of("f"));
+    assert_eq!(starts.len(), 5);
+    let hops: Vec<(u64, u64)> = starts.iter().zip(&ends).map(|(s, f)| {
+        assert_eq!(s["id"], f["id"]);
+        assert!(s["ts"].as_u64() < f["ts"].as_u64());
+        (s["tid"].as_u64().unwrap(), f["tid"].as_u64().unwrap())
+    }).collect();
+    assert_eq!(hops, vec![(3, 1), (1, 2), (2, 1), (1, 2), (2, 1)]);
+}
+
+#[test]
+fn chrome_trace_forgets_sends_outside_the_flow_window() {
+    let mut trace = ChromeTrace::new();
+    // A handler every 1000 ticks sends a message that is never delivered
+    let step = 1000;
+    let within = (FLOW_WINDOW / step) as usize;
+    for t in (0..3 * FLOW_WINDOW).step_by(step as usize) {
+        let event = TraceEvent {
+            logical_time: t,
+            actor_id: "Ping".to_string(),
+            input: ball("Pong", 0, t),
+            state_snapshot: "{}".to_string(),
+            instructions: 1,
+            sent: vec![1_000_000 + t],
+        };
+        trace.events(&event);
+        // Trimming once a window has passed keeps the sends of the last
+        // window, plus the one just made
+        if t == 2 * FLOW_WINDOW {
+            assert_eq!(trace.pending_flows(), within + 1);
+        }
+    }
+    // The last trim was at 2 windows; every send since is still pending
+    assert_eq!(trace.pending_flows(), 2 * within);
+}
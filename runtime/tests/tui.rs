@@ -0,0 +1,84 @@
+use aeroflow_compiler::compile;
+use aeroflow_runtime::tui::{Key, TuiSession};
+
+const LOGIN: &str = r#"
+screen Login {
+    let user: string = ""
+    let remember: bool = false
+    let greeting: string = "Please sign in"
+
+    fn submit() {
+        let greeting = "Welcome, " + user
+    }
+
+    render {
+        Text {greeting}
+        Input {bind: user}
+        Checkbox {"Remember me", bind: remember}
+        Row {
+            Button {"Sign in", onClick: submit()}
+            Button {"Clear", onClick: clear()}
+        }
+    }
+
+    fn clear() {
+        let user = ""
+    }
+}
+"#;
+
+fn session() -> TuiSession {
+    TuiSession::mount(&compile(LOGIN).unwrap(), "Login", 40, 8).unwrap()
+}
+
+fn run(session: &mut TuiSession, script: &str) -> String {
+    for key in Key::script(script) {
+        session.press(key);
+    }
+    session.render().to_string()
+}
+
+#[test]
+fn initial_frame_focuses_first_input() {
+    assert_eq!(run(&mut session(), ""), "\
+── Login ──
+Please sign in
+>user: [_]
+ [ ] Remember me
+ [ Sign in ]  [ Clear ]");
+}
+
+#[test]
+fn typing_edits_bound_input_and_enter_clicks() {
+    let mut s = session();
+    assert_eq!(run(&mut s, "adx<bs>a"), "\
+── Login ──
+Please sign in
+>user: [ada_]
+ [ ] Remember me
+ [ Sign in ]  [ Clear ]");
+
+    assert_eq!(run(&mut s, "<tab> <tab><enter>"), "\
+── Login ──
+Welcome, ada
+ user: [ada]
+ [x] Remember me
+>[ Sign in ]  [ Clear ]");
+
+    // Focus wraps, and Enter on "Clear" resets the bound variable
+    assert_eq!(run(&mut s, "<tab><enter><tab>"), "\
+── Login ──
+Welcome, ada
+>user: [_]
+ [x] Remember me
+ [ Sign in ]  [ Clear ]");
+}
+
+#[test]
+fn buffer_is_clipped_to_terminal_size_and_esc_quits() {
+    let mut s = session();
+    s.resize(12, 3);
+    let frame = s.render();
+    assert_eq!(frame.lines, vec!["── Login ── ", "Please sign ", ">user: [_]  "]);
+    assert!(!s.press(Key::Esc));
+}
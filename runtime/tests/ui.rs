@@ -0,0 +1,148 @@
+use aeroflow_compiler::ir::Value;
+use aeroflow_runtime::render::{UiPatch, UiTree};
+use aeroflow_runtime::ui::{apply, diff};
+use aeroflow_runtime::{Engine, MemorySink, MessageData, RenderEvent, UiEvent, UiNode};
+use std::sync::Arc;
+
+const FORM: &str = r#"
+screen Form {
+    let count: int = 0
+    let name: string = "anon"
+    let agree: bool = false
+
+    fn add(n: int) {
+        let count = count + n
+    }
+
+    fn reset() {
+        let count = 0
+    }
+
+    render {
+        Column {
+            Text {"Count: " + count}
+            Input {bind: name}
+            Checkbox {"Agree", bind: agree}
+            Row {
+                Button {"+1", onClick: add(1)}
+                Button {"+5", onClick: add(5)}
+            }
+        }
+    }
+}
+"#;
+
+fn trees(sink: &MemorySink) -> Vec<UiTree> {
+    sink.take().into_iter().filter_map(|e| match e {
+        RenderEvent::Ui(tree) => Some(tree),
+        _ => None,
+    }).collect()
+}
+
+fn text(s: &str) -> UiNode {
+    UiNode::Text { text: s.to_string() }
+}
+
+fn mounted() -> (Engine, Arc<MemorySink>) {
+    let sink = Arc::new(MemorySink::new());
+    let mut engine = Engine::new();
+    engine.set_render_sink(sink.clone());
+    engine.load_source(FORM).unwrap();
+    engine.mount_screen("Form").unwrap();
+    engine.run_until_idle();
+    (engine, sink)
+}
+
+#[test]
+fn mount_renders_full_tree() {
+    let (_engine, sink) = mounted();
+    let tree = trees(&sink).pop().expect("initial render");
+    assert_eq!(tree.screen.as_deref(), Some("Form"));
+    assert_eq!(tree.root, vec![UiNode::Column { children: vec![
+        text("Count: 0"),
+        UiNode::Input { bind: "name".into(), value: "anon".into() },
+        UiNode::Checkbox { label: "Agree".into(), bind: "agree".into(), checked: false },
+        UiNode::Row { children: vec![
+            UiNode::Button { label: "+1".into(), action: "add".into(), args: vec![Value::Number(1.0)] },
+            UiNode::Button { label: "+5".into(), action: "add".into(), args: vec![Value::Number(5.0)] },
+        ] },
+    ] }]);
+}
+
+#[test]
+fn click_routes_to_screen_and_patches_only_changed_text() {
+    let (engine, sink) = mounted();
+    let mut mirror = trees(&sink).pop().unwrap().root;
+
+    engine.ui_event("Form", UiEvent::Click { action: "add".into(), args: vec![Value::Number(5.0)] });
+    engine.run_until_idle();
+    let update = trees(&sink).pop().expect("re-render after click");
+    assert_eq!(update.patches, vec![UiPatch::Replace { path: vec![0, 0], node: text("Count: 5") }]);
+
+    apply(&mut mirror, &update.patches);
+    assert_eq!(mirror, update.root);
+    assert_eq!(engine.scheduler().read_field("Form", "count"), Some(Value::Number(5.0)));
+}
+
+#[test]
+fn input_and_checkbox_bind_to_screen_state() {
+    let (engine, sink) = mounted();
+    trees(&sink);
+    engine.ui_event("Form", UiEvent::Input { bind: "name".into(), value: "ada".into() });
+    engine.ui_event("Form", UiEvent::Toggle { bind: "agree".into(), checked: true });
+    engine.run_until_idle();
+
+    let updates = trees(&sink);
+    assert_eq!(updates.len(), 2);
+    assert_eq!(updates[0].patches, vec![UiPatch::Replace {
+        path: vec![0, 1],
+        node: UiNode::Input { bind: "name".into(), value: "ada".into() },
+    }]);
+    assert_eq!(engine.scheduler().read_field("Form", "agree"), Some(Value::Bool(true)));
+
+    // Re-sending the same value changes nothing, so nothing is emitted
+    engine.ui_event("Form", UiEvent::Input { bind: "name".into(), value: "ada".into() });
+    engine.run_until_idle();
+    assert!(trees(&sink).is_empty());
+}
+
+#[test]
+fn diff_inserts_and_removes_list_items() {
+    let old = vec![UiNode::List { children: vec![text("a"), text("b"), text("c")] }];
+    let new = vec![UiNode::List { children: vec![text("a")] }];
+    let patches = diff(&old, &new);
+    assert_eq!(patches, vec![UiPatch::Remove { path: vec![0, 2] }, UiPatch::Remove { path: vec![0, 1] }]);
+
+    let mut mirror = new.clone();
+    apply(&mut mirror, &diff(&new, &old));
+    assert_eq!(mirror, old);
+}
+
+#[test]
+fn only_rendered_buttons_can_be_clicked() {
+    let (engine, sink) = mounted();
+    trees(&sink);
+    engine.ui_event("Form", UiEvent::Click { action: "reset".into(), args: vec![] });
+    engine.scheduler().send("Form".to_string(), MessageData::Text("reset".to_string()), "ui".to_string());
+    engine.ui_event("Form", UiEvent::Click { action: "add".into(), args: vec![Value::Number(1.0)] });
+    engine.run_until_idle();
+
+    let reasons: Vec<String> = engine.scheduler().failures().into_iter().map(|f| f.reason).collect();
+    assert_eq!(reasons, [
+        "Screen 'Form' has no button for 'reset'".to_string(),
+        "Screen 'Form' cannot handle message Text(\"reset\")".to_string(),
+    ]);
+    assert_eq!(engine.scheduler().read_field("Form", "count"), Some(Value::Number(1.0)));
+}
+
+#[test]
+fn screens_are_rendered_by_reference() {
+    let sink = Arc::new(MemorySink::new());
+    let mut engine = Engine::new();
+    engine.set_render_sink(sink.clone());
+    engine.load_source(&format!("{}\nrender {{\n    Form {{}}\n}}", FORM)).unwrap();
+    assert_eq!(trees(&sink).pop().unwrap().root, vec![UiNode::Screen { name: "Form".into() }]);
+
+    let err = aeroflow_compiler::compile("screen S {\n    render {\n        Button {\"x\", onClick: 1 + 2}\n    }\n}").unwrap_err();
+    assert!(err.to_string().contains("Button onClick must be a function call"), "{}", err);
+}
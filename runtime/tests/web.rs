@@ -0,0 +1,40 @@
+use aeroflow_compiler::compile;
+use aeroflow_runtime::wasm::WasmScheduler;
+use aeroflow_runtime::web::{render_html, WebBundle};
+use aeroflow_runtime::UiNode;
+
+const COUNTER: &str = include_str!("../../samples/counter.aefl");
+
+#[test]
+fn counter_bundle_matches_snapshot() {
+    let chunk = compile(COUNTER).unwrap();
+    let bundle = WebBundle::build("counter", &chunk).unwrap();
+
+    // Set AEROFLOW_UPDATE_SNAPSHOTS=1 to accept intentional markup changes
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/snapshots/counter.html");
+    if std::env::var_os("AEROFLOW_UPDATE_SNAPSHOTS").is_some() {
+        std::fs::write(path, &bundle.index_html).unwrap();
+    }
+    assert_eq!(bundle.index_html, std::fs::read_to_string(path).unwrap());
+    assert!(bundle.glue_js.contains("scheduler.mount_screen"));
+    assert_eq!(bincode::deserialize::<aeroflow_compiler::ir::Chunk>(&bundle.chunk).unwrap().screens, chunk.screens);
+}
+
+#[test]
+fn wasm_scheduler_delivers_clicks_as_patches() {
+    let bytecode = bincode::serialize(&compile(COUNTER).unwrap()).unwrap();
+    let scheduler = WasmScheduler::new();
+    scheduler.mount_screen("CounterScreen".to_string(), bytecode);
+    while scheduler.step() {}
+    assert!(scheduler.take_render_events().contains("Current count: 0"));
+
+    scheduler.ui_event("CounterScreen".to_string(), r#"{"type":"click","action":"increment","args":[]}"#.to_string());
+    while scheduler.step() {}
+    let events: serde_json::Value = serde_json::from_str(&scheduler.take_render_events()).unwrap();
+    assert_eq!(events[0]["data"]["patches"], serde_json::json!([
+        { "op": "replace", "path": [0], "node": { "type": "Text", "text": "Current count: 1" } }
+    ]));
+
+    let node: UiNode = serde_json::from_value(events[0]["data"]["patches"][0]["node"].clone()).unwrap();
+    assert_eq!(render_html(&[node]), "<p class=\"af-text\">Current count: 1</p>\n");
+}